@@ -1,7 +1,22 @@
-use chrono::{Datelike, Duration, Local, NaiveDate};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc,
+    Weekday,
+};
 use clap::{Parser, Subcommand, ValueEnum};
-use std::path::PathBuf;
-use timetracker::{ListFilter, SortOrder, in_memory_tracker::JsonStorageStrategy};
+use log::warn;
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+use timetracker::{
+    DurationStyle, Grouping, ListFilter, ListOptions, RoundingMode, SearchPattern, SortOrder,
+    in_memory_tracker::JsonStorageStrategy,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{export::CSV_DELIMITER, helpers::PorcelainEvent, settings::SettingFlags};
 
 /// Purposefully Simple Personal Time-Tracker made by (and mainly for) Daniel Biegler https://www.danielbiegler.de
 #[derive(Parser, Debug)]
@@ -14,6 +29,10 @@ pub struct Args {
     #[arg(short, long, value_enum, default_value_t = OutputJsonFormat::Pretty)]
     pub json_format: OutputJsonFormat,
 
+    /// File format of the store. Determines the file name, so switching formats starts with a separate store.
+    #[arg(long, value_enum, default_value_t = StorageFormat::Json)]
+    pub storage_format: StorageFormat,
+
     /// Level of feedback for your inputs. Gets output into `stderr` so you can still have logs and output into a file normally.
     ///
     /// Environment variable `$RUST_LOG` takes precedence and overwrites this argument.
@@ -22,71 +41,675 @@ pub struct Args {
     #[arg(long, default_value = "info")]
     pub log_level: String,
 
+    /// Seconds to wait for other running invocations to release the store before giving up.
+    #[arg(long, default_value_t = 5, value_name = "SECONDS")]
+    pub lock_timeout: u64,
+
+    /// Keep a binary copy of the store next to it for faster loading of large stores.
+    /// The cache is always regenerable from the JSON file and never overrides it. Only applies to the `json` storage format.
+    #[arg(long, default_value_t = false)]
+    pub cache: bool,
+
+    /// Fail instead of sanitizing when descriptions in the store contain control characters or are overly long.
+    /// Useful for checking a manually edited store. Loads from the JSON file even when `--cache` is set.
+    #[arg(long, default_value_t = false)]
+    pub strict: bool,
+
+    /// Amount of backups of the store to keep inside the `backups` folder of the output directory.
+    /// A backup gets written before every change, 0 disables backups.
+    #[arg(long, default_value_t = 10, value_name = "N")]
+    pub keep_backups: usize,
+
+    /// Initialize the output directory if there is no store yet, instead of failing changes.
+    #[arg(long, default_value_t = false)]
+    pub auto_init: bool,
+
+    /// Recover from saves that got interrupted without asking: adopt swap files holding changes newer than the store
+    /// and delete incomplete or outdated ones. Without it commands changing the store ask in terminals and only warn otherwise.
+    #[arg(long, default_value_t = false)]
+    pub auto_recover: bool,
+
+    /// Colorize the output. `auto` colorizes only when writing to a terminal and `$NO_COLOR` is not set.
+    /// Defaults to `color` of the `config.json` in the output directory, otherwise `auto`.
+    #[arg(long, value_enum)]
+    pub color: Option<ColorChoice>,
+
+    /// How durations get displayed in tables, `status --format plain` and the `csv` export.
+    /// Defaults to `duration-format` of the `config.json` in the output directory, otherwise `decimal`.
+    #[arg(long, value_enum)]
+    pub duration_format: Option<DurationFormat>,
+
+    /// After every change, additionally print a final line of `key=value` pairs on `stdout` for capturing in scripts,
+    /// for example `event=end hours=1.42 start=2024-06-12T09:00:00Z stop=2024-06-12T10:25:12Z title="Fix login bug"`.
+    /// `title` is the only quoted value, its quotes and backslashes get escaped with a backslash and line breaks get stripped.
+    /// Failing commands print `event=error` with a stable `code` like `no_active_time_box` and the quoted `message` instead.
+    #[arg(long, default_value_t = false)]
+    pub porcelain: bool,
+
+    /// Run this shell command after every saved change, e.g. to notify or to update a status bar. Runs once per change
+    /// of the store, so `note --end` runs it for the note and for ending. Gets the change as environment variables,
+    /// for example `TIMETRACKER_EVENT=end`, `TIMETRACKER_HOURS` and `TIMETRACKER_TITLE`, with the title unquoted.
+    /// Replacing the whole store via `import`, `restore` or `undo` runs no hook. Failing hooks only warn, the change is saved already.
+    #[arg(long, env = "TIMETRACKER_HOOK", value_name = "COMMAND")]
+    pub hook: Option<String>,
+
+    /// Fit tables into this many columns by wrapping descriptions. Defaults to `width` of the `config.json`,
+    /// otherwise the width of the terminal. Tables piped elsewhere only wrap overly long descriptions.
+    #[arg(long, env = "TIMETRACKER_WIDTH", value_name = "COLUMNS")]
+    pub width: Option<usize>,
+
+    /// Render `list`, `status` and `report` as simple labeled lines instead of tables,
+    /// for example `Entry 3: 2024-06-12 09:00 to 10:25, 1 hour 25 minutes. Notes: Fix login bug; Review PR.`
+    /// Easier to navigate with screen readers, durations are spelled out in words. Also enabled by `plain` of the `config.json`.
+    #[arg(long, default_value_t = false)]
+    pub plain: bool,
+
+    /// Print `list` and `report` directly instead of through `$PAGER`, by default `less -RFX`, when they are taller than the terminal.
+    /// Also disabled by `pager` of the `config.json`. Output that does not go to a terminal never gets paged.
+    #[arg(long, default_value_t = false)]
+    pub no_pager: bool,
+
+    /// Never suggest `lock --before` once every time box of the weeks before was exported.
+    /// Also disabled by `suggestions` of the `config.json`.
+    #[arg(long, default_value_t = false)]
+    pub no_suggestions: bool,
+
+    /// Work on the store of this project inside the `projects` folder of the output directory, e.g. one per client.
+    /// Defaults to `$TIMETRACKER_PROJECT`, otherwise the store directly inside the output directory.
+    /// `all` merges the finished time boxes of every project for `list` and `export`.
+    #[arg(long, value_name = "NAME")]
+    pub project: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+impl Args {
+    /// Only what was actually passed, so that settings of the store and the config file still apply.
+    pub fn setting_flags(&self) -> SettingFlags {
+        let mut flags = SettingFlags {
+            color: self.color,
+            duration_format: self.duration_format,
+            width: self.width,
+            plain: self.plain.then_some(true),
+            pager: self.no_pager.then_some(false),
+            suggestions: self.no_suggestions.then_some(false),
+            ..Default::default()
+        };
+
+        if let Commands::Goal {
+            command:
+                GoalCommands::Status {
+                    weekly,
+                    work_days,
+                    work_hours,
+                },
+        } = &self.command
+        {
+            flags.weekly_goal = *weekly;
+            flags.work_days = work_days.clone();
+            flags.work_hours = *work_hours;
+        }
+
+        flags
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    /// Initialize a new file for time tracking. Does not overwrite if the file already exists.
-    Init {},
+    /// Initialize a new file for time tracking. Does not overwrite if the file already exists, unless forced.
+    Init {
+        #[command(flatten)]
+        options: InitArgs,
+    },
     /// Begin working on something. Creates a new active time box if there is none.
-    Begin { description: String },
+    Begin {
+        #[arg(required_unless_present = "pick")]
+        description: Option<String>,
+        /// Choose from the titles of recent time boxes or type a new description. Needs a terminal.
+        #[arg(long, default_value_t = false, conflicts_with = "description")]
+        pick: bool,
+        /// How long you expect it to take, for example `2h`, `45m`, `1h30m` or `1.5h`. See `report --estimates`.
+        #[arg(short, long, value_parser = parse_estimate, value_name = "DURATION")]
+        estimate: Option<TimeDelta>,
+        /// External reference the time box belongs to, e.g. the ticket `PROJ-123` or a URL. See `list --ref`.
+        #[arg(long = "ref", value_name = "REF")]
+        reference: Option<String>,
+        /// Use the ticket id in the description as reference, e.g. `PROJ-123`. Only if there is exactly one.
+        #[arg(long, default_value_t = false, conflicts_with = "reference")]
+        auto_ref: bool,
+        /// Start earlier than now, e.g. `08:30` for today or `2024-06-12 08:30`, in local time.
+        /// Has to be after the stop of the last finished time box, time boxes never overlap.
+        #[arg(long, value_parser = parse_note_time, value_name = "TIME", conflicts_with = "pick")]
+        backfill: Option<DateTime<Utc>>,
+    },
     /// Add a note to the active time box.
     Note {
         /// End the time box after adding the note.
         #[arg(short, long, default_value_t = false)]
         end: bool,
+        /// Backdate the note, e.g. `14:32` for today or `2024-06-12 14:32`, in local time.
+        /// The note gets inserted in chronological order instead of appended.
+        #[arg(long, value_parser = parse_note_time, value_name = "TIME")]
+        at: Option<DateTime<Utc>>,
+        /// Allow `--at` to be earlier than the start, which makes the note the new start and title of the time box.
+        #[arg(long, default_value_t = false, requires = "at")]
+        allow_extend: bool,
+        description: String,
+    },
+    /// Changes the description of the last note of the active time box.
+    Amend {
         description: String,
+        /// Change the first note instead, which is the title of the time box in lists and exports.
+        #[arg(short, long, default_value_t = false, conflicts_with = "index")]
+        title: bool,
+        /// Change the note at this index instead, see the `#` column of `status`.
+        #[arg(short, long)]
+        index: Option<usize>,
+    },
+    /// Changes the description of any note of the active time box, keeping its time.
+    EditNote {
+        /// Index of the note, see the `#` column of `status`. 0 is the first note.
+        index: usize,
+        description: String,
+    },
+    /// Split the active time box in front of a note, e.g. after working on two things without beginning anew.
+    /// The notes before it end as a finished time box, the note and the ones after it stay active.
+    Split {
+        /// Index of the note beginning the second part, see the `#` column of `status`.
+        index: usize,
     },
-    /// Changes the description of the active time box.
-    Amend { description: String },
-    /// End the active time box.
+    /// Pause the active time box, e.g. for lunch. Pauses do not count towards its duration.
+    Pause {},
+    /// Continue the paused active time box.
+    Unpause {},
+    /// End the active time box. Ends its pause too if it is paused.
     End {},
     /// Makes the last finished time box active again. Useful if you prematurely finish. We've all been there, bud.
-    Resume {},
+    Resume {
+        /// Resume the finished time box at this index instead of the last one, see the `#` column of `list`.
+        index: Option<usize>,
+    },
+
+    /// Combines the last two finished time boxes into one, e.g. after ending prematurely and beginning anew.
+    Merge {},
 
     /// Cancels i.e. removes the active time box.
     Cancel {},
     /// Clears i.e. removes all finished time boxes. Does not modify the store if there is a active time box.
-    Clear {},
+    /// With `--date` only the finished time boxes starting within, regardless of an active time box.
+    Clear {
+        /// Only remove time boxes starting on this date or within this range, for example `last-month`.
+        /// Accepts the same values as `list --date`.
+        #[arg(short, long, default_value = None, value_parser = parse_date_filter, value_name = "DATE_OR_RANGE")]
+        date: Option<ListFilter>,
+        /// Skip the confirmation asked before removing more than 10 time boxes by date.
+        #[arg(short, long, default_value_t = false)]
+        yes: bool,
+        /// Clear even though loading had to repair the store, e.g. sort notes after a sync conflict.
+        /// Its history may be mixed up, so check it via `list` first.
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
 
-    /// Print human readable information about the active time box.
-    Status {},
+    /// Print information about the active time box.
+    ///
+    /// Exits with code 1 if there is no active time box and the format is `plain` or `json`,
+    /// so scripts and shell prompts can branch on it. The status bar formats always succeed.
+    Status {
+        #[arg(short, long, value_enum, default_value_t = StatusFormat::Table)]
+        format: StatusFormat,
+        /// Show when each note was relative to now, e.g. `25 minutes ago`, instead of the date and time.
+        #[arg(long, default_value_t = false)]
+        relative: bool,
+    },
+    /// Print a single line about the active time box for shell prompts, without trailing line break or logs.
+    /// Prints `--idle` if there is none, even without a store.
+    Prompt {
+        /// Template with the placeholders `{hours_active}`, `{minutes_active}`, `{description}`, `{last_note}`
+        /// and `{notes_count}`. Unknown placeholders stay as they are, `{{` and `}}` print literal braces.
+        #[arg(short, long, default_value = "{hours_active} {description}")]
+        format: String,
+        /// Printed instead when there is no active time box.
+        #[arg(long, default_value = "", value_name = "TEXT")]
+        idle: String,
+    },
     /// Print human readable information about the finished time boxes.
     List {
-        /// Lists all finished time boxes.
-        #[arg(short, long, default_value_t = false)]
-        all: bool,
-        /// Used for pagination if no filter is applied.
-        #[arg(short, long, default_value_t = 0)]
-        page: usize,
-        /// Used for pagination if no filter is applied.
-        #[arg(short, long, default_value_t = 25)]
-        limit: usize,
-        /// Filter by date or date range
-        ///
-        /// Accepts:
-        ///
-        /// - 'today', 'yesterday' or custom dates: YYYY-MM-DD
-        ///
-        /// - 'this-week', 'last-week', 'this-month', 'last-month' or custom ranges: YYYY-MM-DD..YYYY-MM-DD
+        #[command(flatten)]
+        selection: ListArgs,
+        /// Render a table with a subtotal per period, followed by the grand total.
+        #[arg(short, long, value_enum, default_value = None)]
+        group_by: Option<ListGroupBy>,
+        #[command(flatten)]
+        rounding: RoundingArgs,
+        /// Additionally show the active time box if it matches the filters, e.g. when it started on `--date`.
+        /// Its time so far gets its own `active` line instead of counting towards the total.
+        #[arg(long, default_value_t = false)]
+        include_active: bool,
+        /// Show when each note was relative to now, e.g. `2 days ago`, instead of the date and time. Tables only.
+        #[arg(long, default_value_t = false)]
+        relative: bool,
+        /// One row per time box with its start, duration and the shortened first line of its description. Tables only.
+        #[arg(long, default_value_t = false, conflicts_with = "group_by")]
+        compact: bool,
+        /// Sum up time boxes older than this many months in one row per month instead of listing their notes,
+        /// e.g. `3months` for long histories via `--all`. The total still counts them. Counts from the start of the month.
+        #[arg(long, value_name = "MONTHS", value_parser = parse_months, conflicts_with_all = ["group_by", "compact"])]
+        collapse_older_than: Option<u32>,
+    },
+    /// Print hours aggregated per day or week. `summary` gives a quick per-day view for stand-ups.
+    #[command(visible_alias = "summary")]
+    Report {
+        /// Granularity of the rows.
+        #[arg(short, long, value_enum, default_value_t = ReportGroupBy::Day)]
+        group_by: ReportGroupBy,
+        /// Additionally nest the rows under headings with a subtotal each. Requires grouping by day.
+        #[arg(short, long, value_enum, default_value = None)]
+        roll_up: Option<ReportRollUp>,
+        /// Filter by date or date range, accepts the same values as `list`.
         #[arg(short, long, default_value = None, value_parser = parse_date_filter, value_name = "DATE_OR_RANGE")]
         date: Option<ListFilter>,
-        /// Order of the listed time boxes.
-        /// Descending means the latest time boxes come first.
-        #[arg(short, long, value_enum, default_value_t = ListOrder::Ascending)]
-        order: ListOrder,
+        /// Render as human readable table or as JSON for other tools.
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+        /// Skip time boxes that were marked as exported, see `export --mark-exported`.
+        #[arg(long, default_value_t = false)]
+        unexported_only: bool,
+        /// Compare estimated and actual hours instead, only of time boxes with an estimate, see `begin --estimate`.
+        #[arg(long, default_value_t = false, conflicts_with_all = ["roll_up", "round"])]
+        estimates: bool,
+        #[command(flatten)]
+        rounding: RoundingArgs,
+    },
+    /// Plan towards a weekly goal of tracked hours.
+    Goal {
+        #[command(subcommand)]
+        command: GoalCommands,
     },
     /// Generate output for integrating into other tools.
     Export {
-        #[arg(value_enum, default_value_t = ExportStrategy::Csv)]
-        strategy: ExportStrategy,
+        /// Defaults to the one matching the extension of `--output`, otherwise `csv`.
+        #[arg(value_enum)]
+        strategy: Option<ExportStrategy>,
+        #[command(flatten)]
+        format: ExportFormatArgs,
+        #[command(flatten)]
+        file: ExportFileArgs,
+        /// Mark the exported time boxes as exported, e.g. after invoicing them.
+        /// Combine with `--unexported-only` to export every time box only once.
+        #[arg(long, default_value_t = false)]
+        mark_exported: bool,
+        /// Export negative durations as they are instead of as zero. Those come from time boxes ending before they start.
+        #[arg(long, default_value_t = false)]
+        raw_durations: bool,
+        #[command(flatten)]
+        rounding: RoundingArgs,
+        #[command(flatten)]
+        selection: ListArgs,
+    },
+    /// Change properties of a finished time box.
+    Set {
+        /// Index of the time box, see the `#` column of `list`.
+        index: usize,
+        /// Unmark the time box as exported, so `--unexported-only` selects it again.
+        #[arg(long, required = true)]
+        clear_exported: bool,
+    },
+    /// Lock the finished time boxes before a date against changes, e.g. once they are billed.
+    /// Merging, resuming or clearing them fails, and no time box or note can be added before the date.
+    Lock {
+        /// Lock what starts before the local midnight of this date, for example `2024-06-10`.
+        #[arg(long, value_name = "DATE")]
+        before: NaiveDate,
+    },
+    /// Add the time boxes of another store to this one, keeping them in chronological order.
+    Import {
+        /// File to import, e.g. the `tasks.json` of the old single binary version or an export of another machine.
+        /// Time boxes with the same start, stop and first note as one in the store are skipped.
+        path: PathBuf,
+        #[arg(short, long, value_enum, default_value_t = ImportFormat::LegacyJson)]
+        format: ImportFormat,
     },
+    /// Check the store and its related files for problems.
+    Doctor {},
+    /// Write the store in its storage format into a temporary directory, load it again and print every field that differs,
+    /// e.g. before trusting a storage format with your history. Exits with code 1 if anything differs.
+    VerifyRoundtrip {
+        /// Compare with this store instead. Its storage format follows the extension, e.g. `storage.toml`.
+        #[arg(long, value_name = "FILE")]
+        against: Option<PathBuf>,
+    },
+    /// Print the effective settings and where each comes from: a flag, the `config.json`, the store or the default.
+    Info {},
+    /// List every project with its tracked hours and active time box, see `--project`.
+    Projects {},
+    /// Restore the store from a backup. Lists the available backups if none is given.
+    Restore {
+        /// Number of the backup as listed, 1 being the newest.
+        backup: Option<usize>,
+        /// Skip the confirmation.
+        #[arg(short, long, default_value_t = false)]
+        yes: bool,
+    },
+    /// Undo the last change by restoring the most recent backup. Undoing again reverts the undo.
+    Undo {},
     /// Generate shell-completion
     ShellCompletion { shell: clap_complete::aot::Shell },
 }
 
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct InitArgs {
+    /// Only ignore swap, cache, lock and backup files, so the store itself can be versioned with git.
+    #[arg(long, default_value_t = false)]
+    pub track_in_git: bool,
+    /// Overwrite an existing store after confirming. It gets backed up first, so `undo` brings it back.
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+    /// Skip the confirmation of `--force`.
+    #[arg(short, long, default_value_t = false, requires = "force")]
+    pub yes: bool,
+    /// Convert the store of the old single binary version, usually `tasks.json`, instead of starting empty.
+    #[arg(long, value_name = "PATH")]
+    pub from_legacy: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GoalCommands {
+    /// Print the hours left this week and how many that makes per remaining work day, including today.
+    Status {
+        /// Hours to track per ISO week, for example `40h` or `37h30m`. Defaults to the goal of `goal set`.
+        #[arg(long, value_parser = parse_weekly_goal, value_name = "DURATION")]
+        weekly: Option<TimeDelta>,
+        /// Days of your work week, separated by commas. Defaults to the days of `goal set`, otherwise `mon,tue,wed,thu,fri`.
+        #[arg(long, value_parser = parse_weekday, value_delimiter = ',', value_name = "DAYS")]
+        work_days: Option<Vec<Weekday>>,
+        /// Most hours you work per day. Needing more per day makes the goal unreachable at the current pace.
+        /// Defaults to the hours of `goal set`, otherwise `8h`.
+        #[arg(long, value_parser = parse_work_hours, value_name = "DURATION")]
+        work_hours: Option<TimeDelta>,
+    },
+    /// Remember a weekly goal inside the store, `status` and `summary` then show the progress of the current ISO week.
+    Set {
+        /// Hours to track per ISO week, for example `40h` or `37h30m`.
+        #[arg(value_parser = parse_weekly_goal, value_name = "DURATION")]
+        weekly: TimeDelta,
+        /// Also remember the days of your work week, separated by commas.
+        #[arg(long, value_parser = parse_weekday, value_delimiter = ',', value_name = "DAYS")]
+        work_days: Option<Vec<Weekday>>,
+        /// Also remember the most hours you work per day.
+        #[arg(long, value_parser = parse_work_hours, value_name = "DURATION")]
+        work_hours: Option<TimeDelta>,
+    },
+    /// Print the weekly goal and the progress of the current ISO week.
+    Show {},
+}
+
+/// Selects finished time boxes, shared by all commands outputting them so they behave identically.
+#[derive(clap::Args, Debug, Clone)]
+pub struct ListArgs {
+    /// Selects all finished time boxes, filters still apply.
+    #[arg(short, long, default_value_t = false)]
+    pub all: bool,
+    /// Page of `--limit` many time boxes, 0 being the first.
+    #[arg(short, long)]
+    pub page: Option<usize>,
+    /// Time boxes per page. Defaults to 25, or to all matching time boxes when filtering without `--page`.
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+    /// Filter by date or date range
+    ///
+    /// Accepts:
+    ///
+    /// - 'today', 'yesterday' or custom dates: YYYY-MM-DD
+    ///
+    /// - 'this-week', 'last-week', 'this-month', 'last-month' or custom ranges: YYYY-MM-DD..YYYY-MM-DD
+    #[arg(short, long, default_value = None, value_parser = parse_date_filter, value_name = "DATE_OR_RANGE")]
+    pub date: Option<ListFilter>,
+    /// Only time boxes with this hashtag in any note, e.g. 'clienta' for notes containing '#ClientA'.
+    #[arg(long, value_name = "NAME")]
+    pub tag: Option<String>,
+    /// Only time boxes with this text in any note, ignoring case. Combines with the other filters.
+    #[arg(long, value_name = "TERM")]
+    pub search: Option<String>,
+    /// Only time boxes with a match of this regular expression in any note, ignoring case unless it starts with `(?-i)`.
+    /// Combines with the other filters, e.g. `--regex "invoice.?parser"`.
+    #[arg(long, value_name = "PATTERN", value_parser = parse_search_pattern)]
+    pub regex: Option<SearchPattern>,
+    /// Only time boxes with this reference, ignoring case, see `begin --ref`.
+    #[arg(long = "ref", value_name = "REF")]
+    pub reference: Option<String>,
+    /// Only time boxes lasting at least this many minutes, hiding e.g. accidental ones.
+    #[arg(long, value_name = "MINUTES")]
+    pub min_minutes: Option<u32>,
+    /// Order of the time boxes.
+    /// Descending means the latest time boxes come first.
+    #[arg(short, long, value_enum, default_value_t = ListOrder::Ascending)]
+    pub order: ListOrder,
+    /// Skip time boxes that were marked as exported, see `export --mark-exported`.
+    #[arg(long, default_value_t = false)]
+    pub unexported_only: bool,
+}
+
+/// Settings of the individual export strategies
+#[derive(clap::Args, Debug, Clone, Copy)]
+pub struct ExportFormatArgs {
+    /// Separates the fields of the `csv` strategy, for example ',' or ';' depending on your locale.
+    #[arg(long, default_value_t = CSV_DELIMITER, value_parser = parse_csv_delimiter)]
+    pub delimiter: char,
+    /// Additionally export the active time box with the `json` strategy.
+    #[arg(long, default_value_t = false)]
+    pub include_active: bool,
+    /// Export a plain array of the finished time boxes with the `json` strategy, each with its `hours`,
+    /// as it was before the `json` export got its `active`, `finished` and `total_hours` fields.
+    #[arg(long, default_value_t = false, conflicts_with = "include_active")]
+    pub raw: bool,
+}
+
+/// Where the export goes instead of `stdout`
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct ExportFileArgs {
+    /// Write the export into this file instead of `stdout`, warnings still go to `stderr`.
+    /// Avoids shells mangling the encoding when redirecting, e.g. PowerShell. Creates missing directories.
+    #[arg(long, visible_alias = "file")]
+    pub output: Option<PathBuf>,
+    /// Replace the file of `--output` if it exists already.
+    #[arg(long, default_value_t = false, requires = "output")]
+    pub force: bool,
+}
+
+impl ExportFileArgs {
+    /// The `explicit` strategy wins, otherwise the extension of `--output` decides, defaulting to `csv`.
+    pub fn strategy(&self, explicit: Option<ExportStrategy>) -> anyhow::Result<ExportStrategy> {
+        if let Some(strategy) = explicit {
+            return Ok(strategy);
+        }
+
+        match &self.output {
+            None => Ok(ExportStrategy::Csv),
+            Some(path) => ExportStrategy::from_extension(path).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unable to tell the export strategy from the extension of \"{}\". \
+                    Pass one explicitly, for example `export csv --output {}`",
+                    path.display(),
+                    path.display()
+                )
+            }),
+        }
+    }
+}
+
+impl Default for ExportFormatArgs {
+    fn default() -> Self {
+        Self {
+            delimiter: CSV_DELIMITER,
+            include_active: false,
+            raw: false,
+        }
+    }
+}
+
+/// Rounds the duration of each time box, shared by all commands summing up durations so the sums match.
+#[derive(clap::Args, Debug, Clone, Copy)]
+pub struct RoundingArgs {
+    /// Round the duration of each time box to a multiple of this, e.g. `15m` for invoicing.
+    /// Totals sum up the rounded durations.
+    #[arg(long, value_name = "DURATION", value_parser = parse_rounding_granularity)]
+    pub round: Option<TimeDelta>,
+    /// Direction of `--round`. Rounding up bills time boxes without duration as one multiple.
+    #[arg(long, value_enum, default_value_t = RoundMode::Up, requires = "round")]
+    pub round_mode: RoundMode,
+}
+
+impl RoundingArgs {
+    pub fn rounding(&self) -> Option<(TimeDelta, RoundingMode)> {
+        self.round
+            .map(|granularity| (granularity, self.round_mode.into()))
+    }
+}
+
+/// Page size if neither `--limit` nor a filter is given
+const DEFAULT_LIMIT: usize = 25;
+
+impl ListArgs {
+    /// Filters, order and pagination compose, see `TimeTrackingStore::finished`.
+    pub fn options(&self) -> ListOptions {
+        let mut options = ListOptions::new()
+            .order(self.order.clone().into())
+            .unexported_only(self.unexported_only);
+
+        let filters = [
+            self.date.clone(),
+            self.tag.clone().map(ListFilter::Tag),
+            self.search.clone().map(ListFilter::Search),
+            self.regex.clone().map(ListFilter::Pattern),
+            self.reference.clone().map(ListFilter::Reference),
+            self.min_minutes
+                .map(|m| ListFilter::MinDuration(TimeDelta::minutes(m.into()))),
+        ];
+        for f in filters.into_iter().flatten() {
+            options = options.filter(f);
+        }
+
+        if self.all {
+            if self.page.is_some() || self.limit.is_some() {
+                warn!("`--all` selects every time box, ignoring `--page` and `--limit`");
+            }
+
+            return options.take(usize::MAX);
+        }
+
+        let limit = match (self.limit, self.page) {
+            (Some(limit), _) => limit,
+            (None, None) if !options.query.filters.is_empty() => usize::MAX,
+            (None, _) => DEFAULT_LIMIT,
+        };
+
+        options
+            .skip(self.page.unwrap_or(0).saturating_mul(limit))
+            .take(limit)
+    }
+}
+
+impl Commands {
+    /// Commands removing tracked time, they never load from the cache so they see every repair of the store.
+    pub fn is_destructive(&self) -> bool {
+        matches!(self, Commands::Clear { .. })
+    }
+
+    /// Read-only commands share the lock of the store so they never block each other.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Commands::Status { .. }
+            | Commands::Prompt { .. }
+            | Commands::List { .. }
+            | Commands::Report { .. }
+            | Commands::Goal {
+                command: GoalCommands::Status { .. } | GoalCommands::Show {},
+            }
+            | Commands::Doctor {}
+            | Commands::VerifyRoundtrip { .. }
+            | Commands::Info {}
+            | Commands::Projects {}
+            | Commands::ShellCompletion { .. } => true,
+            Commands::Export { mark_exported, .. } => !mark_exported,
+            Commands::Init { .. }
+            | Commands::Begin { .. }
+            | Commands::Note { .. }
+            | Commands::Amend { .. }
+            | Commands::EditNote { .. }
+            | Commands::Split { .. }
+            | Commands::Pause {}
+            | Commands::Unpause {}
+            | Commands::End {}
+            | Commands::Resume { .. }
+            | Commands::Merge {}
+            | Commands::Cancel {}
+            | Commands::Clear { .. }
+            | Commands::Set { .. }
+            | Commands::Lock { .. }
+            | Commands::Import { .. }
+            | Commands::Goal {
+                command: GoalCommands::Set { .. },
+            }
+            | Commands::Restore { .. }
+            | Commands::Undo {} => false,
+        }
+    }
+
+    /// What `--porcelain` reports once the command changed the store, `None` for read-only commands.
+    pub fn porcelain_event(&self) -> Option<PorcelainEvent> {
+        match self {
+            Commands::Begin { .. } => Some(PorcelainEvent::Begin),
+            Commands::Note { .. } => Some(PorcelainEvent::Note),
+            Commands::Amend { .. } => Some(PorcelainEvent::Amend),
+            Commands::EditNote { .. } => Some(PorcelainEvent::EditNote),
+            Commands::Split { .. } => Some(PorcelainEvent::Split),
+            Commands::Pause {} => Some(PorcelainEvent::Pause),
+            Commands::Unpause {} => Some(PorcelainEvent::Unpause),
+            Commands::End {} => Some(PorcelainEvent::End),
+            Commands::Resume { .. } => Some(PorcelainEvent::Resume),
+            Commands::Merge {} => Some(PorcelainEvent::Merge),
+            Commands::Cancel {} => Some(PorcelainEvent::Cancel),
+            Commands::Clear { .. } => Some(PorcelainEvent::Clear),
+            Commands::Export { .. } => Some(PorcelainEvent::Export),
+            Commands::Set { index, .. } => Some(PorcelainEvent::Set { index: *index }),
+            Commands::Lock { .. } => Some(PorcelainEvent::Lock),
+            Commands::Import { .. } => Some(PorcelainEvent::Import),
+            Commands::Goal {
+                command: GoalCommands::Set { .. },
+            } => Some(PorcelainEvent::Goal),
+            Commands::Restore { .. } => Some(PorcelainEvent::Restore),
+            Commands::Undo {} => Some(PorcelainEvent::Undo),
+            Commands::Init { .. }
+            | Commands::Status { .. }
+            | Commands::Prompt { .. }
+            | Commands::List { .. }
+            | Commands::Report { .. }
+            | Commands::Goal {
+                command: GoalCommands::Status { .. } | GoalCommands::Show {},
+            }
+            | Commands::Doctor {}
+            | Commands::VerifyRoundtrip { .. }
+            | Commands::Info {}
+            | Commands::Projects {}
+            | Commands::ShellCompletion { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ImportFormat {
+    /// Store of the old single binary version, usually `tasks.json`. Its pending task becomes the active time box.
+    LegacyJson,
+    /// Output of `export csv`, with any delimiter and duration format. Notes between the start and the stop
+    /// get the time of the start, pauses and estimates are not part of the export.
+    Csv,
+    /// Output of `export json`, with or without `--raw`. Only the `--raw` export keeps pauses and estimates.
+    /// Its active time box never becomes active, end it where it was exported instead.
+    Json,
+}
+
 #[derive(Debug, Clone, ValueEnum)]
 pub enum ExportStrategy {
     /// Default output for sanity checking when debugging
@@ -95,6 +718,253 @@ pub enum ExportStrategy {
     Csv,
     /// JavaScript Object Notation, useful for as an intermediary for example `jq`
     Json,
+    /// Like `json` but only the finished time boxes, each with its `hours` and `minutes` computed
+    JsonRich,
+    /// iCalendar with one event per time box, useful for checking your tracked time in calendar apps
+    Ics,
+    /// JSON Lines with one compact finished time box per line, useful for streaming into pipelines
+    Ndjson,
+}
+
+impl ExportStrategy {
+    /// `csv`, `json`, `ics` and `ndjson`/`jsonl` files, ignoring the case of the extension.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(ExportStrategy::Csv),
+            "json" => Some(ExportStrategy::Json),
+            "ics" => Some(ExportStrategy::Ics),
+            "ndjson" | "jsonl" => Some(ExportStrategy::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum ReportGroupBy {
+    /// One row per day
+    Day,
+    /// One row per ISO week
+    Week,
+    /// One row per hashtag by hours descending. Time boxes with several tags count towards each of them.
+    Tag,
+    /// One row per reference by hours descending, see `begin --ref`. Time boxes without one are left out.
+    Ref,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ListGroupBy {
+    /// One table per day
+    Day,
+    /// One table per ISO week
+    Week,
+    /// One table per month
+    Month,
+}
+
+impl From<ListGroupBy> for Grouping {
+    fn from(value: ListGroupBy) -> Self {
+        match value {
+            ListGroupBy::Day => Grouping::Day,
+            ListGroupBy::Week => Grouping::Week,
+            ListGroupBy::Month => Grouping::Month,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum ReportRollUp {
+    /// Nest days under their ISO week
+    Week,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum ReportFormat {
+    /// Human readable ascii table
+    Table,
+    /// JavaScript Object Notation, useful for as an intermediary for example `jq`
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolves `auto`, see https://no-color.org
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum StatusFormat {
+    /// Human readable ascii table
+    Table,
+    /// Single line with the active hours and the title, for example for shell prompts
+    Plain,
+    /// The active time box including `duration_active_in_hours`, or `null`
+    Json,
+    /// JSON with `text`, `tooltip` and `class` for custom modules of waybar
+    Waybar,
+    /// Small JSON with stable fields for status bars: `active`, `paused`, `title`, `elapsed_minutes` and `today_hours`
+    JsonMin,
+}
+
+fn parse_csv_delimiter(s: &str) -> Result<char, String> {
+    let mut chars = s.chars();
+
+    let delimiter = match (chars.next(), chars.next()) {
+        (Some(c), None) => c,
+        _ => return Err("Delimiter must be exactly one character".to_string()),
+    };
+
+    match delimiter {
+        '\t' | '\n' | '\r' => Err("Delimiter must not be a tab or line break".to_string()),
+        '"' => Err("Delimiter must not be a quote since it is used for quoting fields".to_string()),
+        c if !c.is_ascii() => Err("Delimiter must be an ASCII character".to_string()),
+        // These make up the header, times and hours, which the import splits before unquoting
+        c if c.is_ascii_alphanumeric() || "-:+._".contains(c) => Err(
+            "Delimiter must not be a letter, digit or one of `-:+._` since they appear in the fields"
+                .to_string(),
+        ),
+        c => Ok(c),
+    }
+}
+
+/// Warnings of value parsers, which run before the logger exists. Logged via `take_parse_warnings`.
+static PARSE_WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Warnings collected while parsing the arguments, e.g. about durations without unit.
+pub fn take_parse_warnings() -> Vec<String> {
+    std::mem::take(
+        &mut PARSE_WARNINGS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+    )
+}
+
+/// The one parser behind every flag taking a duration, e.g. `2h`, `1.5h`, `0.25h`, `1h30m`, `90m` or `45s`.
+/// Numbers without unit mean minutes, with a warning since `1.5` reads like hours just as well.
+/// Negative durations are invalid, whether zero is allowed depends on the flag, see `parse_positive_duration`.
+pub fn parse_duration(s: &str) -> Result<TimeDelta, String> {
+    let invalid =
+        || format!("Invalid duration '{s}', use for example `2h`, `45m`, `1h30m` or `1.5h`");
+    let input = s.trim().to_lowercase();
+
+    if input.starts_with('-') {
+        return Err(format!("Invalid duration '{s}', it must not be negative"));
+    }
+    if !input.is_empty() && input.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        let minutes: f64 = input.parse().map_err(|_| invalid())?;
+        PARSE_WARNINGS.lock().unwrap_or_else(PoisonError::into_inner).push(format!(
+            "Assuming minutes for the duration '{s}' without unit, append one like `{input}m` or `{input}h`"
+        ));
+        return Ok(TimeDelta::seconds((minutes * 60.0).round() as i64));
+    }
+
+    let mut duration = TimeDelta::zero();
+    let mut number = String::new();
+    for c in input.chars() {
+        let seconds_per_unit = match c {
+            '0'..='9' | '.' => {
+                number.push(c);
+                continue;
+            }
+            'h' => 60.0 * 60.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ => return Err(invalid()),
+        };
+
+        let value: f64 = number.parse().map_err(|_| invalid())?;
+        duration += TimeDelta::seconds((value * seconds_per_unit).round() as i64);
+        number.clear();
+    }
+
+    if input.is_empty() || !number.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(duration)
+}
+
+/// `parse_duration` for flags where nothing else than a positive duration makes sense, `what` names the flag in the error.
+fn parse_positive_duration(s: &str, what: &str) -> Result<TimeDelta, String> {
+    let duration = parse_duration(s)?;
+    if duration <= TimeDelta::zero() {
+        return Err(format!("{what} must be longer than zero, got '{s}'"));
+    }
+
+    Ok(duration)
+}
+
+fn parse_estimate(s: &str) -> Result<TimeDelta, String> {
+    parse_positive_duration(s, "An estimate")
+}
+
+fn parse_weekly_goal(s: &str) -> Result<TimeDelta, String> {
+    parse_positive_duration(s, "The weekly goal")
+}
+
+fn parse_work_hours(s: &str) -> Result<TimeDelta, String> {
+    parse_positive_duration(s, "The work hours per day")
+}
+
+fn parse_rounding_granularity(s: &str) -> Result<TimeDelta, String> {
+    parse_positive_duration(s, "The rounding granularity")
+}
+
+/// Count of months like `3months`, `1month` or `3`.
+fn parse_months(s: &str) -> Result<u32, String> {
+    let input = s.trim().to_lowercase();
+    let number = input
+        .strip_suffix("months")
+        .or_else(|| input.strip_suffix("month"))
+        .unwrap_or(&input);
+
+    number
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid count of months '{s}', use for example `3months`"))
+}
+
+fn parse_search_pattern(s: &str) -> Result<SearchPattern, String> {
+    SearchPattern::new(s).map_err(|e| e.to_string())
+}
+
+fn parse_note_time(s: &str) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+    let local = match NaiveTime::parse_from_str(s, "%H:%M") {
+        Ok(time) => Local::now().date_naive().and_time(time),
+        Err(_) => NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").map_err(|_| {
+            format!("Invalid time '{s}', use for example `14:32` or `2024-06-12 14:32`")
+        })?,
+    };
+
+    // Clocks turned back repeat times, the earlier one is the likelier guess
+    local
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|time| time.to_utc())
+        .ok_or_else(|| format!("The time '{s}' does not exist in the local timezone"))
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("Invalid weekday '{s}', use for example `mon` or `monday`"))
 }
 
 fn parse_date_filter(s: &str) -> Result<ListFilter, String> {
@@ -173,17 +1043,100 @@ fn parse_date_filter(s: &str) -> Result<ListFilter, String> {
     }
 }
 
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum OutputJsonFormat {
     Compact,
     Pretty,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StorageFormat {
+    /// `storage.json`
+    Json,
+    /// `storage.toml`, friendlier for editing by hand and for diffs when versioning the store
+    Toml,
+    /// `storage.jsonl` with one finished time box per line plus `storage.state` with the active one.
+    /// Ending appends a line instead of rewriting everything, for large stores on slow disks.
+    /// Backups still copy the whole store, see `--keep-backups`
+    Jsonl,
+}
+
+impl StorageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StorageFormat::Json => "json",
+            StorageFormat::Toml => "toml",
+            StorageFormat::Jsonl => "jsonl",
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("storage.{}", self.extension())
+    }
+
+    /// The storage format matching the extension of `path`, `None` for other extensions.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        [
+            StorageFormat::Json,
+            StorageFormat::Toml,
+            StorageFormat::Jsonl,
+        ]
+        .into_iter()
+        .find(|format| format.extension() == extension)
+    }
+}
+
 impl From<OutputJsonFormat> for JsonStorageStrategy {
     fn from(value: OutputJsonFormat) -> Self {
         match value {
-            OutputJsonFormat::Compact => Self { pretty: false },
-            OutputJsonFormat::Pretty => Self { pretty: true },
+            OutputJsonFormat::Compact => Self {
+                pretty: false,
+                repair: true,
+            },
+            OutputJsonFormat::Pretty => Self {
+                pretty: true,
+                repair: true,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DurationFormat {
+    /// Decimal hours, for example `2.37h`
+    Decimal,
+    /// For example `2h 22m`
+    HoursMinutes,
+    /// For example `02:22`
+    Clock,
+}
+
+impl From<DurationFormat> for DurationStyle {
+    fn from(value: DurationFormat) -> Self {
+        match value {
+            DurationFormat::Decimal => Self::DecimalHours,
+            DurationFormat::HoursMinutes => Self::HoursMinutes,
+            DurationFormat::Clock => Self::Clock,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum RoundMode {
+    Up,
+    Down,
+    /// Halfway rounds up
+    Nearest,
+}
+
+impl From<RoundMode> for RoundingMode {
+    fn from(value: RoundMode) -> Self {
+        match value {
+            RoundMode::Up => Self::Up,
+            RoundMode::Down => Self::Down,
+            RoundMode::Nearest => Self::Nearest,
         }
     }
 }
@@ -202,3 +1155,231 @@ impl From<ListOrder> for SortOrder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_time_is_local_today_or_a_full_date() {
+        let today = Local::now().date_naive();
+        let at = parse_note_time("14:32").unwrap().with_timezone(&Local);
+        assert_eq!(today.and_hms_opt(14, 32, 0).unwrap(), at.naive_local());
+
+        let at = parse_note_time(" 2024-06-12 09:05 ")
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(
+            "2024-06-12 09:05",
+            at.naive_local().format("%Y-%m-%d %H:%M").to_string()
+        );
+
+        assert!(parse_note_time("25:00").is_err());
+        assert!(parse_note_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn csv_delimiter_must_be_single_ascii_char() {
+        assert_eq!(Ok(','), parse_csv_delimiter(","));
+        assert_eq!(Ok('|'), parse_csv_delimiter("|"));
+        assert!(parse_csv_delimiter("").is_err());
+        assert!(parse_csv_delimiter(",,").is_err());
+        assert!(parse_csv_delimiter("\t").is_err());
+        assert!(parse_csv_delimiter("\n").is_err());
+        assert!(parse_csv_delimiter("\"").is_err());
+        assert!(parse_csv_delimiter("§").is_err());
+        for delimiter in ["t", "0", "-", ":", "+", ".", "_"] {
+            assert!(parse_csv_delimiter(delimiter).is_err(), "{delimiter}");
+        }
+    }
+
+    #[test]
+    fn rounding_granularity_must_be_positive() {
+        let parse = |args: &[&str]| Args::try_parse_from([&["_", "export"], args].concat());
+
+        assert!(parse(&["--round", "0"]).is_err());
+        assert!(parse(&["--round", "-15"]).is_err());
+        assert!(parse(&["--round-mode", "down"]).is_err());
+
+        let Commands::Export { rounding, .. } = parse(&["--round", "15"]).unwrap().command else {
+            unreachable!()
+        };
+        assert_eq!(
+            Some((TimeDelta::minutes(15), RoundingMode::Up)),
+            rounding.rounding()
+        );
+    }
+
+    #[test]
+    fn summary_is_a_daily_report() {
+        let Commands::Report { group_by, date, .. } =
+            Args::try_parse_from(["_", "summary", "--date", "yesterday"])
+                .unwrap()
+                .command
+        else {
+            unreachable!()
+        };
+        assert_eq!(ReportGroupBy::Day, group_by);
+        assert!(matches!(date, Some(ListFilter::Date(_))));
+    }
+
+    #[test]
+    fn reports_round_like_exports_but_not_estimates() {
+        let parse = |args: &[&str]| Args::try_parse_from([&["_", "summary"], args].concat());
+
+        let Commands::Report { rounding, .. } =
+            parse(&["--round", "15m", "--round-mode", "nearest"])
+                .unwrap()
+                .command
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            Some((TimeDelta::minutes(15), RoundingMode::Nearest)),
+            rounding.rounding()
+        );
+
+        assert!(parse(&["--round", "15m", "--estimates"]).is_err());
+    }
+
+    #[test]
+    fn filters_list_every_match_and_combine() {
+        let parse = |args: &[&str]| Args::try_parse_from([&["_", "list"], args].concat());
+
+        let Commands::List { selection, .. } = parse(&["--tag", "#ClientA"]).unwrap().command
+        else {
+            unreachable!()
+        };
+        let options = selection.options();
+        assert!(
+            matches!(options.query.filters[..], [ListFilter::Tag(ref tag)] if tag == "#ClientA")
+        );
+        assert_eq!(usize::MAX, options.take);
+
+        let Commands::List { selection, .. } =
+            parse(&["--search", "login", "--date", "today", "--tag", "bug"])
+                .unwrap()
+                .command
+        else {
+            unreachable!()
+        };
+        assert!(matches!(
+            selection.options().query.filters[..],
+            [
+                ListFilter::Date(_),
+                ListFilter::Tag(_),
+                ListFilter::Search(ref term)
+            ] if term == "login"
+        ));
+
+        let Commands::List { selection, .. } =
+            parse(&["--regex", "log(in|out)", "--search", "bug"])
+                .unwrap()
+                .command
+        else {
+            unreachable!()
+        };
+        assert!(matches!(
+            selection.options().query.filters[..],
+            [ListFilter::Search(_), ListFilter::Pattern(ref p)] if p.as_str() == "log(in|out)"
+        ));
+
+        let error = parse(&["--regex", "log(in"]).unwrap_err();
+        assert_eq!(clap::error::ErrorKind::ValueValidation, error.kind());
+    }
+
+    #[test]
+    fn collapsing_takes_a_count_of_months() {
+        let parse = |args: &[&str]| Args::try_parse_from([&["_", "list"], args].concat());
+        let months = |value: &str| match parse(&["--collapse-older-than", value])
+            .ok()
+            .map(|args| args.command)
+        {
+            Some(Commands::List {
+                collapse_older_than,
+                ..
+            }) => collapse_older_than,
+            _ => None,
+        };
+
+        assert_eq!(Some(3), months("3months"));
+        assert_eq!(Some(1), months("1 Month"));
+        assert_eq!(Some(6), months("6"));
+        assert_eq!(None, months("3weeks"));
+        assert!(parse(&["--collapse-older-than", "3", "--compact"]).is_err());
+    }
+
+    #[test]
+    fn durations_parse_the_same_for_every_flag() {
+        let minutes = |m: i64| Ok(TimeDelta::minutes(m));
+        let seconds = |s: i64| Ok(TimeDelta::seconds(s));
+
+        for (input, expected) in [
+            ("2h", minutes(120)),
+            ("1.5h", minutes(90)),
+            ("1.5H", minutes(90)),
+            ("0.25h", minutes(15)),
+            (".5h", minutes(30)),
+            ("1h30m", minutes(90)),
+            ("1h 30m", Err(())),
+            (" 90m ", minutes(90)),
+            ("45s", seconds(45)),
+            ("1m30s", seconds(90)),
+            ("0.5m", seconds(30)),
+            ("90", minutes(90)),
+            ("2.5", seconds(150)),
+            ("0h", minutes(0)),
+            ("0", minutes(0)),
+            ("-15m", Err(())),
+            ("-15", Err(())),
+            ("", Err(())),
+            ("h", Err(())),
+            ("2d", Err(())),
+            ("1..5h", Err(())),
+            ("1h30", Err(())),
+            ("1.5.h", Err(())),
+        ] {
+            assert_eq!(expected, parse_duration(input).map_err(|_| ()), "{input}");
+        }
+
+        let warnings = take_parse_warnings();
+        assert!(
+            warnings
+                .iter()
+                .any(|w| w.starts_with("Assuming minutes for the duration '90'"))
+        );
+        assert!(!warnings.iter().any(|w| w.contains("'90m'")));
+    }
+
+    #[test]
+    fn durations_must_be_positive_where_zero_makes_no_sense() {
+        assert_eq!(Ok(TimeDelta::minutes(90)), parse_estimate("1.5h"));
+        assert_eq!(
+            Err("An estimate must be longer than zero, got '0h0m'".to_string()),
+            parse_estimate("0h0m")
+        );
+        assert!(
+            parse_weekly_goal("0")
+                .unwrap_err()
+                .starts_with("The weekly goal")
+        );
+        assert!(
+            parse_work_hours("0s")
+                .unwrap_err()
+                .starts_with("The work hours")
+        );
+        assert!(parse_estimate("-1h").unwrap_err().contains("negative"));
+
+        let parse = |args: &[&str]| Args::try_parse_from([&["_"], args].concat());
+        assert!(parse(&["begin", "x", "--estimate", "0m"]).is_err());
+        assert!(parse(&["goal", "set", "0.5h"]).is_ok());
+        assert!(parse(&["goal", "status", "--work-hours", "0h"]).is_err());
+
+        let Commands::Export { rounding, .. } =
+            parse(&["export", "--round", "0.25h"]).unwrap().command
+        else {
+            unreachable!()
+        };
+        assert_eq!(Some(TimeDelta::minutes(15)), rounding.round);
+    }
+}
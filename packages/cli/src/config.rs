@@ -0,0 +1,83 @@
+use std::{io::ErrorKind, path::Path};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::args::{ColorChoice, DurationFormat};
+
+/// How the CLI presents things, kept next to the store in `config.json` regardless of the storage format.
+/// Settings describing the tracked time live in the store instead, see [`timetracker::StoreSettings`].
+/// Every field defaults the same way its flag does, flags take precedence.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Config {
+    /// See `--color`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<ColorChoice>,
+    /// See `--duration-format`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_format: Option<DurationFormat>,
+    /// See `--width`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<usize>,
+    /// See `--plain`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plain: Option<bool>,
+    /// See `--no-pager`, `false` disables the pager.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pager: Option<bool>,
+    /// See `--no-suggestions`, `false` disables suggestions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestions: Option<bool>,
+}
+
+impl Config {
+    /// Missing files are no error, they mean nothing was configured yet.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).with_context(|| {
+                format!(
+                    "Failed to parse the config, try fixing malformed fields. Path: \"{}\"",
+                    path.display()
+                )
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(anyhow::Error::new(e)
+                .context(format!("Failed to read the config \"{}\"", path.display()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_config_is_empty_and_fields_use_the_flag_values() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.json");
+
+        assert_eq!(Config::default(), Config::load(&path)?);
+
+        std::fs::write(
+            &path,
+            r#"{"color": "never", "duration-format": "hours-minutes", "width": 100}"#,
+        )?;
+        assert_eq!(
+            Config {
+                color: Some(ColorChoice::Never),
+                duration_format: Some(DurationFormat::HoursMinutes),
+                width: Some(100),
+                plain: None,
+                pager: None,
+                suggestions: None,
+            },
+            Config::load(&path)?
+        );
+
+        std::fs::write(&path, r#"{"color": "sometimes"}"#)?;
+        assert!(Config::load(&path).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,820 @@
+use anyhow::{Context, bail};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, collections::HashSet, path::Path};
+use timetracker::{
+    DurationStyle, ExportedTimeBox, NoteKind, TimeBox, TimeBoxNote, TimeTrackerExport,
+    format_duration,
+};
+
+use crate::helpers::{DurationOptions, write_atomic};
+
+/// Default separator of the fields of the CSV export
+pub const CSV_DELIMITER: char = ';';
+
+/// Quotes a field as described in RFC 4180 if it contains the delimiter, quotes or line breaks.
+/// Embedded quotes get escaped by doubling them.
+fn escape_csv_field(field: &str, delimiter: char) -> Cow<'_, str> {
+    let needs_quoting = field
+        .chars()
+        .any(|c| c == delimiter || c == '"' || c == '\n' || c == '\r');
+
+    match needs_quoting {
+        true => Cow::Owned(format!("\"{}\"", field.replace('"', "\"\""))),
+        false => Cow::Borrowed(field),
+    }
+}
+
+/// The `hours` column is a plain number for `DurationStyle::DecimalHours` so spreadsheets can sum it up.
+pub fn generate_csv_export(
+    finished_time_boxes: &[TimeBox],
+    delimiter: char,
+    duration_style: DurationStyle,
+    durations: DurationOptions,
+) -> anyhow::Result<String> {
+    let mut output = String::with_capacity(4096);
+    let d = delimiter;
+
+    output.push_str(&format!(
+        "time_start{d}time_stop{d}hours{d}description{d}ref"
+    ));
+
+    for time_box in finished_time_boxes.iter() {
+        let time_start = time_box
+            .time_start()?
+            .with_timezone(&chrono::Local)
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+
+        let time_stop = time_box
+            .time_stop()?
+            .with_timezone(&chrono::Local)
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+
+        let duration = durations.duration(time_box)?;
+
+        let hours = match duration_style {
+            DurationStyle::DecimalHours => {
+                format!("{:.2}", duration.num_seconds() as f64 / 60.0 / 60.0)
+            }
+            style => format_duration(duration, style),
+        };
+
+        let description = time_box
+            .notes
+            .iter()
+            .map(|t| format!("- {}", t.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        output.push_str(&format!(
+            "\n{}{d}{}{d}{}{d}{}{d}{}",
+            escape_csv_field(&time_start, d),
+            escape_csv_field(&time_stop, d),
+            escape_csv_field(&hours, d),
+            escape_csv_field(&description, d),
+            escape_csv_field(time_box.reference.as_deref().unwrap_or_default(), d)
+        ));
+    }
+
+    output.push('\n');
+
+    Ok(output)
+}
+
+/// Splits CSV into records of fields, the counterpart of `escape_csv_field`.
+/// Quoted fields may contain the delimiter, line breaks and doubled quotes.
+fn parse_csv_records(content: &str, delimiter: char) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => bail!("Unterminated quoted field in record {}", records.len() + 1),
+                }
+            },
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            // Blank lines, e.g. the final line break of `println!`, hold no record
+            '\n' if field.is_empty() && record.is_empty() => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Reconstructs the time boxes of the `csv` export, whatever its delimiter and duration format.
+/// Each line starting with `- ` begins a note, other lines continue the one before.
+/// The export only contains the start and stop, so notes in between get the time of the start.
+/// Pauses, estimates and whether a time box was exported are not part of the export either.
+pub fn parse_csv_export(content: &str) -> anyhow::Result<Vec<TimeBox>> {
+    let Some(delimiter) = content
+        .strip_prefix("time_start")
+        .and_then(|s| s.chars().next())
+    else {
+        bail!(
+            "Missing the header `time_start;time_stop;hours;description;ref` of the `csv` export"
+        );
+    };
+    let parse_time = |s: &str| -> anyhow::Result<DateTime<Utc>> {
+        Ok(DateTime::parse_from_rfc3339(s)
+            .with_context(|| format!("Invalid time '{s}'"))?
+            .to_utc())
+    };
+
+    let mut time_boxes = Vec::new();
+    for (line, record) in parse_csv_records(content, delimiter)?
+        .into_iter()
+        .enumerate()
+        .skip(1)
+    {
+        // Exports from before there were references lack their field
+        let (start, stop, description, reference) = match record.as_slice() {
+            [start, stop, _hours, description] => (start, stop, description, None),
+            [start, stop, _hours, description, reference] => {
+                (start, stop, description, Some(reference.trim()))
+            }
+            _ => bail!(
+                "Expected 4 or 5 fields in record {line}, found {}",
+                record.len()
+            ),
+        };
+        let (start, stop) = (parse_time(start)?, parse_time(stop)?);
+
+        let mut descriptions: Vec<String> = Vec::new();
+        for text in description.split('\n') {
+            match (text.strip_prefix("- "), descriptions.last_mut()) {
+                (Some(note), _) => descriptions.push(note.to_string()),
+                (None, Some(last)) => {
+                    last.push('\n');
+                    last.push_str(text);
+                }
+                (None, None) => bail!("Description of record {line} does not start with `- `"),
+            }
+        }
+
+        let count = descriptions.len();
+        let notes = descriptions
+            .into_iter()
+            .enumerate()
+            .map(|(i, description)| TimeBoxNote {
+                time: if i > 0 && i + 1 == count { stop } else { start },
+                description,
+                kind: NoteKind::Regular,
+            })
+            .collect();
+
+        time_boxes.push(TimeBox {
+            notes,
+            exported_at: None,
+            pauses: Vec::new(),
+            estimate: None,
+            reference: reference
+                .filter(|reference| !reference.is_empty())
+                .map(str::to_string),
+        });
+    }
+
+    Ok(time_boxes)
+}
+
+/// Either shape of the `json` export, see `parse_json_export`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonExport {
+    Export(TimeTrackerExport),
+    /// `--raw`, additional fields like `hours` get ignored
+    Raw(Vec<TimeBox>),
+}
+
+/// Reconstructs the finished time boxes of the `json` export, with or without `--raw`,
+/// and whether it contained an active time box, which gets left out.
+pub fn parse_json_export(content: &str) -> anyhow::Result<(Vec<TimeBox>, bool)> {
+    let export: JsonExport = serde_json::from_str(content).context(
+        "Expected the output of `export json`, either an object or an array with `--raw`",
+    )?;
+
+    Ok(match export {
+        JsonExport::Export(export) => (
+            export
+                .finished
+                .iter()
+                .map(ExportedTimeBox::to_time_box)
+                .collect(),
+            export.active.is_some(),
+        ),
+        JsonExport::Raw(time_boxes) => (time_boxes, false),
+    })
+}
+
+/// Time box of the raw JSON export, with its duration as computed for the CSV export
+#[derive(Debug, Serialize)]
+pub struct JsonExportItem<'a> {
+    #[serde(flatten)]
+    pub time_box: &'a TimeBox,
+    pub hours: f64,
+}
+
+/// Finished time box of the `json-rich` export, with the durations other tools would otherwise compute themselves
+#[derive(Debug, Serialize)]
+pub struct JsonRichExportItem<'a> {
+    pub time_start: DateTime<Utc>,
+    pub time_stop: DateTime<Utc>,
+    pub hours: f64,
+    pub minutes: f64,
+    pub notes: &'a [TimeBoxNote],
+}
+
+impl<'a> JsonRichExportItem<'a> {
+    pub fn new(time_box: &'a TimeBox, durations: DurationOptions) -> anyhow::Result<Self> {
+        let seconds = durations.duration(time_box)?.num_seconds() as f64;
+        Ok(Self {
+            time_start: time_box.time_start()?,
+            time_stop: time_box.time_stop()?,
+            hours: seconds / 60.0 / 60.0,
+            minutes: seconds / 60.0,
+            notes: &time_box.notes,
+        })
+    }
+}
+
+/// Durations of the finished time boxes get computed as for the CSV export.
+pub fn generate_json_rich_export(
+    finished_time_boxes: &[TimeBox],
+    durations: DurationOptions,
+) -> anyhow::Result<Vec<JsonRichExportItem<'_>>> {
+    finished_time_boxes
+        .iter()
+        .map(|time_box| JsonRichExportItem::new(time_box, durations))
+        .collect()
+}
+
+/// Durations of the finished time boxes get computed as for the CSV export.
+pub fn generate_json_export(
+    finished_time_boxes: &[TimeBox],
+    active_time_box: Option<&TimeBox>,
+    durations: DurationOptions,
+) -> anyhow::Result<TimeTrackerExport> {
+    let finished = finished_time_boxes
+        .iter()
+        .map(|tb| Ok(ExportedTimeBox::finished(tb, durations.duration(tb)?)?))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let active = active_time_box.map(ExportedTimeBox::active).transpose()?;
+
+    Ok(TimeTrackerExport::new(active, finished))
+}
+
+pub fn generate_json_export_raw<'a>(
+    finished_time_boxes: &'a [TimeBox],
+    durations: DurationOptions,
+) -> anyhow::Result<Vec<JsonExportItem<'a>>> {
+    finished_time_boxes
+        .iter()
+        .map(|time_box| {
+            Ok(JsonExportItem {
+                time_box,
+                hours: durations.duration(time_box)?.num_seconds() as f64 / 60.0 / 60.0,
+            })
+        })
+        .collect()
+}
+
+/// JSON Lines with one compact time box per line, without a trailing newline
+pub fn generate_ndjson_export(finished_time_boxes: &[TimeBox]) -> anyhow::Result<String> {
+    let lines = finished_time_boxes
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(lines.join("\n"))
+}
+
+/// 64 bit FNV-1a, unlike the hashers of `std` it stays the same across Rust versions
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Escapes text values as described in RFC 5545 section 3.3.11
+fn escape_ics_text(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            ';' => output.push_str("\\;"),
+            ',' => output.push_str("\\,"),
+            '\n' => output.push_str("\\n"),
+            '\r' => (),
+            c => output.push(c),
+        }
+    }
+
+    output
+}
+
+/// Folds content lines longer than 75 octets as described in RFC 5545 section 3.1.
+/// Never splits multi-byte characters. Each line ends with CRLF.
+fn fold_ics_line(line: &str) -> String {
+    const MAX_OCTETS: usize = 75;
+
+    let mut output = String::with_capacity(line.len() + 8);
+    let mut octets = 0;
+
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_OCTETS {
+            output.push_str("\r\n ");
+            octets = 1; // The leading space of the continuation counts towards the limit
+        }
+
+        output.push(c);
+        octets += c.len_utf8();
+    }
+
+    output.push_str("\r\n");
+    output
+}
+
+/// iCalendar with one event per time box, useful for checking tracked time in calendar apps
+pub fn generate_ics_export(finished_time_boxes: &[TimeBox]) -> anyhow::Result<String> {
+    const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+    let mut output = String::with_capacity(4096);
+
+    output.push_str(&fold_ics_line("BEGIN:VCALENDAR"));
+    output.push_str(&fold_ics_line("VERSION:2.0"));
+    output.push_str(&fold_ics_line(
+        "PRODID:-//Daniel Biegler//bieglers-simple-timetracker//EN",
+    ));
+
+    let mut uids = HashSet::new();
+
+    for time_box in finished_time_boxes.iter() {
+        let time_start = time_box.time_start()?.format(DATE_FORMAT).to_string();
+        let time_stop = time_box.time_stop()?.format(DATE_FORMAT).to_string();
+
+        let summary = time_box.title();
+
+        let description = time_box
+            .notes
+            .iter()
+            .map(|n| n.description.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // Time boxes have no identity and imports may add ones starting at the same time,
+        // so the contents tell them apart while keeping repeated exports identical
+        let base = format!(
+            "{time_start}-{time_stop}-{:016x}",
+            fnv1a(description.as_bytes())
+        );
+        let mut uid = base.clone();
+        for n in 2.. {
+            if uids.insert(uid.clone()) {
+                break;
+            }
+            uid = format!("{base}-{n}");
+        }
+
+        output.push_str(&fold_ics_line("BEGIN:VEVENT"));
+        output.push_str(&fold_ics_line(&format!(
+            "UID:{uid}@bieglers-simple-timetracker"
+        )));
+        // Using the stop keeps repeated exports identical
+        output.push_str(&fold_ics_line(&format!("DTSTAMP:{time_stop}")));
+        output.push_str(&fold_ics_line(&format!("DTSTART:{time_start}")));
+        output.push_str(&fold_ics_line(&format!("DTEND:{time_stop}")));
+        output.push_str(&fold_ics_line(&format!(
+            "SUMMARY:{}",
+            escape_ics_text(summary)
+        )));
+        output.push_str(&fold_ics_line(&format!(
+            "DESCRIPTION:{}",
+            escape_ics_text(&description)
+        )));
+        output.push_str(&fold_ics_line("END:VEVENT"));
+    }
+
+    output.push_str(&fold_ics_line("END:VCALENDAR"));
+
+    Ok(output)
+}
+
+/// Writes `content` of `export --output` into the file at `path` via `write_atomic`, creating missing directories.
+/// Only replaces an existing file if `force` is set.
+pub fn write_export(path: &Path, content: &str, force: bool) -> anyhow::Result<()> {
+    if !force && path.try_exists()? {
+        bail!(
+            "Refusing to overwrite the existing file \"{}\", pass `--force` to replace it",
+            path.display()
+        );
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| {
+            format!(
+                "Failed to create the directory of the export file \"{}\"",
+                path.display()
+            )
+        })?;
+    }
+
+    write_atomic(path, format!("{content}\n").as_bytes())
+        .with_context(|| format!("Failed to write the export file \"{}\"", path.display()))?;
+    info!("Exported into \"{}\"", path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+    use timetracker::{
+        ListOptions, RoundingMode, StoreSettings, in_memory_tracker::InMemoryTimeTracker,
+    };
+
+    use super::*;
+    use crate::{helpers::collect_for_output, test_fixtures::time_box};
+
+    #[test]
+    fn json_export_contains_computed_fields() -> anyhow::Result<()> {
+        let mut finished = vec![
+            time_box("2024-06-01T09:00:00Z", 1),
+            time_box("2024-06-02T09:00:00Z", 2),
+        ];
+        finished[1].notes[1].time -= TimeDelta::minutes(33);
+        let mut active = time_box("2024-06-03T09:00:00Z", 0);
+        active.notes[0].time = Utc::now() - TimeDelta::minutes(30);
+        active.notes.truncate(1);
+
+        let export = generate_json_export(&finished, Some(&active), DurationOptions::default())?;
+        let json: serde_json::Value = serde_json::to_value(&export)?;
+
+        for (tb, exported) in finished.iter().zip(json["finished"].as_array().unwrap()) {
+            assert_eq!(tb.duration_in_hours()?, exported["hours"].as_f64().unwrap());
+            assert_eq!(
+                serde_json::to_value(tb.time_start()?)?,
+                exported["time_start"]
+            );
+            assert_eq!(
+                serde_json::to_value(tb.time_stop()?)?,
+                exported["time_stop"]
+            );
+        }
+        assert_eq!(1.0 + 87.0 / 60.0, json["total_hours"].as_f64().unwrap());
+        assert!(json["active"]["time_stop"].is_null());
+        assert_eq!("start", json["active"]["notes"][0]["description"]);
+
+        let without_active = generate_json_export(&finished, None, DurationOptions::default())?;
+        assert!(serde_json::to_value(&without_active)?["active"].is_null());
+
+        // The raw export is a plain array of time boxes
+        let raw = serde_json::to_value(generate_json_export_raw(
+            &finished,
+            DurationOptions::default(),
+        )?)?;
+        assert_eq!(1.0, raw[0]["hours"].as_f64().unwrap());
+        assert_eq!(serde_json::to_value(&finished[0].notes)?, raw[0]["notes"]);
+
+        // Only the rich export has `minutes` next to the `hours`
+        let rich = serde_json::to_value(generate_json_rich_export(
+            &finished,
+            DurationOptions::default(),
+        )?)?;
+        assert_eq!(1.0 + 27.0 / 60.0, rich[1]["hours"].as_f64().unwrap());
+        assert_eq!(87.0, rich[1]["minutes"].as_f64().unwrap());
+        assert_eq!(
+            serde_json::to_value(finished[1].time_stop()?)?,
+            rich[1]["time_stop"]
+        );
+        assert!(json["finished"][0].get("minutes").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn csv_export_quotes_fields_as_rfc_4180() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].description = r#"Said "hi"; then left"#.to_string();
+        time_box.notes[1].description = "line one\nline two".to_string();
+
+        let mut referenced = time_box.clone();
+        referenced.reference = Some("PROJ-123".to_string());
+
+        let csv = generate_csv_export(
+            &[referenced, time_box],
+            CSV_DELIMITER,
+            DurationStyle::DecimalHours,
+            DurationOptions::default(),
+        )?;
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(CSV_DELIMITER as u8)
+            .from_reader(csv.as_bytes());
+
+        assert_eq!(
+            vec!["time_start", "time_stop", "hours", "description", "ref"],
+            reader.headers()?.iter().collect::<Vec<_>>()
+        );
+
+        let records = reader.records().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(2, records.len());
+
+        for (record, reference) in records.iter().zip(["PROJ-123", ""]) {
+            assert_eq!(5, record.len());
+            assert_eq!("1.00", &record[2]);
+            assert_eq!("- Said \"hi\"; then left\n- line one\nline two", &record[3]);
+            assert_eq!(reference, &record[4]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn csv_export_clamps_negative_durations_unless_raw() -> anyhow::Result<()> {
+        let mut inverted = time_box("2024-06-01T09:00:00Z", 2);
+        inverted.notes.reverse();
+        let hours = |raw: bool| -> anyhow::Result<String> {
+            let csv = generate_csv_export(
+                std::slice::from_ref(&inverted),
+                CSV_DELIMITER,
+                DurationStyle::DecimalHours,
+                DurationOptions {
+                    raw,
+                    rounding: None,
+                },
+            )?;
+            let mut reader = csv::ReaderBuilder::new()
+                .delimiter(CSV_DELIMITER as u8)
+                .from_reader(csv.as_bytes());
+            Ok(reader.records().next().unwrap()?[2].to_string())
+        };
+
+        assert_eq!("0.00", hours(false)?);
+        assert_eq!("-2.00", hours(true)?);
+
+        let rich_minutes = |raw: bool| -> anyhow::Result<f64> {
+            let durations = DurationOptions {
+                raw,
+                rounding: None,
+            };
+            let rich = generate_json_rich_export(std::slice::from_ref(&inverted), durations)?;
+            Ok(rich[0].minutes)
+        };
+        assert_eq!(0.0, rich_minutes(false)?);
+        assert_eq!(-120.0, rich_minutes(true)?);
+
+        let tracker = InMemoryTimeTracker {
+            active: None,
+            finished: vec![time_box("2024-06-01T08:00:00Z", 1), inverted.clone()].into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        let output = collect_for_output(&tracker, &ListOptions::new(), false)?;
+        assert_eq!(1.0, output.hours);
+
+        Ok(())
+    }
+
+    #[test]
+    fn csv_export_and_totals_use_rounded_durations() -> anyhow::Result<()> {
+        let mut empty = time_box("2024-06-01T08:00:00Z", 0);
+        empty.notes.truncate(1);
+        let mut time_boxes = vec![time_box("2024-06-01T09:00:00Z", 1), empty];
+        time_boxes[0].notes[1].time += TimeDelta::minutes(5);
+
+        let durations = DurationOptions {
+            raw: false,
+            rounding: Some((TimeDelta::minutes(15), RoundingMode::Up)),
+        };
+
+        let csv = generate_csv_export(
+            &time_boxes,
+            CSV_DELIMITER,
+            DurationStyle::DecimalHours,
+            durations,
+        )?;
+        let hours: Vec<String> = csv::ReaderBuilder::new()
+            .delimiter(CSV_DELIMITER as u8)
+            .from_reader(csv.as_bytes())
+            .records()
+            .map(|r| Ok(r?[2].to_string()))
+            .collect::<anyhow::Result<_>>()?;
+        assert_eq!(vec!["1.25", "0.25"], hours);
+
+        let rich = generate_json_rich_export(&time_boxes, durations)?;
+        assert_eq!(1.25, rich[0].hours);
+        assert_eq!(75.0, rich[0].minutes);
+
+        assert_eq!(TimeDelta::minutes(90), durations.total(&time_boxes)?);
+        assert_eq!(
+            TimeDelta::minutes(65),
+            DurationOptions::default().total(&time_boxes)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn csv_export_with_custom_delimiter() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].description = "a, b; c".to_string();
+        let time_boxes = [time_box.clone(), time_box];
+
+        let semicolon = generate_csv_export(
+            &time_boxes,
+            ';',
+            DurationStyle::DecimalHours,
+            DurationOptions::default(),
+        )?;
+        let comma = generate_csv_export(
+            &time_boxes,
+            ',',
+            DurationStyle::DecimalHours,
+            DurationOptions::default(),
+        )?;
+        assert_ne!(semicolon, comma);
+        assert!(comma.starts_with("time_start,time_stop,hours,description,ref\n"));
+
+        let parse = |csv: &str, delimiter: u8| -> anyhow::Result<Vec<csv::StringRecord>> {
+            Ok(csv::ReaderBuilder::new()
+                .delimiter(delimiter)
+                .from_reader(csv.as_bytes())
+                .records()
+                .collect::<Result<Vec<_>, _>>()?)
+        };
+
+        // Same content regardless of the delimiter
+        assert_eq!(parse(&semicolon, b';')?, parse(&comma, b',')?);
+        Ok(())
+    }
+
+    #[test]
+    fn csv_export_quotes_every_field_containing_the_delimiter() -> anyhow::Result<()> {
+        let time_boxes = [time_box("2024-06-01T09:00:00Z", 1)];
+        let csv = generate_csv_export(
+            &time_boxes,
+            ' ',
+            DurationStyle::Words,
+            DurationOptions::default(),
+        )?;
+
+        let records = csv::ReaderBuilder::new()
+            .delimiter(b' ')
+            .from_reader(csv.as_bytes())
+            .records()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(1, records.len());
+        assert_eq!(5, records[0].len());
+        assert_eq!("1 hour", &records[0][2]);
+        assert_eq!("- start\n- stop", &records[0][3]);
+
+        let parsed = parse_csv_export(&csv)?;
+        assert_eq!(1, parsed.len());
+        assert_eq!(time_boxes[0].time_start()?, parsed[0].time_start()?);
+        assert_eq!(time_boxes[0].time_stop()?, parsed[0].time_stop()?);
+        Ok(())
+    }
+
+    #[test]
+    fn csv_fields_only_get_quoted_if_needed() {
+        assert_eq!("plain", escape_csv_field("plain", ';'));
+        assert_eq!("\"a;b\"", escape_csv_field("a;b", ';'));
+        assert_eq!("a;b", escape_csv_field("a;b", ','));
+        assert_eq!("\"say \"\"hi\"\"\"", escape_csv_field("say \"hi\"", ';'));
+        assert_eq!("\"a\nb\"", escape_csv_field("a\nb", ';'));
+    }
+
+    #[test]
+    fn ics_text_gets_escaped() {
+        assert_eq!("a\\, b\\; c\\\\d\\ne", escape_ics_text("a, b; c\\d\r\ne"));
+    }
+
+    #[test]
+    fn ics_lines_get_folded_at_75_octets() {
+        assert_eq!("short\r\n", fold_ics_line("short"));
+
+        let folded = fold_ics_line(&"x".repeat(160));
+        let lines: Vec<_> = folded.split_terminator("\r\n").collect();
+        assert_eq!(3, lines.len());
+        assert_eq!(75, lines[0].len());
+        assert_eq!(format!(" {}", "x".repeat(74)), lines[1]);
+        assert_eq!(format!(" {}", "x".repeat(11)), lines[2]);
+
+        // Multi-byte characters stay intact
+        let folded = fold_ics_line(&"ä".repeat(80));
+        for line in folded.split_terminator("\r\n") {
+            assert!(line.len() <= 75);
+        }
+        assert_eq!("ä".repeat(80), folded.replace("\r\n ", "").trim_end());
+    }
+
+    #[test]
+    fn ics_export_has_one_event_per_time_box() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].description = "Fix login, finally".to_string();
+        time_box.notes[1].description = "Done; deployed".to_string();
+
+        let ics = generate_ics_export(&[time_box])?;
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert_eq!(1, ics.matches("BEGIN:VEVENT").count());
+        assert!(ics.contains("\r\nDTSTART:20240601T090000Z\r\n"));
+        assert!(ics.contains("\r\nDTEND:20240601T100000Z\r\n"));
+        assert!(ics.contains("\r\nSUMMARY:Fix login\\, finally\r\n"));
+        assert!(ics.contains("\r\nDESCRIPTION:Fix login\\, finally\\nDone\\; deployed\r\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn ics_uids_differ_for_time_boxes_starting_at_the_same_time() -> anyhow::Result<()> {
+        let uids = |ics: &str| -> Vec<String> {
+            ics.lines()
+                .filter(|l| l.starts_with("UID:"))
+                .map(str::to_owned)
+                .collect()
+        };
+        let short = time_box("2024-06-01T09:00:00Z", 1);
+        let long = time_box("2024-06-01T09:00:00Z", 2);
+        let mut other = short.clone();
+        other.notes[0].description = "Something else".to_string();
+
+        // Same start, with another stop, other notes or even identical
+        let time_boxes = [short.clone(), long, other, short];
+        let first = uids(&generate_ics_export(&time_boxes)?);
+        let unique: HashSet<_> = first.iter().collect();
+        assert_eq!(4, unique.len(), "{first:?}");
+        assert!(first[0].starts_with("UID:20240601T090000Z-20240601T100000Z-"));
+
+        // Repeated exports keep the UIDs, so calendars update events instead of duplicating them
+        assert_eq!(first, uids(&generate_ics_export(&time_boxes)?));
+        Ok(())
+    }
+
+    #[test]
+    fn ndjson_export_has_one_time_box_per_line() -> anyhow::Result<()> {
+        let mut time_boxes = vec![
+            time_box("2024-06-01T09:00:00Z", 1),
+            time_box("2024-06-01T11:00:00Z", 2),
+        ];
+        time_boxes[0].notes[0].description = "Multi\nline".to_string();
+
+        let ndjson = generate_ndjson_export(&time_boxes)?;
+
+        assert_eq!(time_boxes.len(), ndjson.lines().count());
+        for (line, expected) in ndjson.lines().zip(&time_boxes) {
+            let parsed: TimeBox = serde_json::from_str(line)?;
+            assert_eq!(expected, &parsed);
+        }
+        assert_eq!("", generate_ndjson_export(&[])?);
+        Ok(())
+    }
+
+    #[test]
+    fn csv_export_file_gets_written_atomically() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("reports").join("june.csv");
+        let time_boxes = [time_box("2024-06-01T09:00:00Z", 1)];
+        let csv = generate_csv_export(
+            &time_boxes,
+            ';',
+            DurationStyle::default(),
+            DurationOptions::default(),
+        )?;
+
+        // Missing directories get created
+        write_export(&path, &csv, false)?;
+        assert_eq!(format!("{csv}\n"), std::fs::read_to_string(&path)?);
+        let entries = std::fs::read_dir(path.parent().unwrap())?.count();
+        assert_eq!(1, entries, "the swap file got renamed");
+
+        let error = write_export(&path, "replaced", false).unwrap_err();
+        assert!(error.to_string().contains("--force"), "{error}");
+        assert_eq!(format!("{csv}\n"), std::fs::read_to_string(&path)?);
+
+        write_export(&path, "replaced", true)?;
+        assert_eq!("replaced\n", std::fs::read_to_string(&path)?);
+
+        Ok(())
+    }
+}
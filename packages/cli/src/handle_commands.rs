@@ -1,107 +1,716 @@
-use std::{fs::File, io::Write, path::Path};
+use std::{
+    fs::File,
+    io::{IsTerminal, Write},
+    path::Path,
+    process::ExitCode,
+};
 
 use anyhow::{Context, anyhow, bail};
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, Utc, Weekday};
 use clap::CommandFactory;
-use log::{debug, warn};
+use log::{debug, info, warn};
 use timetracker::{
-    ListOptions, TimeTrackerStorageStrategy, TimeTrackingStore,
+    DurationStyle, EstimateSummary, Grouping, ListFilter, ListOptions, ObservedStore, Query,
+    RoundingMode, StoreSettings, TimeBox, TimeTrackingStore, clamped_duration, diff_stores,
+    format_duration, group_time_boxes, humanize_delta,
     in_memory_tracker::InMemoryTimeTracker,
+    json_cache::{CacheState, inspect_cache},
+    legacy::LegacyFileLoadingStrategy,
+    plan_weekly_goal, roll_up_weeks, rounded_hours, suggest_lock, summarize_days,
+    summarize_estimates, summarize_references, summarize_tags,
 };
 
 use crate::{
-    args::{Args, ExportStrategy},
-    helpers::{generate_csv_export, generate_table, generate_table_active},
+    args::{
+        Args, ExportFileArgs, ExportFormatArgs, ExportStrategy, ImportFormat, InitArgs,
+        ListGroupBy, OutputJsonFormat, ReportFormat, ReportGroupBy, ReportRollUp, StatusFormat,
+        StorageFormat,
+    },
+    export::{
+        generate_csv_export, generate_ics_export, generate_json_export, generate_json_export_raw,
+        generate_json_rich_export, generate_ndjson_export, parse_csv_export, parse_json_export,
+        write_export,
+    },
+    helpers::{
+        DateColumn, DurationOptions, IndexColumn, OutputSettings, PICK_TITLES, StatusMin,
+        TableStyle, WaybarStatus, collapse_cutoff, collect_for_output, compact_description_width,
+        confirm, footer_duration, generate_goal_status, generate_plain_active, generate_plain_list,
+        generate_plain_months, generate_plain_total, generate_prompt, generate_status_json,
+        generate_status_line, generate_table, generate_table_active, generate_table_compact,
+        generate_table_months, generate_weekly_progress, gitignore_content, hours_style,
+        hours_today, is_tracked_in_git, list_backups, load_store, local_midnight, matching_active,
+        paint, pick_description, print_paged, roundtrip_store, save_store, split_at_cutoff,
+        tracked_this_week, write_backup,
+    },
+    projects::{list_projects, load_project},
+    report::{
+        generate_report_plain_days, generate_report_plain_estimates,
+        generate_report_plain_references, generate_report_plain_tags, generate_report_plain_weeks,
+        generate_report_table_days, generate_report_table_estimates,
+        generate_report_table_references, generate_report_table_tags, generate_report_table_weeks,
+    },
+    settings::EffectiveSettings,
 };
 
-type StoreModified = bool;
+/// What a command changed, so storage formats able to write less than the whole store can do so.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreMutation {
+    /// Nothing to save.
+    Unchanged,
+    /// Only the active time box or the settings changed.
+    Active,
+    /// These time boxes got finished and added, the active time box or the settings may have changed too.
+    Appended(Vec<TimeBox>),
+    /// Anything else, e.g. finished time boxes got changed or removed.
+    Rewritten,
+}
 
 pub fn handle_command_start(
-    tracker: &mut InMemoryTimeTracker,
+    tracker: &mut impl TimeTrackingStore,
     description: &str,
-) -> anyhow::Result<StoreModified> {
+) -> anyhow::Result<StoreMutation> {
     tracker
         .begin(description)
         .context(
             "Unable to begin a new time box because tracking is already active. \
             Finish your active time box before beginning a new one.",
         )
-        .map(|_| Ok(true))?
+        .map(|_| Ok(StoreMutation::Active))?
 }
 
-pub fn handle_command_status(tracker: &InMemoryTimeTracker) -> anyhow::Result<StoreModified> {
-    match tracker.active()? {
-        Some(tb) => println!("{}", generate_table_active(tb)?),
-        None => {
+/// Suggests `lock --before` on the first change of a week once the time boxes of the weeks before are exported,
+/// see `suggest_lock`. Remembering the suggestion keeps it from coming up again this week, so the store has to be saved.
+/// Read-only commands share the store with others, so only changing commands get here.
+pub fn suggest_lock_once(
+    tracker: &mut InMemoryTimeTracker,
+    mutation: StoreMutation,
+    today: NaiveDate,
+) -> anyhow::Result<StoreMutation> {
+    let week_start = today.week(Weekday::Mon).first_day();
+    let mut settings = tracker.settings()?;
+    let Some(before) = suggest_lock(&settings, &tracker.finished, local_midnight(week_start)?)
+    else {
+        return Ok(mutation);
+    };
+
+    info!(
+        "Every time box before this week is exported, lock them against changes via `lock --before {week_start}`. \
+        Pass `--no-suggestions` to not get suggestions like this"
+    );
+    settings.lock_suggested_for = Some(before);
+    tracker.set_settings(settings)?;
+
+    Ok(match mutation {
+        StoreMutation::Unchanged => StoreMutation::Active,
+        mutation => mutation,
+    })
+}
+
+/// Begins at `start` instead of now, for starts that were forgotten.
+pub fn handle_command_backfill(
+    tracker: &mut impl TimeTrackingStore,
+    description: &str,
+    start: DateTime<Utc>,
+) -> anyhow::Result<StoreMutation> {
+    let local = |time: DateTime<Utc>| time.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+
+    if start > Utc::now() {
+        bail!(
+            "Unable to backfill to {} since it is in the future, use `begin` without `--backfill` instead",
+            local(start)
+        );
+    }
+
+    match tracker.begin_at(description, start) {
+        Err(timetracker::Error::OverlapsFinishedTimeBox { stop, .. }) => bail!(
+            "Unable to backfill to {} since the last time box stopped later at {}, pick a time after it",
+            local(start),
+            local(stop)
+        ),
+        result => result.context(
+            "Unable to begin a new time box because tracking is already active. \
+            Finish your active time box before beginning a new one.",
+        )?,
+    };
+
+    Ok(StoreMutation::Active)
+}
+
+/// Tells how long ago the last finished time box stopped, so forgotten starts get noticed.
+/// Warns from an hour on unless the time box got backfilled already.
+pub fn log_idle_gap(tracker: &InMemoryTimeTracker, backfilled: bool) -> anyhow::Result<()> {
+    let Some(stop) = tracker.last_stop()? else {
+        return Ok(());
+    };
+
+    let gap = Utc::now() - stop;
+    let ago = humanize_delta(gap);
+    match gap >= TimeDelta::hours(1) && !backfilled {
+        true => {
+            warn!("The last time box ended {ago}, pass `--backfill <TIME>` if you started earlier")
+        }
+        false => info!("The last time box ended {ago}"),
+    }
+
+    Ok(())
+}
+
+/// Sets the ticket id of the active time box as its reference, see `TimeBox::ticket_ids`.
+/// Leaves the reference alone if there is none or several, since guessing would be worse.
+pub fn set_reference_from_ticket_id(tracker: &mut impl TimeTrackingStore) -> anyhow::Result<()> {
+    let Some(active) = tracker.active()? else {
+        return Ok(());
+    };
+
+    match active.ticket_ids().as_slice() {
+        [] => warn!("Found no ticket id like `PROJ-123` to use as reference"),
+        [id] => {
+            tracker.set_reference(Some(id))?;
+            info!("Using the ticket id {id} as reference");
+        }
+        ids => warn!(
+            "Found several ticket ids, pass the reference via `--ref` instead: {}",
+            ids.join(", ")
+        ),
+    }
+
+    Ok(())
+}
+
+/// Begins with a description picked interactively from the titles of recent time boxes.
+pub fn handle_command_begin_pick(
+    tracker: &mut ObservedStore<InMemoryTimeTracker>,
+) -> anyhow::Result<StoreMutation> {
+    if !std::io::stdin().is_terminal() {
+        bail!(
+            "Picking needs an interactive terminal, pass the description instead: `begin <DESCRIPTION>`"
+        );
+    }
+
+    // Fail before asking, not after
+    if tracker.active()?.is_some() {
+        bail!(
+            "Unable to begin a new time box because tracking is already active. \
+            Finish your active time box before beginning a new one."
+        );
+    }
+
+    let titles = tracker.inner().recent_titles(PICK_TITLES);
+    let description = pick_description(
+        &titles,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stderr(),
+    )?;
+
+    handle_command_start(tracker, &description)
+}
+
+/// Prints without trailing line break so the line fits into any prompt.
+pub fn handle_command_prompt(
+    tracker: &InMemoryTimeTracker,
+    format: &str,
+    idle: &str,
+) -> anyhow::Result<StoreMutation> {
+    let line = match tracker.active()? {
+        Some(tb) => generate_prompt(&tb, format)?,
+        None => idle.to_string(),
+    };
+    print!("{line}");
+    std::io::stdout().flush()?;
+
+    Ok(StoreMutation::Unchanged)
+}
+
+/// Never modifies the store, returns the exit code instead so scripts can check for an active time box.
+/// Tables end with the progress towards the `weekly_goal` if there is one.
+pub fn handle_command_status(
+    tracker: &InMemoryTimeTracker,
+    format: StatusFormat,
+    relative: bool,
+    weekly_goal: Option<TimeDelta>,
+    settings: &OutputSettings,
+) -> anyhow::Result<ExitCode> {
+    match (tracker.active()?, format) {
+        (Some(tb), StatusFormat::Table) if settings.plain => {
+            println!("{}", generate_plain_active(&tb, &Local)?);
+            print_weekly_progress(tracker, weekly_goal, settings)?;
+        }
+        (Some(tb), StatusFormat::Table) => {
+            println!(
+                "{}",
+                generate_table_active(
+                    tb,
+                    DateColumn::new(relative),
+                    Some(Utc::now()),
+                    settings.width,
+                    &TableStyle::new(settings.color),
+                    settings.duration_style
+                )?
+            );
+            print_weekly_progress(tracker, weekly_goal, settings)?;
+        }
+        (Some(tb), StatusFormat::Plain) => {
+            println!("{}", generate_status_line(&tb, settings.duration_style)?)
+        }
+        (Some(tb), StatusFormat::Json) => println!("{}", generate_status_json(&tb)?),
+        (None, StatusFormat::Table) => {
             return Err(anyhow!(
                 "There is currently no active time box. \
                 You may begin a new one by using the `begin` command, \
                 see `timetracker-cli help begin` for more info"
             ));
         }
+        (None, StatusFormat::Plain) => return Ok(ExitCode::FAILURE),
+        (None, StatusFormat::Json) => {
+            println!("null");
+            return Ok(ExitCode::FAILURE);
+        }
+        (active, StatusFormat::JsonMin) => {
+            let status = StatusMin::new(active.as_ref(), hours_today(tracker)?)?;
+            println!("{}", serde_json::to_string(&status)?);
+        }
+        (active, StatusFormat::Waybar) => {
+            let status = StatusMin::new(active.as_ref(), hours_today(tracker)?)?;
+            println!("{}", serde_json::to_string(&WaybarStatus::from(&status))?);
+        }
     }
 
-    Ok(false)
+    Ok(ExitCode::SUCCESS)
 }
 
+/// With `at` the note gets backdated, refusing times before the start unless `allow_extend`
+/// and times in the future since those have not happened yet.
 pub fn handle_command_note(
-    tracker: &mut InMemoryTimeTracker,
+    tracker: &mut impl TimeTrackingStore,
     description: &str,
     finish: bool,
-) -> anyhow::Result<StoreModified> {
-    tracker.push_note(description)?;
+    at: Option<DateTime<Utc>>,
+    allow_extend: bool,
+) -> anyhow::Result<StoreMutation> {
+    match at {
+        None => tracker.push_note(description)?,
+        Some(at) => {
+            let active = tracker
+                .active()?
+                .ok_or(timetracker::Error::NoActiveTimeBox)?;
+            let start = active.time_start()?;
+            if at < start && !allow_extend {
+                bail!(
+                    "Refusing to add a note at {} before the start of the time box at {}. \
+                    Pass `--allow-extend` to make it the new start.",
+                    at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                    start.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                );
+            }
+            if at > Utc::now() {
+                bail!(
+                    "Refusing to add a note at {} since that is in the future",
+                    at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                );
+            }
+            tracker.push_note_at(description, at)?
+        }
+    };
 
-    if finish {
-        tracker.end()?;
+    match finish {
+        true => Ok(StoreMutation::Appended(vec![tracker.end()?])),
+        false => Ok(StoreMutation::Active),
     }
-
-    Ok(true)
 }
 
+/// With `mark_exported` every exported time box gets stamped, but only if there was anything to export.
+#[allow(clippy::too_many_arguments)]
 pub fn handle_command_export(
-    tracker: &InMemoryTimeTracker,
+    tracker: &mut impl TimeTrackingStore,
     strategy: ExportStrategy,
-) -> anyhow::Result<StoreModified> {
-    let finished = tracker.finished(&ListOptions::new())?.items;
+    format: &ExportFormatArgs,
+    file: &ExportFileArgs,
+    options: &ListOptions,
+    mark_exported: bool,
+    durations: DurationOptions,
+    settings: &OutputSettings,
+) -> anyhow::Result<StoreMutation> {
+    let output = collect_for_output(tracker, options, true)?;
+    let finished = &output.finished.items;
 
     let content = match strategy {
         ExportStrategy::Debug => format!("{finished:#?}"),
-        ExportStrategy::Csv => generate_csv_export(&finished)?,
-        // Including computed fields like hours would probably be nice. Do that once the need comes up.
-        ExportStrategy::Json => serde_json::to_string_pretty::<Vec<_>>(&finished)?,
+        ExportStrategy::Csv => generate_csv_export(
+            finished,
+            format.delimiter,
+            settings.duration_style,
+            durations,
+        )?,
+        ExportStrategy::Json if format.raw => {
+            serde_json::to_string_pretty(&generate_json_export_raw(finished, durations)?)?
+        }
+        ExportStrategy::Json => {
+            let active = output.active.as_ref().filter(|_| format.include_active);
+            serde_json::to_string_pretty(&generate_json_export(finished, active, durations)?)?
+        }
+        ExportStrategy::JsonRich => {
+            serde_json::to_string_pretty(&generate_json_rich_export(finished, durations)?)?
+        }
+        ExportStrategy::Ics => generate_ics_export(finished)?,
+        ExportStrategy::Ndjson => generate_ndjson_export(finished)?,
     };
 
-    if finished.is_empty() {
-        warn!("Exporting did nothing because there are no finished time boxes");
+    let is_empty = output.warn_if_empty("Exporting");
+
+    match &file.output {
+        Some(path) => write_export(path, &content, file.force)?,
+        None => println!("{content}"),
     }
 
-    println!("{content}");
+    if !format.include_active {
+        output.warn_about_active(settings)?;
+    }
 
-    if let Some(tb) = tracker.active()? {
-        warn!(
-            "There is an active time box:\n{}",
-            generate_table_active(tb)?
-        )
+    if !mark_exported || is_empty {
+        return Ok(StoreMutation::Unchanged);
     }
 
-    Ok(false)
+    // Marking may move a time box behind otherwise equal ones, starting at the back keeps the remaining indices valid
+    let mut indices = output.finished.indices.clone();
+    indices.sort_unstable_by(|a, b| b.cmp(a));
+
+    let now = Utc::now();
+    for index in indices {
+        tracker.set_exported_at(index, Some(now))?;
+    }
+    info!(
+        "Marked {} time boxes as exported",
+        output.finished.indices.len()
+    );
+
+    Ok(StoreMutation::Rewritten)
+}
+
+/// `options` select the time boxes to report on, see `Commands::Report` for the defaults.
+/// Tables of days or weeks end with the progress towards the `weekly_goal` if there is one.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_command_report(
+    tracker: &InMemoryTimeTracker,
+    options: &ListOptions,
+    group_by: ReportGroupBy,
+    roll_up: Option<ReportRollUp>,
+    format: ReportFormat,
+    estimates: bool,
+    rounding: Option<(TimeDelta, RoundingMode)>,
+    weekly_goal: Option<TimeDelta>,
+    settings: &OutputSettings,
+) -> anyhow::Result<StoreMutation> {
+    if group_by != ReportGroupBy::Day && roll_up.is_some() {
+        bail!("Rolling up only works when grouping by day, e.g. `--group-by day --roll-up week`");
+    }
+
+    let output = collect_for_output(tracker, options, false)?;
+
+    if output.warn_if_empty("Reporting") {
+        return Ok(StoreMutation::Unchanged);
+    }
+
+    if estimates {
+        let (grouping, label_col_label) = match group_by {
+            ReportGroupBy::Day => (Grouping::Day, "Day"),
+            ReportGroupBy::Week => (Grouping::Week, "Week"),
+            ReportGroupBy::Tag | ReportGroupBy::Ref => {
+                bail!("Estimates can only be grouped by day or week")
+            }
+        };
+        let summaries = summarize_estimates(&output.finished.items, grouping, &Local);
+        if summaries.is_empty() {
+            warn!("Reporting did nothing because there are no time boxes with an estimate");
+            return Ok(StoreMutation::Unchanged);
+        }
+
+        let content = match format {
+            ReportFormat::Table if settings.plain => generate_report_plain_estimates(
+                &summaries,
+                &EstimateSummary::of("total".to_string(), &output.finished.items),
+            ),
+            ReportFormat::Table => generate_report_table_estimates(
+                label_col_label,
+                &summaries,
+                &EstimateSummary::of("total".to_string(), &output.finished.items),
+            ),
+            ReportFormat::Json => serde_json::to_string_pretty(&summaries)?,
+        };
+        print_paged(&format!("{content}\n"), settings);
+
+        return Ok(StoreMutation::Unchanged);
+    }
+
+    if group_by == ReportGroupBy::Tag {
+        let tags = summarize_tags(&output.finished.items, rounding);
+        if tags.is_empty() {
+            warn!("Reporting did nothing because there are no time boxes with a tag");
+            return Ok(StoreMutation::Unchanged);
+        }
+
+        let content = match format {
+            ReportFormat::Table => {
+                let tagged: Vec<&TimeBox> = output
+                    .finished
+                    .items
+                    .iter()
+                    .filter(|tb| !tb.tags().is_empty())
+                    .collect();
+                let hours = tagged.iter().map(|tb| rounded_hours(tb, rounding)).sum();
+                match settings.plain {
+                    true => generate_report_plain_tags(&tags, (tagged.len(), hours)),
+                    false => generate_report_table_tags(&tags, (tagged.len(), hours)),
+                }
+            }
+            ReportFormat::Json => serde_json::to_string_pretty(&tags)?,
+        };
+        print_paged(&format!("{content}\n"), settings);
+
+        return Ok(StoreMutation::Unchanged);
+    }
+
+    if group_by == ReportGroupBy::Ref {
+        let references = summarize_references(&output.finished.items, rounding);
+        if references.is_empty() {
+            warn!("Reporting did nothing because there are no time boxes with a reference");
+            return Ok(StoreMutation::Unchanged);
+        }
+
+        let content = match format {
+            ReportFormat::Table => {
+                let total = references
+                    .iter()
+                    .fold((0, 0.0), |acc, r| (acc.0 + r.count, acc.1 + r.total));
+                match settings.plain {
+                    true => generate_report_plain_references(&references, total),
+                    false => generate_report_table_references(&references, total),
+                }
+            }
+            ReportFormat::Json => serde_json::to_string_pretty(&references)?,
+        };
+        print_paged(&format!("{content}\n"), settings);
+
+        return Ok(StoreMutation::Unchanged);
+    }
+
+    let days = summarize_days(&output.finished.items, &Local, rounding);
+    let is_table = matches!(format, ReportFormat::Table);
+
+    let content = match (group_by, roll_up, format) {
+        (ReportGroupBy::Day, None, ReportFormat::Table) if settings.plain => {
+            generate_report_plain_days(&days)
+        }
+        (ReportGroupBy::Day, None, ReportFormat::Table) => generate_report_table_days(&days),
+        (ReportGroupBy::Day, None, ReportFormat::Json) => serde_json::to_string_pretty(&days)?,
+        (_, roll_up, ReportFormat::Table) if settings.plain => {
+            generate_report_plain_weeks(&roll_up_weeks(days), roll_up == Some(ReportRollUp::Week))
+        }
+        (_, roll_up, ReportFormat::Table) => {
+            generate_report_table_weeks(&roll_up_weeks(days), roll_up == Some(ReportRollUp::Week))
+        }
+        (_, _, ReportFormat::Json) => serde_json::to_string_pretty(&roll_up_weeks(days))?,
+    };
+
+    print_paged(&format!("{content}\n"), settings);
+
+    if is_table {
+        print_weekly_progress(tracker, weekly_goal, settings)?;
+    }
+
+    Ok(StoreMutation::Unchanged)
 }
 
+/// Prints nothing without a `weekly_goal`, see `goal set`.
+fn print_weekly_progress(
+    tracker: &InMemoryTimeTracker,
+    weekly_goal: Option<TimeDelta>,
+    settings: &OutputSettings,
+) -> anyhow::Result<()> {
+    if let Some(goal) = weekly_goal {
+        let tracked = tracked_this_week(tracker, Local::now().date_naive())?;
+        let style = match settings.plain {
+            true => DurationStyle::Words,
+            false => settings.duration_style,
+        };
+        println!("{}", generate_weekly_progress(tracked, goal, style));
+    }
+
+    Ok(())
+}
+
+/// The goal, work days and work hours come from the `effective` settings.
+pub fn handle_command_goal_status(
+    tracker: &InMemoryTimeTracker,
+    effective: &EffectiveSettings,
+    settings: &OutputSettings,
+) -> anyhow::Result<StoreMutation> {
+    let Some(weekly) = effective.weekly_goal.value else {
+        bail!("There is no weekly goal, pass `--weekly <DURATION>` or remember one via `goal set`");
+    };
+    let work_hours = effective.work_hours.value;
+    let today = Local::now().date_naive();
+    let tracked = tracked_this_week(tracker, today)?;
+    let plan = plan_weekly_goal(
+        weekly,
+        tracked,
+        today,
+        &effective.work_days.value,
+        work_hours,
+    );
+
+    println!(
+        "{}",
+        generate_goal_status(&plan, weekly, tracked, work_hours, settings.duration_style)
+    );
+
+    Ok(StoreMutation::Unchanged)
+}
+
+/// Saves the goal into the settings of the store, so it travels with the store.
+/// Work days and work hours stay as they are unless given.
+pub fn handle_command_goal_set(
+    tracker: &mut impl TimeTrackingStore,
+    weekly: TimeDelta,
+    work_days: Option<Vec<Weekday>>,
+    work_hours: Option<TimeDelta>,
+) -> anyhow::Result<StoreMutation> {
+    let mut store_settings = tracker.settings()?;
+    store_settings.weekly_goal = Some(weekly);
+    if work_days.is_some() {
+        store_settings.work_days = work_days;
+    }
+    if work_hours.is_some() {
+        store_settings.work_hours = work_hours;
+    }
+    tracker.set_settings(store_settings)?;
+    info!(
+        "Set the weekly goal to {}",
+        format_duration(weekly, DurationStyle::DecimalHours)
+    );
+
+    Ok(StoreMutation::Active)
+}
+
+pub fn handle_command_goal_show(
+    tracker: &InMemoryTimeTracker,
+    weekly_goal: Option<TimeDelta>,
+    settings: &OutputSettings,
+) -> anyhow::Result<StoreMutation> {
+    match weekly_goal {
+        Some(goal) => print_weekly_progress(tracker, Some(goal), settings)?,
+        None => warn!("There is no weekly goal yet, remember one via `goal set <DURATION>`"),
+    }
+
+    Ok(StoreMutation::Unchanged)
+}
+
+/// Prints one line per setting, e.g. `color: never (config file)`, after the paths they get read from.
+pub fn handle_command_info(
+    effective: &EffectiveSettings,
+    storage_path: &Path,
+    config_path: &Path,
+) -> anyhow::Result<StoreMutation> {
+    println!("store: {}", storage_path.display());
+    println!("config file: {}", config_path.display());
+    for (name, value, source) in effective.describe() {
+        println!("{name}: {value} ({source})");
+    }
+
+    Ok(StoreMutation::Unchanged)
+}
+
+/// One line per project with its tracked hours and active time box, the default project first.
+pub fn handle_command_projects(
+    output: &Path,
+    format: StorageFormat,
+    settings: &OutputSettings,
+) -> anyhow::Result<StoreMutation> {
+    for project in list_projects(output)? {
+        let Some(tracker) = load_project(output, &project, format, false)? else {
+            println!("{project}: not initialized");
+            continue;
+        };
+
+        let total = tracker
+            .finished
+            .iter()
+            .map(clamped_duration)
+            .fold(TimeDelta::zero(), |total, duration| total + duration);
+        let active = match &tracker.active {
+            Some(tb) => format!("active: {}", tb.summary_line(&Local)),
+            None => "nothing active".to_string(),
+        };
+        println!(
+            "{project}: {} tracked, {active}",
+            format_duration(total, settings.duration_style)
+        );
+    }
+
+    Ok(StoreMutation::Unchanged)
+}
+
+/// Writes an empty store, or the converted legacy store of `options.from_legacy`.
+/// A store overwritten via `options.force` gets backed up first, like before every change.
 pub fn handle_command_init(
     storage_directory: &Path,
     storage_file: &Path,
-    strategy: &impl TimeTrackerStorageStrategy,
+    backups_directory: &Path,
+    keep_backups: usize,
+    format: StorageFormat,
+    json_format: OutputJsonFormat,
+    options: &InitArgs,
 ) -> anyhow::Result<()> {
+    let tracker = match options.from_legacy.as_deref() {
+        Some(path) => {
+            InMemoryTimeTracker::init(&LegacyFileLoadingStrategy { path }).with_context(|| {
+                format!("Failed to migrate the legacy store \"{}\"", path.display())
+            })?
+        }
+        None => InMemoryTimeTracker::default(),
+    };
+
     std::fs::create_dir_all(storage_directory)?;
     debug!("Created directories for: {}", storage_directory.display());
 
     if std::fs::exists(storage_file)? {
-        bail!(
-            "Time Tracker already exists on path: \"{}\"",
-            storage_file.display()
-        )
-    } else {
-        InMemoryTimeTracker::default().to_writer(strategy, &mut File::create_new(storage_file)?)?;
-    };
+        if !options.force {
+            bail!(
+                "Time Tracker already exists on path: \"{}\", pass `--force` to overwrite it",
+                storage_file.display()
+            )
+        }
+
+        let question = format!(
+            "Overwrite the existing store \"{}\" with {} finished time boxes?",
+            storage_file.display(),
+            tracker.finished.len()
+        );
+        if !options.yes && !confirm(&question)? {
+            info!("Initializing aborted");
+            return Ok(());
+        }
+
+        if let Some(backup) = write_backup(storage_file, backups_directory, keep_backups)? {
+            info!(
+                "Backed up the overwritten store to \"{}\"",
+                backup.display()
+            );
+        }
+    }
+    save_store(&tracker, storage_file, format, json_format)?;
+
+    if let Some(path) = options.from_legacy.as_deref() {
+        info!(
+            "Migrated {} finished time boxes{} from \"{}\"",
+            tracker.finished.len(),
+            if tracker.active.is_some() {
+                " and the active one"
+            } else {
+                ""
+            },
+            path.display()
+        );
+    }
 
     let path_gitignore_file = storage_directory.join(".gitignore");
     if !std::fs::exists(&path_gitignore_file)? {
@@ -118,7 +727,7 @@ pub fn handle_command_init(
         );
 
         file_new_gitignore
-            .write_all(b"*")
+            .write_all(gitignore_content(options.track_in_git).as_bytes())
             .context("Failed writing content into .gitignore file")?;
 
         debug!(
@@ -130,83 +739,629 @@ pub fn handle_command_init(
     Ok(())
 }
 
-pub fn handle_command_amend(
+/// Replaces the store with the content of a backup. The replaced content gets backed up on save
+/// like for every other change, so restoring can be reverted by restoring again.
+pub fn handle_command_restore(
+    tracker: &mut InMemoryTimeTracker,
+    backups_directory: &Path,
+    format: StorageFormat,
+    backup: Option<usize>,
+    skip_confirmation: bool,
+) -> anyhow::Result<StoreMutation> {
+    let backups = list_backups(backups_directory, format.extension())?;
+    if backups.is_empty() {
+        bail!(
+            "There are no backups inside \"{}\"",
+            backups_directory.display()
+        );
+    }
+
+    let Some(number) = backup else {
+        for (i, path) in backups.iter().enumerate() {
+            let summary = match load_store(path, format, false) {
+                Ok(t) => format!(
+                    "{} finished, {}",
+                    t.finished.len(),
+                    if t.active.is_some() {
+                        "active"
+                    } else {
+                        "inactive"
+                    }
+                ),
+                Err(e) => format!("unreadable: {}", e.message_with_sources()),
+            };
+            println!("{:>3}  {}  ({summary})", i + 1, path.display());
+        }
+        return Ok(StoreMutation::Unchanged);
+    };
+
+    let path = match number.checked_sub(1).and_then(|i| backups.get(i)) {
+        Some(path) => path,
+        None => bail!(
+            "There is no backup with number {number}, choose between 1 and {}",
+            backups.len()
+        ),
+    };
+
+    let restored = load_store(path, format, false)
+        .with_context(|| format!("Failed to load backup \"{}\"", path.display()))?;
+
+    let question = format!(
+        "Replace the store ({} finished) with the backup \"{}\" ({} finished)?",
+        tracker.finished.len(),
+        path.display(),
+        restored.finished.len()
+    );
+    if !skip_confirmation && !confirm(&question)? {
+        info!("Restoring aborted");
+        return Ok(StoreMutation::Unchanged);
+    }
+
+    *tracker = restored;
+    info!("Restored backup \"{}\"", path.display());
+
+    Ok(StoreMutation::Rewritten)
+}
+
+/// Replaces the store with the most recent backup. The replaced content gets backed up on save,
+/// so undoing again reverts the undo.
+pub fn handle_command_undo(
     tracker: &mut InMemoryTimeTracker,
+    backups_directory: &Path,
+    format: StorageFormat,
+) -> anyhow::Result<StoreMutation> {
+    let backups = list_backups(backups_directory, format.extension())?;
+    let Some(path) = backups.first() else {
+        bail!(
+            "Nothing to undo, there are no backups inside \"{}\"",
+            backups_directory.display()
+        );
+    };
+
+    *tracker = load_store(path, format, false)
+        .with_context(|| format!("Failed to load backup \"{}\"", path.display()))?;
+    info!(
+        "Undid the last change by restoring backup \"{}\"",
+        path.display()
+    );
+
+    Ok(StoreMutation::Rewritten)
+}
+
+pub fn handle_command_amend(
+    tracker: &mut impl TimeTrackingStore,
     description: &str,
-) -> anyhow::Result<StoreModified> {
-    tracker.amend(description)?;
-    Ok(true)
+    title: bool,
+    index: Option<usize>,
+) -> anyhow::Result<StoreMutation> {
+    match (title, index) {
+        (true, _) => tracker.edit_note(0, description)?,
+        (false, Some(index)) => tracker.edit_note(index, description)?,
+        (false, None) => tracker.amend(description)?,
+    };
+
+    Ok(StoreMutation::Active)
 }
 
-pub fn handle_command_resume(tracker: &mut InMemoryTimeTracker) -> anyhow::Result<StoreModified> {
-    tracker.resume()?;
-    Ok(true)
+pub fn handle_command_edit_note(
+    tracker: &mut impl TimeTrackingStore,
+    index: usize,
+    description: &str,
+) -> anyhow::Result<StoreMutation> {
+    tracker.edit_note(index, description)?;
+    Ok(StoreMutation::Active)
 }
 
-pub fn handle_command_end(tracker: &mut InMemoryTimeTracker) -> anyhow::Result<StoreModified> {
-    tracker.end()?;
-    Ok(true)
+pub fn handle_command_split(
+    tracker: &mut impl TimeTrackingStore,
+    index: usize,
+) -> anyhow::Result<StoreMutation> {
+    let (finished, active) = tracker.split_active(index).with_context(|| {
+        format!(
+            "Unable to split the active time box in front of the note at index {index}. \
+            Both parts need at least one note, see the `#` column of the `status` command."
+        )
+    })?;
+
+    info!(
+        "Ended {} and kept \"{}\" active",
+        finished.summary_line(&Local),
+        active.title()
+    );
+
+    Ok(StoreMutation::Appended(vec![finished]))
+}
+
+pub fn handle_command_merge(tracker: &mut impl TimeTrackingStore) -> anyhow::Result<StoreMutation> {
+    let merged = tracker
+        .merge_last_finished()
+        .context("Unable to merge, merging needs at least two finished time boxes.")?;
+
+    info!("Merged into {}", merged.summary_line(&Local));
+
+    Ok(StoreMutation::Rewritten)
 }
 
-pub fn handle_command_cancel(tracker: &mut InMemoryTimeTracker) -> anyhow::Result<StoreModified> {
+pub fn handle_command_resume(
+    tracker: &mut impl TimeTrackingStore,
+    index: Option<usize>,
+) -> anyhow::Result<StoreMutation> {
+    match index {
+        Some(index) => tracker.resume_at(index).with_context(|| {
+            format!(
+                "Unable to resume the time box at index {index}. \
+                See the `#` column of the `list` command for available indices."
+            )
+        })?,
+        None => tracker.resume()?,
+    };
+
+    Ok(StoreMutation::Rewritten)
+}
+
+pub fn handle_command_set(
+    tracker: &mut impl TimeTrackingStore,
+    index: usize,
+    clear_exported: bool,
+) -> anyhow::Result<StoreMutation> {
+    if !clear_exported {
+        return Ok(StoreMutation::Unchanged);
+    }
+
+    tracker.set_exported_at(index, None).with_context(|| {
+        format!(
+            "Unable to change the time box at index {index}. \
+            See the `#` column of the `list` command for available indices."
+        )
+    })?;
+
+    Ok(StoreMutation::Rewritten)
+}
+
+pub fn handle_command_lock(
+    tracker: &mut impl TimeTrackingStore,
+    before: NaiveDate,
+) -> anyhow::Result<StoreMutation> {
+    let mut settings = tracker.settings()?;
+    settings.locked_before = Some(local_midnight(before)?);
+    tracker.set_settings(settings)?;
+    info!("Locked the finished time boxes starting before {before} against changes");
+
+    Ok(StoreMutation::Active)
+}
+
+pub fn handle_command_pause(tracker: &mut impl TimeTrackingStore) -> anyhow::Result<StoreMutation> {
+    tracker
+        .pause()
+        .context("Unable to pause. Pausing needs an active time box that is not paused already.")?;
+    Ok(StoreMutation::Active)
+}
+
+pub fn handle_command_unpause(
+    tracker: &mut impl TimeTrackingStore,
+) -> anyhow::Result<StoreMutation> {
+    tracker
+        .unpause()
+        .context("Unable to unpause. Unpausing needs a paused active time box.")?;
+    Ok(StoreMutation::Active)
+}
+
+pub fn handle_command_end(tracker: &mut impl TimeTrackingStore) -> anyhow::Result<StoreMutation> {
+    Ok(StoreMutation::Appended(vec![tracker.end()?]))
+}
+
+pub fn handle_command_cancel(
+    tracker: &mut impl TimeTrackingStore,
+) -> anyhow::Result<StoreMutation> {
     tracker.cancel()?;
-    Ok(true)
+    Ok(StoreMutation::Active)
 }
 
-pub fn handle_command_clear(tracker: &mut InMemoryTimeTracker) -> anyhow::Result<StoreModified> {
-    match tracker.active()? {
-        None => Ok(tracker.clear()? > 0),
-        Some(_) => {
-            warn!("Clearing did nothing because there is an active time box!");
-            Ok(false)
-        }
+/// Removing more than this many time boxes by date needs a confirmation
+const CLEAR_CONFIRMATION_THRESHOLD: usize = 10;
+
+/// Without a `date` everything gets removed, unless there is an active time box.
+/// Repaired stores only get cleared when `force`d, their history may be mixed up.
+pub fn handle_command_clear(
+    tracker: &mut impl TimeTrackingStore,
+    date: Option<&ListFilter>,
+    skip_confirmation: bool,
+    force: bool,
+) -> anyhow::Result<StoreMutation> {
+    let repairs = tracker.repairs()?;
+    if !repairs.is_empty() && !force {
+        let repairs: Vec<String> = repairs.iter().map(|r| format!("- {r}")).collect();
+        bail!(
+            "Refusing to clear because loading had to repair the store, its history may be mixed up. \
+            Check it via `list` and pass `--force` to clear anyway. Repairs:\n{}",
+            repairs.join("\n")
+        );
     }
+
+    let Some(date) = date else {
+        return match tracker.active()? {
+            None => Ok(match tracker.clear()? {
+                0 => StoreMutation::Unchanged,
+                _ => StoreMutation::Rewritten,
+            }),
+            Some(_) => {
+                warn!("Clearing did nothing because there is an active time box!");
+                Ok(StoreMutation::Unchanged)
+            }
+        };
+    };
+
+    let query = Query::new().filter(date.clone());
+    let count = tracker
+        .finished(&ListOptions::new().take(usize::MAX).query(query.clone()))?
+        .items
+        .len();
+    let question = format!("Remove {count} finished time boxes?");
+    if count > CLEAR_CONFIRMATION_THRESHOLD && !skip_confirmation && !confirm(&question)? {
+        info!("Clearing aborted");
+        return Ok(StoreMutation::Unchanged);
+    }
+
+    let removed = tracker.clear_filtered(&query)?;
+    info!("Removed {removed} finished time boxes");
+
+    Ok(match removed {
+        0 => StoreMutation::Unchanged,
+        _ => StoreMutation::Rewritten,
+    })
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_command_list(
     tracker: &InMemoryTimeTracker,
     options: &ListOptions,
-) -> anyhow::Result<StoreModified> {
-    let mut finished = tracker.finished(options)?;
-    let active = tracker.active()?;
-
-    if finished.items.is_empty() {
-        warn!("Listing did nothing because there are no finished tasks");
-        return Ok(false);
-    }
-
-    let hours = finished.items.iter().fold(0.0f64, |acc, tb| {
-        acc + tb.duration_in_hours().unwrap_or_default()
-    });
-    let sum_col_label = format!("total {hours:.2}h");
-
-    let table = generate_table(
-        "%Y-%m-%d %H:%M",
-        "At",
-        "Description",
-        &sum_col_label,
-        finished.items.as_mut_slice(),
+    group_by: Option<ListGroupBy>,
+    durations: DurationOptions,
+    include_active: bool,
+    relative: bool,
+    compact: bool,
+    collapse_older_than: Option<u32>,
+    settings: &OutputSettings,
+) -> anyhow::Result<StoreMutation> {
+    let output = collect_for_output(tracker, options, true)?;
+    let active = match include_active {
+        true => matching_active(output.active.as_ref(), &options.query, Utc::now()),
+        false => None,
+    };
+
+    if active.is_none() && output.warn_if_empty("Listing") {
+        return Ok(StoreMutation::Unchanged);
+    }
+
+    let total_label = |label: &str, duration: TimeDelta| match settings.plain {
+        true => generate_plain_total(label, duration),
+        false => format!(
+            "{label} {}",
+            paint(
+                &footer_duration(duration, settings.duration_style),
+                hours_style(),
+                settings.color
+            )
+        ),
+    };
+
+    // Plain lists end with their sum instead of putting it underneath the columns
+    let table = |time_boxes: &mut [TimeBox],
+                 indices: &[usize],
+                 sum_col_label: &str|
+     -> anyhow::Result<String> {
+        Ok(match settings.plain {
+            true => format!(
+                "{}\n{sum_col_label}",
+                generate_plain_list(time_boxes, indices, durations, &Local)?
+            ),
+            false => generate_table(
+                DateColumn::new(relative),
+                "At",
+                "Description",
+                sum_col_label,
+                time_boxes,
+                IndexColumn::TimeBoxes(indices),
+                Some(
+                    &time_boxes
+                        .iter()
+                        .map(|tb| {
+                            Ok(format_duration(
+                                durations.duration(tb)?,
+                                settings.duration_style,
+                            ))
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?,
+                ),
+                settings.width,
+                &TableStyle::new(settings.color),
+            ),
+        })
+    };
+
+    // Paged as a whole, so the active time box stays underneath the finished ones
+    let mut content = String::new();
+
+    match group_by {
+        _ if output.finished.items.is_empty() => {}
+        None if compact && !settings.plain => {
+            content.push_str(&generate_table_compact(
+                DateColumn::new(relative),
+                &output.finished.items,
+                compact_description_width(settings.width),
+                durations,
+                settings.duration_style,
+            )?);
+        }
+        None => {
+            let (mut recent, indices, older) = match collapse_older_than {
+                Some(months) => split_at_cutoff(
+                    &output.finished.items,
+                    &output.finished.indices,
+                    collapse_cutoff(Local::now().date_naive(), months),
+                    &Local,
+                ),
+                None => (
+                    output.finished.items.clone(),
+                    output.finished.indices.clone(),
+                    Vec::new(),
+                ),
+            };
+
+            // Counts the collapsed months too, so the total stays the same as without collapsing
+            let sum_col_label = total_label("total", durations.total(&output.finished.items)?);
+            let recent_block = match recent.is_empty() {
+                true => format!("{sum_col_label}\n"),
+                false => format!("{}\n", table(&mut recent, &indices, &sum_col_label)?),
+            };
+            let older_block = match (older.is_empty(), settings.plain) {
+                (true, _) => String::new(),
+                (false, true) => format!("{}\n", generate_plain_months(&older, durations, &Local)?),
+                (false, false) => format!(
+                    "{}\n",
+                    generate_table_months(&older, durations, settings.duration_style, &Local)?
+                ),
+            };
+
+            // The collapsed months go where their time boxes would be, so on top unless listing descending
+            match older.first() == output.finished.items.first() {
+                true => content.push_str(&format!("{older_block}{recent_block}")),
+                false => content.push_str(&format!("{recent_block}{older_block}")),
+            }
+        }
+        Some(grouping) => {
+            // Groups are consecutive runs of the listed time boxes, so their indices are consecutive too
+            let mut indices = output.finished.indices.as_slice();
+            for (key, mut group) in
+                group_time_boxes(&output.finished.items, grouping.into(), &Local)
+            {
+                let (group_indices, rest) = indices.split_at(group.len());
+                indices = rest;
+
+                let subtotal = durations.total(&group)?;
+                let sum_col_label = total_label(&format!("{key} subtotal"), subtotal);
+                content.push_str(&format!(
+                    "{}\n",
+                    table(&mut group, group_indices, &sum_col_label)?
+                ));
+            }
+
+            content.push_str(&format!(
+                "{}\n",
+                total_label("Grand total", durations.total(&output.finished.items)?)
+            ));
+        }
+    }
+
+    // The active time box counts separately, its time is still growing
+    match active {
+        Some((time_box, elapsed)) => {
+            let block = match settings.plain {
+                true => generate_plain_active(time_box, &Local)?,
+                false => format!(
+                    "{}\n{}",
+                    paint(
+                        "ACTIVE",
+                        TableStyle::new(settings.color).active().header,
+                        settings.color
+                    ),
+                    generate_table_active(
+                        time_box.clone(),
+                        DateColumn::new(relative),
+                        None,
+                        settings.width,
+                        &TableStyle::new(settings.color),
+                        settings.duration_style,
+                    )?
+                ),
+            };
+            content.push_str(&format!("{block}\n{}\n", total_label("active", elapsed)));
+        }
+        None => output.warn_about_active(settings)?,
+    }
+
+    print_paged(&content, settings);
+
+    Ok(StoreMutation::Unchanged)
+}
+
+/// Compares the store with itself after writing and loading it again, or with the store at `against`,
+/// and prints every difference. Exits with code 1 if there is any.
+pub fn handle_command_verify_roundtrip(
+    tracker: &InMemoryTimeTracker,
+    against: Option<&Path>,
+    format: StorageFormat,
+    json_format: OutputJsonFormat,
+    strict: bool,
+) -> anyhow::Result<ExitCode> {
+    let other = match against {
+        Some(path) => {
+            let format = StorageFormat::from_path(path).unwrap_or(format);
+            load_store(path, format, strict).with_context(|| {
+                format!(
+                    "Failed to load the store to compare with \"{}\"",
+                    path.display()
+                )
+            })?
+        }
+        None => roundtrip_store(tracker, format, json_format, strict)?,
+    };
+
+    let differences = diff_stores(tracker, &other);
+    if differences.is_empty() {
+        info!(
+            "Both stores are equal, {} finished time box(es) compared",
+            tracker.finished.len()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for difference in &differences {
+        println!("{difference}");
+    }
+    warn!("Found {} difference(s)", differences.len());
+
+    Ok(ExitCode::FAILURE)
+}
+
+/// Imports into the active time box too, so an active time box on both sides gets refused as ambiguous.
+pub fn handle_command_import(
+    tracker: &mut InMemoryTimeTracker,
+    path: &Path,
+    format: ImportFormat,
+) -> anyhow::Result<StoreMutation> {
+    let finished_only = |finished: Vec<TimeBox>| InMemoryTimeTracker {
+        active: None,
+        finished: finished.into(),
+        settings: StoreSettings::default(),
+        repairs: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    let imported = match format {
+        ImportFormat::LegacyJson => InMemoryTimeTracker::init(&LegacyFileLoadingStrategy { path })
+            .map_err(anyhow::Error::new),
+        ImportFormat::Csv => std::fs::read_to_string(path)
+            .map_err(anyhow::Error::new)
+            .and_then(|content| parse_csv_export(&content))
+            .map(finished_only),
+        ImportFormat::Json => std::fs::read_to_string(path)
+            .map_err(anyhow::Error::new)
+            .and_then(|content| parse_json_export(&content))
+            .map(|(finished, had_active)| {
+                if had_active {
+                    warn!(
+                        "Leaving out the active time box of the export, end it where it was exported and import again"
+                    );
+                }
+                finished_only(finished)
+            }),
+    }
+    .with_context(|| format!("Failed to read the store to import \"{}\"", path.display()))?;
+
+    let has_active = imported.active.is_some();
+    let summary = tracker.import(imported).context(
+        "Refusing to import because both stores have an active time box and only one can stay active. \
+        End or cancel the active time box first.",
+    )?;
+
+    info!(
+        "Imported {} finished time boxes{}, skipped {} that were tracked already",
+        summary.imported,
+        if has_active {
+            " and the active time box"
+        } else {
+            ""
+        },
+        summary.skipped
     );
 
-    println!("{table}");
+    Ok(match summary.imported > 0 || has_active {
+        true => StoreMutation::Rewritten,
+        false => StoreMutation::Unchanged,
+    })
+}
 
-    if let Some(active) = active {
-        warn!(
-            "There is a pending task:\n{}",
-            generate_table_active(active)?
-        )
+pub fn handle_command_doctor(
+    tracker: &InMemoryTimeTracker,
+    storage_file: &Path,
+    cache_file: &Path,
+) -> anyhow::Result<StoreMutation> {
+    let mut problems: Vec<String> = Vec::new();
+
+    info!("Store is valid: {}", storage_file.display());
+
+    for (index, tb) in tracker.finished.iter().enumerate() {
+        if let Ok(delta) = tb.timedelta_total()
+            && delta < TimeDelta::zero()
+        {
+            problems.push(format!(
+                "Time box #{index} \"{}\" ends before it starts, its duration of {} counts as zero",
+                tb.title(),
+                format_duration(delta, DurationStyle::HoursMinutes)
+            ));
+        }
+    }
+
+    match inspect_cache(cache_file, storage_file) {
+        Ok(CacheState::Missing) => info!("There is no cache, nothing to verify"),
+        Ok(CacheState::Stale) => {
+            info!("Cache is stale, it gets refreshed on the next invocation using `--cache`")
+        }
+        Ok(CacheState::Valid(cached)) if cached.same_data(tracker) => {
+            info!("Cache matches the store")
+        }
+        Ok(CacheState::Valid(_)) => problems.push(format!(
+            "Cache differs from the store, delete it to regenerate it: {}",
+            cache_file.display()
+        )),
+        Err(e) => problems.push(format!(
+            "Cache is unreadable, delete it to regenerate it: {}: {}",
+            cache_file.display(),
+            e.message_with_sources()
+        )),
+    }
+
+    // A store versioned with git would otherwise hide its backups and everything else that is new
+    let gitignore_file = storage_file.with_file_name(".gitignore");
+    if std::fs::read_to_string(&gitignore_file).is_ok_and(|c| c.trim() == gitignore_content(false))
+        && is_tracked_in_git(storage_file)
+    {
+        let question = format!(
+            "The store is tracked by git but \"{}\" ignores everything. Rewrite it to only ignore swap, cache, lock and backup files?",
+            gitignore_file.display()
+        );
+        match std::io::stdin().is_terminal() && confirm(&question)? {
+            true => {
+                std::fs::write(&gitignore_file, gitignore_content(true)).with_context(|| {
+                    format!("Failed rewriting .gitignore file: {}", gitignore_file.display())
+                })?;
+                info!("Rewrote .gitignore file: {}", gitignore_file.display());
+            }
+            false => problems.push(format!(
+                "The store is tracked by git but \"{}\" ignores everything, run `doctor` in a terminal to rewrite it",
+                gitignore_file.display()
+            )),
+        }
+    }
+
+    if !problems.is_empty() {
+        bail!(
+            "Found {} problem(s):\n- {}",
+            problems.len(),
+            problems.join("\n- ")
+        );
     }
 
-    Ok(false)
+    Ok(StoreMutation::Unchanged)
 }
 
 pub fn handle_command_shell_completion(
     shell: clap_complete::aot::Shell,
-) -> anyhow::Result<StoreModified> {
+) -> anyhow::Result<StoreMutation> {
     let mut cmd = Args::command();
     let name = cmd.get_bin_name().unwrap_or("timetracker-cli").to_string();
 
     clap_complete::aot::generate(shell, &mut cmd, name, &mut std::io::stdout());
 
-    Ok(false)
+    Ok(StoreMutation::Unchanged)
 }
@@ -1,215 +1,3087 @@
-use anyhow::anyhow;
-use chrono::{Local, Utc};
-use log::{debug, error};
-use std::{cmp, fs::File, path::Path};
-use timetracker::{TimeBox, TimeTrackerStorageStrategy, in_memory_tracker::InMemoryTimeTracker};
+use anstyle::{AnsiColor, Style};
+use anyhow::{Context, anyhow, bail};
+use chrono::{
+    DateTime, Datelike, Local, Months, NaiveDate, NaiveTime, SecondsFormat, TimeDelta, TimeZone,
+    Utc, Weekday,
+};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::{
+    cmp,
+    fs::File,
+    hash::{BuildHasher, RandomState},
+    io::{BufRead, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use timetracker::{
+    Diagnostic, DurationStyle, Grouping, ListFilter, ListOptions, ListResult, NoteKind, Query,
+    RoundingMode, Severity, TimeBox, TimeBoxNote, TimeTrackerStorageStrategy, TimeTrackingStore,
+    WeeklyGoalPlan, clamped_hours, format_duration, format_estimate, group_time_boxes,
+    hours_in_range, humanize_delta, humanize_duration,
+    in_memory_tracker::{InMemoryTimeTracker, JsonFileLoadingStrategy, JsonStorageStrategy},
+    jsonl_tracker::{
+        JsonlFileLoadingStrategy, JsonlStateStorageStrategy, JsonlStorageStrategy, state_path,
+    },
+    timedelta_in_range,
+    toml_tracker::{TomlFileLoadingStrategy, TomlStorageStrategy},
+};
+use unicode_width::UnicodeWidthStr;
 
-/// TODO: Make it dynamic in the future if the need comes up
+use crate::args::{OutputJsonFormat, StorageFormat};
+
+/// Global presentation choices of the user, independent of the command.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputSettings {
+    pub color: bool,
+    pub duration_style: DurationStyle,
+    /// Tables fit into this many columns, `None` to not limit them, see `generate_table`.
+    pub width: Option<usize>,
+    /// Labeled lines instead of tables, see `generate_plain_list`.
+    pub plain: bool,
+    /// Page output taller than the terminal, only ever enabled if stdout is one, see `print_paged`.
+    pub pager: bool,
+}
+
+/// Everything commands like `list` and `export` output.
+/// Always assembled via `collect_for_output` so the same arguments select the same time boxes.
+#[derive(Debug)]
+pub struct OutputSet {
+    pub finished: ListResult,
+    pub active: Option<TimeBox>,
+    /// Sum of hours of the finished time boxes
+    pub hours: f64,
+}
+
+impl OutputSet {
+    /// Warns that `action` did nothing. Returns whether there are no finished time boxes.
+    pub fn warn_if_empty(&self, action: &str) -> bool {
+        let is_empty = self.finished.items.is_empty();
+
+        if is_empty {
+            warn!("{action} did nothing because there are no finished time boxes");
+        }
+
+        is_empty
+    }
+
+    /// Warns about the active time box if there is one, since it is not part of the output.
+    pub fn warn_about_active(&self, settings: &OutputSettings) -> anyhow::Result<()> {
+        if let Some(tb) = self.active.clone() {
+            let content = match settings.plain {
+                true => generate_plain_active(&tb, &Local)?,
+                // Logs go to `stderr` which may be redirected regardless of `--color`
+                false => generate_table_active(
+                    tb,
+                    DateColumn::Format("%Y-%m-%d %H:%M"),
+                    None,
+                    settings.width,
+                    &TableStyle::new(false),
+                    settings.duration_style,
+                )?,
+            };
+            warn!("There is an active time box:\n{content}")
+        }
+
+        Ok(())
+    }
+}
+
+pub fn collect_for_output(
+    tracker: &impl TimeTrackingStore,
+    options: &ListOptions,
+    include_active: bool,
+) -> anyhow::Result<OutputSet> {
+    let finished = tracker.finished(options)?;
+
+    let active = match include_active {
+        true => tracker.active()?,
+        false => None,
+    };
+
+    for tb in finished.items.iter() {
+        if let Ok(delta) = tb.timedelta_total()
+            && delta < TimeDelta::zero()
+        {
+            warn!(
+                "The time box {} ends before it starts, counting its duration as zero. See `doctor`.",
+                tb.summary_line(&Local)
+            );
+        }
+    }
+
+    let hours = finished
+        .items
+        .iter()
+        .fold(0.0f64, |acc, tb| acc + clamped_hours(tb));
+
+    Ok(OutputSet {
+        finished,
+        active,
+        hours,
+    })
+}
+
+/// Time of the active `time_box` until `now` excluding pauses, never negative.
+pub fn elapsed_active(time_box: &TimeBox, now: DateTime<Utc>) -> TimeDelta {
+    let start = time_box.time_start().unwrap_or_default();
+    (now - start - time_box.timedelta_paused(now)).max(TimeDelta::zero())
+}
+
+/// The active time box with its `elapsed_active` time if it matches `query` like a finished one would,
+/// e.g. if it started on the date of `list --date`.
+pub fn matching_active<'a>(
+    active: Option<&'a TimeBox>,
+    query: &Query,
+    now: DateTime<Utc>,
+) -> Option<(&'a TimeBox, TimeDelta)> {
+    active
+        .filter(|tb| query.matches(tb))
+        .map(|tb| (tb, elapsed_active(tb, now)))
+}
+
+/// Descriptions never get wider than this, even on wide terminals, since long lines are hard to read
 const TEXT_WRAP_COL: usize = 50;
+/// Descriptions get at least this wide, even on narrow terminals, otherwise barely a word fits
+const TEXT_WRAP_COL_MIN: usize = 10;
+/// Borders and padding of a table row, i.e. `│ # │ date │ description │` without the content
+const TABLE_ROW_OVERHEAD_COLS: usize = 10;
+/// Borders and padding of a footer row spanning the table, i.e. `│ sum │` without the content
+const TABLE_FOOTER_OVERHEAD_COLS: usize = 4;
+/// Columns of a row of `generate_table_compact` besides the description, i.e. borders, date and a duration like `10.25h`
+const COMPACT_ROW_OVERHEAD_COLS: usize = TABLE_ROW_OVERHEAD_COLS + 16 + 8;
+
+/// How wide descriptions of `generate_table_compact` may get to fit into `table_width`, see `TEXT_WRAP_COL`.
+pub fn compact_description_width(table_width: Option<usize>) -> usize {
+    table_width
+        .map_or(TEXT_WRAP_COL, |width| {
+            width.saturating_sub(COMPACT_ROW_OVERHEAD_COLS)
+        })
+        .clamp(TEXT_WRAP_COL_MIN, TEXT_WRAP_COL)
+}
+
+/// Width of the terminal for fitting tables into it.
+/// Returns `None` if stdout is no terminal, for example when piping.
+pub fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(width, _)| width.0 as usize)
+}
+
+/// Pager for output taller than the terminal if `$PAGER` is not set, like git does.
+/// Exits right away if the output fits after all, keeps colors and leaves the output on screen after quitting.
+const DEFAULT_PAGER: &str = "less -RFX";
+
+/// Prints `content` through the pager of `pager_command` if paging is enabled and it is taller than the terminal.
+/// Falls back to printing it directly if the pager cannot be started, e.g. because it is not installed.
+pub fn print_paged(content: &str, settings: &OutputSettings) {
+    let is_taller = terminal_size::terminal_size()
+        .is_some_and(|(_, height)| content.lines().count() >= height.0 as usize);
+
+    if settings.pager && is_taller {
+        match spawn_pager(&pager_command(), content) {
+            Ok(()) => return,
+            Err(e) => debug!("Printing directly since the pager failed to start: {e}"),
+        }
+    }
+
+    print!("{content}");
+}
+
+/// `$PAGER` if set, otherwise `DEFAULT_PAGER`
+fn pager_command() -> String {
+    std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string())
+}
+
+/// Runs `command`, split at whitespace into the program and its arguments, with `content` as input
+/// and waits for it to exit. Quitting the pager early is no error.
+fn spawn_pager(command: &str, content: &str) -> std::io::Result<()> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or(ErrorKind::NotFound)?;
+
+    let mut pager = std::process::Command::new(program)
+        .args(parts)
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = pager.stdin.take() {
+        match stdin.write_all(content.as_bytes()) {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => {
+                debug!("Failed to write into the pager: {e}")
+            }
+            _ => (),
+        }
+    }
+
+    pager.wait().map(|_| ())
+}
+
+/// Active time boxes running longer than this were likely forgotten to be ended
+const OVERRUN_HOURS: f64 = 8.0;
+
+/// Removes ANSI escape sequences
+fn strip_ansi(s: &str) -> String {
+    let mut plain = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Control Sequence Introducer `ESC [`, parameters, then a final byte between '@' and '~'
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+
+    plain
+}
+
+/// Display width ignoring ANSI escape sequences, so styled labels get padded like plain ones
+fn visible_width(s: &str) -> usize {
+    strip_ansi(s).width()
+}
+
+/// Wraps `text` into the escape sequences of `style` if `color` is enabled
+pub fn paint(text: &str, style: Style, color: bool) -> String {
+    match color {
+        true => format!("{}{text}{}", style.render(), style.render_reset()),
+        false => text.to_string(),
+    }
+}
 
+/// Green while running, yellow once the active time box runs suspiciously long
+fn active_hours_style(hours_active: f64) -> Style {
+    let color = match hours_active > OVERRUN_HOURS {
+        true => AnsiColor::Yellow,
+        false => AnsiColor::Green,
+    };
+
+    Style::new().fg_color(Some(color.into()))
+}
+
+/// Style of sums of hours in footers
+pub fn hours_style() -> Style {
+    Style::new().fg_color(Some(AnsiColor::Green.into()))
+}
+
+/// Styles of the parts of `generate_table`. Cells get padded before they get styled,
+/// so escape sequences never count towards the width of a column.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TableStyle {
+    /// Whether values inside the sum get colored too, e.g. the active hours
+    pub color: bool,
+    pub header: Style,
+    pub date: Style,
+    pub description: Style,
+    pub sum: Style,
+}
+
+impl TableStyle {
+    /// Dimmed dates and bold sums if `color` is enabled, no escape sequences at all otherwise
+    pub fn new(color: bool) -> Self {
+        match color {
+            true => Self {
+                color,
+                header: Style::new(),
+                date: Style::new().dimmed(),
+                description: Style::new(),
+                sum: Style::new().bold(),
+            },
+            false => Self::default(),
+        }
+    }
+
+    /// Yellow header for the table of the active time box
+    pub fn active(self) -> Self {
+        match self.color {
+            true => Self {
+                header: Style::new().fg_color(Some(AnsiColor::Yellow.into())),
+                ..self
+            },
+            false => self,
+        }
+    }
+}
+
+/// Like `paint`, but `style` also continues after the parts of `text` that are painted already
+fn paint_over(text: &str, style: Style) -> String {
+    let reset = anstyle::Reset.render().to_string();
+    let text = text.replace(&reset, &format!("{reset}{}", style.render()));
+    paint(&text, style, true)
+}
+
+/// What the leading `#` column of a table shows
+#[derive(Debug, Clone, Copy)]
+pub enum IndexColumn<'a> {
+    /// Index of each time box, see `ListResult::indices`
+    TimeBoxes(&'a [usize]),
+    /// Index of each note inside its time box, see `edit-note`
+    Notes,
+    /// Like `Notes`, except for the last row that shows the time of now instead of a note, see `generate_table_active`
+    NotesUntilNow,
+}
+
+/// What the date column of a table shows for the time of each note
+#[derive(Debug, Clone, Copy)]
+pub enum DateColumn<'a> {
+    /// Local time in this `chrono` format, e.g. `%Y-%m-%d %H:%M`
+    Format(&'a str),
+    /// How long before `now` it was, e.g. `25 minutes ago`, see `humanize_delta`
+    Relative(DateTime<Utc>),
+}
+
+impl DateColumn<'_> {
+    /// Local date and time, or how long ago as of now if `relative`, see `--relative`
+    pub fn new(relative: bool) -> Self {
+        match relative {
+            true => DateColumn::Relative(Utc::now()),
+            false => DateColumn::Format("%Y-%m-%d %H:%M"),
+        }
+    }
+
+    fn render(&self, time: DateTime<Utc>) -> String {
+        match self {
+            DateColumn::Format(format) => time.with_timezone(&Local).format(format).to_string(),
+            DateColumn::Relative(now) => humanize_delta(*now - time),
+        }
+    }
+}
+
+fn pad_to_width(s: &str, width: usize) -> String {
+    let padding = width.saturating_sub(s.width());
+    format!("{s}{}", " ".repeat(padding))
+}
+
+/// Splits `label` after its commas into lines of at most `max_width`, parts wider than that at their spaces.
+fn wrap_label(label: &str, max_width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for part in label.split(", ") {
+        match lines.last_mut() {
+            Some(line) if visible_width(line) + visible_width(part) + 2 <= max_width => {
+                line.push_str(", ");
+                line.push_str(part);
+                continue;
+            }
+            Some(line) => line.push(','),
+            None => (),
+        }
+        lines.extend(
+            textwrap::wrap(part, max_width)
+                .into_iter()
+                .map(String::from),
+        );
+    }
+
+    lines
+}
+
+/// Renders time boxes as ascii table.
+/// The leading `index_column` is for addressing time boxes or notes in other commands.
+/// `durations` has one entry per time box, shown right-aligned in the date column of the line above it.
+/// The description column shrinks so the table fits into `table_width`, its content gets wrapped
+/// at word boundaries to at most `TEXT_WRAP_COL`, which is also the width without `table_width`.
+/// Pads with spaces up to the display `width`, unlike `{:<N}` which counts chars,
+/// so wide characters like CJK or emoji do not shift the borders.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_table(
-    date_format: &str,
+    date_column: DateColumn,
     date_col_label: &str,
     description_col_label: &str,
     sum_col_label: &str,
     time_boxes: &mut [TimeBox],
+    index_column: IndexColumn,
+    durations: Option<&[String]>,
+    table_width: Option<usize>,
+    style: &TableStyle,
 ) -> String {
+    const INDEX_COL_LABEL: &str = "#";
+
     let mut output = String::with_capacity(1024);
 
+    let index_col_max_index = match index_column {
+        IndexColumn::TimeBoxes(indices) => indices.iter().max().copied(),
+        IndexColumn::Notes => time_boxes
+            .iter()
+            .map(|tb| tb.notes.len().saturating_sub(1))
+            .max(),
+        IndexColumn::NotesUntilNow => time_boxes
+            .iter()
+            .map(|tb| tb.notes.len().saturating_sub(2))
+            .max(),
+    };
+    let index_col_max_len = index_col_max_index
+        .map(|i| i.to_string().len())
+        .into_iter()
+        .chain([INDEX_COL_LABEL.len()])
+        .max()
+        .unwrap_or_default();
+    // Short sums are underneath both the index and date column, 3 accounts for the padded separator
+    let index_col_span = index_col_max_len + 3;
+
+    let date_col_max_len = time_boxes
+        .iter()
+        .flat_map(|tb| tb.notes.iter())
+        .map(|note| date_column.render(note.time).width())
+        // Durations get a space on both sides to stand out from the line they are on
+        .chain(durations.unwrap_or_default().iter().map(|d| d.width() + 2))
+        .chain([date_col_label.len()])
+        .max()
+        .unwrap_or_default();
+
+    // The description column gets what is left of the width, the other columns fit their content
+    let wrap_width = match table_width {
+        Some(width) => width
+            .saturating_sub(index_col_max_len + date_col_max_len + TABLE_ROW_OVERHEAD_COLS)
+            .clamp(TEXT_WRAP_COL_MIN, TEXT_WRAP_COL),
+        None => TEXT_WRAP_COL,
+    };
     time_boxes.iter_mut().for_each(|block| {
-        block
-            .notes
-            .iter_mut()
-            .for_each(|note| textwrap::fill_inplace(&mut note.description, TEXT_WRAP_COL));
+        block.notes.iter_mut().for_each(|note| {
+            // Unlike `fill_inplace` also breaks words longer than the column, e.g. URLs
+            note.description = textwrap::fill(&note.description, wrap_width)
+        });
     });
 
-    let date_format_expanded_len = Utc::now().format(date_format).to_string().len();
-    let date_col_max_len = cmp::max(date_col_label.len(), date_format_expanded_len);
     let description_col_max_len = cmp::max(
         description_col_label.len(),
         time_boxes // The longest line of any description
             .iter()
             .flat_map(|block| block.notes.iter())
-            .map(|note| note.description.lines().map(|l| l.len()).max().unwrap_or(0))
+            .map(|note| {
+                note.description
+                    .lines()
+                    .map(|l| l.width())
+                    .max()
+                    .unwrap_or(0)
+            })
             .max()
             .unwrap(), // We may assert there is one
     );
 
-    let sum_col_max_len = cmp::max(date_col_max_len, sum_col_label.len());
-    let date_col_max_len = sum_col_max_len; // Make sure the first column is in sync, since sum is underneath
+    // Long sums like the one of the active time box span the whole table instead of widening the date column.
+    // The description column widens for them, up to `table_width` where they get split at their commas.
+    let sum_tab_len = index_col_span + date_col_max_len;
+    let sum_spans_table = visible_width(sum_col_label) > sum_tab_len;
+    let sum_col_lines = match (sum_spans_table, table_width) {
+        (true, Some(width)) => wrap_label(
+            sum_col_label,
+            width.saturating_sub(TABLE_FOOTER_OVERHEAD_COLS),
+        ),
+        _ => vec![sum_col_label.to_string()],
+    };
+    let description_col_max_len = match sum_spans_table {
+        true => sum_col_lines
+            .iter()
+            .map(|line| visible_width(line).saturating_sub(sum_tab_len + 3))
+            .chain([description_col_max_len])
+            .max()
+            .unwrap_or_default(),
+        false => description_col_max_len,
+    };
+    let sum_col_max_len = match sum_spans_table {
+        true => sum_tab_len + 3 + description_col_max_len,
+        false => sum_tab_len,
+    };
+
+    // Left-most part of each line, either the border or the index column including its right border
+    let index_col = |left: &str, content: &str, right: &str| {
+        format!("{left} {content:>index_col_max_len$} {right}")
+    };
+    let index_col_line =
+        |left: &str, right: &str| format!("{left}─{:─^index_col_max_len$}─{right}", "─");
 
     // Header Top
     output.push_str(&format!(
-        "┌─{:─^date_col_max_len$}─┬─{0:─<description_col_max_len$}─┐\n",
+        "{}─{:─^date_col_max_len$}─┬─{1:─<description_col_max_len$}─┐\n",
+        index_col_line("┌", "┬"),
         "─",
     ));
 
     // Header Content
     output.push_str(&format!(
-        "│ {date_col_label:^date_col_max_len$} │ {description_col_label:^description_col_max_len$} │\n",
+        "│ {} │ {} │ {} │\n",
+        paint(
+            &format!("{INDEX_COL_LABEL:>index_col_max_len$}"),
+            style.header,
+            true
+        ),
+        paint(
+            &format!("{date_col_label:^date_col_max_len$}"),
+            style.header,
+            true
+        ),
+        paint(
+            &format!("{description_col_label:^description_col_max_len$}"),
+            style.header,
+            true
+        ),
     ));
 
-    // Header Bottom
-    output.push_str(&format!(
-        "├─{:─^date_col_max_len$}─┼─{0:─^description_col_max_len$}─┤\n",
-        "─",
-    ));
+    // Header Bottom and the separator lines in front of each time box, with its duration if there is one
+    let separator = |index: usize| {
+        let duration = match durations.and_then(|durations| durations.get(index)) {
+            Some(duration) => paint(&format!(" {duration} "), style.sum, true),
+            None => "─".repeat(2),
+        };
+        let padding = "─".repeat(date_col_max_len.saturating_sub(visible_width(&duration)));
+        format!(
+            "{}─{padding}{duration}─┼─{:─^description_col_max_len$}─┤\n",
+            index_col_line("├", "┼"),
+            "─",
+        )
+    };
 
     // Each Row
     time_boxes.iter().enumerate().for_each(|(index, block)| {
-        // Separator line
-        if index > 0 {
-            output.push_str(&format!(
-                "├─{:─^date_col_max_len$}─┼─{0:─^description_col_max_len$}─┤\n",
-                "─",
-            ));
-        }
+        output.push_str(&separator(index));
 
-        block.notes.iter().for_each(|note| {
-            let col_date = note
-                .time
-                .with_timezone(&Local)
-                .format(date_format)
-                .to_string();
+        let col_index = |index_note: usize, line: usize| match (index_column, line) {
+            (IndexColumn::TimeBoxes(indices), 0) if index_note == 0 => indices
+                .get(index)
+                .map(|i| i.to_string())
+                .unwrap_or_default(),
+            (IndexColumn::Notes, 0) => index_note.to_string(),
+            (IndexColumn::NotesUntilNow, 0) if index_note + 1 < block.notes.len() => {
+                index_note.to_string()
+            }
+            _ => String::new(),
+        };
 
-            // Need an empty check because `.lines()` returns nothing on an empty string
-            // resulting in no line being drawn at all
-            if note.description.is_empty() {
-                output.push_str(&format!(
-                    "│ {col_date:^date_col_max_len$} │ {:<description_col_max_len$} │\n",
-                    note.description
-                ));
-            } else {
-                for (i, line) in note.description.lines().enumerate() {
-                    let date = match i {
-                        0 => &col_date,
-                        _ => "",
-                    };
+        block
+            .notes
+            .iter()
+            .enumerate()
+            .for_each(|(index_note, note)| {
+                let col_date = date_column.render(note.time);
 
-                    // Content
+                // Need an empty check because `.lines()` returns nothing on an empty string
+                // resulting in no line being drawn at all
+                if note.description.is_empty() {
                     output.push_str(&format!(
-                        "│ {date:^date_col_max_len$} │ {line:<description_col_max_len$} │\n"
+                        "{} {} │ {:<description_col_max_len$} │\n",
+                        index_col("│", &col_index(index_note, 0), "│"),
+                        paint(&format!("{col_date:^date_col_max_len$}"), style.date, true),
+                        note.description
                     ));
+                } else {
+                    for (i, line) in note.description.lines().enumerate() {
+                        let date = match i {
+                            0 => &col_date,
+                            _ => "",
+                        };
+
+                        // Content
+                        output.push_str(&format!(
+                            "{} {} │ {} │\n",
+                            index_col("│", &col_index(index_note, i), "│"),
+                            paint(&format!("{date:^date_col_max_len$}"), style.date, true),
+                            paint(
+                                &pad_to_width(line, description_col_max_len),
+                                style.description,
+                                true
+                            ),
+                        ));
+                    }
                 }
-            }
-        });
+            });
     });
 
     // Footer Top
-    output.push_str(&format!(
-        "├─{:─^date_col_max_len$}─┼─{0:─^description_col_max_len$}─┘\n",
-        "─",
-    ));
+    output.push_str(&match sum_spans_table {
+        true => format!(
+            "{}─{:─^date_col_max_len$}─┴─{1:─^description_col_max_len$}─┤\n",
+            index_col_line("├", "┴"),
+            "─",
+        ),
+        false => format!(
+            "{}─{:─^date_col_max_len$}─┼─{1:─^description_col_max_len$}─┘\n",
+            index_col_line("├", "┴"),
+            "─",
+        ),
+    });
 
     // Footer Content
-    output.push_str(&format!("│ {sum_col_label:>sum_col_max_len$} │\n"));
+    for line in sum_col_lines {
+        let sum_col_padding = " ".repeat(sum_col_max_len - visible_width(&line));
+        output.push_str(&format!(
+            "│ {sum_col_padding}{} │\n",
+            paint_over(&line, style.sum)
+        ));
+    }
 
     // Footer Bottom
-    output.push_str(&format!("└─{:─^date_col_max_len$}─┘\n", "─",));
+    output.push_str(&format!("└─{:─^sum_col_max_len$}─┘\n", "─",));
 
     output
 }
 
-pub fn generate_table_active(time_box: TimeBox) -> anyhow::Result<String> {
-    let hours = time_box.duration_in_hours()?;
-    let hours_active = time_box.duration_active_in_hours()?;
-    let sum_col_label = format!("tasks {hours:.2}h, {hours_active:.2}h active");
+/// For example `PAUSED since 12:03` in local time, `None` if the time box is not paused.
+pub fn paused_label(time_box: &TimeBox) -> Option<String> {
+    time_box.paused_since().map(|since| {
+        format!(
+            "PAUSED since {}",
+            since.with_timezone(&Local).format("%H:%M")
+        )
+    })
+}
 
-    Ok(generate_table(
-        "%Y-%m-%d %H:%M",
-        "At",
-        "Description",
-        &sum_col_label,
-        &mut [time_box],
-    ))
+/// Replaces `{name}` with what `value_of(name)` returns, `{{` and `}}` become literal braces.
+/// Unknown placeholders and unclosed braces stay as they are, so typos remain visible.
+pub fn fill_template(template: &str, value_of: impl Fn(&str) -> Option<String>) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        filled.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            filled.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let placeholder = match rest.starts_with('{') {
+            true => rest.find('}').map(|end| &rest[..=end]),
+            false => None,
+        };
+        match placeholder.and_then(|p| value_of(&p[1..p.len() - 1]).map(|v| (p, v))) {
+            Some((placeholder, value)) => {
+                filled.push_str(&value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                filled.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+
+    filled
 }
 
-pub fn generate_csv_export(finished_time_boxes: &[TimeBox]) -> anyhow::Result<String> {
-    let mut output = String::with_capacity(4096);
+/// Output of `prompt`, see `fill_template`. Only regular notes count, like for the title.
+pub fn generate_prompt(time_box: &TimeBox, template: &str) -> anyhow::Result<String> {
+    let active = time_box.timedelta_active()?;
+    let regular: Vec<&TimeBoxNote> = time_box
+        .notes
+        .iter()
+        .filter(|n| n.kind.is_regular())
+        .collect();
+
+    Ok(fill_template(template, |name| match name {
+        "hours_active" => Some(format!("{:.1}h", active.num_seconds() as f64 / 60.0 / 60.0)),
+        "minutes_active" => Some(format!("{}m", active.num_minutes())),
+        "description" => Some(time_box.title().to_string()),
+        "last_note" => Some(
+            regular
+                .last()
+                .and_then(|n| n.description.lines().next())
+                .unwrap_or_default()
+                .to_string(),
+        ),
+        "notes_count" => Some(regular.len().to_string()),
+        _ => None,
+    }))
+}
 
-    output.push_str("time_start;time_stop;hours;description");
+/// Single line for shell prompts, for example `2.25h — fixing the parser`
+/// or `2.25h — fixing the parser (PAUSED since 12:03)`
+pub fn generate_status_line(
+    time_box: &TimeBox,
+    duration_style: DurationStyle,
+) -> anyhow::Result<String> {
+    let active = format_duration(time_box.timedelta_active()?, duration_style);
 
-    for time_box in finished_time_boxes.iter() {
-        let time_start = time_box
-            .time_start()?
-            .with_timezone(&chrono::Local)
-            .to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+    Ok(match paused_label(time_box) {
+        Some(paused) => format!("{active} — {} ({paused})", time_box.title()),
+        None => format!("{active} — {}", time_box.title()),
+    })
+}
 
-        let time_stop = time_box
-            .time_stop()?
-            .with_timezone(&chrono::Local)
-            .to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+#[derive(Debug, Serialize)]
+struct ActiveStatus<'a> {
+    #[serde(flatten)]
+    time_box: &'a TimeBox,
+    duration_active_in_hours: f64,
+}
 
-        let hours = time_box.duration_in_hours()?;
+pub fn generate_status_json(time_box: &TimeBox) -> anyhow::Result<String> {
+    let status = ActiveStatus {
+        time_box,
+        duration_active_in_hours: time_box.duration_active_in_hours()?,
+    };
 
-        let description = time_box
-            .notes
-            .iter()
-            .map(|t| {
-                format!(
-                    "- {}",
-                    t.description
-                        // Not "optimal" going through the string twice but negligable
-                        // TODO Does escaping even work this way? Ehh revisit this in case it comes up
-                        .replace('"', "\\\"")
-                        .replace(';', "\\;")
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
+    Ok(serde_json::to_string_pretty(&status)?)
+}
 
-        output.push_str(&format!(
-            "\n{time_start};{time_stop};{hours:.2};\"{description}\""
-        ));
+/// Output of `status --format json-min`, the field names are stable.
+#[derive(Debug, Serialize)]
+pub struct StatusMin<'a> {
+    pub active: bool,
+    pub paused: bool,
+    pub title: Option<&'a str>,
+    pub elapsed_minutes: i64,
+    pub today_hours: f64,
+}
+
+impl<'a> StatusMin<'a> {
+    /// `today_hours` includes the active time box
+    pub fn new(active: Option<&'a TimeBox>, today_hours: f64) -> anyhow::Result<Self> {
+        let elapsed_minutes = match active {
+            Some(tb) => tb.timedelta_active()?.num_minutes(),
+            None => 0,
+        };
+
+        Ok(StatusMin {
+            active: active.is_some(),
+            paused: active.is_some_and(|tb| tb.paused_since().is_some()),
+            title: active.map(|tb| tb.title()),
+            elapsed_minutes,
+            today_hours: (today_hours * 100.0).round() / 100.0,
+        })
     }
+}
 
-    output.push('\n');
+/// Output of `status --format waybar`, see the `return-type: json` contract of waybar's custom modules.
+#[derive(Debug, Serialize)]
+pub struct WaybarStatus {
+    pub text: String,
+    pub tooltip: String,
+    pub class: &'static str,
+}
 
-    Ok(output)
+impl From<&StatusMin<'_>> for WaybarStatus {
+    fn from(status: &StatusMin) -> Self {
+        match status.title {
+            Some(title) => WaybarStatus {
+                text: format!(
+                    "{}:{:02} {title}",
+                    status.elapsed_minutes / 60,
+                    status.elapsed_minutes % 60
+                ),
+                tooltip: format!("{title}\n{:.2}h today", status.today_hours),
+                class: if status.paused { "paused" } else { "active" },
+            },
+            // An empty text hides the module
+            None => WaybarStatus {
+                text: String::new(),
+                tooltip: format!("No active time box\n{:.2}h today", status.today_hours),
+                class: "inactive",
+            },
+        }
+    }
 }
 
-pub fn save_json_to_disk(
-    tracker: &InMemoryTimeTracker,
-    path: &Path,
-    strategy: &impl TimeTrackerStorageStrategy,
-) -> anyhow::Result<()> {
-    let time = chrono::Utc::now().timestamp_micros();
+/// Hours of the time boxes started today, including the active one.
+pub fn hours_today(tracker: &impl TimeTrackingStore) -> anyhow::Result<f64> {
+    let today = Local::now().date_naive();
+    let output = collect_for_output(
+        tracker,
+        &ListOptions::new()
+            .take(usize::MAX)
+            .filter(ListFilter::Date(today)),
+        true,
+    )?;
 
-    let path_swap = match path.parent() {
-        Some(f) => f,
-        None => {
-            if path.is_absolute() {
-                Path::new("/")
-            } else {
-                Path::new("")
+    let hours_active = match output.active {
+        Some(tb) if tb.time_start()?.with_timezone(&Local).date_naive() == today => {
+            tb.duration_active_in_hours()?.max(0.0)
+        }
+        _ => 0.0,
+    };
+
+    Ok(output.hours + hours_active)
+}
+
+/// Start of `date` in the local time zone. Clocks turned back repeat times, the earlier one counts.
+pub fn local_midnight(date: NaiveDate) -> anyhow::Result<DateTime<Utc>> {
+    date.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|time| time.to_utc())
+        .with_context(|| format!("Midnight of {date} does not exist in the local time zone"))
+}
+
+/// Tracked time of the local ISO week containing `today`, including the active time box.
+/// Time boxes crossing midnight at the start or end of the week count partially, see `timedelta_in_range`.
+pub fn tracked_this_week(
+    tracker: &impl TimeTrackingStore,
+    today: NaiveDate,
+) -> anyhow::Result<TimeDelta> {
+    let week = today.week(Weekday::Mon);
+    let from = local_midnight(week.first_day())?;
+    let to = local_midnight(week.last_day() + TimeDelta::days(1))?;
+
+    let output = collect_for_output(tracker, &ListOptions::new().take(usize::MAX), true)?;
+
+    let finished = hours_in_range(&output.finished.items, from, to);
+    let active = match output.active {
+        Some(tb) => timedelta_in_range(&tb, Utc::now(), from, to),
+        None => TimeDelta::zero(),
+    };
+
+    Ok(TimeDelta::seconds((finished * 60.0 * 60.0).round() as i64) + active)
+}
+
+/// One line like `This week: 31.50h / 40.00h (79%)`
+pub fn generate_weekly_progress(
+    tracked: TimeDelta,
+    goal: TimeDelta,
+    duration_style: DurationStyle,
+) -> String {
+    let percent = match goal > TimeDelta::zero() {
+        true => tracked.num_seconds() as f64 / goal.num_seconds() as f64 * 100.0,
+        false => 100.0,
+    };
+
+    format!(
+        "This week: {} / {} ({percent:.0}%)",
+        format_duration(tracked, duration_style),
+        format_duration(goal, duration_style)
+    )
+}
+
+/// One line like `Tracked 30.50h of 40.00h this week, 9.50h left → ~3.17h/day over Wed–Fri`
+pub fn generate_goal_status(
+    plan: &WeeklyGoalPlan,
+    goal: TimeDelta,
+    tracked: TimeDelta,
+    work_hours: TimeDelta,
+    duration_style: DurationStyle,
+) -> String {
+    let fmt = |delta: TimeDelta| format_duration(delta, duration_style);
+
+    if plan.is_met() {
+        return format!(
+            "Goal of {} met, tracked {} this week",
+            fmt(goal),
+            fmt(tracked)
+        );
+    }
+
+    let progress = format!(
+        "Tracked {} of {} this week, {} left",
+        fmt(tracked),
+        fmt(goal),
+        fmt(plan.remaining)
+    );
+
+    match plan.per_day {
+        None => format!("{progress} but no work days remain this week"),
+        Some(per_day) => {
+            let days = format_weekdays(&plan.days);
+            match plan.reachable {
+                true => format!("{progress} → ~{}/day over {days}", fmt(per_day)),
+                false => format!(
+                    "{progress} → ~{}/day over {days}, unreachable at current pace of at most {}/day",
+                    fmt(per_day),
+                    fmt(work_hours)
+                ),
             }
         }
     }
-    .join(format!(".__{time}_swap_tasks.json"));
+}
 
-    let mut file_swap = File::create(&path_swap)?;
-    debug!("Created file: {}", path_swap.display());
+/// Consecutive days as span like `Wed–Fri`, others separated by commas like `Mon, Wed, Fri`
+fn format_weekdays(days: &[NaiveDate]) -> String {
+    let consecutive = days.windows(2).all(|w| w[1] - w[0] == TimeDelta::days(1));
 
-    tracker.to_writer(strategy, &mut file_swap)?;
+    match (days, consecutive) {
+        ([first, _, ..], true) => format!("{}–{}", first.weekday(), days[days.len() - 1].weekday()),
+        _ => days
+            .iter()
+            .map(|d| d.weekday().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
 
-    debug!(
-        "Serialized swap tasks file to disk: {}",
-        path_swap.display()
+/// Styled via `TableStyle::active`. With `now`, a last row `(now)` shows that time and the active duration so far,
+/// without an index since it is no note.
+pub fn generate_table_active(
+    mut time_box: TimeBox,
+    date_column: DateColumn,
+    now: Option<DateTime<Utc>>,
+    table_width: Option<usize>,
+    style: &TableStyle,
+    duration_style: DurationStyle,
+) -> anyhow::Result<String> {
+    let total = footer_duration(time_box.timedelta_total()?, duration_style);
+    let active = footer_duration(time_box.timedelta_active()?, duration_style);
+    let mut sum_col_label = format!(
+        "tasks {total}, {} active, started {}",
+        paint(
+            &active,
+            active_hours_style(time_box.duration_active_in_hours()?),
+            style.color
+        ),
+        humanize_delta(Utc::now() - time_box.time_start()?)
     );
+    if let Some(paused) = paused_label(&time_box) {
+        sum_col_label.push_str(&format!(", {paused}"));
+    }
+    if let Some(estimate) = time_box.estimate {
+        let actual = time_box.timedelta_active()?;
+        sum_col_label.push_str(&format!(
+            ", {}",
+            format_estimate(estimate, actual, duration_style)
+        ));
+    }
 
-    match std::fs::rename(&path_swap, path) {
-        Ok(_) => (),
-        Err(e) => {
-            error!(
-                "Failed overwriting tasks file \"{}\" with the new content of the swap file \"{}\". Do not run the program again until you resolve this issue, otherwise adding or removing tasks will result in loss of data. Replace the contents of the tasks file with the newer content of the swap file manually.",
-                path.display(),
-                path_swap.display()
+    // The footer is summed up already, so the row of now is only rendered
+    let index_column = match now {
+        Some(now) => {
+            let description = format!(
+                "(now) {} active",
+                format_duration(time_box.timedelta_active()?, duration_style)
             );
-            return Err(anyhow!(e));
+            time_box.notes.push(TimeBoxNote {
+                time: now,
+                description,
+                kind: NoteKind::System,
+            });
+            IndexColumn::NotesUntilNow
         }
+        None => IndexColumn::Notes,
     };
 
-    debug!("Successfully replaced tasks file with newer content from the swap file");
+    Ok(generate_table(
+        date_column,
+        "At",
+        "Description",
+        &sum_col_label,
+        &mut [time_box],
+        index_column,
+        None,
+        table_width,
+        &style.active(),
+    ))
+}
 
-    Ok(())
+/// `delta` in `style` for the footers of tables. Decimal hours are hard to read for short durations,
+/// so they get followed by the `humanize_duration`, e.g. `1.38h (1h 23m)`.
+pub fn footer_duration(delta: TimeDelta, style: DurationStyle) -> String {
+    match style {
+        DurationStyle::DecimalHours => format!(
+            "{} ({})",
+            format_duration(delta, style),
+            humanize_duration(delta)
+        ),
+        style => format_duration(delta, style),
+    }
+}
+
+/// One row per time box with its start, duration and description shortened to `max_desc` columns,
+/// see `TimeBox::description_summary`, followed by the total. For long lists where every note would be too much.
+pub fn generate_table_compact(
+    date_column: DateColumn,
+    time_boxes: &[TimeBox],
+    max_desc: usize,
+    durations: DurationOptions,
+    duration_style: DurationStyle,
+) -> anyhow::Result<String> {
+    let rows = time_boxes
+        .iter()
+        .map(|tb| {
+            Ok(vec![
+                date_column.render(tb.time_start()?),
+                format_duration(durations.duration(tb)?, duration_style),
+                tb.description_summary(max_desc),
+            ])
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let labels = ["Start", "Duration", "Description"].map(String::from);
+    let footer = [
+        "total".to_string(),
+        format_duration(durations.total(time_boxes)?, duration_style),
+        String::new(),
+    ];
+
+    Ok(generate_grid(&labels, &rows, &footer, &[0, 2]))
+}
+
+/// First day of the month `months` before the one of `today`. Time boxes starting earlier are at least
+/// that many months old, so `list --collapse-older-than` sums them up per month.
+pub fn collapse_cutoff(today: NaiveDate, months: u32) -> NaiveDate {
+    today
+        .with_day(1)
+        .and_then(|first| first.checked_sub_months(Months::new(months)))
+        .unwrap_or(NaiveDate::MIN)
+}
+
+/// Splits the listed `time_boxes` into the ones starting from `cutoff` on, with their `indices`,
+/// and the older ones, keeping the order of both.
+pub fn split_at_cutoff<Tz: TimeZone>(
+    time_boxes: &[TimeBox],
+    indices: &[usize],
+    cutoff: NaiveDate,
+    tz: &Tz,
+) -> (Vec<TimeBox>, Vec<usize>, Vec<TimeBox>) {
+    let mut recent = (Vec::new(), Vec::new());
+    let mut older = Vec::new();
+
+    for (tb, index) in time_boxes.iter().zip(indices) {
+        let start = tb.time_start().unwrap_or_default().with_timezone(tz);
+        match start.date_naive() < cutoff {
+            true => older.push(tb.clone()),
+            false => {
+                recent.0.push(tb.clone());
+                recent.1.push(*index);
+            }
+        }
+    }
+
+    (recent.0, recent.1, older)
+}
+
+/// One row per month with the count and duration of its time boxes, followed by their subtotal.
+/// For `list --collapse-older-than`, where every note of old time boxes would be too much.
+pub fn generate_table_months<Tz: TimeZone>(
+    time_boxes: &[TimeBox],
+    durations: DurationOptions,
+    duration_style: DurationStyle,
+    tz: &Tz,
+) -> anyhow::Result<String> {
+    let rows = group_time_boxes(time_boxes, Grouping::Month, tz)
+        .into_iter()
+        .map(|(key, group)| {
+            Ok(vec![
+                key.to_string(),
+                group.len().to_string(),
+                format_duration(durations.total(&group)?, duration_style),
+            ])
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let labels = ["Month", "Boxes", "Duration"].map(String::from);
+    let footer = [
+        "subtotal".to_string(),
+        time_boxes.len().to_string(),
+        format_duration(durations.total(time_boxes)?, duration_style),
+    ];
+
+    Ok(generate_grid(&labels, &rows, &footer, &[0]))
+}
+
+/// Counterpart of `generate_table_months` for `--plain`, for example `2024-03: 41 boxes, 152 hours 18 minutes.`
+pub fn generate_plain_months<Tz: TimeZone>(
+    time_boxes: &[TimeBox],
+    durations: DurationOptions,
+    tz: &Tz,
+) -> anyhow::Result<String> {
+    let line = |label: &str, group: &[TimeBox]| -> anyhow::Result<String> {
+        Ok(format!(
+            "{label}: {}, {}.",
+            plain_boxes(group.len()),
+            format_duration(durations.total(group)?, DurationStyle::Words)
+        ))
+    };
+
+    let mut lines = group_time_boxes(time_boxes, Grouping::Month, tz)
+        .into_iter()
+        .map(|(key, group)| line(&key.to_string(), &group))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    lines.push(line("Subtotal", time_boxes)?);
+
+    Ok(lines.join("\n"))
+}
+
+/// Counterpart of `generate_table` for `--plain`, one line per time box without any alignment, for example
+/// `Entry 3: 2024-06-12 09:00 to 10:25, 1 hour 25 minutes. Notes: Fix login bug; Review PR.`
+/// The date of the stop is only repeated if it differs from the start.
+pub fn generate_plain_list<Tz: TimeZone>(
+    time_boxes: &[TimeBox],
+    indices: &[usize],
+    durations: DurationOptions,
+    tz: &Tz,
+) -> anyhow::Result<String>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let mut lines = Vec::with_capacity(time_boxes.len());
+
+    for (tb, index) in time_boxes.iter().zip(indices) {
+        let start = tb.time_start()?.with_timezone(tz);
+        let stop = tb.time_stop()?.with_timezone(tz);
+        let stop_format = match start.date_naive() == stop.date_naive() {
+            true => "%H:%M",
+            false => "%Y-%m-%d %H:%M",
+        };
+        let notes: Vec<&str> = tb.notes.iter().map(|n| n.description.as_str()).collect();
+
+        lines.push(format!(
+            "Entry {index}: {} to {}, {}. Notes: {}.",
+            start.format("%Y-%m-%d %H:%M"),
+            stop.format(stop_format),
+            format_duration(durations.duration(tb)?, DurationStyle::Words),
+            notes.join("; ")
+        ));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Counterpart of `generate_table_active` for `--plain`, a summary line followed by one line per note.
+pub fn generate_plain_active<Tz: TimeZone>(time_box: &TimeBox, tz: &Tz) -> anyhow::Result<String>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let mut summary = format!(
+        "Active since {}, {} so far.",
+        time_box
+            .time_start()?
+            .with_timezone(tz)
+            .format("%Y-%m-%d %H:%M"),
+        format_duration(time_box.timedelta_active()?, DurationStyle::Words)
+    );
+    if let Some(since) = time_box.paused_since() {
+        summary.push_str(&format!(
+            " Paused since {}.",
+            since.with_timezone(tz).format("%H:%M")
+        ));
+    }
+    if let Some(estimate) = time_box.estimate {
+        summary.push_str(&format!(
+            " Estimated {}.",
+            format_duration(estimate, DurationStyle::Words)
+        ));
+    }
+
+    let notes = time_box.notes.iter().enumerate().map(|(index, note)| {
+        format!(
+            "Note {index} at {}: {}",
+            note.time.with_timezone(tz).format("%H:%M"),
+            note.description
+        )
+    });
+
+    Ok([summary]
+        .into_iter()
+        .chain(notes)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// For example `Total: 1 hour 25 minutes.`
+pub fn generate_plain_total(label: &str, duration: TimeDelta) -> String {
+    format!(
+        "{label}: {}.",
+        format_duration(duration, DurationStyle::Words)
+    )
+}
+
+/// For example `2 boxes`
+pub fn plain_boxes(count: usize) -> String {
+    match count {
+        1 => "1 box".to_string(),
+        count => format!("{count} boxes"),
+    }
+}
+
+/// Renders `rows` of cells as ascii table underneath the `labels`, followed by the `footer` row.
+/// Cells of the `left_aligned` columns are left aligned like labels, the others right aligned like numbers.
+/// Pads with spaces up to the display width, so wide characters do not shift the borders.
+pub fn generate_grid<Row: AsRef<[String]>>(
+    labels: &[String],
+    rows: &[Row],
+    footer: &[String],
+    left_aligned: &[usize],
+) -> String {
+    let widths: Vec<usize> = (0..labels.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row.as_ref())
+                .chain([footer, labels])
+                .map(|row| row[i].width())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let line = |left: &str, middle: &str, right: &str| {
+        let columns: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+        format!("{left}{}{right}\n", columns.join(middle))
+    };
+    let row = |cells: &[String]| {
+        let columns: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .enumerate()
+            .map(|(i, (cell, w))| {
+                let padding = " ".repeat(w.saturating_sub(cell.width()));
+                match left_aligned.contains(&i) {
+                    true => format!(" {cell}{padding} "),
+                    false => format!(" {padding}{cell} "),
+                }
+            })
+            .collect();
+        format!("│{}│\n", columns.join("│"))
+    };
+
+    let mut output = String::with_capacity(1024);
+    output.push_str(&line("┌", "┬", "┐"));
+    output.push_str(&row(labels));
+    output.push_str(&line("├", "┼", "┤"));
+    for cells in rows.iter() {
+        output.push_str(&row(cells.as_ref()));
+    }
+    output.push_str(&line("├", "┼", "┤"));
+    output.push_str(&row(footer));
+    output.push_str(&line("└", "┴", "┘"));
+
+    output
+}
+
+/// How exports and sums derive the duration of each time box.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DurationOptions {
+    /// Keep negative durations instead of counting them as zero, see `clamped_duration`.
+    pub raw: bool,
+    /// Granularity and direction to round each duration in before summing up.
+    pub rounding: Option<(TimeDelta, RoundingMode)>,
+}
+
+impl DurationOptions {
+    pub fn duration(&self, time_box: &TimeBox) -> anyhow::Result<TimeDelta> {
+        let duration = match self.rounding {
+            Some((granularity, mode)) => time_box.duration_rounded(granularity, mode)?,
+            None => time_box.timedelta_total()?,
+        };
+
+        Ok(match self.raw {
+            true => duration,
+            false => duration.max(TimeDelta::zero()),
+        })
+    }
+
+    /// Sum of the durations, so it matches the listed durations also when rounding.
+    pub fn total(&self, time_boxes: &[TimeBox]) -> anyhow::Result<TimeDelta> {
+        time_boxes.iter().map(|tb| self.duration(tb)).sum()
+    }
+}
+
+/// Loads the store at `path` with the loading strategy of `format`, never from the cache.
+/// Logs the diagnostics of loading, see `log_diagnostics`.
+pub fn load_store(
+    path: &Path,
+    format: StorageFormat,
+    strict: bool,
+) -> Result<InMemoryTimeTracker, timetracker::Error> {
+    let tracker = match format {
+        StorageFormat::Json => {
+            InMemoryTimeTracker::init(&JsonFileLoadingStrategy::new(path).strict(strict))
+        }
+        StorageFormat::Toml => {
+            InMemoryTimeTracker::init(&TomlFileLoadingStrategy::new(path).strict(strict))
+        }
+        StorageFormat::Jsonl => {
+            InMemoryTimeTracker::init(&JsonlFileLoadingStrategy::new(path).strict(strict))
+        }
+    }?;
+
+    log_diagnostics(&tracker.diagnostics);
+    Ok(tracker)
+}
+
+/// The library only collects diagnostics, they get shown as log messages of the matching level.
+pub fn log_diagnostics(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        match diagnostic.severity {
+            Severity::Info => info!("{diagnostic}"),
+            Severity::Warning => warn!("{diagnostic}"),
+        }
+    }
+}
+
+/// Writes the whole store in `format`, see `save_to_disk`.
+/// JSON Lines stores consist of two files, the finished time boxes get written first.
+pub fn save_store(
+    tracker: &InMemoryTimeTracker,
+    path: &Path,
+    format: StorageFormat,
+    json_format: OutputJsonFormat,
+) -> anyhow::Result<()> {
+    match format {
+        StorageFormat::Json => {
+            save_to_disk(tracker, path, &json_format.into() as &JsonStorageStrategy)
+        }
+        StorageFormat::Toml => save_to_disk(tracker, path, &TomlStorageStrategy { repair: true }),
+        StorageFormat::Jsonl => {
+            save_to_disk(tracker, path, &JsonlStorageStrategy { repair: true })?;
+            save_to_disk(
+                tracker,
+                &state_path(path),
+                &JsonlStateStorageStrategy { repair: true },
+            )
+        }
+    }
+}
+
+/// Writes `tracker` in `format` into a new temporary directory and loads it again, the way it would be saved and loaded.
+/// The directory gets removed afterwards, even if saving or loading failed.
+pub fn roundtrip_store(
+    tracker: &InMemoryTimeTracker,
+    format: StorageFormat,
+    json_format: OutputJsonFormat,
+    strict: bool,
+) -> anyhow::Result<InMemoryTimeTracker> {
+    let random = RandomState::new().hash_one(SWAP_COUNTER.fetch_add(1, Ordering::Relaxed));
+    let dir = std::env::temp_dir().join(format!(
+        "timetracker-roundtrip-{}-{random:016x}",
+        std::process::id()
+    ));
+    std::fs::create_dir(&dir).with_context(|| {
+        format!(
+            "Failed to create the temporary directory \"{}\"",
+            dir.display()
+        )
+    })?;
+
+    let path = dir.join(format.file_name());
+    let roundtrip = save_store(tracker, &path, format, json_format).and_then(|_| {
+        load_store(&path, format, strict).context("Failed to load the store again after writing it")
+    });
+
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        warn!(
+            "Failed to remove the temporary directory \"{}\": {e}",
+            dir.display()
+        );
+    }
+
+    roundtrip
+}
+
+/// Swap files are named `.__<time>_<pid>_<random>_swap_tasks.<extension of the store>`
+const SWAP_PREFIX: &str = ".__";
+const SWAP_SUFFIX: &str = "_swap_tasks";
+
+/// Tells apart swap files of saves within the same process and microsecond
+static SWAP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Writes `bytes` into a swap file next to `path` first and then renames it, so `path` is never half written.
+/// The swap file name is unique per write and gets created exclusively, so racing invocations
+/// never write into each others swap file.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    let time = chrono::Utc::now().timestamp_micros();
+    let pid = std::process::id();
+    // Randomly seeded per process, so even reused PIDs get other names
+    let random = RandomState::new().hash_one(SWAP_COUNTER.fetch_add(1, Ordering::Relaxed));
+
+    let path_swap = match path.parent() {
+        Some(f) => f,
+        None => {
+            if path.is_absolute() {
+                Path::new("/")
+            } else {
+                Path::new("")
+            }
+        }
+    }
+    .join(format!(
+        "{SWAP_PREFIX}{time}_{pid}_{random:016x}{SWAP_SUFFIX}.{}",
+        path.extension().and_then(|e| e.to_str()).unwrap_or("json")
+    ));
+
+    let mut file_swap = File::create_new(&path_swap).with_context(|| {
+        format!(
+            "Failed creating the swap file \"{}\", it exists already",
+            path_swap.display()
+        )
+    })?;
+    debug!("Created file: {}", path_swap.display());
+
+    file_swap
+        .write_all(bytes)
+        .with_context(|| format!("Failed writing the swap file \"{}\"", path_swap.display()))?;
+
+    debug!("Wrote swap file to disk: {}", path_swap.display());
+
+    match std::fs::rename(&path_swap, path) {
+        Ok(_) => (),
+        Err(e) => {
+            error!(
+                "Failed overwriting \"{}\" with the new content of the swap file \"{}\". Do not run the program again until you resolve this issue, otherwise the newer content may get lost. Replace the contents of the file with the newer content of the swap file manually.",
+                path.display(),
+                path_swap.display()
+            );
+            return Err(anyhow!(e));
+        }
+    };
+
+    debug!(
+        "Successfully replaced \"{}\" with newer content from the swap file",
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// Serializes the store with `strategy` and saves it via `write_atomic`.
+pub fn save_to_disk(
+    tracker: &InMemoryTimeTracker,
+    path: &Path,
+    strategy: &impl TimeTrackerStorageStrategy,
+) -> anyhow::Result<()> {
+    let mut bytes = Vec::new();
+    tracker.to_writer(strategy, &mut bytes)?;
+
+    write_atomic(path, &bytes)
+}
+
+/// Swap file left behind next to the store by a save that died before renaming it, see `save_to_disk`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LeftoverSwap {
+    /// Loads as a store and was written after the store, so it holds the latest changes
+    Newer(PathBuf),
+    /// Incomplete, e.g. because writing it got interrupted, or older than the store
+    Stale(PathBuf),
+}
+
+/// Swap files of the store at `path` with its extension, the most recently written first.
+/// Only call this while holding the lock of the store, otherwise it finds swap files of saves in progress.
+pub fn find_leftover_swaps(
+    path: &Path,
+    format: StorageFormat,
+) -> anyhow::Result<Vec<LeftoverSwap>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let suffix = format!("{SWAP_SUFFIX}.{}", format.extension());
+    let store_modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    let mut swaps = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_swap = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|n| n.starts_with(SWAP_PREFIX) && n.ends_with(&suffix));
+        if !is_swap {
+            continue;
+        }
+
+        let modified = entry.metadata()?.modified()?;
+        let path = entry.path();
+        let is_newer = store_modified.is_none_or(|store| modified > store)
+            && load_store(&path, format, false).is_ok();
+        swaps.push((
+            modified,
+            match is_newer {
+                true => LeftoverSwap::Newer(path),
+                false => LeftoverSwap::Stale(path),
+            },
+        ));
+    }
+
+    swaps.sort_by_key(|(modified, _)| cmp::Reverse(*modified));
+    Ok(swaps.into_iter().map(|(_, swap)| swap).collect())
+}
+
+/// Adopts the most recent newer swap file of the store at `path` by renaming it over the store
+/// and deletes the other leftover swap files, but only those `decide` agrees to, asked newest first.
+/// Once one got adopted the remaining ones are older than the store, so they count as stale.
+/// Returns whether the store got replaced.
+pub fn recover_swaps(
+    path: &Path,
+    format: StorageFormat,
+    mut decide: impl FnMut(&LeftoverSwap) -> anyhow::Result<bool>,
+) -> anyhow::Result<bool> {
+    let mut adopted = false;
+
+    for swap in find_leftover_swaps(path, format)? {
+        let swap = match swap {
+            LeftoverSwap::Newer(swap) if adopted => LeftoverSwap::Stale(swap),
+            swap => swap,
+        };
+        if !decide(&swap)? {
+            continue;
+        }
+
+        match swap {
+            LeftoverSwap::Newer(swap) => {
+                std::fs::rename(&swap, path).with_context(|| {
+                    format!("Failed adopting the swap file \"{}\"", swap.display())
+                })?;
+                info!("Adopted the newer swap file \"{}\"", swap.display());
+                adopted = true;
+            }
+            LeftoverSwap::Stale(swap) => {
+                std::fs::remove_file(&swap).with_context(|| {
+                    format!("Failed deleting the swap file \"{}\"", swap.display())
+                })?;
+                info!("Deleted the stale swap file \"{}\"", swap.display());
+            }
+        }
+    }
+
+    Ok(adopted)
+}
+
+const BACKUP_PREFIX: &str = "storage-";
+
+/// Backups of the store inside `dir` with the file `extension` of the store, the newest first.
+/// Names contain a sortable timestamp so sorting by name is sorting by age.
+pub fn list_backups(dir: &Path, extension: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(anyhow!(e)),
+    };
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let is_backup = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with(BACKUP_PREFIX))
+            && path.extension().is_some_and(|e| e == extension);
+
+        if is_backup {
+            backups.push(path);
+        }
+    }
+
+    backups.sort();
+    backups.reverse();
+
+    Ok(backups)
+}
+
+/// Copies the file at `path` into `dir`, together with the state file of JSON Lines stores,
+/// and deletes all but the `keep` most recent backups of the same format.
+/// Returns the path of the new backup, `None` if `keep` is 0 or there is nothing to back up yet.
+pub fn write_backup(path: &Path, dir: &Path, keep: usize) -> anyhow::Result<Option<PathBuf>> {
+    if keep == 0 || !path.exists() {
+        return Ok(None);
+    }
+
+    std::fs::create_dir_all(dir)?;
+
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("json");
+    let time = Utc::now().format("%Y-%m-%dT%H-%M-%S%.6fZ");
+    let backup = dir.join(format!("{BACKUP_PREFIX}{time}.{extension}"));
+    std::fs::copy(path, &backup)?;
+    if let Some(state) = jsonl_state(path) {
+        std::fs::copy(state, state_path(&backup))?;
+    }
+    debug!(
+        "Backed up \"{}\" to \"{}\"",
+        path.display(),
+        backup.display()
+    );
+
+    for outdated in list_backups(dir, extension)?.iter().skip(keep) {
+        std::fs::remove_file(outdated)?;
+        if let Some(state) = jsonl_state(outdated) {
+            std::fs::remove_file(state)?;
+        }
+        debug!("Removed outdated backup \"{}\"", outdated.display());
+    }
+
+    Ok(Some(backup))
+}
+
+/// State file of the JSON Lines store at `path` if there is one, backups have to include it.
+fn jsonl_state(path: &Path) -> Option<PathBuf> {
+    let is_jsonl = path
+        .extension()
+        .is_some_and(|e| e == StorageFormat::Jsonl.extension());
+
+    Some(state_path(path)).filter(|state| is_jsonl && state.exists())
+}
+
+/// Content of the `.gitignore` that `init` writes into the storage directory.
+pub fn gitignore_content(track_in_git: bool) -> &'static str {
+    match track_in_git {
+        // Swap files carry the extension of the storage format
+        true => ".__*_swap_tasks.*\n*.cache\n*.lock\nbackups/\n",
+        false => "*",
+    }
+}
+
+/// Whether git tracks `file`. Without git or outside of a repository nothing is tracked.
+pub fn is_tracked_in_git(file: &Path) -> bool {
+    let (Some(directory), Some(name)) = (file.parent(), file.file_name()) else {
+        return false;
+    };
+
+    std::process::Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(["ls-files", "--error-unmatch"])
+        .arg(name)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Asks on `stderr` so the question does not end up in redirected output.
+pub fn confirm(question: &str) -> anyhow::Result<bool> {
+    eprint!("{question} [y/N] ");
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Amount of recent titles `begin --pick` offers
+pub const PICK_TITLES: usize = 10;
+
+/// Lists `titles` numbered from 1 on `output` and asks until the answer is a listed number or a new description.
+pub fn pick_description(
+    titles: &[String],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> anyhow::Result<String> {
+    for (i, title) in titles.iter().enumerate() {
+        writeln!(output, "{:>3}  {title}", i + 1)?;
+    }
+
+    loop {
+        write!(output, "Number or new description: ")?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            bail!("Aborted picking a description, no input left");
+        }
+
+        let answer = answer.trim();
+        match answer.parse::<usize>() {
+            _ if answer.is_empty() => continue,
+            Ok(number) => match number.checked_sub(1).and_then(|i| titles.get(i)) {
+                Some(title) => return Ok(title.clone()),
+                None => writeln!(
+                    output,
+                    "There is no title with number {number}, choose between 1 and {}",
+                    titles.len()
+                )?,
+            },
+            Err(_) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+/// Changes that `--porcelain` summarizes, named like the commands causing them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PorcelainEvent {
+    Begin,
+    Note,
+    Amend,
+    EditNote,
+    Split,
+    Pause,
+    Unpause,
+    End,
+    Resume,
+    Merge,
+    Cancel,
+    Clear,
+    Export,
+    Set { index: usize },
+    Lock,
+    Import,
+    Goal,
+    Restore,
+    Undo,
+}
+
+impl PorcelainEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            PorcelainEvent::Begin => "begin",
+            PorcelainEvent::Note => "note",
+            PorcelainEvent::Amend => "amend",
+            PorcelainEvent::EditNote => "edit-note",
+            PorcelainEvent::Split => "split",
+            PorcelainEvent::Pause => "pause",
+            PorcelainEvent::Unpause => "unpause",
+            PorcelainEvent::End => "end",
+            PorcelainEvent::Resume => "resume",
+            PorcelainEvent::Merge => "merge",
+            PorcelainEvent::Cancel => "cancel",
+            PorcelainEvent::Clear => "clear",
+            PorcelainEvent::Export => "export",
+            PorcelainEvent::Set { .. } => "set",
+            PorcelainEvent::Lock => "lock",
+            PorcelainEvent::Import => "import",
+            PorcelainEvent::Goal => "goal",
+            PorcelainEvent::Restore => "restore",
+            PorcelainEvent::Undo => "undo",
+        }
+    }
+}
+
+/// Quotes `value` for `--porcelain`: Backslashes and quotes get escaped with a backslash, control characters like line breaks get stripped.
+pub fn porcelain_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars().filter(|c| !c.is_control()) {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Single line of space separated `key=value` pairs describing the change from `before` to `after`, see `porcelain_fields`.
+/// Only `title` can contain spaces, so it is the only quoted value, see `porcelain_quote`.
+pub fn generate_porcelain(
+    event: PorcelainEvent,
+    before: &InMemoryTimeTracker,
+    after: &InMemoryTimeTracker,
+    now: DateTime<Utc>,
+) -> String {
+    porcelain_fields(event, before, after, now)
+        .into_iter()
+        .map(|(key, value)| match key {
+            "title" => format!("{key}={}", porcelain_quote(&value)),
+            _ => format!("{key}={value}"),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Stable code of the library error behind `error`, see `timetracker::Error::code`.
+/// `None` for errors of the CLI itself, e.g. a config file it cannot parse.
+pub fn error_code(error: &anyhow::Error) -> Option<&'static str> {
+    error
+        .chain()
+        .find_map(|e| e.downcast_ref::<timetracker::Error>())
+        .map(timetracker::Error::code)
+}
+
+/// What failing commands print on `stderr`, the message and its causes like returning the error from `main` would,
+/// with the code of `error_code` in front, e.g. `Error [no_active_time_box]: Unable to end…`.
+pub fn format_error(error: &anyhow::Error) -> String {
+    match error_code(error) {
+        Some(code) => format!("Error [{code}]: {error:?}"),
+        None => format!("Error: {error:?}"),
+    }
+}
+
+/// Line of `--porcelain` for failing commands, e.g. `event=error code=no_active_time_box message="Unable to end…"`.
+/// `code` is missing for errors of the CLI itself, see `error_code`. The message includes its causes.
+pub fn generate_porcelain_error(error: &anyhow::Error) -> String {
+    let mut fields = vec!["event=error".to_string()];
+    if let Some(code) = error_code(error) {
+        fields.push(format!("code={code}"));
+    }
+    fields.push(format!(
+        "message={}",
+        porcelain_quote(&format!("{error:#}"))
+    ));
+    fields.join(" ")
+}
+
+/// `hours`, `start`, `stop` if `finished` and `title` of a time box, with `hours` of active ones counted until `now`.
+pub fn time_box_fields(
+    tb: &TimeBox,
+    finished: bool,
+    now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let start = tb.time_start().unwrap_or_default();
+    let (hours, stop) = match finished {
+        true => (clamped_hours(tb), tb.time_stop().ok()),
+        false => (
+            elapsed_active(tb, now).num_seconds() as f64 / 60.0 / 60.0,
+            None,
+        ),
+    };
+
+    let mut fields = vec![
+        ("hours", format!("{hours:.2}")),
+        ("start", start.to_rfc3339_opts(SecondsFormat::Secs, true)),
+    ];
+    if let Some(stop) = stop {
+        fields.push(("stop", stop.to_rfc3339_opts(SecondsFormat::Secs, true)));
+    }
+    fields.push(("title", tb.title().to_string()));
+    fields
+}
+
+/// Keys and unquoted values describing the change from `before` to `after`, starting with `event`.
+/// Times are UTC, `hours` of the active time box are counted until `now`.
+fn porcelain_fields(
+    event: PorcelainEvent,
+    before: &InMemoryTimeTracker,
+    after: &InMemoryTimeTracker,
+    now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let mut fields = vec![("event", event.name().to_string())];
+
+    let time_box_fields = |tb: &TimeBox, finished: bool| time_box_fields(tb, finished, now);
+
+    // Ending inserts the time box in order, so look for the one that was not finished before
+    let newly_finished = || {
+        after
+            .finished
+            .iter()
+            .find(|tb| !before.finished.contains(tb))
+    };
+
+    match event {
+        PorcelainEvent::Begin
+        | PorcelainEvent::Note
+        | PorcelainEvent::Amend
+        | PorcelainEvent::EditNote
+        | PorcelainEvent::Split
+        | PorcelainEvent::Pause
+        | PorcelainEvent::Unpause
+        | PorcelainEvent::End
+        | PorcelainEvent::Resume => match (&after.active, newly_finished()) {
+            (Some(tb), _) => fields.extend(time_box_fields(tb, false)),
+            (None, Some(tb)) => fields.extend(time_box_fields(tb, true)),
+            (None, None) => {}
+        },
+        // Merging only touches finished time boxes, whatever is active meanwhile
+        PorcelainEvent::Merge => {
+            if let Some(tb) = newly_finished() {
+                fields.extend(time_box_fields(tb, true));
+            }
+        }
+        PorcelainEvent::Cancel => {
+            if let Some(tb) = &before.active {
+                fields.extend(time_box_fields(tb, false));
+            }
+        }
+        PorcelainEvent::Clear => fields.push((
+            "removed",
+            (before.finished.len() - after.finished.len()).to_string(),
+        )),
+        PorcelainEvent::Export => {
+            let exported = |t: &InMemoryTimeTracker| {
+                t.finished
+                    .iter()
+                    .filter(|tb| tb.exported_at.is_some())
+                    .count()
+            };
+            fields.push((
+                "marked",
+                exported(after).saturating_sub(exported(before)).to_string(),
+            ));
+        }
+        PorcelainEvent::Set { index } => {
+            fields.push(("index", index.to_string()));
+            if let Some(tb) = after.finished.get(index) {
+                fields.extend(time_box_fields(tb, true));
+            }
+        }
+        PorcelainEvent::Lock => {
+            if let Some(before) = after.settings.locked_before {
+                fields.push((
+                    "locked_before",
+                    before.to_rfc3339_opts(SecondsFormat::Secs, true),
+                ));
+            }
+        }
+        PorcelainEvent::Import => {
+            fields.push((
+                "imported",
+                (after.finished.len() - before.finished.len()).to_string(),
+            ));
+            fields.push(("active", after.active.is_some().to_string()));
+        }
+        PorcelainEvent::Goal => {
+            if let Some(goal) = after.settings.weekly_goal {
+                let hours = goal.num_seconds() as f64 / 60.0 / 60.0;
+                fields.push(("weekly_goal", format!("{hours:.2}")));
+            }
+        }
+        PorcelainEvent::Restore | PorcelainEvent::Undo => {
+            fields.push(("active", after.active.is_some().to_string()));
+            fields.push(("finished", after.finished.len().to_string()));
+        }
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta, Utc};
+    use timetracker::{NoteKind, Pause, StoreSettings, TimeBoxNote};
+
+    use super::*;
+    use crate::test_fixtures::time_box;
+
+    #[test]
+    fn status_line_and_json_contain_active_duration() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].time = Utc::now() - TimeDelta::minutes(135);
+        time_box.notes[0].description = "fixing the parser\nwith details".to_string();
+        time_box.notes.truncate(1);
+
+        assert_eq!(
+            "2.25h — fixing the parser",
+            generate_status_line(&time_box, DurationStyle::DecimalHours)?
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&generate_status_json(&time_box)?)?;
+        assert_eq!(1, json["notes"].as_array().unwrap().len());
+        let hours = json["duration_active_in_hours"].as_f64().unwrap();
+        assert!((hours - 2.25).abs() < 0.01, "{hours}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn template_placeholders_get_filled() {
+        let value_of = |name: &str| (name == "x").then(|| "1".to_string());
+
+        assert_eq!("a 1 b 1", fill_template("a {x} b {x}", value_of));
+        assert_eq!("{unknown} 1", fill_template("{unknown} {x}", value_of));
+        assert_eq!("{x} {1}", fill_template("{{x}} {{{x}}}", value_of));
+        assert_eq!("unclosed {x", fill_template("unclosed {x", value_of));
+        assert_eq!("} {", fill_template("} {", value_of));
+        assert_eq!("⏱ 1", fill_template("⏱ {x}", value_of));
+        assert_eq!("", fill_template("", value_of));
+    }
+
+    #[test]
+    fn prompt_shows_the_active_time_box() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].time = Utc::now() - TimeDelta::minutes(72);
+        time_box.notes[0].description = "fixing parser\nwith details".to_string();
+        time_box.notes[1].time = Utc::now();
+        time_box.notes[1].description = "found it".to_string();
+        time_box.notes.push(TimeBoxNote {
+            time: Utc::now(),
+            description: "Resumed".to_string(),
+            kind: NoteKind::System,
+        });
+
+        assert_eq!(
+            "⏱ 1.2h fixing parser",
+            generate_prompt(&time_box, "⏱ {hours_active} {description}")?
+        );
+        assert_eq!(
+            "72m, 2 notes, last: found it {nope}",
+            generate_prompt(
+                &time_box,
+                "{minutes_active}, {notes_count} notes, last: {last_note} {nope}"
+            )?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn status_indicates_pauses() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].time = Utc::now() - TimeDelta::minutes(135);
+        time_box.notes.truncate(1);
+        let since = Utc::now() - TimeDelta::minutes(15);
+        time_box.pauses.push(Pause {
+            start: since,
+            stop: None,
+        });
+
+        let expected = format!(
+            "2.00h — start (PAUSED since {})",
+            since.with_timezone(&Local).format("%H:%M")
+        );
+        assert_eq!(
+            expected,
+            generate_status_line(&time_box, DurationStyle::DecimalHours)?
+        );
+
+        let status = StatusMin::new(Some(&time_box), 2.0)?;
+        assert!(status.paused);
+        assert_eq!(120, status.elapsed_minutes);
+        assert_eq!("paused", WaybarStatus::from(&status).class);
+
+        Ok(())
+    }
+
+    #[test]
+    fn backup_rotation_deletes_the_oldest() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let storage = dir.path().join("storage.json");
+        let backups = dir.path().join("backups");
+
+        let mut written = Vec::new();
+        for i in 0..5 {
+            std::fs::write(&storage, format!("{i}"))?;
+            written.push(write_backup(&storage, &backups, 3)?.unwrap());
+            // Backup names have microsecond precision
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let remaining = list_backups(&backups, "json")?;
+        written.reverse();
+        assert_eq!(written[..3], remaining[..]);
+        assert_eq!("4", std::fs::read_to_string(&remaining[0])?);
+        assert_eq!("2", std::fs::read_to_string(&remaining[2])?);
+
+        assert_eq!(None, write_backup(&storage, &backups, 0)?);
+        assert_eq!(3, list_backups(&backups, "json")?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn table_borders_align_with_wide_characters() {
+        let mut boxes = vec![time_box("2024-06-01T09:00:00Z", 1)];
+        boxes[0].notes[0].description = "作業開始".to_string();
+        boxes[0].notes[1].description = "ascii".to_string();
+
+        let table = generate_table(
+            DateColumn::Format("%H:%M"),
+            "At",
+            "Description",
+            "total",
+            &mut boxes,
+            IndexColumn::Notes,
+            None,
+            None,
+            &TableStyle::default(),
+        );
+        let wide = table.lines().find(|l| l.contains("作業開始")).unwrap();
+        let ascii = table.lines().find(|l| l.contains("ascii")).unwrap();
+
+        assert_eq!(ascii.width(), wide.width());
+        assert!(wide.ends_with(" │"));
+    }
+
+    #[test]
+    fn status_bar_formats_keep_their_field_names() -> anyhow::Result<()> {
+        #[derive(Debug, serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct JsonMin {
+            active: bool,
+            paused: bool,
+            title: Option<String>,
+            elapsed_minutes: i64,
+            today_hours: f64,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Waybar {
+            text: String,
+            tooltip: String,
+            class: String,
+        }
+
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].time = Utc::now() - TimeDelta::minutes(74);
+        time_box.notes[0].description = "Fix login".to_string();
+        time_box.notes.truncate(1);
+
+        let status = StatusMin::new(Some(&time_box), 5.2)?;
+        let min: JsonMin = serde_json::from_str(&serde_json::to_string(&status)?)?;
+        assert!(min.active);
+        assert_eq!(Some("Fix login"), min.title.as_deref());
+        assert_eq!(74, min.elapsed_minutes);
+
+        let waybar: Waybar =
+            serde_json::from_str(&serde_json::to_string(&WaybarStatus::from(&status))?)?;
+        assert_eq!("1:14 Fix login", waybar.text);
+        assert_eq!("Fix login\n5.20h today", waybar.tooltip);
+        assert_eq!("active", waybar.class);
+
+        let status = StatusMin::new(None, 0.0)?;
+        let min: JsonMin = serde_json::from_str(&serde_json::to_string(&status)?)?;
+        assert!(!min.active && min.title.is_none());
+        let waybar: Waybar =
+            serde_json::from_str(&serde_json::to_string(&WaybarStatus::from(&status))?)?;
+        assert_eq!("inactive", waybar.class);
+
+        Ok(())
+    }
+
+    #[test]
+    fn footers_follow_decimal_hours_with_a_readable_duration() -> anyhow::Result<()> {
+        let minutes = TimeDelta::minutes;
+        assert_eq!(
+            "1.38h (1h 23m)",
+            footer_duration(minutes(83), DurationStyle::DecimalHours)
+        );
+        assert_eq!(
+            "0.00h (12s)",
+            footer_duration(TimeDelta::seconds(12), DurationStyle::DecimalHours)
+        );
+        assert_eq!(
+            "1h 23m",
+            footer_duration(minutes(83), DurationStyle::HoursMinutes)
+        );
+        assert_eq!("01:23", footer_duration(minutes(83), DurationStyle::Clock));
+
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].time = Utc::now() - minutes(45);
+        time_box.notes.truncate(1);
+        let table = generate_table_active(
+            time_box,
+            DateColumn::Format("%H:%M"),
+            None,
+            None,
+            &TableStyle::new(false),
+            DurationStyle::DecimalHours,
+        )?;
+        assert!(
+            table.contains("tasks 0.00h (0s), 0.75h (45m) active"),
+            "{table}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn long_footers_span_the_table() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].time = Utc::now() - TimeDelta::minutes(45);
+        time_box.notes.truncate(1);
+        let table = |width: Option<usize>| {
+            generate_table_active(
+                time_box.clone(),
+                DateColumn::Format("%H:%M"),
+                None,
+                width,
+                &TableStyle::new(false),
+                DurationStyle::DecimalHours,
+            )
+        };
+
+        // The date column keeps the width of its dates, the description column makes room for the footer instead
+        let unlimited = table(None)?;
+        assert!(unlimited.contains("│ # │  At   │"), "{unlimited}");
+        assert!(unlimited.contains("├───┴───────┴─"), "{unlimited}");
+        assert_eq!(1, unlimited.lines().filter(|l| l.contains("tasks")).count());
+        let widths: Vec<usize> = unlimited.lines().map(|l| l.width()).collect();
+        assert!(widths.iter().all(|w| *w == widths[0]), "{unlimited}");
+
+        // Narrow terminals split it at its commas
+        let narrow = table(Some(30))?;
+        assert!(narrow.contains("│ # │  At   │"), "{narrow}");
+        assert!(
+            narrow.lines().count() > unlimited.lines().count(),
+            "{narrow}"
+        );
+        assert!(narrow.lines().all(|l| l.width() <= 30), "{narrow}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn status_tables_end_with_a_row_of_now() -> anyhow::Result<()> {
+        let now = Utc::now();
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].time = now - TimeDelta::minutes(45);
+        time_box.notes.truncate(1);
+        let table = |now: Option<DateTime<Utc>>| {
+            generate_table_active(
+                time_box.clone(),
+                DateColumn::Relative(now.unwrap_or_default()),
+                now,
+                None,
+                &TableStyle::new(false),
+                DurationStyle::DecimalHours,
+            )
+        };
+
+        // `status` passes now, `list` shows the active time box without it
+        let status = table(Some(now))?;
+        let list = table(None)?;
+        assert!(!list.contains("(now)"), "{list}");
+
+        let rows: Vec<&str> = status.lines().filter(|l| l.starts_with("│ ")).collect();
+        assert_eq!(
+            vec!["0", "", "tasks"],
+            rows[1..]
+                .iter()
+                .map(|row| row.trim_start_matches("│ ").split(' ').next().unwrap())
+                .collect::<Vec<_>>(),
+            "{status}"
+        );
+        assert!(rows[2].contains("(now) 0.75h active"), "{status}");
+
+        // The row of now is no note, so the footer stays the same
+        let footer = |table: &str| table.lines().rev().nth(1).unwrap().to_string();
+        assert!(footer(&status).contains("tasks 0.00h (0s), 0.75h (45m) active"));
+        assert_eq!(footer(&list).trim(), footer(&status).trim());
+
+        Ok(())
+    }
+
+    #[test]
+    fn colors_do_not_change_the_layout() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].time = Utc::now() - TimeDelta::hours(1);
+        time_box.notes.truncate(1);
+
+        let plain = generate_table_active(
+            time_box.clone(),
+            DateColumn::Format("%Y-%m-%d %H:%M"),
+            None,
+            Some(40),
+            &TableStyle::new(false),
+            DurationStyle::DecimalHours,
+        )?;
+        let colored = generate_table_active(
+            time_box.clone(),
+            DateColumn::Format("%Y-%m-%d %H:%M"),
+            None,
+            Some(40),
+            &TableStyle::new(true),
+            DurationStyle::DecimalHours,
+        )?;
+
+        assert!(!plain.contains('\x1b'));
+        assert!(colored.contains("\x1b[32m"));
+        assert_eq!(plain, strip_ansi(&colored));
+
+        time_box.notes[0].time = Utc::now() - TimeDelta::hours(9);
+        let overrun = generate_table_active(
+            time_box,
+            DateColumn::Format("%Y-%m-%d %H:%M"),
+            None,
+            Some(40),
+            &TableStyle::new(true),
+            DurationStyle::DecimalHours,
+        )?;
+        assert!(overrun.contains("\x1b[33m"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn styles_get_applied_after_padding() {
+        let mut tb = time_box("2024-06-01T09:00:00Z", 1);
+        tb.notes[0].description =
+            "作業開始 with a description long enough to get wrapped into several lines".to_string();
+        let sum = format!("total {}, more", paint("1.00h", hours_style(), true));
+        let render = |style: &TableStyle| {
+            generate_table(
+                DateColumn::Format("%Y-%m-%d %H:%M"),
+                "At",
+                "Description",
+                &sum,
+                &mut [tb.clone()],
+                IndexColumn::TimeBoxes(&[12]),
+                None,
+                Some(50),
+                style,
+            )
+        };
+
+        let plain = render(&TableStyle::default());
+        let styled = render(&TableStyle::new(true));
+        assert_eq!(strip_ansi(&plain), strip_ansi(&styled));
+        assert_ne!(plain, styled);
+
+        // Borders line up on the visible width, escape sequences do not count. The footer is narrower
+        let visible = strip_ansi(&styled);
+        let (rows, footer) = visible.split_at(visible.find('┘').unwrap() + '┘'.len_utf8());
+        let widths: Vec<usize> = rows.lines().map(|l| l.width()).collect();
+        assert!(widths.iter().all(|w| *w == widths[0]), "{widths:?}");
+        assert!(widths[0] <= 50);
+        let widths: Vec<usize> = footer.lines().skip(1).map(|l| l.width()).collect();
+        assert!(widths.iter().all(|w| *w == widths[0]), "{widths:?}");
+
+        // Dates are dimmed, the description keeps the default color
+        let row = styled.lines().find(|l| l.contains("作業開始")).unwrap();
+        assert!(row.contains("\x1b[2m2024-06-01"), "{row:?}");
+        assert!(row.contains("\x1b[0m │ 作業開始"), "{row:?}");
+
+        // The sum stays bold after the painted hours
+        let footer = styled.lines().find(|l| l.contains("total")).unwrap();
+        assert!(footer.starts_with("│ "), "{footer:?}");
+        assert!(footer.contains("\x1b[1mtotal"), "{footer:?}");
+        assert!(footer.contains("\x1b[0m\x1b[1m, more"), "{footer:?}");
+    }
+
+    #[test]
+    fn active_table_header_is_yellow() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-01T09:00:00Z", 1);
+        time_box.notes[0].time = Utc::now() - TimeDelta::hours(1);
+        time_box.notes.truncate(1);
+
+        let colored = generate_table_active(
+            time_box.clone(),
+            DateColumn::Format("%Y-%m-%d %H:%M"),
+            None,
+            None,
+            &TableStyle::new(true),
+            DurationStyle::DecimalHours,
+        )?;
+        let header = colored.lines().nth(1).unwrap();
+        assert!(
+            header
+                .split("\x1b[33m")
+                .any(|cell| cell.split("\x1b[0m").next().unwrap().trim() == "Description"),
+            "{header:?}"
+        );
+
+        let plain = generate_table_active(
+            time_box,
+            DateColumn::Format("%Y-%m-%d %H:%M"),
+            None,
+            None,
+            &TableStyle::new(false),
+            DurationStyle::DecimalHours,
+        )?;
+        assert!(!plain.contains('\x1b'));
+        assert_eq!(TableStyle::default(), TableStyle::new(false).active());
+
+        Ok(())
+    }
+
+    #[test]
+    fn relative_date_column_and_started_footer() -> anyhow::Result<()> {
+        let mut time_box = time_box("2024-06-12T09:00:00Z", 0);
+        time_box.notes[1].time += TimeDelta::minutes(35);
+        let now: DateTime<Utc> = "2024-06-12T10:00:00Z".parse()?;
+
+        let table = generate_table(
+            DateColumn::Relative(now),
+            "At",
+            "Description",
+            "total",
+            &mut [time_box.clone()],
+            IndexColumn::Notes,
+            None,
+            None,
+            &TableStyle::new(false),
+        );
+        assert!(
+            table.contains("│ 0 │   1 hour ago   │ start       │"),
+            "{table}"
+        );
+        assert!(table.contains("│ 1 │ 25 minutes ago │"), "{table}");
+
+        time_box.notes[0].time = Utc::now() - TimeDelta::minutes(25);
+        time_box.notes.truncate(1);
+        let active = generate_table_active(
+            time_box,
+            DateColumn::new(false),
+            None,
+            None,
+            &TableStyle::new(false),
+            DurationStyle::DecimalHours,
+        )?;
+        assert!(active.contains("started 25 minutes ago"), "{active}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn table_wraps_long_descriptions_at_word_boundaries() {
+        let mut tb = time_box("2024-06-01T09:00:00Z", 1);
+        let description = "Lorem ipsum dolor sit amet consectetur adipiscing elit sed do \
+            eiusmod tempor incididunt ut labore et dolore magna aliqua";
+        assert_eq!(120, description.len());
+        tb.notes[0].description = description.to_string();
+
+        let table = generate_table(
+            DateColumn::Format("%H:%M"),
+            "At",
+            "Description",
+            "",
+            &mut [tb],
+            IndexColumn::Notes,
+            None,
+            Some(40),
+            &TableStyle::default(),
+        );
+
+        // Only the rows of the first note, the index and date column are empty for wrapped fragments
+        let lines: Vec<&str> = table
+            .lines()
+            .skip(3)
+            .take_while(|l| !l.contains("stop"))
+            .collect();
+        assert!(lines.len() > 1);
+        assert!(
+            lines
+                .iter()
+                .skip(1)
+                .all(|l| l.starts_with("│   │       │ "))
+        );
+
+        let fragments: Vec<&str> = lines
+            .iter()
+            .map(|l| l.split('│').nth(3).unwrap().trim())
+            .collect();
+        assert!(fragments.iter().all(|f| f.len() <= 40));
+        assert_eq!(description, fragments.join(" "));
+    }
+
+    #[test]
+    fn pagers_that_do_not_start_are_an_error_for_falling_back() {
+        assert!(spawn_pager("/nonexistent", "content").is_err());
+        assert!(spawn_pager("   ", "content").is_err());
+        // Pagers quitting before reading everything are fine
+        assert!(spawn_pager("true", &"line\n".repeat(100_000)).is_ok());
+    }
+
+    #[test]
+    fn compact_tables_have_one_row_per_time_box() -> anyhow::Result<()> {
+        let mut time_boxes = vec![
+            time_box("2024-06-01T12:00:00Z", 1),
+            time_box("2024-06-02T12:00:00Z", 2),
+            time_box("2024-06-03T12:00:00Z", 3),
+        ];
+        time_boxes[0].notes[0].description = "Refactor the session handling\nwith details".into();
+        time_boxes[1].notes[0].description = "Review 日本語 docs".into();
+
+        let table = generate_table_compact(
+            DateColumn::Format("%Y-%m-%d"),
+            &time_boxes,
+            16,
+            DurationOptions::default(),
+            DurationStyle::DecimalHours,
+        )?;
+
+        let expected = "\
+┌────────────┬──────────┬──────────────────┐
+│ Start      │ Duration │ Description      │
+├────────────┼──────────┼──────────────────┤
+│ 2024-06-01 │    1.00h │ Refactor the se… │
+│ 2024-06-02 │    2.00h │ Review 日本語 d… │
+│ 2024-06-03 │    3.00h │ start            │
+├────────────┼──────────┼──────────────────┤
+│ total      │    6.00h │                  │
+└────────────┴──────────┴──────────────────┘
+";
+        assert_eq!(expected, table);
+        assert_eq!(3, table.lines().filter(|l| l.starts_with("│ 2024")).count());
+
+        assert_eq!(TEXT_WRAP_COL, compact_description_width(None));
+        assert_eq!(26, compact_description_width(Some(60)));
+        assert_eq!(TEXT_WRAP_COL_MIN, compact_description_width(Some(20)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_tables_show_the_duration_of_each_time_box() {
+        let mut longer = time_box("2024-06-01T11:00:00Z", 2);
+        longer.notes[1].time += TimeDelta::minutes(15);
+        let mut time_boxes = [time_box("2024-06-01T09:00:00Z", 1), longer];
+        let durations: Vec<String> = time_boxes
+            .iter()
+            .map(|tb| format_duration(tb.timedelta_total().unwrap(), DurationStyle::DecimalHours))
+            .collect();
+
+        let table = generate_table(
+            DateColumn::Format("%H:%M"),
+            "At",
+            "Description",
+            "total 3.25h",
+            &mut time_boxes,
+            IndexColumn::TimeBoxes(&[0, 1]),
+            Some(&durations),
+            None,
+            &TableStyle::default(),
+        );
+
+        let expected = "\
+┌───┬─────────┬─────────────┐
+│ # │   At    │ Description │
+├───┼─ 1.00h ─┼─────────────┤
+│ 0 │  09:00  │ start       │
+│   │  10:00  │ stop        │
+├───┼─ 2.25h ─┼─────────────┤
+│ 1 │  11:00  │ start       │
+│   │  13:15  │ stop        │
+├───┴─────────┼─────────────┘
+│ total 3.25h │
+└─────────────┘
+";
+        assert_eq!(expected, table);
+    }
+
+    #[test]
+    fn collapsing_starts_at_the_first_of_a_month() {
+        let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+
+        assert_eq!(date("2026-07-01"), collapse_cutoff(date("2026-10-16"), 3));
+        assert_eq!(date("2025-11-01"), collapse_cutoff(date("2026-01-31"), 2));
+        assert_eq!(date("2026-10-01"), collapse_cutoff(date("2026-10-16"), 0));
+    }
+
+    #[test]
+    fn lists_collapse_older_months_above_the_recent_time_boxes() -> anyhow::Result<()> {
+        let time_boxes = [
+            time_box("2024-03-04T09:00:00Z", 1),
+            time_box("2024-03-18T09:00:00Z", 2),
+            time_box("2024-04-02T09:00:00Z", 3),
+            time_box("2024-07-01T09:00:00Z", 1),
+        ];
+        let durations = DurationOptions::default();
+
+        let (mut recent, indices, older) =
+            split_at_cutoff(&time_boxes, &[0, 1, 2, 3], "2024-06-01".parse()?, &Utc);
+        assert_eq!(vec![3], indices);
+
+        let combined = format!(
+            "{}\n{}",
+            generate_table_months(&older, durations, DurationStyle::DecimalHours, &Utc)?,
+            generate_table(
+                DateColumn::Format("%Y-%m-%d %H:%M"),
+                "At",
+                "Description",
+                "total 7.00h",
+                &mut recent,
+                IndexColumn::TimeBoxes(&indices),
+                None,
+                None,
+                &TableStyle::default(),
+            )
+        );
+
+        let expected = "\
+┌──────────┬───────┬──────────┐
+│ Month    │ Boxes │ Duration │
+├──────────┼───────┼──────────┤
+│ 2024-03  │     2 │    3.00h │
+│ 2024-04  │     1 │    3.00h │
+├──────────┼───────┼──────────┤
+│ subtotal │     3 │    6.00h │
+└──────────┴───────┴──────────┘
+
+┌───┬──────────────────┬─────────────┐
+│ # │        At        │ Description │
+├───┼──────────────────┼─────────────┤
+│ 3 │ 2024-07-01 09:00 │ start       │
+│   │ 2024-07-01 10:00 │ stop        │
+├───┴──────────────────┼─────────────┘
+│          total 7.00h │
+└──────────────────────┘
+";
+        assert_eq!(expected, combined);
+
+        assert_eq!(
+            "2024-03: 2 boxes, 3 hours.\n2024-04: 1 box, 3 hours.\nSubtotal: 3 boxes, 6 hours.",
+            generate_plain_months(&older, durations, &Utc)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn tables_fit_into_the_given_width() {
+        let mut tb = time_box("2024-06-01T12:00:00Z", 1);
+        tb.notes[0].description =
+            "Refactor the session handling, see https://example.com/issues/1234".to_string();
+        tb.notes[1].description = "Deployed".to_string();
+
+        let render = |width: usize| {
+            generate_table(
+                DateColumn::Format("%Y-%m-%d"),
+                "At",
+                "Description",
+                "total 1.00h",
+                &mut [tb.clone()],
+                IndexColumn::TimeBoxes(&[7]),
+                None,
+                Some(width),
+                &TableStyle::default(),
+            )
+        };
+
+        let narrow = "\
+┌───┬────────────┬───────────────────┐
+│ # │     At     │    Description    │
+├───┼────────────┼───────────────────┤
+│ 7 │ 2024-06-01 │ Refactor the      │
+│   │            │ session handling, │
+│   │            │ see https://      │
+│   │            │ example.com/      │
+│   │            │ issues/1234       │
+│   │ 2024-06-01 │ Deployed          │
+├───┴────────────┼───────────────────┘
+│    total 1.00h │
+└────────────────┘
+";
+        assert_eq!(narrow, render(40));
+
+        // Wider terminals do not widen descriptions beyond `TEXT_WRAP_COL`
+        let wide = "\
+┌───┬────────────┬─────────────────────────────────────────────┐
+│ # │     At     │                 Description                 │
+├───┼────────────┼─────────────────────────────────────────────┤
+│ 7 │ 2024-06-01 │ Refactor the session handling, see https:// │
+│   │            │ example.com/issues/1234                     │
+│   │ 2024-06-01 │ Deployed                                    │
+├───┴────────────┼─────────────────────────────────────────────┘
+│    total 1.00h │
+└────────────────┘
+";
+        assert_eq!(wide, render(80));
+        assert_eq!(wide, render(120));
+        assert!(render(40).lines().all(|l| l.width() <= 40));
+
+        // Sums wider than the date column get split instead of widening it
+        assert_eq!(
+            vec![
+                "tasks 1.00h,",
+                "0.50h active,",
+                "est 2.00h /",
+                "actual 0.50h"
+            ],
+            wrap_label("tasks 1.00h, 0.50h active, est 2.00h / actual 0.50h", 13)
+        );
+    }
+
+    #[test]
+    fn plain_list_has_one_line_per_time_box() -> anyhow::Result<()> {
+        let mut tb = time_box("2024-06-01T12:00:00Z", 1);
+        tb.notes[0].description =
+            "Refactor the session handling, see https://example.com/issues/1234".to_string();
+        tb.notes[1].description = "Deployed".to_string();
+        let mut overnight = time_box("2024-06-01T23:30:00Z", 2);
+        overnight.notes[1].time += TimeDelta::minutes(25);
+
+        let plain =
+            generate_plain_list(&[tb, overnight], &[7, 8], DurationOptions::default(), &Utc)?;
+
+        let expected = "\
+Entry 7: 2024-06-01 12:00 to 13:00, 1 hour. Notes: Refactor the session handling, see https://example.com/issues/1234; Deployed.
+Entry 8: 2024-06-01 23:30 to 2024-06-02 01:55, 2 hours 25 minutes. Notes: start; stop.";
+        assert_eq!(expected, plain);
+        assert!(!plain.contains(['│', '─']));
+
+        Ok(())
+    }
+
+    #[test]
+    fn goal_status_names_the_remaining_days() {
+        let work_week = [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ];
+        // Wednesday
+        let today = NaiveDate::from_ymd_opt(2024, 6, 12).unwrap();
+        let goal = TimeDelta::hours(40);
+        let eight = TimeDelta::hours(8);
+        let status = |tracked: TimeDelta, work_days: &[Weekday]| {
+            let plan = timetracker::plan_weekly_goal(goal, tracked, today, work_days, eight);
+            generate_goal_status(&plan, goal, tracked, eight, DurationStyle::DecimalHours)
+        };
+
+        assert_eq!(
+            "Tracked 30.50h of 40.00h this week, 9.50h left → ~3.17h/day over Wed–Fri",
+            status(TimeDelta::minutes(30 * 60 + 30), &work_week)
+        );
+        assert_eq!(
+            "Tracked 10.00h of 40.00h this week, 30.00h left → ~15.00h/day over Wed, Fri, unreachable at current pace of at most 8.00h/day",
+            status(
+                TimeDelta::hours(10),
+                &[Weekday::Mon, Weekday::Wed, Weekday::Fri]
+            )
+        );
+        assert_eq!(
+            "Tracked 10.00h of 40.00h this week, 30.00h left but no work days remain this week",
+            status(TimeDelta::hours(10), &[Weekday::Mon])
+        );
+        assert_eq!(
+            "Goal of 40.00h met, tracked 41.00h this week",
+            status(TimeDelta::hours(41), &work_week)
+        );
+    }
+
+    #[test]
+    fn weekly_progress_shows_the_percentage_of_the_goal() {
+        let goal = TimeDelta::hours(40);
+
+        assert_eq!(
+            "This week: 31.50h / 40.00h (79%)",
+            generate_weekly_progress(
+                TimeDelta::minutes(31 * 60 + 30),
+                goal,
+                DurationStyle::DecimalHours
+            )
+        );
+        assert_eq!(
+            "This week: 42 hours / 40 hours (105%)",
+            generate_weekly_progress(TimeDelta::hours(42), goal, DurationStyle::Words)
+        );
+    }
+
+    #[test]
+    fn pick_description_by_number_or_new_text() -> anyhow::Result<()> {
+        let titles = vec!["Review".to_string(), "Deploy".to_string()];
+        let pick = |input: &str| pick_description(&titles, &mut input.as_bytes(), &mut Vec::new());
+
+        assert_eq!("Deploy", pick("2\n")?);
+        assert_eq!("Write docs", pick("\n  Write docs \n")?);
+        assert_eq!("Review", pick("3\n0\n1\n")?);
+        assert!(pick("3\n").is_err());
+        assert!(pick("").is_err());
+
+        let mut output = Vec::new();
+        pick_description(&titles, &mut "1\n".as_bytes(), &mut output)?;
+        assert!(String::from_utf8(output)?.starts_with("  1  Review\n  2  Deploy\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn porcelain_quote_escapes_and_strips_line_breaks() {
+        assert_eq!(r#""Fix login bug""#, porcelain_quote("Fix login bug"));
+        assert_eq!(r#""say \"hi\"""#, porcelain_quote(r#"say "hi""#));
+        assert_eq!(r#""C:\\temp""#, porcelain_quote(r"C:\temp"));
+        assert_eq!(r#""ab""#, porcelain_quote("a\r\nb"));
+        assert_eq!(r#""""#, porcelain_quote(""));
+    }
+
+    #[test]
+    fn porcelain_line_per_event() -> anyhow::Result<()> {
+        let now: DateTime<Utc> = "2024-06-12T11:00:00Z".parse()?;
+        let older = time_box("2024-06-11T09:00:00Z", 2);
+
+        let mut active = time_box("2024-06-12T09:00:00Z", 0);
+        active.notes.truncate(1);
+        active.notes[0].description = "Fix login bug\nwith details".to_string();
+
+        let mut paused = active.clone();
+        paused.pauses.push(Pause {
+            start: "2024-06-12T10:00:00Z".parse()?,
+            stop: None,
+        });
+
+        let mut finished = active.clone();
+        finished.notes.push(TimeBoxNote {
+            time: "2024-06-12T10:25:12Z".parse()?,
+            description: "done".to_string(),
+            kind: NoteKind::Regular,
+        });
+
+        let idle = InMemoryTimeTracker {
+            active: None,
+            finished: vec![older.clone()].into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        let working = InMemoryTimeTracker {
+            active: Some(active),
+            finished: vec![older.clone()].into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        let pausing = InMemoryTimeTracker {
+            active: Some(paused),
+            finished: vec![older.clone()].into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        let ended = InMemoryTimeTracker {
+            active: None,
+            finished: vec![older.clone(), finished.clone()].into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        let mut merged = older.clone();
+        merged.notes.extend(finished.notes.iter().cloned());
+        let merged = InMemoryTimeTracker {
+            active: None,
+            finished: vec![merged].into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        let ended_while_working = InMemoryTimeTracker {
+            active: working.active.clone(),
+            ..ended.clone()
+        };
+        let merged_while_working = InMemoryTimeTracker {
+            active: working.active.clone(),
+            ..merged.clone()
+        };
+        let mut with_goal = idle.clone();
+        with_goal.settings.weekly_goal = Some(TimeDelta::minutes(37 * 60 + 30));
+        let mut locked = ended.clone();
+        locked.settings.locked_before = Some("2024-06-10T00:00:00Z".parse()?);
+        let mut exported = ended.clone();
+        exported
+            .finished
+            .update_all(|tb| tb.exported_at = Some(now));
+
+        let active_fields = r#"hours=2.00 start=2024-06-12T09:00:00Z title="Fix login bug""#;
+        let finished_fields = r#"hours=1.42 start=2024-06-12T09:00:00Z stop=2024-06-12T10:25:12Z title="Fix login bug""#;
+
+        let cases = [
+            (
+                PorcelainEvent::Begin,
+                &idle,
+                &working,
+                format!("event=begin {active_fields}"),
+            ),
+            (
+                PorcelainEvent::Note,
+                &working,
+                &working,
+                format!("event=note {active_fields}"),
+            ),
+            (
+                PorcelainEvent::Note,
+                &working,
+                &ended,
+                format!("event=note {finished_fields}"),
+            ),
+            (
+                PorcelainEvent::Amend,
+                &working,
+                &working,
+                format!("event=amend {active_fields}"),
+            ),
+            (
+                PorcelainEvent::EditNote,
+                &working,
+                &working,
+                format!("event=edit-note {active_fields}"),
+            ),
+            (
+                PorcelainEvent::Split,
+                &working,
+                &working,
+                format!("event=split {active_fields}"),
+            ),
+            (
+                PorcelainEvent::Pause,
+                &working,
+                &pausing,
+                r#"event=pause hours=1.00 start=2024-06-12T09:00:00Z title="Fix login bug""#
+                    .to_string(),
+            ),
+            (
+                PorcelainEvent::Unpause,
+                &pausing,
+                &working,
+                format!("event=unpause {active_fields}"),
+            ),
+            (
+                PorcelainEvent::End,
+                &working,
+                &ended,
+                format!("event=end {finished_fields}"),
+            ),
+            (
+                PorcelainEvent::Resume,
+                &ended,
+                &working,
+                format!("event=resume {active_fields}"),
+            ),
+            (
+                PorcelainEvent::Merge,
+                &ended,
+                &merged,
+                r#"event=merge hours=25.42 start=2024-06-11T09:00:00Z stop=2024-06-12T10:25:12Z title="start""#.to_string(),
+            ),
+            (
+                PorcelainEvent::Merge,
+                &ended_while_working,
+                &merged_while_working,
+                r#"event=merge hours=25.42 start=2024-06-11T09:00:00Z stop=2024-06-12T10:25:12Z title="start""#.to_string(),
+            ),
+            (
+                PorcelainEvent::Cancel,
+                &working,
+                &idle,
+                format!("event=cancel {active_fields}"),
+            ),
+            (
+                PorcelainEvent::Clear,
+                &ended,
+                &InMemoryTimeTracker::default(),
+                "event=clear removed=2".to_string(),
+            ),
+            (
+                PorcelainEvent::Export,
+                &ended,
+                &exported,
+                "event=export marked=2".to_string(),
+            ),
+            (
+                PorcelainEvent::Set { index: 1 },
+                &exported,
+                &ended,
+                format!("event=set index=1 {finished_fields}"),
+            ),
+            (PorcelainEvent::Goal, &idle, &with_goal, "event=goal weekly_goal=37.50".to_string()),
+            (
+                PorcelainEvent::Lock,
+                &ended,
+                &locked,
+                "event=lock locked_before=2024-06-10T00:00:00Z".to_string(),
+            ),
+            (
+                PorcelainEvent::Restore,
+                &idle,
+                &ended,
+                "event=restore active=false finished=2".to_string(),
+            ),
+            (
+                PorcelainEvent::Undo,
+                &ended,
+                &working,
+                "event=undo active=true finished=1".to_string(),
+            ),
+        ];
+
+        for (event, before, after, expected) in cases {
+            assert_eq!(
+                expected,
+                generate_porcelain(event, before, after, now),
+                "{event:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn active_time_box_gets_listed_only_if_it_matches() -> anyhow::Result<()> {
+        let now: DateTime<Utc> = "2024-06-12T11:00:00Z".parse()?;
+        let mut active = time_box("2024-06-12T09:00:00Z", 0);
+        active.notes.truncate(1);
+        active.pauses.push(Pause {
+            start: "2024-06-12T10:00:00Z".parse()?,
+            stop: Some("2024-06-12T10:30:00Z".parse()?),
+        });
+
+        let today = Query::new().date("2024-06-12".parse()?);
+        let (listed, elapsed) =
+            matching_active(Some(&active), &today, now).context("active time box matches")?;
+        assert_eq!(&active, listed);
+        assert_eq!(TimeDelta::minutes(90), elapsed);
+
+        let this_week = Query::new().date_range("2024-06-10".parse()?, "2024-06-16".parse()?);
+        assert!(matching_active(Some(&active), &this_week, now).is_some());
+
+        let yesterday = Query::new().date("2024-06-11".parse()?);
+        assert_eq!(None, matching_active(Some(&active), &yesterday, now));
+        let last_week = Query::new().date_range("2024-06-03".parse()?, "2024-06-09".parse()?);
+        assert_eq!(None, matching_active(Some(&active), &last_week, now));
+
+        assert_eq!(None, matching_active(None, &today, now));
+
+        Ok(())
+    }
+
+    #[test]
+    fn leftover_swaps_get_adopted_or_deleted_depending_on_the_crash_point() -> anyhow::Result<()> {
+        use std::time::{Duration, SystemTime};
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.json");
+        let strategy = JsonStorageStrategy {
+            pretty: true,
+            repair: true,
+        };
+        let hour = Duration::from_secs(60 * 60);
+        let saved_at = SystemTime::now() - hour;
+
+        let mut tracker = InMemoryTimeTracker::default();
+        tracker.begin("saved")?;
+        save_to_disk(&tracker, &path, &strategy)?;
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(saved_at)?;
+        assert_eq!(
+            Vec::<LeftoverSwap>::new(),
+            find_leftover_swaps(&path, StorageFormat::Json)?
+        );
+
+        let mut newer = tracker.clone();
+        newer.push_note("not saved completely")?;
+        let mut content = Vec::new();
+        newer.to_writer(&strategy, &mut content)?;
+
+        let swap = |name: &str, content: &[u8], modified: SystemTime| -> anyhow::Result<PathBuf> {
+            let swap = dir.path().join(name);
+            std::fs::write(&swap, content)?;
+            File::options()
+                .write(true)
+                .open(&swap)?
+                .set_modified(modified)?;
+            Ok(swap)
+        };
+        // Died before renaming, after writing the swap file completely
+        let complete = swap(".__3_1_0_swap_tasks.json", &content, saved_at + hour / 2)?;
+        // Died while writing the swap file
+        let incomplete = swap(".__4_1_0_swap_tasks.json", &content[..20], saved_at + hour)?;
+        // Died long ago, the store got saved since
+        let outdated = swap(".__1_1_0_swap_tasks.json", &content, saved_at - hour)?;
+        // Swap files of other formats belong to other stores
+        swap(".__2_1_0_swap_tasks.toml", b"", saved_at)?;
+
+        assert_eq!(
+            vec![
+                LeftoverSwap::Stale(incomplete.clone()),
+                LeftoverSwap::Newer(complete.clone()),
+                LeftoverSwap::Stale(outdated.clone()),
+            ],
+            find_leftover_swaps(&path, StorageFormat::Json)?
+        );
+
+        // Declining keeps everything as it is
+        assert!(!recover_swaps(&path, StorageFormat::Json, |_| Ok(false))?);
+        assert_eq!(3, find_leftover_swaps(&path, StorageFormat::Json)?.len());
+        assert_eq!(tracker, load_store(&path, StorageFormat::Json, false)?);
+
+        assert!(recover_swaps(&path, StorageFormat::Json, |_| Ok(true))?);
+        assert_eq!(newer, load_store(&path, StorageFormat::Json, false)?);
+        assert_eq!(
+            Vec::<LeftoverSwap>::new(),
+            find_leftover_swaps(&path, StorageFormat::Json)?
+        );
+        assert!(dir.path().join(".__2_1_0_swap_tasks.toml").exists());
+
+        // Only the latest of several newer swap files gets adopted
+        let older = swap(
+            ".__5_1_0_swap_tasks.json",
+            &content,
+            SystemTime::now() + hour,
+        )?;
+        let mut latest = newer.clone();
+        latest.push_note("latest")?;
+        let mut latest_content = Vec::new();
+        latest.to_writer(&strategy, &mut latest_content)?;
+        swap(
+            ".__6_1_0_swap_tasks.json",
+            &latest_content,
+            SystemTime::now() + hour * 2,
+        )?;
+
+        let mut asked = Vec::new();
+        recover_swaps(&path, StorageFormat::Json, |swap| {
+            asked.push(swap.clone());
+            Ok(true)
+        })?;
+        assert_eq!(LeftoverSwap::Stale(older), asked[1]);
+        assert_eq!(latest, load_store(&path, StorageFormat::Json, false)?);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+use log::warn;
+use timetracker::TrackingEvent;
+
+use crate::helpers::time_box_fields;
+
+/// Keys and unquoted values describing `event` of `ObservedStore`, starting with `event` like `--porcelain`.
+/// Times are UTC, `hours` of the active time box are counted until `now`.
+pub fn hook_fields(event: &TrackingEvent, now: DateTime<Utc>) -> Vec<(&'static str, String)> {
+    let (name, mut fields) = match event {
+        TrackingEvent::Began(tb) => ("begin", time_box_fields(tb, false, now)),
+        TrackingEvent::NoteAdded(tb) => ("note", time_box_fields(tb, false, now)),
+        TrackingEvent::Paused(tb) => ("pause", time_box_fields(tb, false, now)),
+        TrackingEvent::Unpaused(tb) => ("unpause", time_box_fields(tb, false, now)),
+        TrackingEvent::Ended(tb) => ("end", time_box_fields(tb, true, now)),
+        TrackingEvent::Amended(tb) => ("amend", time_box_fields(tb, false, now)),
+        TrackingEvent::NoteEdited(tb) => ("edit-note", time_box_fields(tb, false, now)),
+        TrackingEvent::EstimateSet(tb) => ("estimate", time_box_fields(tb, false, now)),
+        TrackingEvent::ReferenceSet(tb) => ("reference", time_box_fields(tb, false, now)),
+        TrackingEvent::Split { active, .. } => ("split", time_box_fields(active, false, now)),
+        TrackingEvent::Merged(tb) => ("merge", time_box_fields(tb, true, now)),
+        TrackingEvent::Resumed(tb) => ("resume", time_box_fields(tb, false, now)),
+        TrackingEvent::ExportedAtSet { index, time_box } => {
+            let mut fields = vec![("index", index.to_string())];
+            fields.extend(time_box_fields(time_box, true, now));
+            ("set", fields)
+        }
+        TrackingEvent::Canceled(tb) => ("cancel", time_box_fields(tb, false, now)),
+        TrackingEvent::Cleared(removed) => ("clear", vec![("removed", removed.to_string())]),
+        TrackingEvent::SettingsChanged(_) => ("settings", Vec::new()),
+    };
+
+    fields.insert(0, ("event", name.to_string()));
+    fields
+}
+
+/// Runs `command` through the shell for a saved `event`, with each of the `hook_fields`
+/// in an environment variable like `TIMETRACKER_EVENT` or `TIMETRACKER_TITLE`, and waits for it to exit.
+/// The change is saved already, so failing hooks only warn.
+pub fn run_hook(command: &str, event: &TrackingEvent, now: DateTime<Utc>) {
+    let mut hook = match cfg!(windows) {
+        true => {
+            let mut hook = std::process::Command::new("cmd");
+            hook.arg("/C").arg(command);
+            hook
+        }
+        false => {
+            let mut hook = std::process::Command::new("sh");
+            hook.arg("-c").arg(command);
+            hook
+        }
+    };
+    for (key, value) in hook_fields(event, now) {
+        hook.env(format!("TIMETRACKER_{}", key.to_uppercase()), value);
+    }
+
+    match hook.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("The hook \"{command}\" failed with {status}"),
+        Err(e) => warn!("Failed to run the hook \"{command}\": {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::time_box;
+
+    #[test]
+    fn hook_fields_per_tracking_event() -> anyhow::Result<()> {
+        let now: DateTime<Utc> = "2024-06-12T11:00:00Z".parse()?;
+        let mut active = time_box("2024-06-12T09:00:00Z", 0);
+        active.notes.truncate(1);
+        let finished = time_box("2024-06-12T09:00:00Z", 1);
+
+        let fields = |event: &TrackingEvent| {
+            hook_fields(event, now)
+                .into_iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        assert_eq!(
+            "event=begin hours=2.00 start=2024-06-12T09:00:00Z title=start",
+            fields(&TrackingEvent::Began(active.clone()))
+        );
+        assert_eq!(
+            "event=end hours=1.00 start=2024-06-12T09:00:00Z stop=2024-06-12T10:00:00Z title=start",
+            fields(&TrackingEvent::Ended(finished.clone()))
+        );
+        assert_eq!(
+            "event=split hours=2.00 start=2024-06-12T09:00:00Z title=start",
+            fields(&TrackingEvent::Split {
+                finished: finished.clone(),
+                active,
+            })
+        );
+        assert_eq!(
+            "event=set index=3 hours=1.00 start=2024-06-12T09:00:00Z stop=2024-06-12T10:00:00Z title=start",
+            fields(&TrackingEvent::ExportedAtSet {
+                index: 3,
+                time_box: finished,
+            })
+        );
+        assert_eq!("event=clear removed=2", fields(&TrackingEvent::Cleared(2)));
+
+        Ok(())
+    }
+}
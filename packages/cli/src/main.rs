@@ -1,102 +1,645 @@
-use anyhow::Context;
+use std::{
+    cell::RefCell,
+    io::{ErrorKind, IsTerminal},
+    path::Path,
+    process::ExitCode,
+    rc::Rc,
+    time::Duration,
+};
+
+use anyhow::{Context, bail};
 use clap::Parser;
+use log::{info, warn};
 use timetracker::{
-    ListOptions, TimeTrackingStore,
-    in_memory_tracker::{InMemoryTimeTracker, JsonFileLoadingStrategy, JsonStorageStrategy},
+    ListOptions, ObservedStore, StoreSettings, TimeTrackingStore, TrackingEvent,
+    in_memory_tracker::InMemoryTimeTracker,
+    json_cache::{CachedJsonFileLoadingStrategy, write_cache},
+    jsonl_tracker::{JsonlStateStorageStrategy, append_time_boxes, state_path},
+    storage_lock::{LockMode, StorageLock},
 };
 
 use crate::{
-    args::{Args, Commands},
+    args::{Args, Commands, GoalCommands, InitArgs, StorageFormat, take_parse_warnings},
+    config::Config,
     handle_commands::{
-        handle_command_amend, handle_command_cancel, handle_command_clear, handle_command_end,
-        handle_command_export, handle_command_init, handle_command_list, handle_command_note,
-        handle_command_resume, handle_command_shell_completion, handle_command_start,
-        handle_command_status,
+        StoreMutation, handle_command_amend, handle_command_backfill, handle_command_begin_pick,
+        handle_command_cancel, handle_command_clear, handle_command_doctor,
+        handle_command_edit_note, handle_command_end, handle_command_export,
+        handle_command_goal_set, handle_command_goal_show, handle_command_goal_status,
+        handle_command_import, handle_command_info, handle_command_init, handle_command_list,
+        handle_command_lock, handle_command_merge, handle_command_note, handle_command_pause,
+        handle_command_projects, handle_command_prompt, handle_command_report,
+        handle_command_restore, handle_command_resume, handle_command_set,
+        handle_command_shell_completion, handle_command_split, handle_command_start,
+        handle_command_status, handle_command_undo, handle_command_unpause,
+        handle_command_verify_roundtrip, log_idle_gap, set_reference_from_ticket_id,
+        suggest_lock_once,
     },
-    helpers::save_json_to_disk,
+    helpers::{
+        DurationOptions, LeftoverSwap, confirm, format_error, generate_porcelain,
+        generate_porcelain_error, load_store, log_diagnostics, recover_swaps, save_store,
+        save_to_disk, write_backup,
+    },
+    hook::run_hook,
+    projects::{PROJECT_ENV, Project, merge_projects},
+    settings::resolve_settings,
 };
 
 mod args;
+mod config;
+mod export;
 mod handle_commands;
 mod helpers;
+mod hook;
+mod projects;
+mod report;
+mod settings;
+#[cfg(test)]
+mod test_fixtures;
 
-fn main() -> anyhow::Result<()> {
+/// Exit code of read-only commands when there is no store to read yet
+const EXIT_NOT_INITIALIZED: u8 = 3;
+
+fn main() -> ExitCode {
     let args = Args::parse();
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(&args.log_level))
+    // Logs would end up in the prompt of the shell
+    if !matches!(args.command, Commands::Prompt { .. }) {
+        env_logger::Builder::from_env(
+            env_logger::Env::default().default_filter_or(&args.log_level),
+        )
         .init();
+    }
+    for warning in take_parse_warnings() {
+        warn!("{warning}");
+    }
+
+    let porcelain = args.porcelain;
+    match run(args) {
+        Ok(code) => code,
+        Err(e) => {
+            if porcelain {
+                println!("{}", generate_porcelain_error(&e));
+            }
+            eprintln!("{}", format_error(&e));
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let storage_path = args.output.join("storage.json");
+fn run(args: Args) -> anyhow::Result<ExitCode> {
+    let project = Project::resolve(
+        args.project.as_deref(),
+        std::env::var(PROJECT_ENV).ok().as_deref(),
+    )?;
+    let directory = match project.directory(&args.output) {
+        Some(directory) if !matches!(args.command, Commands::Projects {}) => directory,
+        _ => return run_across_projects(args),
+    };
 
-    let mut tracker: InMemoryTimeTracker = match args.command {
-        Commands::Init {} => {
-            return handle_command_init(
-                &args.output,
-                &storage_path,
-                &args.json_format.into() as &JsonStorageStrategy,
-            );
+    let storage_path = directory.join(args.storage_format.file_name());
+    let lock_path = directory.join("storage.lock");
+    let cache_path = directory.join("storage.cache");
+    let backups_path = directory.join("backups");
+    let config_path = args.output.join("config.json");
+
+    // Initializing replaces the store, so it waits for the exclusive lock like any other change.
+    // The lock file lives in the directory, which therefore has to exist first.
+    let auto_init = args.auto_init && !args.command.is_read_only();
+    if matches!(args.command, Commands::Init { .. }) || auto_init {
+        std::fs::create_dir_all(&directory).with_context(|| {
+            format!("Failed to create the directory \"{}\"", directory.display())
+        })?;
+    }
+
+    // Held until the end of `run` so that loading and saving happen as one unit
+    let _lock = match StorageLock::acquire(
+        &lock_path,
+        match args.command.is_read_only() {
+            true => LockMode::Shared,
+            false => LockMode::Exclusive,
+        },
+        Duration::from_secs(args.lock_timeout),
+    ) {
+        Ok(lock) => lock,
+        // The output directory does not exist
+        Err(timetracker::Error::Io(e)) if e.kind() == ErrorKind::NotFound => {
+            return not_initialized(&args.command, &storage_path);
+        }
+        Err(e @ timetracker::Error::StorageLocked { .. }) => {
+            return Err(anyhow::Error::new(e).context(format!(
+                "The store is in use by another running invocation. \
+                Try again once it finished or increase the `--lock-timeout`. \
+                Lock file: \"{}\"",
+                lock_path.display()
+            )));
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e).context(format!(
+                "Failed to lock the store. \
+                Tried to lock path: \"{}\"",
+                lock_path.display()
+            )));
         }
-        _ => InMemoryTimeTracker::init(&JsonFileLoadingStrategy {
+    };
+
+    if let Commands::Init { options } = &args.command {
+        return init_store(&args, &directory, &storage_path, &backups_path, options)
+            .map(|_| ExitCode::SUCCESS);
+    }
+
+    if auto_init && !storage_path.exists() {
+        init_store(
+            &args,
+            &directory,
+            &storage_path,
+            &backups_path,
+            &InitArgs::default(),
+        )?;
+        info!("Initialized a new store: \"{}\"", storage_path.display());
+    }
+
+    // Only with the lock held, otherwise the swap files could belong to saves in progress
+    recover_swaps(&storage_path, args.storage_format, |swap| {
+        decide_about_swap(swap, args.command.is_read_only(), args.auto_recover)
+    })?;
+
+    // Doctor verifies the cache against the JSON file, so it must not load from the cache itself.
+    // Strict mode has to see the JSON as written, the cache only ever holds sanitized content.
+    let use_cache = args.cache
+        && args.storage_format == StorageFormat::Json
+        && !args.strict
+        && !args.command.is_destructive()
+        && !matches!(args.command, Commands::Doctor {});
+
+    if args.cache && args.storage_format != StorageFormat::Json {
+        warn!("Ignoring `--cache` because it only applies to the `json` storage format");
+    }
+
+    let loaded = match use_cache {
+        true => InMemoryTimeTracker::init(&CachedJsonFileLoadingStrategy {
             path: &storage_path,
+            cache_path: &cache_path,
         })
-        .with_context(|| {
+        .inspect(|tracker| log_diagnostics(&tracker.diagnostics)),
+        false => load_store(&storage_path, args.storage_format, args.strict),
+    };
+
+    let tracker = match loaded {
+        Err(timetracker::Error::Io(e)) if e.kind() == ErrorKind::NotFound => {
+            return not_initialized(&args.command, &storage_path);
+        }
+        loaded => loaded.with_context(|| {
             format!(
-                "Failed to load tracked time. \
-                Try initializing the directory first via the `init` command or fix malformed fields. \
+                "Failed to load tracked time. Try fixing malformed fields. \
                 Tried to read data from path: \"{}\"",
                 storage_path.display()
             )
         })?,
     };
 
-    let is_dirty: bool = match args.command {
-        Commands::Init {} => unreachable!("Init gets handled prior to this."),
-        Commands::Begin { description } => handle_command_start(&mut tracker, &description)?,
-        Commands::Status {} => handle_command_status(&tracker)?,
+    // Hooks must only run for saved changes, so observing merely queues the events until then
+    let hook_events: Rc<RefCell<Vec<TrackingEvent>>> = Rc::default();
+    let mut tracker = match args.hook {
+        Some(_) => {
+            let queue = hook_events.clone();
+            ObservedStore::new(tracker).observe(move |event| queue.borrow_mut().push(event.clone()))
+        }
+        None => ObservedStore::new(tracker),
+    };
+
+    let config = Config::load(&config_path)?;
+    let effective = resolve_settings(&tracker.settings()?, &config, &args.setting_flags());
+    let settings = effective.output_settings();
+    // Restoring brings back the store as it was, without remembering suggestions
+    let suggest = effective.suggestions.value
+        && !args.command.is_read_only()
+        && !matches!(args.command, Commands::Restore { .. } | Commands::Undo {});
+    let porcelain = match args.porcelain {
+        true => args
+            .command
+            .porcelain_event()
+            .map(|e| (e, tracker.inner().clone())),
+        false => None,
+    };
+    let mutation = match args.command {
+        Commands::Init { .. } => unreachable!("Init gets handled prior to this."),
+        Commands::Begin {
+            description,
+            estimate,
+            reference,
+            auto_ref,
+            backfill,
+            ..
+        } => {
+            let begun = match (description, backfill) {
+                (Some(description), Some(start)) => {
+                    handle_command_backfill(&mut tracker, &description, start)?
+                }
+                (Some(description), None) => handle_command_start(&mut tracker, &description)?,
+                (None, _) => handle_command_begin_pick(&mut tracker)?,
+            };
+            log_idle_gap(tracker.inner(), backfill.is_some())?;
+            if estimate.is_some() {
+                tracker.set_estimate(estimate)?;
+            }
+            if reference.is_some() {
+                tracker.set_reference(reference.as_deref())?;
+            }
+            if auto_ref {
+                set_reference_from_ticket_id(&mut tracker)?;
+            }
+            begun
+        }
+        // Read-only, so there is nothing to save afterwards
+        Commands::Status { format, relative } => {
+            return handle_command_status(
+                tracker.inner(),
+                format,
+                relative,
+                effective.weekly_goal.value,
+                &settings,
+            );
+        }
+        Commands::Prompt { format, idle } => {
+            handle_command_prompt(tracker.inner(), &format, &idle)?
+        }
         Commands::Note {
             description,
             end: finish,
-        } => handle_command_note(&mut tracker, &description, finish)?,
-        Commands::Amend { description } => handle_command_amend(&mut tracker, &description)?,
-        Commands::Resume {} => handle_command_resume(&mut tracker)?,
-        Commands::Export { strategy } => handle_command_export(&tracker, strategy)?,
+            at,
+            allow_extend,
+        } => handle_command_note(&mut tracker, &description, finish, at, allow_extend)?,
+        Commands::Amend {
+            description,
+            title,
+            index,
+        } => handle_command_amend(&mut tracker, &description, title, index)?,
+        Commands::EditNote { index, description } => {
+            handle_command_edit_note(&mut tracker, index, &description)?
+        }
+        Commands::Resume { index } => handle_command_resume(&mut tracker, index)?,
+        Commands::Export {
+            strategy,
+            format,
+            file,
+            mark_exported,
+            raw_durations,
+            rounding,
+            selection,
+        } => handle_command_export(
+            &mut tracker,
+            file.strategy(strategy)?,
+            &format,
+            &file,
+            &selection.options(),
+            mark_exported,
+            DurationOptions {
+                raw: raw_durations,
+                rounding: rounding.rounding(),
+            },
+            &settings,
+        )?,
+        Commands::Set {
+            index,
+            clear_exported,
+        } => handle_command_set(&mut tracker, index, clear_exported)?,
+        Commands::Lock { before } => handle_command_lock(&mut tracker, before)?,
+        Commands::Split { index } => handle_command_split(&mut tracker, index)?,
+        Commands::Pause {} => handle_command_pause(&mut tracker)?,
+        Commands::Unpause {} => handle_command_unpause(&mut tracker)?,
         Commands::End {} => handle_command_end(&mut tracker)?,
+        Commands::Merge {} => handle_command_merge(&mut tracker)?,
         Commands::Cancel {} => handle_command_cancel(&mut tracker)?,
-        Commands::Clear {} => handle_command_clear(&mut tracker)?,
+        Commands::Clear { date, yes, force } => {
+            handle_command_clear(&mut tracker, date.as_ref(), yes, force)?
+        }
         Commands::List {
-            all,
-            page,
-            limit,
-            order,
+            selection,
+            group_by,
+            rounding,
+            include_active,
+            relative,
+            compact,
+            collapse_older_than,
+        } => handle_command_list(
+            tracker.inner(),
+            &selection.options(),
+            group_by,
+            DurationOptions {
+                raw: false,
+                rounding: rounding.rounding(),
+            },
+            include_active,
+            relative,
+            compact,
+            collapse_older_than,
+            &settings,
+        )?,
+        Commands::Report {
+            group_by,
+            roll_up,
             date,
+            format,
+            unexported_only,
+            estimates,
+            rounding,
         } => {
-            let options = ListOptions::new().order(order.into());
-            if all {
-                handle_command_list(&tracker, &options.take(usize::MAX))?
-            } else if let Some(f) = date {
-                handle_command_list(&tracker, &options.filter(f))?
-            } else {
-                handle_command_list(&tracker, &options.page(page, limit))?
+            let mut options = ListOptions::new()
+                .take(usize::MAX)
+                .unexported_only(unexported_only);
+            if let Some(date) = date {
+                options = options.filter(date);
             }
+            handle_command_report(
+                tracker.inner(),
+                &options,
+                group_by,
+                roll_up,
+                format,
+                estimates,
+                rounding.rounding(),
+                effective.weekly_goal.value,
+                &settings,
+            )?
+        }
+        // The flags of `goal status` are part of the effective settings
+        Commands::Goal {
+            command: GoalCommands::Status { .. },
+        } => handle_command_goal_status(tracker.inner(), &effective, &settings)?,
+        Commands::Goal {
+            command:
+                GoalCommands::Set {
+                    weekly,
+                    work_days,
+                    work_hours,
+                },
+        } => handle_command_goal_set(&mut tracker, weekly, work_days, work_hours)?,
+        Commands::Goal {
+            command: GoalCommands::Show {},
+        } => handle_command_goal_show(tracker.inner(), effective.weekly_goal.value, &settings)?,
+        Commands::Import { path, format } => {
+            handle_command_import(tracker.inner_mut(), &path, format)?
+        }
+        Commands::Doctor {} => handle_command_doctor(tracker.inner(), &storage_path, &cache_path)?,
+        Commands::VerifyRoundtrip { against } => {
+            return handle_command_verify_roundtrip(
+                tracker.inner(),
+                against.as_deref(),
+                args.storage_format,
+                args.json_format,
+                args.strict,
+            );
+        }
+        Commands::Info {} => handle_command_info(&effective, &storage_path, &config_path)?,
+        Commands::Projects {} => unreachable!("Projects get handled prior to this."),
+        Commands::Restore { backup, yes } => handle_command_restore(
+            tracker.inner_mut(),
+            &backups_path,
+            args.storage_format,
+            backup,
+            yes,
+        )?,
+        Commands::Undo {} => {
+            handle_command_undo(tracker.inner_mut(), &backups_path, args.storage_format)?
         }
         Commands::ShellCompletion { shell } => handle_command_shell_completion(shell)?,
     };
 
-    if is_dirty {
-        save_json_to_disk(
+    // Replacing the whole store, e.g. via `restore`, emits no events. Neither does remembering the suggestion below
+    let mut tracker = tracker.into_inner();
+    let mutation = match suggest {
+        true => suggest_lock_once(&mut tracker, mutation, chrono::Local::now().date_naive())?,
+        false => mutation,
+    };
+
+    if mutation != StoreMutation::Unchanged {
+        write_backup(&storage_path, &backups_path, args.keep_backups).with_context(|| {
+            format!(
+                "Failed to back up the store before saving. Use `--keep-backups 0` to skip backups. \
+                Tried to write into: \"{}\"",
+                backups_path.display()
+            )
+        })?;
+
+        // Repaired stores get rewritten, so the file does not keep what had to be repaired
+        match (args.storage_format, mutation) {
+            (StorageFormat::Jsonl, StoreMutation::Active) if !tracker.was_repaired() => {
+                save_to_disk(
+                    &tracker,
+                    &state_path(&storage_path),
+                    &JsonlStateStorageStrategy { repair: true },
+                )?
+            }
+            (StorageFormat::Jsonl, StoreMutation::Appended(time_boxes))
+                if !tracker.was_repaired() =>
+            {
+                append_time_boxes(&storage_path, &time_boxes)?;
+                save_to_disk(
+                    &tracker,
+                    &state_path(&storage_path),
+                    &JsonlStateStorageStrategy { repair: true },
+                )?
+            }
+            _ => save_store(
+                &tracker,
+                &storage_path,
+                args.storage_format,
+                args.json_format,
+            )?,
+        }
+
+        if use_cache && let Err(e) = write_cache(&cache_path, &storage_path, &tracker) {
+            warn!(
+                "Failed refreshing cache \"{}\": {}",
+                cache_path.display(),
+                e.message_with_sources()
+            );
+        }
+
+        let now = chrono::Utc::now();
+        if let Some((event, before)) = porcelain {
+            println!("{}", generate_porcelain(event, &before, &tracker, now));
+        }
+        if let Some(hook) = &args.hook {
+            for event in hook_events.borrow().iter() {
+                run_hook(hook, event, now);
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn init_store(
+    args: &Args,
+    directory: &Path,
+    storage_path: &Path,
+    backups_path: &Path,
+    options: &InitArgs,
+) -> anyhow::Result<()> {
+    handle_command_init(
+        directory,
+        storage_path,
+        backups_path,
+        args.keep_backups,
+        args.storage_format,
+        args.json_format,
+        options,
+    )
+}
+
+/// `projects` and `--project all` read every project. Saves replace stores at once,
+/// so reading without their locks never sees a half written store. Nothing gets changed across projects.
+fn run_across_projects(args: Args) -> anyhow::Result<ExitCode> {
+    let config = Config::load(&args.output.join("config.json"))?;
+    let effective = resolve_settings(&StoreSettings::default(), &config, &args.setting_flags());
+    let settings = effective.output_settings();
+
+    if let Commands::Projects {} = args.command {
+        handle_command_projects(&args.output, args.storage_format, &settings)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut tracker = match args.command {
+        Commands::List { .. }
+        | Commands::Export {
+            mark_exported: false,
+            ..
+        } => merge_projects(&args.output, args.storage_format, args.strict)?,
+        _ => bail!(
+            "`--project all` only works with `list` and with `export` without `--mark-exported`, \
+            pass the name of a project instead"
+        ),
+    };
+
+    match args.command {
+        Commands::List {
+            selection,
+            group_by,
+            rounding,
+            include_active,
+            relative,
+            compact,
+            collapse_older_than,
+        } => handle_command_list(
             &tracker,
-            &storage_path,
-            &args.json_format.into() as &JsonStorageStrategy,
-        )?
+            &selection.options(),
+            group_by,
+            DurationOptions {
+                raw: false,
+                rounding: rounding.rounding(),
+            },
+            include_active,
+            relative,
+            compact,
+            collapse_older_than,
+            &settings,
+        )?,
+        Commands::Export {
+            strategy,
+            format,
+            file,
+            raw_durations,
+            rounding,
+            selection,
+            ..
+        } => handle_command_export(
+            &mut tracker,
+            file.strategy(strategy)?,
+            &format,
+            &file,
+            &selection.options(),
+            false,
+            DurationOptions {
+                raw: raw_durations,
+                rounding: rounding.rounding(),
+            },
+            &settings,
+        )?,
+        _ => unreachable!("Only listing and exporting get merged"),
+    };
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Read-only commands have nothing to show, which is not an error but gets its own exit code.
+/// Prompts are idle instead, shells show them in every directory.
+/// Read-only commands merely share the lock with other readers, so they leave swap files alone.
+/// Otherwise asks in terminals, `auto_recover` agrees to everything.
+fn decide_about_swap(
+    swap: &LeftoverSwap,
+    read_only: bool,
+    auto_recover: bool,
+) -> anyhow::Result<bool> {
+    let (question, found) = match swap {
+        LeftoverSwap::Newer(path) => (
+            "Adopt it as the store?",
+            format!(
+                "The swap file \"{}\" holds changes newer than the store, saving them got interrupted.",
+                path.display()
+            ),
+        ),
+        LeftoverSwap::Stale(path) => (
+            "Delete it?",
+            format!(
+                "The swap file \"{}\" is incomplete or older than the store, saving it got interrupted.",
+                path.display()
+            ),
+        ),
+    };
+
+    if auto_recover && !read_only {
+        return Ok(true);
+    }
+    if !read_only && std::io::stdin().is_terminal() {
+        return confirm(&format!("{found} {question}"));
+    }
+
+    warn!("{found} Pass `--auto-recover` to a command changing the store to recover it.");
+    Ok(false)
+}
+
+fn not_initialized(command: &Commands, storage_path: &Path) -> anyhow::Result<ExitCode> {
+    if let Commands::Prompt { idle, .. } = command {
+        print!("{idle}");
+        return Ok(ExitCode::SUCCESS);
     }
 
-    Ok(())
+    let message = format!(
+        "No time tracking data found at \"{}\" — run `init` first",
+        storage_path.display()
+    );
+
+    match command.is_read_only() {
+        true => {
+            eprintln!("{message}");
+            Ok(ExitCode::from(EXIT_NOT_INITIALIZED))
+        }
+        false => bail!("{message} or pass `--auto-init`"),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use timetracker::TimeTrackerInitStrategy;
+    use chrono::{Local, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+    use timetracker::{
+        DurationStyle, ListFilter, ListOptions, TimeBoxNote, TimeTrackerInitStrategy,
+        in_memory_tracker::JsonStorageStrategy,
+    };
 
     use super::*;
+    use crate::{
+        args::{ExportFileArgs, ExportFormatArgs, ExportStrategy, ImportFormat, OutputJsonFormat},
+        export::{
+            CSV_DELIMITER, generate_csv_export, generate_json_export, generate_json_export_raw,
+            parse_csv_export,
+        },
+        helpers::{
+            DateColumn, OutputSettings, TableStyle, collect_for_output, error_code,
+            generate_table_active, list_backups, local_midnight,
+        },
+        test_fixtures::{note, time, time_box_of},
+    };
 
     struct TestLoadingStrategy {}
     impl TimeTrackerInitStrategy for TestLoadingStrategy {
@@ -154,7 +697,7 @@ mod tests {
 
         handle_command_start(&mut tracker, "#1")?;
         assert_eq!(1, tracker.active()?.unwrap().notes.len());
-        handle_command_note(&mut tracker, "#2", false).unwrap();
+        handle_command_note(&mut tracker, "#2", false, None, false).unwrap();
         assert_eq!(2, tracker.active()?.unwrap().notes.len());
         Ok(())
     }
@@ -164,7 +707,7 @@ mod tests {
         let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
         assert!(tracker.active()?.is_none());
 
-        let err = handle_command_note(&mut tracker, "#1", false).unwrap_err();
+        let err = handle_command_note(&mut tracker, "#1", false, None, false).unwrap_err();
         assert!(matches!(
             err.downcast::<timetracker::Error>().unwrap(),
             timetracker::Error::NoActiveTimeBox
@@ -174,12 +717,49 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn backdated_note_extends_the_start_only_when_allowed() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+        handle_command_start(&mut tracker, "#1")?;
+        let start = tracker.active()?.unwrap().time_start()?;
+        let earlier = start - TimeDelta::minutes(20);
+
+        let err = handle_command_note(&mut tracker, "#0", false, Some(earlier), false).unwrap_err();
+        assert!(err.to_string().contains("--allow-extend"));
+        assert_eq!(1, tracker.active()?.unwrap().notes.len());
+
+        let future = Utc::now() + TimeDelta::hours(1);
+        assert!(handle_command_note(&mut tracker, "#2", false, Some(future), true).is_err());
+
+        handle_command_note(&mut tracker, "#0", false, Some(earlier), true)?;
+        let active = tracker.active()?.unwrap();
+        assert_eq!(earlier, active.time_start()?);
+        assert_eq!("#0", active.title());
+
+        // Between both notes needs no permission
+        let between = start - TimeDelta::minutes(10);
+        assert_eq!(
+            StoreMutation::Active,
+            handle_command_note(&mut tracker, "#0.5", false, Some(between), false)?
+        );
+        let descriptions: Vec<String> = tracker
+            .active()?
+            .unwrap()
+            .notes
+            .iter()
+            .map(|n| n.description.clone())
+            .collect();
+        assert_eq!(vec!["#0", "#0.5", "#1"], descriptions);
+
+        Ok(())
+    }
+
     #[test]
     fn amend_note() -> anyhow::Result<()> {
         let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
 
         handle_command_start(&mut tracker, "#1")?;
-        handle_command_amend(&mut tracker, "new")?;
+        handle_command_amend(&mut tracker, "new", false, None)?;
         let description = tracker
             .active()?
             .unwrap()
@@ -193,11 +773,50 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn amend_title() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+
+        handle_command_start(&mut tracker, "Bad title")?;
+        handle_command_note(&mut tracker, "details", false, None, false)?;
+        handle_command_note(&mut tracker, "more details", false, None, false)?;
+        handle_command_amend(&mut tracker, "Better title", true, None)?;
+
+        let active = tracker.active()?.unwrap();
+        assert_eq!("Better title", active.title());
+        assert_eq!("more details", active.notes[2].description);
+        assert!(
+            generate_table_active(
+                active,
+                DateColumn::Format("%Y-%m-%d %H:%M"),
+                None,
+                None,
+                &TableStyle::new(false),
+                DurationStyle::DecimalHours
+            )?
+            .contains("Better title")
+        );
+
+        handle_command_end(&mut tracker)?;
+        let csv = generate_csv_export(
+            &tracker.finished,
+            CSV_DELIMITER,
+            DurationStyle::DecimalHours,
+            DurationOptions::default(),
+        )?;
+        assert!(csv.contains("Better title"));
+        assert!(!csv.contains("Bad title"));
+
+        assert!(Args::try_parse_from(["_", "amend", "x", "--title", "--index", "1"]).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn fail_to_amend_note_due_no_active_time_box() -> anyhow::Result<()> {
         let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
 
-        let err = handle_command_amend(&mut tracker, "new").unwrap_err();
+        let err = handle_command_amend(&mut tracker, "new", false, None).unwrap_err();
         assert!(matches!(
             err.downcast::<timetracker::Error>().unwrap(),
             timetracker::Error::NoActiveTimeBox
@@ -234,40 +853,1462 @@ mod tests {
         assert_eq!(1, tracker.finished(&ListOptions::new())?.total);
         assert!(tracker.active()?.is_none());
 
-        handle_command_resume(&mut tracker)?;
+        handle_command_resume(&mut tracker, None)?;
         assert_eq!(0, tracker.finished(&ListOptions::new())?.total);
         assert!(tracker.active()?.is_some());
         Ok(())
     }
 
     #[test]
-    fn clear() -> anyhow::Result<()> {
+    fn resume_finished_task_at_index() -> anyhow::Result<()> {
         let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
 
         handle_command_start(&mut tracker, "#1")?;
         handle_command_end(&mut tracker)?;
-        assert_eq!(1, tracker.finished(&ListOptions::new())?.total);
+        handle_command_start(&mut tracker, "#2")?;
+        handle_command_end(&mut tracker)?;
 
-        handle_command_clear(&mut tracker)?;
-        assert_eq!(0, tracker.finished(&ListOptions::new())?.total);
+        handle_command_resume(&mut tracker, Some(0))?;
+        let finished = tracker.finished(&ListOptions::new())?;
+        assert_eq!(1, finished.total);
+        assert_eq!("#2", finished.items[0].notes[0].description);
+        assert_eq!("#1", tracker.active()?.unwrap().notes[0].description);
         Ok(())
     }
 
     #[test]
-    fn dont_clear_due_pending_task() -> anyhow::Result<()> {
+    fn fail_to_resume_at_index_out_of_bounds() -> anyhow::Result<()> {
         let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
 
         handle_command_start(&mut tracker, "#1")?;
         handle_command_end(&mut tracker)?;
+
+        let err = handle_command_resume(&mut tracker, Some(1)).unwrap_err();
+        assert!(matches!(
+            err.downcast::<timetracker::Error>().unwrap(),
+            timetracker::Error::TimeBoxIndexOutOfBounds { index: 1, len: 1 }
+        ));
         assert_eq!(1, tracker.finished(&ListOptions::new())?.total);
+        assert!(tracker.active()?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn fail_to_resume_at_index_when_already_active() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
 
+        handle_command_start(&mut tracker, "#1")?;
+        handle_command_end(&mut tracker)?;
         handle_command_start(&mut tracker, "#2")?;
-        assert!(tracker.active()?.is_some());
 
-        let modified = handle_command_clear(&mut tracker)?;
-        assert!(!modified);
+        let err = handle_command_resume(&mut tracker, Some(0)).unwrap_err();
+        assert!(matches!(
+            err.downcast::<timetracker::Error>().unwrap(),
+            timetracker::Error::ActiveTimeBoxExistsAlready
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn list_and_export_select_the_same_time_boxes() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+
+        for i in 0..30 {
+            handle_command_start(&mut tracker, &format!("#{i}"))?;
+            handle_command_end(&mut tracker)?;
+        }
+
+        let selection = |args: &[&str]| match Args::try_parse_from(args).unwrap().command {
+            Commands::List { selection, .. } | Commands::Export { selection, .. } => {
+                selection.options()
+            }
+            _ => unreachable!(),
+        };
+
+        let flag_combinations: [&[&str]; 6] = [
+            &[],
+            &["--all"],
+            &["--page", "1", "--limit", "10"],
+            &["--date", "today"],
+            &["--date", "2000-01-01"],
+            &["--order", "descending", "--limit", "5"],
+        ];
+
+        for flags in flag_combinations {
+            let list_args = [&["timetracker-cli", "list"], flags].concat();
+            let export_args = [&["timetracker-cli", "export", "csv"], flags].concat();
+
+            let list = collect_for_output(&tracker, &selection(&list_args), true)?;
+            let export = collect_for_output(&tracker, &selection(&export_args), true)?;
+
+            assert_eq!(list.finished.items, export.finished.items, "{flags:?}");
+            assert_eq!(list.finished.indices, export.finished.indices, "{flags:?}");
+            assert_eq!(list.hours, export.hours, "{flags:?}");
+        }
+
+        // Sanity check that the flags actually select differently
+        let all = collect_for_output(&tracker, &selection(&["_", "export", "--all"]), true)?;
+        let page = collect_for_output(&tracker, &selection(&["_", "export", "-l", "10"]), true)?;
+        assert_eq!(30, all.finished.items.len());
+        assert_eq!(10, page.finished.items.len());
+        Ok(())
+    }
+
+    #[test]
+    fn date_filter_composes_with_pagination_and_all() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+
+        for i in 0..30 {
+            handle_command_start(&mut tracker, &format!("#{i}"))?;
+            handle_command_end(&mut tracker)?;
+        }
+
+        let titles = |args: &[&str]| -> anyhow::Result<Vec<String>> {
+            let Commands::List { selection, .. } =
+                Args::try_parse_from([&["timetracker-cli", "list"], args].concat())?.command
+            else {
+                unreachable!()
+            };
+            let output = collect_for_output(&tracker, &selection.options(), true)?;
+            Ok(output
+                .finished
+                .items
+                .iter()
+                .map(|tb| tb.notes[0].description.clone())
+                .collect())
+        };
+
+        // Without `--limit` a date filter selects every matching time box
+        assert_eq!(30, titles(&["--date", "today"])?.len());
+        assert_eq!(0, titles(&["--date", "2000-01-01"])?.len());
+
+        // Filtering happens before paginating
+        assert_eq!(
+            vec!["#0", "#1"],
+            titles(&["--date", "today", "--limit", "2"])?
+        );
+        assert_eq!(
+            vec!["#2", "#3"],
+            titles(&["--date", "today", "--limit", "2", "--page", "1"])?
+        );
+        assert_eq!(25, titles(&["--date", "today", "--page", "0"])?.len());
+        assert!(titles(&["--date", "2000-01-01", "--limit", "2"])?.is_empty());
+
+        // `--all` ignores pagination but keeps the filter
+        assert_eq!(30, titles(&["--all", "--date", "today"])?.len());
+        assert_eq!(30, titles(&["--all", "--limit", "2"])?.len());
+        assert!(titles(&["--all", "--date", "2000-01-01"])?.is_empty());
+
+        // Descending pages start with the latest time boxes
+        assert_eq!(
+            vec!["#29", "#28"],
+            titles(&["--order", "descending", "--limit", "2"])?
+        );
+        assert_eq!(
+            vec!["#27", "#26"],
+            titles(&["--order", "descending", "--limit", "2", "--page", "1"])?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn clear() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+
+        handle_command_start(&mut tracker, "#1")?;
+        handle_command_end(&mut tracker)?;
         assert_eq!(1, tracker.finished(&ListOptions::new())?.total);
-        assert!(tracker.active()?.is_some());
+
+        handle_command_clear(&mut tracker, None, false, false)?;
+        assert_eq!(0, tracker.finished(&ListOptions::new())?.total);
+        Ok(())
+    }
+
+    #[test]
+    fn clears_get_backed_up_and_rotated() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let storage = dir.path().join("storage.json");
+        let backups = dir.path().join("backups");
+        let keep = 3;
+        let strategy = JsonStorageStrategy {
+            pretty: true,
+            repair: true,
+        };
+
+        let mut tracker = InMemoryTimeTracker::default();
+        save_to_disk(&tracker, &storage, &strategy)?;
+
+        for i in 0..keep + 2 {
+            handle_command_start(&mut tracker, &format!("#{i}"))?;
+            handle_command_end(&mut tracker)?;
+            save_to_disk(&tracker, &storage, &strategy)?;
+            let before_clear = std::fs::read_to_string(&storage)?;
+
+            // Same order as `main` persists a change
+            assert_eq!(
+                StoreMutation::Rewritten,
+                handle_command_clear(&mut tracker, None, false, false)?
+            );
+            write_backup(&storage, &backups, keep)?;
+            save_to_disk(&tracker, &storage, &strategy)?;
+
+            let newest = &list_backups(&backups, "json")?[0];
+            assert_eq!(before_clear, std::fs::read_to_string(newest)?);
+        }
+
+        assert_eq!(keep, list_backups(&backups, "json")?.len());
+        let restored = load_store(
+            &list_backups(&backups, "json")?[0],
+            StorageFormat::Json,
+            true,
+        )?;
+        assert_eq!(format!("#{}", keep + 1), restored.finished[0].title());
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_saves_never_corrupt_the_store() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let storage = dir.path().join("storage.json");
+
+        std::thread::scope(|scope| {
+            let saves: Vec<_> = (0..32)
+                .map(|i| {
+                    let storage = &storage;
+                    scope.spawn(move || {
+                        let mut tracker = InMemoryTimeTracker::default();
+                        for n in 0..=i {
+                            tracker.begin(&format!("#{i} {n}"))?;
+                            tracker.end()?;
+                        }
+                        save_to_disk(
+                            &tracker,
+                            storage,
+                            &JsonStorageStrategy {
+                                pretty: true,
+                                repair: true,
+                            },
+                        )
+                    })
+                })
+                .collect();
+
+            saves.into_iter().try_for_each(|save| save.join().unwrap())
+        })?;
+
+        // Whichever save came last, its content is complete
+        let tracker = load_store(&storage, StorageFormat::Json, true)?;
+        let i = tracker.finished.len() - 1;
+        assert!(
+            tracker
+                .finished
+                .iter()
+                .all(|tb| tb.title().starts_with(&format!("#{i} ")))
+        );
+
+        let leftovers: Vec<_> = std::fs::read_dir(dir.path())?
+            .map(|entry| entry.map(|e| e.file_name()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(vec![std::ffi::OsString::from("storage.json")], leftovers);
+
+        Ok(())
+    }
+
+    #[test]
+    fn init_writes_gitignore_per_mode() -> anyhow::Result<()> {
+        for (track_in_git, expected) in [
+            (false, "*"),
+            (true, ".__*_swap_tasks.*\n*.cache\n*.lock\nbackups/\n"),
+        ] {
+            let dir = tempfile::tempdir()?;
+            let output = dir.path().join(".bieglers-timetracker");
+            let storage = output.join("storage.json");
+            let backups = output.join("backups");
+
+            let options = InitArgs {
+                track_in_git,
+                ..Default::default()
+            };
+            handle_command_init(
+                &output,
+                &storage,
+                &backups,
+                10,
+                StorageFormat::Json,
+                OutputJsonFormat::Pretty,
+                &options,
+            )?;
+
+            assert_eq!(
+                expected,
+                std::fs::read_to_string(output.join(".gitignore"))?
+            );
+            assert!(
+                handle_command_init(
+                    &output,
+                    &storage,
+                    &backups,
+                    10,
+                    StorageFormat::Json,
+                    OutputJsonFormat::Pretty,
+                    &options
+                )
+                .is_err()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn init_migrates_legacy_store_over_existing_one() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join(".bieglers-timetracker");
+        let storage = output.join("storage.json");
+        let backups = output.join("backups");
+        let legacy = dir.path().join("tasks.json");
+        std::fs::write(
+            &legacy,
+            r#"{
+                "version": "V1",
+                "pending": null,
+                "finished": [{
+                    "time_start": "2024-06-03T09:00:00Z",
+                    "time_stop": "2024-06-03T10:00:00Z",
+                    "notes": [
+                        { "time": "2024-06-03T09:00:00Z", "description": "Fix login" },
+                        { "time": "2024-06-03T10:00:00Z", "description": "done" }
+                    ]
+                }]
+            }"#,
+        )?;
+
+        handle_command_init(
+            &output,
+            &storage,
+            &backups,
+            10,
+            StorageFormat::Json,
+            OutputJsonFormat::Pretty,
+            &InitArgs::default(),
+        )?;
+        let options = InitArgs {
+            force: true,
+            yes: true,
+            from_legacy: Some(legacy),
+            ..Default::default()
+        };
+        handle_command_init(
+            &output,
+            &storage,
+            &backups,
+            10,
+            StorageFormat::Json,
+            OutputJsonFormat::Pretty,
+            &options,
+        )?;
+
+        let migrated = load_store(&storage, StorageFormat::Json, true)?;
+        assert!(migrated.active.is_none());
+        assert_eq!("Fix login", migrated.finished[0].title());
+        assert_eq!(2, migrated.finished[0].notes.len());
+
+        let overwritten = load_store(
+            &list_backups(&backups, "json")?[0],
+            StorageFormat::Json,
+            true,
+        )?;
+        assert!(overwritten.finished.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_legacy_store_in_chronological_order() -> anyhow::Result<()> {
+        let legacy = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("legacy_tasks.json");
+
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+        handle_command_start(&mut tracker, "Already tracked")?;
+        tracker.end()?;
+
+        // Both stores having an active time box is ambiguous
+        handle_command_start(&mut tracker, "Busy")?;
+        assert!(handle_command_import(&mut tracker, &legacy, ImportFormat::LegacyJson).is_err());
+        assert_eq!(1, tracker.finished.len());
+        assert_eq!("Busy", tracker.active.as_ref().unwrap().title());
+
+        tracker.cancel()?;
+        assert_eq!(
+            StoreMutation::Rewritten,
+            handle_command_import(&mut tracker, &legacy, ImportFormat::LegacyJson)?
+        );
+
+        assert_eq!(3, tracker.finished.len());
+        assert_eq!("Review PR", tracker.active.as_ref().unwrap().title());
+        let titles: Vec<&str> = tracker.finished.iter().map(|tb| tb.title()).collect();
+        assert_eq!(vec!["Fix login bug", "Planning", "Already tracked"], titles);
+        assert_eq!(3, tracker.finished[0].notes.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn csv_export_imports_into_the_same_time_boxes() -> anyhow::Result<()> {
+        let time_box = |start: &str, notes: &[(i64, &str)]| {
+            let start = time(start);
+            time_box_of(
+                notes
+                    .iter()
+                    .map(|(minutes, description)| {
+                        note(start + TimeDelta::minutes(*minutes), description)
+                    })
+                    .collect(),
+            )
+        };
+        let mut finished = vec![
+            time_box(
+                "2024-06-01T09:00:00Z",
+                &[(0, r#"Said "hi"; then left"#), (85, "line one\nline two")],
+            ),
+            time_box("2024-06-02T09:00:00Z", &[(0, "Only note")]),
+            time_box(
+                "2024-06-03T09:00:00Z",
+                &[(0, "Start"), (0, "Between\n  - not a note"), (30, "Stop")],
+            ),
+        ];
+        finished[1].reference = Some("PROJ-7".to_string());
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("export.csv");
+        let csv = generate_csv_export(
+            &finished,
+            CSV_DELIMITER,
+            DurationStyle::HoursMinutes,
+            DurationOptions::default(),
+        )?;
+        std::fs::write(&path, csv)?;
+
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+        assert_eq!(
+            StoreMutation::Rewritten,
+            handle_command_import(&mut tracker, &path, ImportFormat::Csv)?
+        );
+        assert_eq!(finished.as_slice(), &*tracker.finished);
+        assert!(tracker.active.is_none());
+
+        std::fs::write(
+            &path,
+            "time_start,time_stop,hours,description\n2024-06-01,x,1,- a\n",
+        )?;
+        assert!(handle_command_import(&mut tracker, &path, ImportFormat::Csv).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_exports_import_once_and_never_as_active() -> anyhow::Result<()> {
+        let mut laptop = InMemoryTimeTracker::default();
+        handle_command_start(&mut laptop, "First \"quoted\"\nwith a second line")?;
+        laptop.pause()?;
+        laptop.end()?;
+        handle_command_start(&mut laptop, "Second")?;
+        laptop.set_estimate(Some(TimeDelta::hours(1)))?;
+        laptop.set_reference(Some("PROJ-1"))?;
+        laptop.end()?;
+        handle_command_start(&mut laptop, "Still running")?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("export.json");
+        let durations = DurationOptions::default();
+        let import = |tracker: &mut InMemoryTimeTracker| {
+            handle_command_import(tracker, &path, ImportFormat::Json)
+        };
+
+        let export = generate_json_export(&laptop.finished, laptop.active.as_ref(), durations)?;
+        std::fs::write(&path, serde_json::to_string_pretty(&export)?)?;
+        let mut desktop = InMemoryTimeTracker::default();
+        handle_command_start(&mut desktop, "Desktop only")?;
+        assert_eq!(StoreMutation::Rewritten, import(&mut desktop)?);
+        assert_eq!(2, desktop.finished.len());
+        assert_eq!("Desktop only", desktop.active.as_ref().unwrap().title());
+        let notes = |t: &InMemoryTimeTracker| -> Vec<Vec<TimeBoxNote>> {
+            t.finished.iter().map(|tb| tb.notes.clone()).collect()
+        };
+        assert_eq!(notes(&laptop), notes(&desktop));
+        assert_eq!(Some("PROJ-1"), desktop.finished[1].reference.as_deref());
+
+        // Importing the same export again, e.g. after syncing back, adds nothing
+        assert_eq!(StoreMutation::Unchanged, import(&mut desktop)?);
+        assert_eq!(2, desktop.finished.len());
+
+        // Only the raw export keeps pauses and estimates
+        let raw = generate_json_export_raw(&laptop.finished, durations)?;
+        std::fs::write(&path, serde_json::to_string(&raw)?)?;
+        let mut empty = InMemoryTimeTracker::default();
+        import(&mut empty)?;
+        assert_eq!(laptop.finished, empty.finished);
+
+        std::fs::write(&path, r#"{"finished": 1}"#)?;
+        assert!(import(&mut empty).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn undo_restores_the_last_backup() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let storage = dir.path().join("storage.json");
+        let backups = dir.path().join("backups");
+        let strategy = JsonStorageStrategy {
+            pretty: true,
+            repair: true,
+        };
+
+        let mut tracker = InMemoryTimeTracker::default();
+        save_to_disk(&tracker, &storage, &strategy)?;
+
+        // Same order as `main` persists a change
+        let persist = |tracker: &InMemoryTimeTracker| -> anyhow::Result<()> {
+            write_backup(&storage, &backups, 10)?;
+            save_to_disk(tracker, &storage, &strategy)
+        };
+
+        assert!(handle_command_undo(&mut tracker, &backups, StorageFormat::Json).is_err());
+
+        handle_command_start(&mut tracker, "#1")?;
+        handle_command_end(&mut tracker)?;
+        persist(&tracker)?;
+        handle_command_clear(&mut tracker, None, false, false)?;
+        persist(&tracker)?;
+
+        assert_eq!(
+            StoreMutation::Rewritten,
+            handle_command_undo(&mut tracker, &backups, StorageFormat::Json)?
+        );
+        persist(&tracker)?;
+        assert_eq!(1, tracker.finished(&ListOptions::new())?.total);
+
+        // Undoing the undo clears again
+        assert_eq!(
+            StoreMutation::Rewritten,
+            handle_command_undo(&mut tracker, &backups, StorageFormat::Json)?
+        );
+        assert_eq!(0, tracker.finished(&ListOptions::new())?.total);
+        Ok(())
+    }
+
+    #[test]
+    fn begin_takes_the_reference_or_finds_the_ticket_id() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+        let storage = dir.path().join("storage.json");
+        let reference = || -> anyhow::Result<Option<String>> {
+            let tracker = load_store(&storage, StorageFormat::Json, true)?;
+            Ok(tracker.active.and_then(|tb| tb.reference))
+        };
+
+        run_with(&[
+            "--auto-init",
+            "begin",
+            "work on login",
+            "--ref",
+            " PROJ-123 ",
+        ])?;
+        assert_eq!(Some("PROJ-123".to_string()), reference()?);
+        run_with(&["end"])?;
+
+        run_with(&["begin", "PROJ-9: fix the parser", "--auto-ref"])?;
+        assert_eq!(Some("PROJ-9".to_string()), reference()?);
+        run_with(&["end"])?;
+
+        // Several ticket ids are ambiguous, so there is no reference
+        run_with(&["begin", "PROJ-9 or PROJ-10", "--auto-ref"])?;
+        assert_eq!(None, reference()?);
+        run_with(&["end"])?;
+
+        assert!(Args::try_parse_from(["_", "begin", "x", "--ref", "A-1", "--auto-ref"]).is_err());
+        assert_eq!(
+            ExitCode::SUCCESS,
+            run_with(&["report", "--group-by", "ref", "--format", "json"])?
+        );
+        assert_eq!(ExitCode::SUCCESS, run_with(&["list", "--ref", "proj-9"])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn backfilled_time_boxes_never_overlap_finished_ones() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+        let storage = dir.path().join("storage.json");
+        let local = |s: &str| {
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+                .unwrap()
+                .and_local_timezone(Local)
+                .earliest()
+                .unwrap()
+                .to_utc()
+        };
+
+        let args = ["--auto-init", "begin", "forgot to start"];
+        run_with(&[&args[..], &["--backfill", "2024-06-03 08:30"]].concat())?;
+        run_with(&["note", "done", "--at", "2024-06-03 17:00"])?;
+        run_with(&["end"])?;
+
+        // Starting before the last stop would overlap
+        let error = run_with(&["begin", "next day", "--backfill", "2024-06-03 16:00"])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("stopped later at 2024-06-03 17:00"),
+            "{error}"
+        );
+        let error = run_with(&["begin", "next day", "--backfill", "2999-01-01 08:00"])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("in the future"), "{error}");
+
+        run_with(&["begin", "next day", "--backfill", "2024-06-03 17:00"])?;
+        let tracker = load_store(&storage, StorageFormat::Json, true)?;
+        assert_eq!(local("2024-06-03 08:30"), tracker.finished[0].time_start()?);
+        assert_eq!(
+            local("2024-06-03 17:00"),
+            tracker.active.unwrap().time_start()?
+        );
+
+        assert!(Args::try_parse_from(["_", "begin", "--pick", "--backfill", "08:00"]).is_err());
+        assert!(Args::try_parse_from(["_", "begin", "x", "--backfill", "8 o'clock"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn min_minutes_hides_short_time_boxes() -> anyhow::Result<()> {
+        let time_box = |start: &str, minutes: i64, title: &str| {
+            let start = time(start);
+            time_box_of(vec![
+                note(start, title),
+                note(start + TimeDelta::minutes(minutes), "done"),
+            ])
+        };
+        let tracker = InMemoryTimeTracker {
+            finished: vec![
+                time_box("2025-01-01T09:00:00Z", 2, "accident"),
+                time_box("2025-01-01T10:00:00Z", 20, "review #bug"),
+                time_box("2025-01-01T11:00:00Z", 90, "implement"),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let titles = |args: &[&str]| -> anyhow::Result<Vec<String>> {
+            let Commands::List { selection, .. } =
+                Args::try_parse_from([&["timetracker-cli", "list"], args].concat())?.command
+            else {
+                unreachable!()
+            };
+            Ok(tracker
+                .finished(&selection.options())?
+                .items
+                .iter()
+                .map(|tb| tb.title().to_string())
+                .collect())
+        };
+
+        assert_eq!(
+            vec!["review #bug", "implement"],
+            titles(&["--min-minutes", "10"])?
+        );
+        assert_eq!(
+            vec!["review #bug"],
+            titles(&["--min-minutes", "10", "--tag", "bug"])?
+        );
+        assert_eq!(3, titles(&["--min-minutes", "0"])?.len());
+
+        Ok(())
+    }
+
+    // Hooks run through `sh` here, `cmd` quotes differently
+    #[cfg(unix)]
+    #[test]
+    fn hooks_run_after_each_saved_change() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let events = dir.path().join("events.txt");
+        let hook = format!(
+            "echo \"$TIMETRACKER_EVENT $TIMETRACKER_TITLE\" >> '{}'",
+            events.display()
+        );
+        let run_with = |args: &[&str]| {
+            let args = [
+                &["timetracker-cli", "--output", output, "--hook", &hook],
+                args,
+            ]
+            .concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+        let events = || std::fs::read_to_string(&events).unwrap_or_default();
+
+        run_with(&["--auto-init", "begin", "say \"hi\""])?;
+        run_with(&["note", "second"])?;
+        run_with(&["end"])?;
+        assert_eq!(
+            "begin say \"hi\"\nnote say \"hi\"\nend say \"hi\"\n",
+            events()
+        );
+
+        // Failing and read-only commands change nothing, so there is nothing to tell
+        assert!(run_with(&["end"]).is_err());
+        run_with(&["list"])?;
+        assert_eq!(3, events().lines().count());
+
+        // The change is saved already, so a failing hook does not fail the command
+        let args = [
+            "timetracker-cli",
+            "--output",
+            output,
+            "--hook",
+            "exit 1",
+            "begin",
+            "next",
+        ];
+        assert_eq!(ExitCode::SUCCESS, run(Args::try_parse_from(args)?)?);
+
+        // Every change of the store runs the hook, not every command
+        run_with(&["note", "--end", "done"])?;
+        assert_eq!(
+            vec!["note next", "end next"],
+            events().lines().skip(3).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn undoing_clear_restores_the_store_byte_for_byte() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+        let storage = dir.path().join("storage.json");
+
+        run_with(&["--auto-init", "begin", "first"])?;
+        run_with(&["note", "with \"quotes\""])?;
+        run_with(&["end"])?;
+        run_with(&["begin", "second"])?;
+        run_with(&["end"])?;
+        let before = std::fs::read(&storage)?;
+
+        run_with(&["clear"])?;
+        assert_ne!(before, std::fs::read(&storage)?);
+        run_with(&["undo"])?;
+        assert_eq!(before, std::fs::read(&storage)?);
+
+        // A backup that does not parse is refused and leaves the store as is
+        let backups = list_backups(&dir.path().join("backups"), "json")?;
+        std::fs::write(&backups[0], "{ not a store")?;
+        let error = run_with(&["undo"]).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("Failed to load backup"),
+            "{error:#}"
+        );
+        assert_eq!(before, std::fs::read(&storage)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dont_clear_due_pending_task() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+
+        handle_command_start(&mut tracker, "#1")?;
+        handle_command_end(&mut tracker)?;
+        assert_eq!(1, tracker.finished(&ListOptions::new())?.total);
+
+        handle_command_start(&mut tracker, "#2")?;
+        assert!(tracker.active()?.is_some());
+
+        let modified = handle_command_clear(&mut tracker, None, false, false)?;
+        assert_eq!(StoreMutation::Unchanged, modified);
+        assert_eq!(1, tracker.finished(&ListOptions::new())?.total);
+        assert!(tracker.active()?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn clear_by_date_keeps_other_time_boxes_and_the_active_one() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+        for i in 0..12 {
+            handle_command_start(&mut tracker, &format!("#{i}"))?;
+            handle_command_end(&mut tracker)?;
+        }
+        handle_command_start(&mut tracker, "Active")?;
+
+        let date = tracker.finished[0].time_start()?.date_naive();
+        let today = ListFilter::Date(date);
+        let yesterday = ListFilter::Date(date - TimeDelta::days(1));
+        assert_eq!(
+            StoreMutation::Unchanged,
+            handle_command_clear(&mut tracker, Some(&yesterday), false, false)?
+        );
+        assert_eq!(12, tracker.finished.len());
+
+        // More than the threshold only gets removed once confirmed
+        assert_eq!(
+            StoreMutation::Rewritten,
+            handle_command_clear(&mut tracker, Some(&today), true, false)?
+        );
+        assert!(tracker.finished.is_empty());
+        assert!(tracker.active()?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn clear_refuses_repaired_stores_unless_forced() -> anyhow::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("notes_unsorted.json");
+        let mut tracker = load_store(&path, StorageFormat::Json, false)?;
+        assert!(tracker.was_repaired());
+
+        let err = handle_command_clear(&mut tracker, None, false, false).unwrap_err();
+        assert!(err.to_string().contains("sorted the notes"), "{err}");
+        assert_eq!(1, tracker.finished.len());
+
+        assert_eq!(
+            StoreMutation::Rewritten,
+            handle_command_clear(&mut tracker, None, false, true)?
+        );
+        assert!(tracker.finished.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn lock_gets_suggested_once_a_week_after_exporting() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let start = local_midnight(date("2024-06-05"))? + TimeDelta::hours(9);
+        tracker.begin_at("Fix login bug", start)?;
+        tracker.push_note_at("done", start + TimeDelta::hours(1))?;
+        tracker.end()?;
+        tracker.set_exported_at(0, Some(start + TimeDelta::hours(2)))?;
+
+        let suggest = |tracker: &mut InMemoryTimeTracker, today: &str| {
+            suggest_lock_once(tracker, StoreMutation::Unchanged, date(today)).unwrap()
+        };
+        // Nothing before the week yet, then remembering the suggestion needs saving but only once
+        assert_eq!(
+            StoreMutation::Unchanged,
+            suggest(&mut tracker, "2024-06-07")
+        );
+        assert_eq!(StoreMutation::Active, suggest(&mut tracker, "2024-06-10"));
+        assert_eq!(
+            StoreMutation::Unchanged,
+            suggest(&mut tracker, "2024-06-12")
+        );
+        assert_eq!(
+            Some(local_midnight(date("2024-06-10"))?),
+            tracker.settings.lock_suggested_for
+        );
+
+        assert_eq!(
+            StoreMutation::Active,
+            handle_command_lock(&mut tracker, date("2024-06-10"))?
+        );
+        assert!(matches!(
+            tracker.clear(),
+            Err(timetracker::Error::TimeBoxLocked { .. })
+        ));
+        // Locked already, so there is nothing to suggest the week after
+        assert_eq!(
+            StoreMutation::Unchanged,
+            suggest(&mut tracker, "2024-06-17")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn suggestions_get_remembered_in_the_store_unless_disabled() -> anyhow::Result<()> {
+        let suggested_for = |no_suggestions: bool| -> anyhow::Result<_> {
+            let dir = tempfile::tempdir()?;
+            let output = dir.path().to_str().unwrap();
+            let run_with = |args: &[&str]| {
+                let mut flags = vec!["timetracker-cli", "--output", output];
+                if no_suggestions {
+                    flags.push("--no-suggestions");
+                }
+                run(Args::try_parse_from([&flags[..], args].concat()).unwrap())
+            };
+
+            run_with(&["--auto-init", "begin", "last week"])?;
+            run_with(&["end"])?;
+            // Moves the time box into the week before
+            let storage = dir.path().join("storage.json");
+            let mut tracker = load_store(&storage, StorageFormat::Json, true)?;
+            tracker.finished.update_all(|tb| {
+                for note in tb.notes.iter_mut() {
+                    note.time -= TimeDelta::days(8);
+                }
+            });
+            save_store(
+                &tracker,
+                &storage,
+                StorageFormat::Json,
+                OutputJsonFormat::Pretty,
+            )?;
+            let export = dir.path().join("export.csv");
+            run_with(&[
+                "export",
+                "--mark-exported",
+                "--output",
+                export.to_str().unwrap(),
+            ])?;
+
+            Ok(load_store(&storage, StorageFormat::Json, true)?
+                .settings
+                .lock_suggested_for)
+        };
+
+        assert!(suggested_for(false)?.is_some());
+        assert_eq!(None, suggested_for(true)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn clear_refuses_repaired_stores_with_cache_too() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+        let storage = dir.path().join("storage.json");
+        let cache = dir.path().join("storage.cache");
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("notes_unsorted.json");
+        std::fs::copy(fixture, &storage)?;
+        let content = std::fs::read_to_string(&storage)?;
+
+        // Loading the repaired store leaves no cache behind that would hide the repairs
+        run_with(&["--cache", "list"])?;
+        assert!(!cache.exists());
+
+        let error = run_with(&["--cache", "clear"]).unwrap_err().to_string();
+        assert!(error.contains("--force"), "{error}");
+        assert_eq!(content, std::fs::read_to_string(&storage)?);
+
+        run_with(&["--cache", "clear", "--force"])?;
+        let tracker = load_store(&storage, StorageFormat::Json, true)?;
+        assert!(tracker.finished.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn doctor_compares_the_cache_by_stored_data() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+        let storage = dir.path().join("storage.json");
+        let cache = dir.path().join("storage.cache");
+
+        run_with(&["--auto-init", "--cache", "begin", "cached"])?;
+        assert!(cache.exists());
+
+        // What loading noticed is never part of the cache
+        let mut tracker = load_store(&storage, StorageFormat::Json, false)?;
+        tracker
+            .diagnostics
+            .push(timetracker::Diagnostic::warning("noticed while loading"));
+        handle_command_doctor(&tracker, &storage, &cache)?;
+
+        tracker.push_note("not in the cache")?;
+        let error = handle_command_doctor(&tracker, &storage, &cache).unwrap_err();
+        assert!(error.to_string().contains("Cache differs"), "{error}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn failing_commands_report_the_error_code() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+
+        run_with(&["--auto-init", "begin", "task"])?;
+        let error = run_with(&["begin", "again"]).unwrap_err();
+        assert_eq!(Some("active_time_box_exists"), error_code(&error));
+        let stderr = format_error(&error);
+        assert!(
+            stderr.starts_with("Error [active_time_box_exists]: "),
+            "{stderr}"
+        );
+        let porcelain = generate_porcelain_error(&error);
+        assert!(
+            porcelain.starts_with("event=error code=active_time_box_exists message=\""),
+            "{porcelain}"
+        );
+        assert!(porcelain.ends_with(": there is an active time box already\""));
+
+        // Errors of the CLI itself have no code
+        std::fs::write(dir.path().join("config.json"), "{")?;
+        let error = run_with(&["list"]).unwrap_err();
+        assert_eq!(None, error_code(&error));
+        assert!(format_error(&error).starts_with("Error: Failed to parse the config"));
+        assert!(generate_porcelain_error(&error).starts_with("event=error message=\"Failed"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn mark_exported_stamps_only_exported_time_boxes() -> anyhow::Result<()> {
+        let mut tracker = InMemoryTimeTracker::init(&TestLoadingStrategy {})?;
+        let settings = OutputSettings {
+            color: false,
+            duration_style: DurationStyle::DecimalHours,
+            width: None,
+            plain: false,
+            pager: false,
+        };
+        let unexported = || ListOptions::new().take(usize::MAX).unexported_only(true);
+
+        // Nothing to export, so nothing to persist either
+        assert_eq!(
+            StoreMutation::Unchanged,
+            handle_command_export(
+                &mut tracker,
+                ExportStrategy::Csv,
+                &ExportFormatArgs::default(),
+                &ExportFileArgs::default(),
+                &unexported(),
+                true,
+                DurationOptions::default(),
+                &settings,
+            )?
+        );
+
+        for i in 0..3 {
+            handle_command_start(&mut tracker, &format!("#{i}"))?;
+            handle_command_end(&mut tracker)?;
+        }
+
+        assert_eq!(
+            StoreMutation::Unchanged,
+            handle_command_export(
+                &mut tracker,
+                ExportStrategy::Csv,
+                &ExportFormatArgs::default(),
+                &ExportFileArgs::default(),
+                &ListOptions::new().take(2),
+                false,
+                DurationOptions::default(),
+                &settings,
+            )?
+        );
+        assert_eq!(3, tracker.finished(&unexported())?.items.len());
+
+        assert_eq!(
+            StoreMutation::Rewritten,
+            handle_command_export(
+                &mut tracker,
+                ExportStrategy::Csv,
+                &ExportFormatArgs::default(),
+                &ExportFileArgs::default(),
+                &ListOptions::new().take(2),
+                true,
+                DurationOptions::default(),
+                &settings,
+            )?
+        );
+        assert_eq!(vec![2], tracker.finished(&unexported())?.indices);
+
+        // Already billed time boxes do not get exported again
+        assert_eq!(
+            StoreMutation::Rewritten,
+            handle_command_export(
+                &mut tracker,
+                ExportStrategy::Csv,
+                &ExportFormatArgs::default(),
+                &ExportFileArgs::default(),
+                &unexported(),
+                true,
+                DurationOptions::default(),
+                &settings,
+            )?
+        );
+        assert_eq!(
+            StoreMutation::Unchanged,
+            handle_command_export(
+                &mut tracker,
+                ExportStrategy::Csv,
+                &ExportFormatArgs::default(),
+                &ExportFileArgs::default(),
+                &unexported(),
+                true,
+                DurationOptions::default(),
+                &settings,
+            )?
+        );
+
+        handle_command_set(&mut tracker, 0, true)?;
+        assert_eq!(vec![0], tracker.finished(&unexported())?.indices);
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_store_is_not_reported_as_malformed() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("tracking");
+        let output = output.to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+
+        // Neither the directory nor the store exist
+        assert_eq!(ExitCode::from(EXIT_NOT_INITIALIZED), run_with(&["list"])?);
+
+        // The directory exists, the store does not
+        std::fs::create_dir(output)?;
+        assert_eq!(ExitCode::from(EXIT_NOT_INITIALIZED), run_with(&["report"])?);
+
+        let error = run_with(&["begin", "a"]).unwrap_err().to_string();
+        assert!(error.contains("run `init` first"), "{error}");
+        assert!(!Path::new(output).join("storage.json").exists());
+
+        assert_eq!(ExitCode::SUCCESS, run_with(&["--auto-init", "begin", "a"])?);
+        assert_eq!(
+            ExitCode::SUCCESS,
+            run_with(&["status", "--format", "plain"])?
+        );
+
+        std::fs::write(Path::new(output).join("storage.json"), "{ malformed")?;
+        let error = run_with(&["list"]).unwrap_err().to_string();
+        assert!(error.contains("Failed to load"), "{error}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_writes_into_the_output_file() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("tracking");
+        let output = output.to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+        let file = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+
+        run_with(&["--auto-init", "begin", "Größe prüfen"])?;
+        run_with(&["end"])?;
+
+        // The strategy follows the extension
+        run_with(&["export", "--output", &file("report.CSV")])?;
+        let csv = std::fs::read_to_string(file("report.CSV"))?;
+        assert!(csv.contains("Größe prüfen"), "{csv}");
+        run_with(&["export", "--output", &file("report.json")])?;
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(file("report.json"))?)?;
+        assert_eq!(1, json["finished"].as_array().unwrap().len());
+        run_with(&["export", "--output", &file("report.jsonl")])?;
+        let ndjson = std::fs::read_to_string(file("report.jsonl"))?;
+        assert_eq!(1, ndjson.lines().count(), "{ndjson}");
+
+        // Existing files only get replaced with `--force`
+        let error = run_with(&["export", "json", "--output", &file("report.CSV")])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("--force"), "{error}");
+        assert_eq!(csv, std::fs::read_to_string(file("report.CSV"))?);
+        run_with(&["export", "ics", "--output", &file("report.CSV"), "--force"])?;
+        assert!(std::fs::read_to_string(file("report.CSV"))?.starts_with("BEGIN:VCALENDAR"));
+
+        // Unknown extensions need an explicit strategy
+        let error = run_with(&["export", "--output", &file("report.txt")])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("export strategy"), "{error}");
+        assert!(!dir.path().join("report.txt").exists());
+        run_with(&["export", "debug", "--output", &file("report.txt")])?;
+        assert!(dir.path().join("report.txt").exists());
+
+        // Missing directories get created, `--file` is the same as `--output`
+        run_with(&["export", "csv", "--file", &file("missing/report.csv")])?;
+        assert!(std::fs::read_to_string(file("missing/report.csv"))?.contains("Größe prüfen"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pager_gets_disabled_by_flag_or_config() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let parse = |args: &[&str]| {
+            Args::try_parse_from([&["timetracker-cli", "--output", output], args].concat())
+        };
+        let pager = |args: &Args| -> anyhow::Result<bool> {
+            let config = Config::load(&args.output.join("config.json"))?;
+            Ok(
+                resolve_settings(&StoreSettings::default(), &config, &args.setting_flags())
+                    .pager
+                    .value,
+            )
+        };
+
+        run(parse(&["--auto-init", "begin", "first"])?)?;
+        run(parse(&["end"])?)?;
+
+        // Output that is no terminal never gets paged, the commands behave as before
+        assert!(pager(&parse(&["list"])?)?);
+        assert_eq!(ExitCode::SUCCESS, run(parse(&["list"])?)?);
+        assert!(!pager(&parse(&["--no-pager", "list"])?)?);
+        assert_eq!(ExitCode::SUCCESS, run(parse(&["--no-pager", "list"])?)?);
+        assert_eq!(ExitCode::SUCCESS, run(parse(&["--no-pager", "report"])?)?);
+
+        std::fs::write(dir.path().join("config.json"), r#"{"pager": false}"#)?;
+        assert!(!pager(&parse(&["report"])?)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn projects_keep_separate_stores_and_merge_for_exporting() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+        let project = |name: &str| dir.path().join("projects").join(name);
+
+        run_with(&["init"])?;
+        run_with(&["--project", "clienta", "init"])?;
+        run_with(&["--project", "clientb", "--auto-init", "begin", "first"])?;
+        run_with(&["--project", "clientb", "end"])?;
+        run_with(&["--project", "clienta", "begin", "second"])?;
+        run_with(&["--project", "clienta", "end"])?;
+        run_with(&["--project", "clientb", "begin", "still running"])?;
+
+        let default = load_store(&dir.path().join("storage.json"), StorageFormat::Json, true)?;
+        assert!(default.finished.is_empty());
+        let clienta = load_store(
+            &project("clienta").join("storage.json"),
+            StorageFormat::Json,
+            true,
+        )?;
+        assert_eq!(
+            vec!["second"],
+            clienta
+                .finished
+                .iter()
+                .map(|tb| tb.title())
+                .collect::<Vec<_>>()
+        );
+        assert!(project("clientb").join("backups").exists());
+
+        assert_eq!(ExitCode::SUCCESS, run_with(&["projects"])?);
+        assert_eq!(ExitCode::SUCCESS, run_with(&["--project", "all", "list"])?);
+
+        let csv = dir.path().join("all.csv");
+        run_with(&[
+            "--project",
+            "all",
+            "export",
+            "--output",
+            csv.to_str().unwrap(),
+        ])?;
+        let merged = parse_csv_export(&std::fs::read_to_string(&csv)?)?;
+        assert_eq!(
+            vec!["first", "second"],
+            merged.iter().map(|tb| tb.title()).collect::<Vec<_>>()
+        );
+
+        // Nothing gets changed across projects
+        let error = run_with(&["--project", "all", "end"])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("--project all"), "{error}");
+        let error = run_with(&["--project", "all", "export", "--mark-exported"])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("--mark-exported"), "{error}");
+        assert!(run_with(&["--project", "../escape", "init"]).is_err());
+        assert!(!dir.path().join("escape").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn jsonl_store_appends_finished_time_boxes() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [
+                &[
+                    "timetracker-cli",
+                    "--output",
+                    output,
+                    "--storage-format",
+                    "jsonl",
+                ],
+                args,
+            ]
+            .concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+        let storage = dir.path().join("storage.jsonl");
+        let lines = || -> anyhow::Result<String> { Ok(std::fs::read_to_string(&storage)?) };
+
+        run_with(&["init"])?;
+        run_with(&["begin", "first"])?;
+        assert_eq!("", lines()?);
+        run_with(&["note", "done", "--end"])?;
+        let first = lines()?;
+        assert_eq!(1, first.lines().count());
+
+        run_with(&["begin", "second"])?;
+        run_with(&["end"])?;
+        assert!(lines()?.starts_with(&first));
+        assert_eq!(2, lines()?.lines().count());
+
+        // Backups include the state file, so undoing brings back the active time box
+        run_with(&["undo"])?;
+        assert_eq!(first, lines()?);
+        let tracker = load_store(&storage, StorageFormat::Jsonl, true)?;
+        assert_eq!("second", tracker.active.unwrap().title());
+
+        // An interrupted append leaves an incomplete line, the next save rewrites it away
+        std::fs::write(&storage, format!("{first}{{\"notes\":["))?;
+        run_with(&["pause"])?;
+        assert_eq!(first, lines()?);
+        run_with(&["end"])?;
+        let tracker = load_store(&storage, StorageFormat::Jsonl, true)?;
+        assert_eq!(2, tracker.finished.len());
+        assert!(!tracker.was_repaired());
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_roundtrip_fails_on_differences() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+        let run_with = |args: &[&str]| {
+            let args = [&["timetracker-cli", "--output", output], args].concat();
+            run(Args::try_parse_from(args).unwrap())
+        };
+
+        for format in ["json", "toml", "jsonl"] {
+            let args = ["--storage-format", format];
+            run_with(
+                &[
+                    &args[..],
+                    &["--auto-init", "begin", "first", "--estimate", "1h"],
+                ]
+                .concat(),
+            )?;
+            run_with(&[&args[..], &["note", "with \"quotes\"\nand a line break"]].concat())?;
+            run_with(&[&args[..], &["end"]].concat())?;
+            run_with(&[&args[..], &["begin", "active"]].concat())?;
+
+            assert_eq!(
+                ExitCode::SUCCESS,
+                run_with(&[&args[..], &["verify-roundtrip"]].concat())?,
+                "{format}"
+            );
+        }
+
+        // A copy in another format holds the same store, a changed copy does not
+        let copy = dir.path().join("copy.toml");
+        let mut tracker = load_store(&dir.path().join("storage.json"), StorageFormat::Json, true)?;
+        save_store(
+            &tracker,
+            &copy,
+            StorageFormat::Toml,
+            OutputJsonFormat::Pretty,
+        )?;
+        let against = ["verify-roundtrip", "--against", copy.to_str().unwrap()];
+        assert_eq!(ExitCode::SUCCESS, run_with(&against)?);
+
+        tracker.active = None;
+        save_store(
+            &tracker,
+            &copy,
+            StorageFormat::Toml,
+            OutputJsonFormat::Pretty,
+        )?;
+        assert_eq!(ExitCode::FAILURE, run_with(&against)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn init_waits_for_other_invocations_holding_the_store() -> anyhow::Result<()> {
+        let run_in = |dir: &Path, args: &[&str]| {
+            let output = dir.to_str().unwrap();
+            let flags = ["timetracker-cli", "--output", output, "--lock-timeout", "0"];
+            run(Args::try_parse_from([&flags[..], args].concat()).unwrap())
+        };
+        let held = |dir: &Path| {
+            StorageLock::acquire(
+                &dir.join("storage.lock"),
+                LockMode::Exclusive,
+                Duration::ZERO,
+            )
+        };
+        let in_use = |result: anyhow::Result<ExitCode>| {
+            let error = format!("{:#}", result.unwrap_err());
+            assert!(error.contains("in use by another"), "{error}");
+        };
+
+        let dir = tempfile::tempdir()?;
+        let storage = dir.path().join("storage.json");
+        run_in(dir.path(), &["init"])?;
+        run_in(dir.path(), &["begin", "kept"])?;
+        let content = std::fs::read_to_string(&storage)?;
+
+        let lock = held(dir.path())?;
+        in_use(run_in(dir.path(), &["init", "--force", "--yes"]));
+        assert_eq!(content, std::fs::read_to_string(&storage)?);
+        drop(lock);
+        run_in(dir.path(), &["init", "--force", "--yes"])?;
+        assert!(
+            load_store(&storage, StorageFormat::Json, true)?
+                .active
+                .is_none()
+        );
+
+        // The directory of a new store exists before initializing it
+        let fresh = tempfile::tempdir()?;
+        let _lock = held(fresh.path())?;
+        in_use(run_in(fresh.path(), &["--auto-init", "begin", "task"]));
+        assert!(!fresh.path().join("storage.json").exists());
+
         Ok(())
     }
 }
@@ -0,0 +1,225 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use log::warn;
+use timetracker::in_memory_tracker::InMemoryTimeTracker;
+
+use crate::{args::StorageFormat, helpers::load_store};
+
+/// Environment variable selecting the project if `--project` is not passed.
+pub const PROJECT_ENV: &str = "TIMETRACKER_PROJECT";
+
+/// Named projects live in this folder of the output directory, one directory each.
+const PROJECTS_DIRECTORY: &str = "projects";
+
+/// Selects every project at once, so it is no valid name.
+const ALL: &str = "all";
+
+/// Which store commands work on. Each project is a complete store with its own backups and lock,
+/// the `config.json` of the output directory applies to all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Project {
+    /// The store directly inside the output directory, as before there were projects.
+    Default,
+    Named(String),
+    /// Every project including the default one, only for reading.
+    All,
+}
+
+impl Project {
+    /// The `flag` wins over the `env` variable, without either commands work on the default store.
+    pub fn resolve(flag: Option<&str>, env: Option<&str>) -> anyhow::Result<Self> {
+        let name = flag.or(env.filter(|name| !name.is_empty()));
+
+        match name {
+            None => Ok(Project::Default),
+            Some(ALL) => Ok(Project::All),
+            Some(name) => {
+                check_name(name)?;
+                Ok(Project::Named(name.to_owned()))
+            }
+        }
+    }
+
+    /// Directory holding the store of this project, `None` for all projects.
+    pub fn directory(&self, output: &Path) -> Option<PathBuf> {
+        match self {
+            Project::Default => Some(output.to_path_buf()),
+            Project::Named(name) => Some(output.join(PROJECTS_DIRECTORY).join(name)),
+            Project::All => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Project {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Project::Default => f.write_str("default"),
+            Project::Named(name) => f.write_str(name),
+            Project::All => f.write_str(ALL),
+        }
+    }
+}
+
+/// Names become directories, so they must not reach outside of the projects folder.
+fn check_name(name: &str) -> anyhow::Result<()> {
+    let is_valid = !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name != ALL
+        && !name.contains(['/', '\\'])
+        && !name.chars().any(char::is_control);
+
+    if !is_valid {
+        bail!("Invalid project name \"{name}\", it has to be usable as name of a directory");
+    }
+
+    Ok(())
+}
+
+/// The default project first, followed by the named projects inside `output` sorted by name.
+pub fn list_projects(output: &Path) -> anyhow::Result<Vec<Project>> {
+    let directory = output.join(PROJECTS_DIRECTORY);
+
+    let entries = match std::fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![Project::Default]),
+        Err(e) => {
+            return Err(anyhow::Error::new(e).context(format!(
+                "Failed to read the projects \"{}\"",
+                directory.display()
+            )));
+        }
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry
+            .with_context(|| format!("Failed to read the projects \"{}\"", directory.display()))?;
+        if entry.file_type()?.is_dir()
+            && let Some(name) = entry.file_name().to_str()
+            && check_name(name).is_ok()
+        {
+            names.push(name.to_owned());
+        }
+    }
+    names.sort();
+
+    Ok(std::iter::once(Project::Default)
+        .chain(names.into_iter().map(Project::Named))
+        .collect())
+}
+
+/// Finished time boxes of every initialized project in one store, in chronological order.
+/// Only one time box can be active, so active ones are left out with a warning naming their projects.
+pub fn merge_projects(
+    output: &Path,
+    format: StorageFormat,
+    strict: bool,
+) -> anyhow::Result<InMemoryTimeTracker> {
+    let mut merged = InMemoryTimeTracker::default();
+    let mut active = Vec::new();
+
+    for project in list_projects(output)? {
+        let Some(tracker) = load_project(output, &project, format, strict)? else {
+            continue;
+        };
+
+        if tracker.active.is_some() {
+            active.push(project.to_string());
+        }
+        for tb in Vec::from(tracker.finished) {
+            merged.finished.insert_sorted(tb);
+        }
+    }
+
+    if !active.is_empty() {
+        warn!(
+            "Leaving out the active time boxes of the projects: {}",
+            active.join(", ")
+        );
+    }
+
+    Ok(merged)
+}
+
+/// Store of `project`, `None` if it was not initialized yet.
+pub fn load_project(
+    output: &Path,
+    project: &Project,
+    format: StorageFormat,
+    strict: bool,
+) -> anyhow::Result<Option<InMemoryTimeTracker>> {
+    let Some(directory) = project.directory(output) else {
+        bail!("Unable to load all projects as one store");
+    };
+    let path = directory.join(format.file_name());
+
+    match load_store(&path, format, strict) {
+        Ok(tracker) => Ok(Some(tracker)),
+        Err(timetracker::Error::Io(e)) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow::Error::new(e).context(format!(
+            "Failed to load the project \"{project}\". Tried to read data from path: \"{}\"",
+            path.display()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_wins_over_env_which_wins_over_default() -> anyhow::Result<()> {
+        let named = |name: &str| Project::Named(name.to_owned());
+
+        assert_eq!(Project::Default, Project::resolve(None, None)?);
+        assert_eq!(Project::Default, Project::resolve(None, Some(""))?);
+        assert_eq!(named("client"), Project::resolve(None, Some("client"))?);
+        assert_eq!(named("own"), Project::resolve(Some("own"), Some("client"))?);
+        assert_eq!(Project::All, Project::resolve(Some("all"), None)?);
+        assert_eq!(Project::All, Project::resolve(None, Some("all"))?);
+
+        for invalid in ["", ".", "..", "a/b", "..\\up", "tab\there"] {
+            assert!(Project::resolve(Some(invalid), None).is_err(), "{invalid}");
+        }
+
+        let output = Path::new(".bieglers-timetracker");
+        assert_eq!(
+            Some(output.to_path_buf()),
+            Project::Default.directory(output)
+        );
+        assert_eq!(
+            Some(output.join("projects").join("client")),
+            named("client").directory(output)
+        );
+        assert_eq!(None, Project::All.directory(output));
+
+        Ok(())
+    }
+
+    #[test]
+    fn projects_are_the_directories_of_the_projects_folder() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert_eq!(vec![Project::Default], list_projects(dir.path())?);
+
+        let projects = dir.path().join("projects");
+        std::fs::create_dir_all(projects.join("zeta"))?;
+        std::fs::create_dir_all(projects.join("alpha"))?;
+        std::fs::write(projects.join("notes.txt"), "not a project")?;
+
+        assert_eq!(
+            vec![
+                Project::Default,
+                Project::Named("alpha".to_owned()),
+                Project::Named("zeta".to_owned()),
+            ],
+            list_projects(dir.path())?
+        );
+
+        Ok(())
+    }
+}
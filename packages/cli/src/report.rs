@@ -0,0 +1,437 @@
+use chrono::TimeDelta;
+use std::{borrow::Cow, cmp};
+use timetracker::{
+    DailySummary, DurationStyle, EstimateSummary, ReferenceSummary, TagSummary, WeekSummary,
+    format_duration,
+};
+
+use crate::helpers::{generate_grid, plain_boxes};
+
+enum ReportRow<'a> {
+    Heading(&'a str),
+    Entry {
+        label: Cow<'a, str>,
+        count: usize,
+        hours: f64,
+    },
+    Subtotal {
+        count: usize,
+        hours: f64,
+    },
+    Separator,
+}
+
+fn report_rows_days(days: &[DailySummary]) -> Vec<ReportRow<'_>> {
+    days.iter()
+        .map(|d| ReportRow::Entry {
+            label: Cow::Borrowed(&d.label),
+            count: d.count,
+            hours: d.total,
+        })
+        .collect()
+}
+
+fn report_rows_weeks(weeks: &[WeekSummary], with_days: bool) -> Vec<ReportRow<'_>> {
+    let mut rows: Vec<ReportRow> = Vec::with_capacity(weeks.len() * 8);
+
+    for (index, week) in weeks.iter().enumerate() {
+        if !with_days {
+            rows.push(ReportRow::Entry {
+                label: Cow::Borrowed(&week.label),
+                count: week.count(),
+                hours: week.total,
+            });
+            continue;
+        }
+
+        if index > 0 {
+            rows.push(ReportRow::Separator);
+        }
+
+        rows.push(ReportRow::Heading(&week.label));
+        rows.extend(week.days.iter().map(|d| ReportRow::Entry {
+            label: Cow::Borrowed(&d.label),
+            count: d.count,
+            hours: d.total,
+        }));
+        rows.push(ReportRow::Subtotal {
+            count: week.count(),
+            hours: week.total,
+        });
+    }
+
+    rows
+}
+
+fn report_rows_tags(tags: &[TagSummary]) -> Vec<ReportRow<'_>> {
+    tags.iter()
+        .map(|t| ReportRow::Entry {
+            label: Cow::Owned(format!("#{}", t.tag)),
+            count: t.count,
+            hours: t.total,
+        })
+        .collect()
+}
+
+fn report_rows_references(references: &[ReferenceSummary]) -> Vec<ReportRow<'_>> {
+    references
+        .iter()
+        .map(|r| ReportRow::Entry {
+            label: Cow::Borrowed(&r.reference),
+            count: r.count,
+            hours: r.total,
+        })
+        .collect()
+}
+
+/// Flat table with one row per day
+pub fn generate_report_table_days(days: &[DailySummary]) -> String {
+    generate_report_table("Day", &report_rows_days(days), None)
+}
+
+/// Table with one row per week, optionally followed by its days indented underneath
+pub fn generate_report_table_weeks(weeks: &[WeekSummary], with_days: bool) -> String {
+    generate_report_table(
+        if with_days { "Day" } else { "Week" },
+        &report_rows_weeks(weeks, with_days),
+        None,
+    )
+}
+
+/// Table with one row per tag. Tags overlap, so `total` is passed in rather than summed up from the rows.
+pub fn generate_report_table_tags(tags: &[TagSummary], total: (usize, f64)) -> String {
+    generate_report_table("Tag", &report_rows_tags(tags), Some(total))
+}
+
+/// Table with one row per reference, the total counts each time box once
+pub fn generate_report_table_references(
+    references: &[ReferenceSummary],
+    total: (usize, f64),
+) -> String {
+    generate_report_table("Ref", &report_rows_references(references), Some(total))
+}
+
+/// Counterpart of `generate_report_table_days` for `--plain`
+pub fn generate_report_plain_days(days: &[DailySummary]) -> String {
+    generate_report_plain(&report_rows_days(days), None)
+}
+
+/// Counterpart of `generate_report_table_weeks` for `--plain`
+pub fn generate_report_plain_weeks(weeks: &[WeekSummary], with_days: bool) -> String {
+    generate_report_plain(&report_rows_weeks(weeks, with_days), None)
+}
+
+/// Counterpart of `generate_report_table_tags` for `--plain`
+pub fn generate_report_plain_tags(tags: &[TagSummary], total: (usize, f64)) -> String {
+    generate_report_plain(&report_rows_tags(tags), Some(total))
+}
+
+/// Counterpart of `generate_report_table_references` for `--plain`
+pub fn generate_report_plain_references(
+    references: &[ReferenceSummary],
+    total: (usize, f64),
+) -> String {
+    generate_report_plain(&report_rows_references(references), Some(total))
+}
+
+fn report_total(rows: &[ReportRow], total: Option<(usize, f64)>) -> (usize, f64) {
+    total.unwrap_or_else(|| {
+        rows.iter().fold((0, 0.0f64), |acc, row| match row {
+            ReportRow::Entry { count, hours, .. } => (acc.0 + count, acc.1 + hours),
+            _ => acc,
+        })
+    })
+}
+
+/// For example `1 hour 30 minutes`
+fn plain_hours(hours: f64) -> String {
+    format_duration(
+        TimeDelta::seconds((hours * 60.0 * 60.0).round() as i64),
+        DurationStyle::Words,
+    )
+}
+
+/// For example `2 boxes, 1 hour 30 minutes`
+fn plain_count_and_hours(count: usize, hours: f64) -> String {
+    format!("{}, {}", plain_boxes(count), plain_hours(hours))
+}
+
+/// One line per row like `2024-06-03: 2 boxes, 5 hours.`, headings end with a colon instead
+fn generate_report_plain(rows: &[ReportRow], total: Option<(usize, f64)>) -> String {
+    let (total_count, total_hours) = report_total(rows, total);
+
+    let mut lines: Vec<String> = rows
+        .iter()
+        .filter_map(|row| match row {
+            ReportRow::Heading(label) => Some(format!("{label}:")),
+            ReportRow::Entry {
+                label,
+                count,
+                hours,
+            } => Some(format!(
+                "{label}: {}.",
+                plain_count_and_hours(*count, *hours)
+            )),
+            ReportRow::Subtotal { count, hours } => Some(format!(
+                "Subtotal: {}.",
+                plain_count_and_hours(*count, *hours)
+            )),
+            ReportRow::Separator => None,
+        })
+        .collect();
+    lines.push(format!(
+        "Total: {}.",
+        plain_count_and_hours(total_count, total_hours)
+    ));
+
+    lines.join("\n")
+}
+
+/// `total` of count and hours defaults to the sum of the entries.
+fn generate_report_table(
+    label_col_label: &str,
+    rows: &[ReportRow],
+    total: Option<(usize, f64)>,
+) -> String {
+    const INDENT: &str = "  ";
+    const SUBTOTAL: &str = "subtotal";
+    const TOTAL: &str = "total";
+    const COUNT_COL_LABEL: &str = "Boxes";
+    const HOURS_COL_LABEL: &str = "Hours";
+
+    let is_nested = rows.iter().any(|r| matches!(r, ReportRow::Heading(_)));
+
+    let (total_count, total_hours) = report_total(rows, total);
+
+    let entry_label = |label: &str| match is_nested {
+        true => format!("{INDENT}{label}"),
+        false => label.to_string(),
+    };
+    let hours_label = |hours: f64| format!("{hours:.2}h");
+
+    let label_col_max_len = rows
+        .iter()
+        .map(|row| match row {
+            ReportRow::Heading(label) => label.len(),
+            ReportRow::Entry { label, .. } => entry_label(label).len(),
+            ReportRow::Subtotal { .. } => SUBTOTAL.len(),
+            ReportRow::Separator => 0,
+        })
+        .chain([label_col_label.len(), TOTAL.len()])
+        .max()
+        .unwrap_or_default();
+    let count_col_max_len = cmp::max(COUNT_COL_LABEL.len(), total_count.to_string().len());
+    let hours_col_max_len = cmp::max(HOURS_COL_LABEL.len(), hours_label(total_hours).len());
+
+    let mut output = String::with_capacity(1024);
+    let separator = format!(
+        "├─{:─^label_col_max_len$}─┼─{0:─^count_col_max_len$}─┼─{0:─^hours_col_max_len$}─┤\n",
+        "─",
+    );
+
+    // Header
+    output.push_str(&format!(
+        "┌─{:─^label_col_max_len$}─┬─{0:─^count_col_max_len$}─┬─{0:─^hours_col_max_len$}─┐\n",
+        "─",
+    ));
+    output.push_str(&format!(
+        "│ {label_col_label:^label_col_max_len$} │ {COUNT_COL_LABEL:^count_col_max_len$} │ {HOURS_COL_LABEL:^hours_col_max_len$} │\n",
+    ));
+    output.push_str(&separator);
+
+    // Content
+    for row in rows.iter() {
+        match row {
+            ReportRow::Heading(label) => output.push_str(&format!(
+                "│ {label:<label_col_max_len$} │ {:count_col_max_len$} │ {:hours_col_max_len$} │\n",
+                "", ""
+            )),
+            ReportRow::Entry {
+                label,
+                count,
+                hours,
+            } => output.push_str(&format!(
+                "│ {:<label_col_max_len$} │ {count:>count_col_max_len$} │ {:>hours_col_max_len$} │\n",
+                entry_label(label),
+                hours_label(*hours),
+            )),
+            ReportRow::Subtotal { count, hours } => output.push_str(&format!(
+                "│ {SUBTOTAL:>label_col_max_len$} │ {count:>count_col_max_len$} │ {:>hours_col_max_len$} │\n",
+                hours_label(*hours),
+            )),
+            ReportRow::Separator => output.push_str(&separator),
+        }
+    }
+
+    // Footer
+    output.push_str(&separator);
+    output.push_str(&format!(
+        "│ {TOTAL:>label_col_max_len$} │ {total_count:>count_col_max_len$} │ {:>hours_col_max_len$} │\n",
+        hours_label(total_hours),
+    ));
+    output.push_str(&format!(
+        "└─{:─^label_col_max_len$}─┴─{0:─^count_col_max_len$}─┴─{0:─^hours_col_max_len$}─┘\n",
+        "─",
+    ));
+
+    output
+}
+
+/// One row per period with estimated and actual hours, followed by `total`
+pub fn generate_report_table_estimates(
+    label_col_label: &str,
+    summaries: &[EstimateSummary],
+    total: &EstimateSummary,
+) -> String {
+    let cells = |s: &EstimateSummary| {
+        [
+            s.label.clone(),
+            s.count.to_string(),
+            format!("{:.2}h", s.estimate),
+            format!("{:.2}h", s.actual),
+            s.deviation.map(|d| format!("{d:+.0}%")).unwrap_or_default(),
+        ]
+    };
+    let rows: Vec<[String; 5]> = summaries.iter().map(cells).collect();
+    let total = cells(total);
+
+    let labels = [label_col_label, "Boxes", "Estimate", "Actual", "Deviation"].map(String::from);
+
+    generate_grid(&labels, &rows, &total, &[0])
+}
+
+/// Counterpart of `generate_report_table_estimates` for `--plain`, for example
+/// `2024-06-03: 1 box, estimated 2 hours, actually 3 hours, 50 percent over.`
+pub fn generate_report_plain_estimates(
+    summaries: &[EstimateSummary],
+    total: &EstimateSummary,
+) -> String {
+    let line = |label: &str, s: &EstimateSummary| {
+        let deviation = match s.deviation.map(|d| d.round() as i64) {
+            Some(0) => ", as estimated".to_string(),
+            Some(d) if d > 0 => format!(", {d} percent over"),
+            Some(d) => format!(", {} percent under", -d),
+            None => String::new(),
+        };
+
+        format!(
+            "{label}: {}, estimated {}, actually {}{deviation}.",
+            plain_boxes(s.count),
+            plain_hours(s.estimate),
+            plain_hours(s.actual)
+        )
+    };
+
+    summaries
+        .iter()
+        .map(|s| line(&s.label, s))
+        .chain([line("Total", total)])
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use timetracker::{roll_up_weeks, summarize_days};
+
+    use super::*;
+    use crate::test_fixtures::time_box;
+
+    #[test]
+    fn report_table_nests_days_under_weeks() {
+        let boxes = [
+            time_box("2024-06-01T09:00:00Z", 1),
+            time_box("2024-06-03T09:00:00Z", 2),
+            time_box("2024-06-04T09:00:00Z", 3),
+        ];
+
+        let table =
+            generate_report_table_weeks(&roll_up_weeks(summarize_days(&boxes, &Utc, None)), true);
+
+        let expected = "\
+┌──────────────┬───────┬───────┐
+│     Day      │ Boxes │ Hours │
+├──────────────┼───────┼───────┤
+│ 2024-W22     │       │       │
+│   2024-06-01 │     1 │ 1.00h │
+│     subtotal │     1 │ 1.00h │
+├──────────────┼───────┼───────┤
+│ 2024-W23     │       │       │
+│   2024-06-03 │     1 │ 2.00h │
+│   2024-06-04 │     1 │ 3.00h │
+│     subtotal │     2 │ 5.00h │
+├──────────────┼───────┼───────┤
+│        total │     3 │ 6.00h │
+└──────────────┴───────┴───────┘
+";
+        assert_eq!(expected, table);
+    }
+
+    #[test]
+    fn report_table_of_tags_totals_each_time_box_once() {
+        let mut boxes = [
+            time_box("2024-06-03T09:00:00Z", 2),
+            time_box("2024-06-04T09:00:00Z", 1),
+        ];
+        boxes[0].notes[0].description = "#a #b".to_string();
+        boxes[1].notes[0].description = "#b".to_string();
+
+        let table =
+            generate_report_table_tags(&timetracker::summarize_tags(&boxes, None), (2, 3.0));
+
+        let expected = "\
+┌───────┬───────┬───────┐
+│  Tag  │ Boxes │ Hours │
+├───────┼───────┼───────┤
+│ #b    │     2 │ 3.00h │
+│ #a    │     1 │ 2.00h │
+├───────┼───────┼───────┤
+│ total │     2 │ 3.00h │
+└───────┴───────┴───────┘
+";
+        assert_eq!(expected, table);
+    }
+
+    #[test]
+    fn report_plain_nests_days_under_weeks() {
+        let boxes = [
+            time_box("2024-06-01T09:00:00Z", 1),
+            time_box("2024-06-03T09:00:00Z", 2),
+            time_box("2024-06-04T09:00:00Z", 3),
+        ];
+
+        let plain =
+            generate_report_plain_weeks(&roll_up_weeks(summarize_days(&boxes, &Utc, None)), true);
+
+        let expected = "\
+2024-W22:
+2024-06-01: 1 box, 1 hour.
+Subtotal: 1 box, 1 hour.
+2024-W23:
+2024-06-03: 1 box, 2 hours.
+2024-06-04: 1 box, 3 hours.
+Subtotal: 2 boxes, 5 hours.
+Total: 3 boxes, 6 hours.";
+        assert_eq!(expected, plain);
+    }
+
+    #[test]
+    fn report_plain_of_tags_totals_each_time_box_once() {
+        let mut boxes = [
+            time_box("2024-06-03T09:00:00Z", 2),
+            time_box("2024-06-04T09:00:00Z", 1),
+        ];
+        boxes[0].notes[0].description = "#a #b".to_string();
+        boxes[1].notes[0].description = "#b".to_string();
+
+        let plain =
+            generate_report_plain_tags(&timetracker::summarize_tags(&boxes, None), (2, 3.0));
+
+        let expected = "\
+#b: 2 boxes, 3 hours.
+#a: 1 box, 2 hours.
+Total: 2 boxes, 3 hours.";
+        assert_eq!(expected, plain);
+    }
+}
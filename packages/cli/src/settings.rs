@@ -0,0 +1,382 @@
+use std::{fmt::Display, io::IsTerminal};
+
+use chrono::{TimeDelta, Weekday};
+use clap::ValueEnum;
+use timetracker::{DurationStyle, StoreSettings, format_duration};
+
+use crate::{
+    args::{ColorChoice, DurationFormat},
+    config::Config,
+    helpers::{OutputSettings, terminal_width},
+};
+
+/// Where the value of a setting comes from, in increasing precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingSource {
+    Default,
+    Store,
+    ConfigFile,
+    Flag,
+}
+
+impl Display for SettingSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SettingSource::Default => "default",
+            SettingSource::Store => "store",
+            SettingSource::ConfigFile => "config file",
+            SettingSource::Flag => "flag",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: SettingSource,
+}
+
+impl<T> Setting<T> {
+    /// The first candidate that is set wins.
+    fn first_of(
+        candidates: impl IntoIterator<Item = (Option<T>, SettingSource)>,
+        default: T,
+    ) -> Self {
+        candidates
+            .into_iter()
+            .find_map(|(value, source)| value.map(|value| Setting { value, source }))
+            .unwrap_or(Setting {
+                value: default,
+                source: SettingSource::Default,
+            })
+    }
+}
+
+/// Settings passed on the command line, `None` if not passed.
+#[derive(Debug, Default, Clone)]
+pub struct SettingFlags {
+    pub weekly_goal: Option<TimeDelta>,
+    pub work_days: Option<Vec<Weekday>>,
+    pub work_hours: Option<TimeDelta>,
+    pub color: Option<ColorChoice>,
+    pub duration_format: Option<DurationFormat>,
+    pub width: Option<usize>,
+    pub plain: Option<bool>,
+    pub pager: Option<bool>,
+    pub suggestions: Option<bool>,
+}
+
+/// The settings commands run with, always obtained via `resolve_settings`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectiveSettings {
+    /// `None` if there is no goal at all.
+    pub weekly_goal: Setting<Option<TimeDelta>>,
+    pub work_days: Setting<Vec<Weekday>>,
+    pub work_hours: Setting<TimeDelta>,
+    pub color: Setting<ColorChoice>,
+    pub duration_format: Setting<DurationFormat>,
+    /// `None` fits tables into the terminal, see `OutputSettings::width`.
+    pub width: Setting<Option<usize>>,
+    pub plain: Setting<bool>,
+    /// Whether output taller than the terminal may get paged, see `print_paged`.
+    pub pager: Setting<bool>,
+    /// Whether to suggest `lock --before`, see `suggest_lock_once`.
+    pub suggestions: Setting<bool>,
+}
+
+const DEFAULT_WORK_DAYS: [Weekday; 5] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+];
+
+const DEFAULT_WORK_HOURS: TimeDelta = TimeDelta::hours(8);
+
+/// Flags win over everything. Settings describing the tracked time come from the `store` next,
+/// settings of the presentation from the `config` file instead. Defaults apply last.
+pub fn resolve_settings(
+    store: &StoreSettings,
+    config: &Config,
+    flags: &SettingFlags,
+) -> EffectiveSettings {
+    use SettingSource::{ConfigFile, Flag, Store};
+
+    EffectiveSettings {
+        weekly_goal: Setting::first_of(
+            [
+                (flags.weekly_goal.map(Some), Flag),
+                (store.weekly_goal.map(Some), Store),
+            ],
+            None,
+        ),
+        work_days: Setting::first_of(
+            [
+                (flags.work_days.clone(), Flag),
+                (store.work_days.clone(), Store),
+            ],
+            DEFAULT_WORK_DAYS.to_vec(),
+        ),
+        work_hours: Setting::first_of(
+            [(flags.work_hours, Flag), (store.work_hours, Store)],
+            DEFAULT_WORK_HOURS,
+        ),
+        color: Setting::first_of(
+            [(flags.color, Flag), (config.color, ConfigFile)],
+            ColorChoice::Auto,
+        ),
+        duration_format: Setting::first_of(
+            [
+                (flags.duration_format, Flag),
+                (config.duration_format, ConfigFile),
+            ],
+            DurationFormat::Decimal,
+        ),
+        width: Setting::first_of(
+            [
+                (flags.width.map(Some), Flag),
+                (config.width.map(Some), ConfigFile),
+            ],
+            None,
+        ),
+        plain: Setting::first_of([(flags.plain, Flag), (config.plain, ConfigFile)], false),
+        pager: Setting::first_of([(flags.pager, Flag), (config.pager, ConfigFile)], true),
+        suggestions: Setting::first_of(
+            [(flags.suggestions, Flag), (config.suggestions, ConfigFile)],
+            true,
+        ),
+    }
+}
+
+impl EffectiveSettings {
+    pub fn output_settings(&self) -> OutputSettings {
+        OutputSettings {
+            color: self.color.value.enabled(),
+            duration_style: self.duration_format.value.into(),
+            width: self.width.value.or_else(terminal_width),
+            plain: self.plain.value,
+            pager: self.pager.value && std::io::stdout().is_terminal(),
+        }
+    }
+
+    /// Name, value and source of every setting, for `info`.
+    pub fn describe(&self) -> Vec<(&'static str, String, SettingSource)> {
+        let hours = |d: TimeDelta| format_duration(d, DurationStyle::DecimalHours);
+        let value_name = |v: Option<clap::builder::PossibleValue>| {
+            v.map(|v| v.get_name().to_string()).unwrap_or_default()
+        };
+
+        vec![
+            (
+                "weekly-goal",
+                self.weekly_goal.value.map_or("none".to_string(), hours),
+                self.weekly_goal.source,
+            ),
+            (
+                "work-days",
+                self.work_days
+                    .value
+                    .iter()
+                    .map(|day| day.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                self.work_days.source,
+            ),
+            (
+                "work-hours",
+                hours(self.work_hours.value),
+                self.work_hours.source,
+            ),
+            (
+                "color",
+                value_name(self.color.value.to_possible_value()),
+                self.color.source,
+            ),
+            (
+                "duration-format",
+                value_name(self.duration_format.value.to_possible_value()),
+                self.duration_format.source,
+            ),
+            (
+                "width",
+                self.width
+                    .value
+                    .map_or("terminal".to_string(), |w| w.to_string()),
+                self.width.source,
+            ),
+            ("plain", self.plain.value.to_string(), self.plain.source),
+            ("pager", self.pager.value.to_string(), self.pager.source),
+            (
+                "suggestions",
+                self.suggestions.value.to_string(),
+                self.suggestions.source,
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> StoreSettings {
+        StoreSettings {
+            weekly_goal: Some(TimeDelta::hours(40)),
+            work_days: Some(vec![Weekday::Mon, Weekday::Tue]),
+            work_hours: Some(TimeDelta::hours(6)),
+            ..Default::default()
+        }
+    }
+
+    fn config() -> Config {
+        Config {
+            color: Some(ColorChoice::Never),
+            duration_format: Some(DurationFormat::Clock),
+            width: Some(100),
+            plain: Some(true),
+            pager: Some(false),
+            suggestions: Some(false),
+        }
+    }
+
+    fn flags() -> SettingFlags {
+        SettingFlags {
+            weekly_goal: Some(TimeDelta::hours(20)),
+            work_days: Some(vec![Weekday::Sat]),
+            work_hours: Some(TimeDelta::hours(4)),
+            color: Some(ColorChoice::Always),
+            duration_format: Some(DurationFormat::HoursMinutes),
+            width: Some(60),
+            plain: Some(false),
+            pager: Some(true),
+            suggestions: Some(true),
+        }
+    }
+
+    fn sources(settings: &EffectiveSettings) -> Vec<SettingSource> {
+        settings
+            .describe()
+            .into_iter()
+            .map(|(_, _, source)| source)
+            .collect()
+    }
+
+    #[test]
+    fn nothing_set_resolves_to_defaults() {
+        let settings = resolve_settings(
+            &StoreSettings::default(),
+            &Config::default(),
+            &SettingFlags::default(),
+        );
+
+        assert_eq!(vec![SettingSource::Default; 9], sources(&settings));
+        assert_eq!(None, settings.weekly_goal.value);
+        assert_eq!(DEFAULT_WORK_DAYS.to_vec(), settings.work_days.value);
+        assert_eq!(TimeDelta::hours(8), settings.work_hours.value);
+        assert_eq!(ColorChoice::Auto, settings.color.value);
+        assert_eq!(DurationFormat::Decimal, settings.duration_format.value);
+        assert_eq!(None, settings.width.value);
+        assert!(!settings.plain.value);
+        assert!(settings.pager.value);
+        assert!(settings.suggestions.value);
+    }
+
+    #[test]
+    fn store_and_config_file_each_provide_their_own_settings() {
+        let settings = resolve_settings(&store(), &config(), &SettingFlags::default());
+
+        use SettingSource::{ConfigFile, Store};
+        assert_eq!(
+            vec![
+                Store, Store, Store, ConfigFile, ConfigFile, ConfigFile, ConfigFile, ConfigFile,
+                ConfigFile
+            ],
+            sources(&settings)
+        );
+        assert_eq!(Some(TimeDelta::hours(40)), settings.weekly_goal.value);
+        assert_eq!(vec![Weekday::Mon, Weekday::Tue], settings.work_days.value);
+        assert_eq!(TimeDelta::hours(6), settings.work_hours.value);
+        assert_eq!(ColorChoice::Never, settings.color.value);
+        assert_eq!(DurationFormat::Clock, settings.duration_format.value);
+        assert_eq!(Some(100), settings.width.value);
+        assert!(settings.plain.value);
+        assert!(!settings.pager.value);
+        assert!(!settings.suggestions.value);
+    }
+
+    #[test]
+    fn flags_override_store_and_config_file() {
+        let settings = resolve_settings(&store(), &config(), &flags());
+
+        assert_eq!(vec![SettingSource::Flag; 9], sources(&settings));
+        assert_eq!(Some(TimeDelta::hours(20)), settings.weekly_goal.value);
+        assert_eq!(vec![Weekday::Sat], settings.work_days.value);
+        assert_eq!(TimeDelta::hours(4), settings.work_hours.value);
+        assert_eq!(ColorChoice::Always, settings.color.value);
+        assert_eq!(DurationFormat::HoursMinutes, settings.duration_format.value);
+        assert_eq!(Some(60), settings.width.value);
+        assert!(!settings.plain.value);
+    }
+
+    #[test]
+    fn flags_override_defaults() {
+        let settings = resolve_settings(&StoreSettings::default(), &Config::default(), &flags());
+
+        assert_eq!(vec![SettingSource::Flag; 9], sources(&settings));
+        assert_eq!(Some(60), settings.width.value);
+    }
+
+    #[test]
+    fn each_setting_falls_back_on_its_own() {
+        let store = StoreSettings {
+            work_hours: None,
+            ..store()
+        };
+        let config = Config {
+            width: None,
+            ..config()
+        };
+        let flags = SettingFlags {
+            weekly_goal: Some(TimeDelta::hours(30)),
+            color: Some(ColorChoice::Always),
+            ..SettingFlags::default()
+        };
+        let settings = resolve_settings(&store, &config, &flags);
+
+        use SettingSource::{ConfigFile, Default, Flag, Store};
+        assert_eq!(
+            vec![
+                Flag, Store, Default, Flag, ConfigFile, Default, ConfigFile, ConfigFile, ConfigFile
+            ],
+            sources(&settings)
+        );
+        assert_eq!(Some(TimeDelta::hours(30)), settings.weekly_goal.value);
+        assert_eq!(TimeDelta::hours(8), settings.work_hours.value);
+    }
+
+    #[test]
+    fn describe_prints_values_like_their_flags() {
+        let settings = resolve_settings(&store(), &config(), &SettingFlags::default());
+        let values: Vec<String> = settings
+            .describe()
+            .into_iter()
+            .map(|(name, value, source)| format!("{name}={value} ({source})"))
+            .collect();
+
+        assert_eq!(
+            vec![
+                "weekly-goal=40.00h (store)",
+                "work-days=Mon,Tue (store)",
+                "work-hours=6.00h (store)",
+                "color=never (config file)",
+                "duration-format=clock (config file)",
+                "width=100 (config file)",
+                "plain=true (config file)",
+                "pager=false (config file)",
+                "suggestions=false (config file)",
+            ],
+            values
+        );
+    }
+}
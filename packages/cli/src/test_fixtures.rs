@@ -0,0 +1,34 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use timetracker::{NoteKind, TimeBox, TimeBoxNote};
+
+pub fn time(s: &str) -> DateTime<Utc> {
+    s.parse().unwrap()
+}
+
+pub fn note(time: DateTime<Utc>, description: &str) -> TimeBoxNote {
+    TimeBoxNote {
+        time,
+        description: description.to_string(),
+        kind: NoteKind::Regular,
+    }
+}
+
+/// Time box of `notes` that was never paused, estimated, referenced or exported.
+pub fn time_box_of(notes: Vec<TimeBoxNote>) -> TimeBox {
+    TimeBox {
+        notes,
+        exported_at: None,
+        pauses: Vec::new(),
+        estimate: None,
+        reference: None,
+    }
+}
+
+/// Finished time box of `hours` with the notes `start` and `stop`.
+pub fn time_box(start: &str, hours: i64) -> TimeBox {
+    let start = time(start);
+    time_box_of(vec![
+        note(start, "start"),
+        note(start + TimeDelta::hours(hours), "stop"),
+    ])
+}
@@ -0,0 +1,78 @@
+//! Compares loading a large store from JSON against loading it from the binary cache.
+//!
+//! Run via: `cargo run --release -p timetracker --features cache --example bench_cache`
+
+use std::{fs::File, time::Instant};
+
+use chrono::{TimeDelta, Utc};
+use timetracker::{
+    NoteKind, StoreSettings, TimeBox, TimeBoxNote, TimeTrackingStore,
+    in_memory_tracker::{InMemoryTimeTracker, JsonFileLoadingStrategy, JsonStorageStrategy},
+    json_cache::{CachedJsonFileLoadingStrategy, write_cache},
+};
+
+const TIME_BOXES: usize = 10_000;
+const NOTES_PER_TIME_BOX: usize = 4;
+const RUNS: u32 = 10;
+
+fn main() -> Result<(), timetracker::Error> {
+    let dir = std::env::temp_dir().join(format!("timetracker-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).map_err(timetracker::Error::Io)?;
+    let json_path = dir.join("storage.json");
+    let cache_path = dir.join("storage.cache");
+
+    let start = Utc::now() - TimeDelta::days(TIME_BOXES as i64);
+    let tracker = InMemoryTimeTracker {
+        active: None,
+        finished: (0..TIME_BOXES)
+            .map(|i| TimeBox {
+                notes: (0..NOTES_PER_TIME_BOX)
+                    .map(|n| TimeBoxNote {
+                        time: start + TimeDelta::days(i as i64) + TimeDelta::minutes(n as i64 * 25),
+                        description: format!("Note {n} of time box {i} with some words in it"),
+                        kind: NoteKind::Regular,
+                    })
+                    .collect(),
+                exported_at: None,
+                pauses: Vec::new(),
+                estimate: None,
+                reference: None,
+            })
+            .collect(),
+        settings: StoreSettings::default(),
+        repairs: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    let mut file = File::create(&json_path).map_err(timetracker::Error::Io)?;
+    tracker.to_writer(
+        &JsonStorageStrategy {
+            pretty: true,
+            repair: true,
+        },
+        &mut file,
+    )?;
+    drop(file);
+    write_cache(&cache_path, &json_path, &tracker)?;
+
+    let started = Instant::now();
+    for _ in 0..RUNS {
+        InMemoryTimeTracker::init(&JsonFileLoadingStrategy::new(&json_path))?;
+    }
+    let json = started.elapsed() / RUNS;
+
+    let started = Instant::now();
+    for _ in 0..RUNS {
+        InMemoryTimeTracker::init(&CachedJsonFileLoadingStrategy {
+            path: &json_path,
+            cache_path: &cache_path,
+        })?;
+    }
+    let cache = started.elapsed() / RUNS;
+
+    println!("{TIME_BOXES} time boxes, average of {RUNS} runs");
+    println!("json:  {json:?}");
+    println!("cache: {cache:?}");
+
+    std::fs::remove_dir_all(&dir).map_err(timetracker::Error::Io)
+}
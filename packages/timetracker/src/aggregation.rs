@@ -0,0 +1,669 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{Datelike, NaiveDate, TimeDelta, TimeZone};
+use serde::Serialize;
+
+use crate::{RoundingMode, TimeBox, estimate_deviation};
+
+/// Hours tracked on a single day.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailySummary {
+    /// Formatted as `YYYY-MM-DD`
+    pub label: String,
+    pub date: NaiveDate,
+    /// Sum of hours of all time boxes that started on this day
+    pub total: f64,
+    /// Count of time boxes that started on this day
+    pub count: usize,
+}
+
+/// Hours tracked in a single ISO week, broken down by day.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeekSummary {
+    /// Formatted as ISO week, for example `2024-W23`
+    pub label: String,
+    /// Only days with tracked time, in ascending order
+    pub days: Vec<DailySummary>,
+    /// Sum of hours of all days
+    pub total: f64,
+}
+
+impl WeekSummary {
+    /// Count of time boxes that started in this week
+    pub fn count(&self) -> usize {
+        self.days.iter().map(|d| d.count).sum()
+    }
+}
+
+/// Hours tracked per reference, see `TimeBox::reference`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReferenceSummary {
+    /// As set on the time boxes, e.g. `PROJ-123`
+    pub reference: String,
+    /// Sum of hours of all time boxes with this reference
+    pub total: f64,
+    /// Count of time boxes with this reference
+    pub count: usize,
+}
+
+/// Hours tracked per hashtag, see `TimeBox::tags`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagSummary {
+    /// Lowercase and without the leading `#`
+    pub tag: String,
+    /// Sum of hours of all time boxes with this tag
+    pub total: f64,
+    /// Count of time boxes with this tag
+    pub count: usize,
+}
+
+/// Duration of `time_box` for totals, negative durations count as zero.
+/// Those only come from inconsistent data like clock skew or overlapping pauses,
+/// `TimeBox::timedelta_total` returns the signed duration.
+pub fn clamped_duration(time_box: &TimeBox) -> TimeDelta {
+    time_box
+        .timedelta_total()
+        .unwrap_or_default()
+        .max(TimeDelta::zero())
+}
+
+/// See `clamped_duration`.
+pub fn clamped_hours(time_box: &TimeBox) -> f64 {
+    clamped_duration(time_box).num_seconds() as f64 / 60.0 / 60.0
+}
+
+/// `clamped_duration` after rounding to a multiple of the granularity, if any, see `TimeBox::duration_rounded`.
+/// Rounding each time box before summing up matches common invoicing rules.
+pub fn rounded_duration(
+    time_box: &TimeBox,
+    rounding: Option<(TimeDelta, RoundingMode)>,
+) -> TimeDelta {
+    match rounding {
+        Some((granularity, mode)) => time_box
+            .duration_rounded(granularity, mode)
+            .unwrap_or_default()
+            .max(TimeDelta::zero()),
+        None => clamped_duration(time_box),
+    }
+}
+
+/// See `rounded_duration`.
+pub fn rounded_hours(time_box: &TimeBox, rounding: Option<(TimeDelta, RoundingMode)>) -> f64 {
+    rounded_duration(time_box, rounding).num_seconds() as f64 / 60.0 / 60.0
+}
+
+/// Time boxes with several tags count towards each of them, so the totals may add up to more than was tracked.
+/// Time boxes without tags are omitted. Returned by hours descending, ties by tag.
+/// Each time box gets rounded first if `rounding` is given, see `rounded_duration`.
+pub fn summarize_tags(
+    time_boxes: &[TimeBox],
+    rounding: Option<(TimeDelta, RoundingMode)>,
+) -> Vec<TagSummary> {
+    let mut tags: BTreeMap<String, (f64, usize)> = BTreeMap::new();
+
+    for tb in time_boxes {
+        let hours = rounded_hours(tb, rounding);
+        for tag in tb.tags() {
+            let entry = tags.entry(tag).or_default();
+            entry.0 += hours;
+            entry.1 += 1;
+        }
+    }
+
+    let mut summaries: Vec<TagSummary> = tags
+        .into_iter()
+        .map(|(tag, (total, count))| TagSummary { tag, total, count })
+        .collect();
+    // Stable, so ties keep the alphabetical order of the map
+    summaries.sort_by(|a, b| b.total.total_cmp(&a.total));
+
+    summaries
+}
+
+/// Time boxes without a reference are omitted. Returned by hours descending, ties by reference.
+/// Each time box gets rounded first if `rounding` is given, see `rounded_duration`.
+pub fn summarize_references(
+    time_boxes: &[TimeBox],
+    rounding: Option<(TimeDelta, RoundingMode)>,
+) -> Vec<ReferenceSummary> {
+    let mut references: BTreeMap<&str, (f64, usize)> = BTreeMap::new();
+
+    for tb in time_boxes {
+        if let Some(reference) = tb.reference.as_deref() {
+            let entry = references.entry(reference).or_default();
+            entry.0 += rounded_hours(tb, rounding);
+            entry.1 += 1;
+        }
+    }
+
+    let mut summaries: Vec<ReferenceSummary> = references
+        .into_iter()
+        .map(|(reference, (total, count))| ReferenceSummary {
+            reference: reference.to_string(),
+            total,
+            count,
+        })
+        .collect();
+    // Stable, so ties keep the alphabetical order of the map
+    summaries.sort_by(|a, b| b.total.total_cmp(&a.total));
+
+    summaries
+}
+
+/// Groups time boxes by the day they started on in the given timezone.
+/// Days without tracked time are omitted. Returned in ascending order.
+/// Each time box gets rounded first if `rounding` is given, see `rounded_duration`.
+pub fn summarize_days<Tz: TimeZone>(
+    time_boxes: &[TimeBox],
+    tz: &Tz,
+    rounding: Option<(TimeDelta, RoundingMode)>,
+) -> Vec<DailySummary> {
+    let mut days: BTreeMap<NaiveDate, (f64, usize)> = BTreeMap::new();
+
+    for tb in time_boxes.iter() {
+        let date = tb
+            .time_start()
+            .unwrap_or_default()
+            .with_timezone(tz)
+            .date_naive();
+
+        let entry = days.entry(date).or_default();
+        entry.0 += rounded_hours(tb, rounding);
+        entry.1 += 1;
+    }
+
+    days.into_iter()
+        .map(|(date, (total, count))| DailySummary {
+            label: date.format("%Y-%m-%d").to_string(),
+            date,
+            total,
+            count,
+        })
+        .collect()
+}
+
+/// Rolls up daily summaries into the ISO weeks they belong to.
+/// Expects `days` in ascending order like `summarize_days` returns them.
+pub fn roll_up_weeks(days: Vec<DailySummary>) -> Vec<WeekSummary> {
+    let mut weeks: Vec<WeekSummary> = Vec::new();
+
+    for day in days.into_iter() {
+        let week = day.date.iso_week();
+        let label = format!("{}-W{:02}", week.year(), week.week());
+
+        match weeks.last_mut() {
+            Some(w) if w.label == label => {
+                w.total += day.total;
+                w.days.push(day);
+            }
+            _ => weeks.push(WeekSummary {
+                label,
+                total: day.total,
+                days: vec![day],
+            }),
+        }
+    }
+
+    weeks
+}
+
+/// Period to group time boxes by, see `group_time_boxes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grouping {
+    Day,
+    /// ISO week, starting on Monday
+    Week,
+    Month,
+}
+
+/// Period a group of time boxes started in. Displayed like `2024-06-12`, `2024-W23` or `2024-06`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GroupKey {
+    Day(NaiveDate),
+    /// ISO week of the ISO year, which differs from the calendar year around new year
+    Week {
+        year: i32,
+        week: u32,
+    },
+    Month {
+        year: i32,
+        month: u32,
+    },
+}
+
+impl GroupKey {
+    pub fn new(date: NaiveDate, grouping: Grouping) -> Self {
+        match grouping {
+            Grouping::Day => GroupKey::Day(date),
+            Grouping::Week => {
+                let week = date.iso_week();
+                GroupKey::Week {
+                    year: week.year(),
+                    week: week.week(),
+                }
+            }
+            Grouping::Month => GroupKey::Month {
+                year: date.year(),
+                month: date.month(),
+            },
+        }
+    }
+}
+
+impl Display for GroupKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupKey::Day(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            GroupKey::Week { year, week } => write!(f, "{year}-W{week:02}"),
+            GroupKey::Month { year, month } => write!(f, "{year}-{month:02}"),
+        }
+    }
+}
+
+/// Groups consecutive time boxes by the period they started in, in the given timezone.
+/// Keeps the order of `time_boxes`, so sorted input yields exactly one group per period.
+/// Periods without time boxes are omitted.
+pub fn group_time_boxes<Tz: TimeZone>(
+    time_boxes: &[TimeBox],
+    grouping: Grouping,
+    tz: &Tz,
+) -> Vec<(GroupKey, Vec<TimeBox>)> {
+    let mut groups: Vec<(GroupKey, Vec<TimeBox>)> = Vec::new();
+
+    for tb in time_boxes.iter() {
+        let date = tb
+            .time_start()
+            .unwrap_or_default()
+            .with_timezone(tz)
+            .date_naive();
+        let key = GroupKey::new(date, grouping);
+
+        match groups.last_mut() {
+            Some((k, group)) if *k == key => group.push(tb.clone()),
+            _ => groups.push((key, vec![tb.clone()])),
+        }
+    }
+
+    groups
+}
+
+/// Estimated and actual hours of the time boxes that have an estimate.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EstimateSummary {
+    pub label: String,
+    /// Count of time boxes with an estimate, the others are left out
+    pub count: usize,
+    pub estimate: f64,
+    pub actual: f64,
+    /// See `estimate_deviation`
+    pub deviation: Option<f64>,
+}
+
+impl EstimateSummary {
+    /// Sums up the time boxes in `time_boxes` that have an estimate.
+    pub fn of(label: String, time_boxes: &[TimeBox]) -> Self {
+        let (count, estimate, actual) = time_boxes
+            .iter()
+            .filter_map(|tb| tb.estimate.map(|estimate| (estimate, clamped_duration(tb))))
+            .fold(
+                (0, TimeDelta::zero(), TimeDelta::zero()),
+                |acc, (estimate, actual)| (acc.0 + 1, acc.1 + estimate, acc.2 + actual),
+            );
+
+        let hours = |delta: TimeDelta| delta.num_seconds() as f64 / 60.0 / 60.0;
+
+        EstimateSummary {
+            label,
+            count,
+            estimate: hours(estimate),
+            actual: hours(actual),
+            deviation: estimate_deviation(estimate, actual),
+        }
+    }
+}
+
+/// Estimates per period, see `group_time_boxes`. Periods without estimated time boxes are omitted.
+pub fn summarize_estimates<Tz: TimeZone>(
+    time_boxes: &[TimeBox],
+    grouping: Grouping,
+    tz: &Tz,
+) -> Vec<EstimateSummary> {
+    let estimated: Vec<TimeBox> = time_boxes
+        .iter()
+        .filter(|tb| tb.estimate.is_some())
+        .cloned()
+        .collect();
+
+    group_time_boxes(&estimated, grouping, tz)
+        .into_iter()
+        .map(|(key, group)| EstimateSummary::of(key.to_string(), &group))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use super::*;
+    use crate::test_fixtures::time_box;
+
+    /// June 2024 starts on a Saturday and ends on a Sunday, so it touches five ISO weeks
+    fn june_2024() -> Vec<TimeBox> {
+        vec![
+            time_box("2024-06-01T09:00:00Z", TimeDelta::hours(1), "start"),
+            time_box("2024-06-03T09:00:00Z", TimeDelta::hours(2), "start"),
+            time_box("2024-06-03T14:00:00Z", TimeDelta::hours(3), "start"),
+            time_box("2024-06-12T09:00:00Z", TimeDelta::hours(4), "start"),
+            time_box("2024-06-16T09:00:00Z", TimeDelta::hours(1), "start"),
+            time_box("2024-06-19T09:00:00Z", TimeDelta::hours(2), "start"),
+            time_box("2024-06-30T09:00:00Z", TimeDelta::hours(5), "start"),
+        ]
+    }
+
+    #[test]
+    fn inverted_time_boxes_count_as_zero() {
+        let mut inverted = time_box("2024-06-03T09:00:00Z", TimeDelta::hours(2), "start");
+        inverted.notes.reverse();
+        assert_eq!(-2.0, inverted.duration_in_hours().unwrap());
+        assert_eq!(TimeDelta::zero(), clamped_duration(&inverted));
+
+        let days = summarize_days(
+            &[
+                time_box("2024-06-03T12:00:00Z", TimeDelta::hours(1), "start"),
+                inverted,
+            ],
+            &Utc,
+            None,
+        );
+        assert_eq!(1.0, days.iter().map(|d| d.total).sum::<f64>());
+        assert_eq!(2, days.iter().map(|d| d.count).sum::<usize>());
+    }
+
+    #[test]
+    fn summarize_days_groups_by_start_date() {
+        let days = summarize_days(&june_2024(), &Utc, None);
+
+        let labels: Vec<_> = days.iter().map(|d| d.label.as_str()).collect();
+        assert_eq!(
+            vec![
+                "2024-06-01",
+                "2024-06-03",
+                "2024-06-12",
+                "2024-06-16",
+                "2024-06-19",
+                "2024-06-30"
+            ],
+            labels
+        );
+
+        assert_eq!(5.0, days[1].total);
+        assert_eq!(2, days[1].count);
+    }
+
+    #[test]
+    fn summarize_days_uses_the_local_start_date() {
+        // Same UTC date, but the last one starts after midnight in UTC+02:00
+        let time_boxes = [
+            time_box("2024-06-03T20:00:00Z", TimeDelta::hours(1), "start"),
+            time_box("2024-06-03T21:30:00Z", TimeDelta::hours(2), "start"),
+            time_box("2024-06-03T23:00:00Z", TimeDelta::hours(3), "start"),
+        ];
+        let tz = chrono::FixedOffset::east_opt(2 * 60 * 60).unwrap();
+
+        let days = summarize_days(&time_boxes, &tz, None);
+        let rows: Vec<_> = days
+            .iter()
+            .map(|d| (d.label.as_str(), d.total, d.count))
+            .collect();
+        assert_eq!(vec![("2024-06-03", 3.0, 2), ("2024-06-04", 3.0, 1)], rows);
+
+        assert_eq!(1, summarize_days(&time_boxes, &Utc, None).len());
+    }
+
+    #[test]
+    fn tags_share_the_hours_of_their_time_boxes() {
+        let tagged = |start: &str, hours: i64, description: &str| {
+            let mut tb = time_box(start, TimeDelta::hours(hours), "start");
+            tb.notes[1].description = description.to_string();
+            tb
+        };
+        let time_boxes = [
+            tagged("2024-06-03T09:00:00Z", 2, "#a #b"),
+            tagged("2024-06-03T12:00:00Z", 1, "only #b"),
+            tagged("2024-06-04T09:00:00Z", 3, "#c"),
+            time_box("2024-06-05T09:00:00Z", TimeDelta::hours(4), "start"),
+        ];
+
+        let rows: Vec<_> = summarize_tags(&time_boxes, None)
+            .into_iter()
+            .map(|s| (s.tag, s.total, s.count))
+            .collect();
+        assert_eq!(
+            vec![
+                ("b".to_string(), 3.0, 2),
+                ("c".to_string(), 3.0, 1),
+                ("a".to_string(), 2.0, 1),
+            ],
+            rows
+        );
+    }
+
+    #[test]
+    fn references_sum_up_their_time_boxes() {
+        let referenced = |start: &str, hours: i64, reference: Option<&str>| TimeBox {
+            reference: reference.map(str::to_string),
+            ..time_box(start, TimeDelta::hours(hours), "start")
+        };
+        let time_boxes = [
+            referenced("2024-06-03T09:00:00Z", 2, Some("PROJ-2")),
+            referenced("2024-06-03T12:00:00Z", 1, Some("PROJ-1")),
+            referenced("2024-06-04T09:00:00Z", 1, Some("PROJ-2")),
+            referenced("2024-06-05T09:00:00Z", 3, Some("PROJ-3")),
+            referenced("2024-06-06T09:00:00Z", 4, None),
+        ];
+
+        let rows: Vec<_> = summarize_references(&time_boxes, None)
+            .into_iter()
+            .map(|s| (s.reference, s.total, s.count))
+            .collect();
+        assert_eq!(
+            vec![
+                ("PROJ-2".to_string(), 3.0, 2),
+                ("PROJ-3".to_string(), 3.0, 1),
+                ("PROJ-1".to_string(), 1.0, 1),
+            ],
+            rows
+        );
+    }
+
+    #[test]
+    fn rounding_applies_per_time_box_before_summing_up() {
+        let minutes = |start: &str, seconds: i64| {
+            let mut tb = time_box(start, TimeDelta::hours(0), "start");
+            tb.notes[1].time = tb.notes[0].time + TimeDelta::seconds(seconds);
+            tb
+        };
+        // Exactly on, just below and exactly between multiples of 15 minutes
+        let time_boxes = [
+            minutes("2024-06-03T09:00:00Z", 15 * 60),
+            minutes("2024-06-03T10:00:00Z", 15 * 60 - 1),
+            minutes("2024-06-03T11:00:00Z", 22 * 60 + 30),
+        ];
+        let total = |rounding| -> f64 {
+            summarize_days(&time_boxes, &Utc, rounding)
+                .iter()
+                .map(|d| d.total)
+                .sum()
+        };
+        let quarter = |mode| Some((TimeDelta::minutes(15), mode));
+
+        // 15m + 15m + 30m, not the rounded sum of 52m29s
+        assert_eq!(1.0, total(quarter(RoundingMode::Up)));
+        // 15m + 0m + 15m
+        assert_eq!(0.5, total(quarter(RoundingMode::Down)));
+        // 15m + 15m + 30m, halfway rounds up
+        assert_eq!(1.0, total(quarter(RoundingMode::Nearest)));
+        assert!((total(None) - (52.0 * 60.0 + 29.0) / 3600.0).abs() < 1e-9);
+
+        let tagged: Vec<TimeBox> = time_boxes
+            .iter()
+            .cloned()
+            .map(|mut tb| {
+                tb.notes[1].description = "#billing".to_string();
+                tb
+            })
+            .collect();
+        assert_eq!(
+            0.5,
+            summarize_tags(&tagged, quarter(RoundingMode::Down))[0].total
+        );
+    }
+
+    #[test]
+    fn roll_up_month_spanning_five_iso_weeks() {
+        let weeks = roll_up_weeks(summarize_days(&june_2024(), &Utc, None));
+
+        let labels: Vec<_> = weeks.iter().map(|w| w.label.as_str()).collect();
+        assert_eq!(
+            vec!["2024-W22", "2024-W23", "2024-W24", "2024-W25", "2024-W26"],
+            labels
+        );
+
+        let totals: Vec<_> = weeks.iter().map(|w| w.total).collect();
+        assert_eq!(vec![1.0, 5.0, 5.0, 2.0, 5.0], totals);
+
+        // Sunday the 16th still belongs to the week of Wednesday the 12th
+        assert_eq!(2, weeks[2].days.len());
+        assert_eq!(2, weeks[2].count());
+
+        let sum_days: f64 = weeks
+            .iter()
+            .flat_map(|w| w.days.iter())
+            .map(|d| d.total)
+            .sum();
+        let sum_weeks: f64 = weeks.iter().map(|w| w.total).sum();
+        assert_eq!(sum_days, sum_weeks);
+    }
+
+    #[test]
+    fn roll_up_nothing() {
+        assert!(roll_up_weeks(summarize_days(&[], &Utc, None)).is_empty());
+    }
+
+    fn group_labels(time_boxes: &[TimeBox], grouping: Grouping) -> Vec<(String, usize)> {
+        group_time_boxes(time_boxes, grouping, &Utc)
+            .iter()
+            .map(|(key, group)| (key.to_string(), group.len()))
+            .collect()
+    }
+
+    #[test]
+    fn group_weeks_split_between_sunday_and_monday() {
+        let time_boxes = [
+            time_box("2024-06-09T23:00:00Z", TimeDelta::hours(1), "start"), // Sunday
+            time_box("2024-06-10T00:00:00Z", TimeDelta::hours(1), "start"), // Monday
+            time_box("2024-06-16T22:00:00Z", TimeDelta::hours(1), "start"), // Sunday
+        ];
+
+        assert_eq!(
+            vec![("2024-W23".to_string(), 1), ("2024-W24".to_string(), 2)],
+            group_labels(&time_boxes, Grouping::Week)
+        );
+
+        // Local dates decide, in UTC+2 both Sundays already start on Monday
+        let tz = chrono::FixedOffset::east_opt(2 * 60 * 60).unwrap();
+        let groups = group_time_boxes(&time_boxes, Grouping::Week, &tz);
+        assert_eq!(2, groups.len());
+        assert_eq!(
+            GroupKey::Week {
+                year: 2024,
+                week: 24
+            },
+            groups[0].0
+        );
+        assert_eq!(2, groups[0].1.len());
+    }
+
+    #[test]
+    fn group_across_year_transition() {
+        let time_boxes = [
+            time_box("2024-12-29T09:00:00Z", TimeDelta::hours(1), "start"), // Sunday
+            time_box("2024-12-30T09:00:00Z", TimeDelta::hours(1), "start"), // Monday, ISO week 1 of 2025
+            time_box("2024-12-31T09:00:00Z", TimeDelta::hours(1), "start"),
+            time_box("2025-01-01T09:00:00Z", TimeDelta::hours(1), "start"),
+            time_box("2025-01-06T09:00:00Z", TimeDelta::hours(1), "start"),
+        ];
+
+        assert_eq!(
+            vec![
+                ("2024-W52".to_string(), 1),
+                ("2025-W01".to_string(), 3),
+                ("2025-W02".to_string(), 1)
+            ],
+            group_labels(&time_boxes, Grouping::Week)
+        );
+        assert_eq!(
+            vec![("2024-12".to_string(), 3), ("2025-01".to_string(), 2)],
+            group_labels(&time_boxes, Grouping::Month)
+        );
+        assert_eq!(5, group_labels(&time_boxes, Grouping::Day).len());
+    }
+
+    #[test]
+    fn group_keeps_descending_order() {
+        let mut time_boxes = june_2024();
+        time_boxes.reverse();
+
+        let labels: Vec<_> = group_labels(&time_boxes, Grouping::Week)
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        assert_eq!(
+            vec!["2024-W26", "2024-W25", "2024-W24", "2024-W23", "2024-W22"],
+            labels
+        );
+        assert!(group_time_boxes(&[], Grouping::Month, &Utc).is_empty());
+    }
+
+    #[test]
+    fn estimates_exclude_time_boxes_without_estimate() {
+        let mut time_boxes = june_2024();
+        time_boxes[1].estimate = Some(TimeDelta::hours(1)); // took 2h
+        time_boxes[2].estimate = Some(TimeDelta::hours(4)); // took 3h
+        time_boxes[3].estimate = Some(TimeDelta::hours(2)); // took 4h
+
+        let weeks = summarize_estimates(&time_boxes, Grouping::Week, &Utc);
+        assert_eq!(
+            vec![
+                EstimateSummary {
+                    label: "2024-W23".to_string(),
+                    count: 2,
+                    estimate: 5.0,
+                    actual: 5.0,
+                    deviation: Some(0.0),
+                },
+                EstimateSummary {
+                    label: "2024-W24".to_string(),
+                    count: 1,
+                    estimate: 2.0,
+                    actual: 4.0,
+                    deviation: Some(100.0),
+                },
+            ],
+            weeks
+        );
+
+        let days = summarize_estimates(&time_boxes, Grouping::Day, &Utc);
+        let labels: Vec<_> = days.iter().map(|d| d.label.as_str()).collect();
+        assert_eq!(vec!["2024-06-03", "2024-06-12"], labels);
+
+        let total = EstimateSummary::of("total".to_string(), &time_boxes);
+        assert_eq!(3, total.count);
+        assert_eq!(9.0, total.actual);
+
+        let nothing = EstimateSummary::of("total".to_string(), &june_2024());
+        assert_eq!(0, nothing.count);
+        assert_eq!(None, nothing.deviation);
+        assert!(summarize_estimates(&june_2024(), Grouping::Day, &Utc).is_empty());
+    }
+}
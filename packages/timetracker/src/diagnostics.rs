@@ -0,0 +1,51 @@
+use std::fmt::Display;
+
+/// How much a `Diagnostic` matters, the application decides how to present it, e.g. as log level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+}
+
+/// Message about something loading noticed but could handle, e.g. a repaired store or an unreadable cache.
+/// Collected instead of logged, see `InMemoryTimeTracker::diagnostics`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// What the message is about, usually the path of a file.
+    pub context: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn info(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Info,
+            message: message.into(),
+            context: None,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            context: None,
+        }
+    }
+
+    pub fn context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+}
+
+/// Leaves out the severity, applications show it their own way.
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.context {
+            Some(context) => write!(f, "\"{context}\": {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
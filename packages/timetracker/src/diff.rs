@@ -0,0 +1,219 @@
+use std::fmt::Debug;
+
+use crate::{TimeBox, in_memory_tracker::InMemoryTimeTracker};
+
+/// A field holding different values in two stores, see `diff_stores`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreDifference {
+    /// Where inside the store, e.g. `finished[3].notes[1].time`, `active` or `settings.weekly_goal`.
+    pub path: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl std::fmt::Display for StoreDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} != {}", self.path, self.left, self.right)
+    }
+}
+
+/// Every field that differs between the stores, empty if they are equal.
+/// What loading recorded, i.e. `repairs` and `diagnostics`, is not part of the store and gets ignored.
+/// Values are shown via `Debug`, so e.g. times show their full precision.
+pub fn diff_stores(
+    left: &InMemoryTimeTracker,
+    right: &InMemoryTimeTracker,
+) -> Vec<StoreDifference> {
+    let mut differences = Vec::new();
+
+    match (&left.active, &right.active) {
+        (Some(l), Some(r)) => diff_time_boxes(&mut differences, "active", l, r),
+        (l, r) => compare(
+            &mut differences,
+            "active",
+            &l.as_ref().map(TimeBox::title),
+            &r.as_ref().map(TimeBox::title),
+        ),
+    }
+
+    compare(
+        &mut differences,
+        "finished.len",
+        &left.finished.len(),
+        &right.finished.len(),
+    );
+    for (index, (l, r)) in left.finished.iter().zip(right.finished.iter()).enumerate() {
+        diff_time_boxes(&mut differences, &format!("finished[{index}]"), l, r);
+    }
+
+    let (l, r) = (&left.settings, &right.settings);
+    compare(
+        &mut differences,
+        "settings.weekly_goal",
+        &l.weekly_goal,
+        &r.weekly_goal,
+    );
+    compare(
+        &mut differences,
+        "settings.work_days",
+        &l.work_days,
+        &r.work_days,
+    );
+    compare(
+        &mut differences,
+        "settings.work_hours",
+        &l.work_hours,
+        &r.work_hours,
+    );
+    compare(
+        &mut differences,
+        "settings.locked_before",
+        &l.locked_before,
+        &r.locked_before,
+    );
+    compare(
+        &mut differences,
+        "settings.lock_suggested_for",
+        &l.lock_suggested_for,
+        &r.lock_suggested_for,
+    );
+
+    differences
+}
+
+fn diff_time_boxes(
+    differences: &mut Vec<StoreDifference>,
+    path: &str,
+    left: &TimeBox,
+    right: &TimeBox,
+) {
+    compare(
+        differences,
+        &format!("{path}.notes.len"),
+        &left.notes.len(),
+        &right.notes.len(),
+    );
+    for (index, (l, r)) in left.notes.iter().zip(right.notes.iter()).enumerate() {
+        let path = format!("{path}.notes[{index}]");
+        compare(differences, &format!("{path}.time"), &l.time, &r.time);
+        compare(
+            differences,
+            &format!("{path}.description"),
+            &l.description,
+            &r.description,
+        );
+        compare(differences, &format!("{path}.kind"), &l.kind, &r.kind);
+    }
+
+    compare(
+        differences,
+        &format!("{path}.exported_at"),
+        &left.exported_at,
+        &right.exported_at,
+    );
+    compare(
+        differences,
+        &format!("{path}.pauses"),
+        &left.pauses,
+        &right.pauses,
+    );
+    compare(
+        differences,
+        &format!("{path}.estimate"),
+        &left.estimate,
+        &right.estimate,
+    );
+    compare(
+        differences,
+        &format!("{path}.reference"),
+        &left.reference,
+        &right.reference,
+    );
+}
+
+fn compare<T: PartialEq + Debug>(
+    differences: &mut Vec<StoreDifference>,
+    path: &str,
+    left: &T,
+    right: &T,
+) {
+    if left != right {
+        differences.push(StoreDifference {
+            path: path.to_string(),
+            left: format!("{left:?}"),
+            right: format!("{right:?}"),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use crate::{Repair, Result, StoreSettings, TimeTrackingStore};
+
+    use super::*;
+
+    fn store() -> Result<InMemoryTimeTracker> {
+        let mut store = InMemoryTimeTracker::default();
+        store.begin("first")?;
+        store.push_note("second note")?;
+        store.end()?;
+        store.begin("second")?;
+        store.end()?;
+        store.begin("active")?;
+        Ok(store)
+    }
+
+    #[test]
+    fn equal_stores_have_no_differences_even_if_loaded_differently() -> Result<()> {
+        let left = store()?;
+        let mut right = left.clone();
+        right.repairs.push(Repair::DroppedIncompleteLine(3));
+
+        assert!(diff_stores(&left, &right).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn differences_name_the_time_box_and_field() -> Result<()> {
+        let left = store()?;
+        let mut right = left.clone();
+        let mut finished = right.finished.to_vec();
+        finished[0].notes[1].time += TimeDelta::nanoseconds(1);
+        finished[1].estimate = Some(TimeDelta::hours(1));
+        right.finished = finished.into();
+        right.active = None;
+        right.settings = StoreSettings {
+            work_hours: Some(TimeDelta::hours(6)),
+            ..StoreSettings::default()
+        };
+
+        let paths: Vec<String> = diff_stores(&left, &right)
+            .into_iter()
+            .map(|d| d.path)
+            .collect();
+        assert_eq!(
+            vec![
+                "active",
+                "finished[0].notes[1].time",
+                "finished[1].estimate",
+                "settings.work_hours"
+            ],
+            paths
+        );
+
+        let difference = &diff_stores(&left, &right)[0];
+        assert_eq!("active: Some(\"active\") != None", difference.to_string());
+
+        right.finished.pop();
+        assert_eq!(
+            "finished.len",
+            diff_stores(&left, &right)[1].path,
+            "Only the time boxes both stores have get compared"
+        );
+
+        Ok(())
+    }
+}
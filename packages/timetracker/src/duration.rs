@@ -0,0 +1,306 @@
+use chrono::TimeDelta;
+
+use crate::{Error, Result};
+
+/// How durations get displayed to humans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurationStyle {
+    /// `2.37h`
+    #[default]
+    DecimalHours,
+    /// `2h 22m`, or `22m` below one hour
+    HoursMinutes,
+    /// `02:22`
+    Clock,
+    /// `2 hours 22 minutes`, for reading aloud by screen readers
+    Words,
+}
+
+/// Formats `delta` rounded to the nearest minute, or to two decimals for `DurationStyle::DecimalHours`.
+/// Negative durations only occur in malformed stores and get a leading minus.
+pub fn format_duration(delta: TimeDelta, style: DurationStyle) -> String {
+    let sign = if delta < TimeDelta::zero() { "-" } else { "" };
+    let seconds = delta.num_seconds().unsigned_abs();
+
+    let minutes_total = (seconds + 30) / 60;
+    let (hours, minutes) = (minutes_total / 60, minutes_total % 60);
+
+    match style {
+        DurationStyle::DecimalHours => format!("{sign}{:.2}h", seconds as f64 / 60.0 / 60.0),
+        DurationStyle::HoursMinutes if hours == 0 => format!("{sign}{minutes}m"),
+        DurationStyle::HoursMinutes => format!("{sign}{hours}h {minutes}m"),
+        DurationStyle::Clock => format!("{sign}{hours:02}:{minutes:02}"),
+        DurationStyle::Words => {
+            let sign = if sign.is_empty() { "" } else { "minus " };
+            let unit = |n: u64, singular: &str| match n {
+                1 => format!("1 {singular}"),
+                n => format!("{n} {singular}s"),
+            };
+            match (hours, minutes) {
+                (0, minutes) => format!("{sign}{}", unit(minutes, "minute")),
+                (hours, 0) => format!("{sign}{}", unit(hours, "hour")),
+                (hours, minutes) => {
+                    format!("{sign}{} {}", unit(hours, "hour"), unit(minutes, "minute"))
+                }
+            }
+        }
+    }
+}
+
+/// Short form that reads well next to decimal hours, e.g. `1h 23m`, `2h`, `45m` or `12s`.
+/// Rounds to the nearest minute like `format_duration`, only durations below one minute show their seconds.
+pub fn humanize_duration(delta: TimeDelta) -> String {
+    let sign = if delta < TimeDelta::zero() { "-" } else { "" };
+    let seconds = delta.num_seconds().unsigned_abs();
+    if seconds < 60 {
+        return format!("{sign}{seconds}s");
+    }
+
+    let minutes_total = (seconds + 30) / 60;
+    match (minutes_total / 60, minutes_total % 60) {
+        (0, minutes) => format!("{sign}{minutes}m"),
+        (hours, 0) => format!("{sign}{hours}h"),
+        (hours, minutes) => format!("{sign}{hours}h {minutes}m"),
+    }
+}
+
+/// How long ago something was that happened `delta` before now, e.g. `just now`, `25 minutes ago`,
+/// `3 hours ago` or `2 days ago`. Rounds down, hours start at one hour and days at two days,
+/// so yesterday evening still reads in hours. Negative deltas, e.g. from skewed clocks, are just now.
+pub fn humanize_delta(delta: TimeDelta) -> String {
+    let unit = |n: i64, singular: &str| match n {
+        1 => format!("1 {singular} ago"),
+        n => format!("{n} {singular}s ago"),
+    };
+
+    match delta {
+        d if d < TimeDelta::minutes(1) => "just now".to_string(),
+        d if d < TimeDelta::hours(1) => unit(d.num_minutes(), "minute"),
+        d if d < TimeDelta::days(2) => unit(d.num_hours(), "hour"),
+        d => unit(d.num_days(), "day"),
+    }
+}
+
+/// Direction to round durations in, see `TimeBox::duration_rounded`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoundingMode {
+    /// Up to the next multiple, a duration of zero becomes one multiple
+    #[default]
+    Up,
+    Down,
+    /// To the nearest multiple, halfway rounds up
+    Nearest,
+}
+
+/// Rounds `delta` to a multiple of `granularity`, which has to be positive.
+pub fn round_duration(
+    delta: TimeDelta,
+    granularity: TimeDelta,
+    mode: RoundingMode,
+) -> Result<TimeDelta> {
+    if granularity <= TimeDelta::zero() {
+        return Err(Error::InvalidRoundingGranularity(granularity));
+    }
+
+    let (d, g) = (delta.num_milliseconds(), granularity.num_milliseconds());
+    let multiples = match mode {
+        RoundingMode::Up if d == 0 => 1,
+        RoundingMode::Up => -(-d).div_euclid(g),
+        RoundingMode::Down => d.div_euclid(g),
+        RoundingMode::Nearest => (d + g / 2).div_euclid(g),
+    };
+
+    Ok(TimeDelta::milliseconds(multiples.saturating_mul(g)))
+}
+
+/// Deviation of `actual` from `estimate` in percent, positive if it took longer than estimated.
+/// `None` for an estimate of zero since there is nothing to compare against.
+pub fn estimate_deviation(estimate: TimeDelta, actual: TimeDelta) -> Option<f64> {
+    if estimate.is_zero() {
+        return None;
+    }
+
+    let estimate = estimate.num_seconds() as f64;
+    Some((actual.num_seconds() as f64 - estimate) / estimate * 100.0)
+}
+
+/// For example `est 2.00h / actual 2.60h (+30%)`, see `estimate_deviation`.
+pub fn format_estimate(estimate: TimeDelta, actual: TimeDelta, style: DurationStyle) -> String {
+    let mut output = format!(
+        "est {} / actual {}",
+        format_duration(estimate, style),
+        format_duration(actual, style)
+    );
+
+    if let Some(deviation) = estimate_deviation(estimate, actual) {
+        output.push_str(&format!(" ({deviation:+.0}%)"));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(minutes: i64, seconds: i64) -> TimeDelta {
+        TimeDelta::minutes(minutes) + TimeDelta::seconds(seconds)
+    }
+
+    #[test]
+    fn formats_each_style() {
+        let d = delta(142, 12);
+        assert_eq!("2.37h", format_duration(d, DurationStyle::DecimalHours));
+        assert_eq!("2h 22m", format_duration(d, DurationStyle::HoursMinutes));
+        assert_eq!("02:22", format_duration(d, DurationStyle::Clock));
+        assert_eq!(
+            "2 hours 22 minutes",
+            format_duration(d, DurationStyle::Words)
+        );
+
+        assert_eq!(
+            "45m",
+            format_duration(delta(45, 0), DurationStyle::HoursMinutes)
+        );
+        assert_eq!(
+            "123:05",
+            format_duration(delta(123 * 60 + 5, 0), DurationStyle::Clock)
+        );
+        assert_eq!(
+            "1 minute",
+            format_duration(delta(1, 0), DurationStyle::Words)
+        );
+        assert_eq!(
+            "1 hour 1 minute",
+            format_duration(delta(61, 0), DurationStyle::Words)
+        );
+        assert_eq!(
+            "3 hours",
+            format_duration(delta(180, 0), DurationStyle::Words)
+        );
+        assert_eq!(
+            "minus 5 minutes",
+            format_duration(delta(-5, 0), DurationStyle::Words)
+        );
+    }
+
+    #[test]
+    fn rounds_to_the_nearest_minute() {
+        assert_eq!(
+            "59m",
+            format_duration(delta(59, 29), DurationStyle::HoursMinutes)
+        );
+        assert_eq!(
+            "1h 0m",
+            format_duration(delta(59, 30), DurationStyle::HoursMinutes)
+        );
+        assert_eq!(
+            "00:59",
+            format_duration(delta(59, 29), DurationStyle::Clock)
+        );
+        assert_eq!(
+            "01:00",
+            format_duration(delta(59, 30), DurationStyle::Clock)
+        );
+    }
+
+    #[test]
+    fn humanized_durations_drop_what_is_zero() {
+        assert_eq!("0s", humanize_duration(TimeDelta::zero()));
+        assert_eq!("12s", humanize_duration(delta(0, 12)));
+        assert_eq!("59s", humanize_duration(delta(0, 59)));
+        assert_eq!("1m", humanize_duration(delta(1, 0)));
+        assert_eq!("45m", humanize_duration(delta(45, 10)));
+        assert_eq!("1h", humanize_duration(delta(59, 30)));
+        assert_eq!("1h 23m", humanize_duration(delta(83, 0)));
+        assert_eq!("26h 5m", humanize_duration(delta(26 * 60 + 5, 0)));
+        assert_eq!("-1h 30m", humanize_duration(-delta(90, 0)));
+        assert_eq!("-12s", humanize_duration(-delta(0, 12)));
+    }
+
+    #[test]
+    fn negative_durations_get_a_leading_minus() {
+        let d = -delta(90, 0);
+        assert_eq!("-1.50h", format_duration(d, DurationStyle::DecimalHours));
+        assert_eq!("-1h 30m", format_duration(d, DurationStyle::HoursMinutes));
+        assert_eq!("-01:30", format_duration(d, DurationStyle::Clock));
+    }
+
+    #[test]
+    fn rounds_to_multiples_of_the_granularity() -> Result<()> {
+        let quarter = TimeDelta::minutes(15);
+        let round = |minutes, seconds, mode| round_duration(delta(minutes, seconds), quarter, mode);
+
+        assert_eq!(TimeDelta::minutes(75), round(65, 0, RoundingMode::Up)?);
+        assert_eq!(TimeDelta::minutes(60), round(65, 0, RoundingMode::Down)?);
+        assert_eq!(
+            TimeDelta::minutes(60),
+            round(67, 29, RoundingMode::Nearest)?
+        );
+        assert_eq!(
+            TimeDelta::minutes(75),
+            round(67, 30, RoundingMode::Nearest)?
+        );
+
+        // Exact multiples stay as they are
+        for mode in [RoundingMode::Up, RoundingMode::Down, RoundingMode::Nearest] {
+            assert_eq!(TimeDelta::minutes(45), round(45, 0, mode)?);
+        }
+
+        // Nothing tracked still gets billed once when rounding up
+        assert_eq!(quarter, round(0, 0, RoundingMode::Up)?);
+        assert_eq!(TimeDelta::zero(), round(0, 0, RoundingMode::Down)?);
+        assert_eq!(quarter, round(0, 1, RoundingMode::Up)?);
+
+        assert!(matches!(
+            round_duration(delta(10, 0), TimeDelta::zero(), RoundingMode::Up),
+            Err(Error::InvalidRoundingGranularity(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn estimates_compare_against_actuals() {
+        let estimate = TimeDelta::hours(2);
+
+        assert_eq!(Some(30.0), estimate_deviation(estimate, delta(156, 0)));
+        assert_eq!(Some(-50.0), estimate_deviation(estimate, delta(60, 0)));
+        assert_eq!(Some(0.0), estimate_deviation(estimate, estimate));
+        assert_eq!(None, estimate_deviation(TimeDelta::zero(), estimate));
+
+        assert_eq!(
+            "est 2.00h / actual 2.60h (+30%)",
+            format_estimate(estimate, delta(156, 0), DurationStyle::DecimalHours)
+        );
+        assert_eq!(
+            "est 2h 0m / actual 1h 0m (-50%)",
+            format_estimate(estimate, delta(60, 0), DurationStyle::HoursMinutes)
+        );
+        assert_eq!(
+            "est 0.00h / actual 2.00h",
+            format_estimate(TimeDelta::zero(), estimate, DurationStyle::DecimalHours)
+        );
+    }
+
+    #[test]
+    fn humanized_deltas_at_their_boundaries() {
+        let cases = [
+            (TimeDelta::seconds(-30), "just now"),
+            (TimeDelta::seconds(59), "just now"),
+            (TimeDelta::seconds(60), "1 minute ago"),
+            (TimeDelta::minutes(25), "25 minutes ago"),
+            (TimeDelta::seconds(3599), "59 minutes ago"),
+            (TimeDelta::minutes(60), "1 hour ago"),
+            (TimeDelta::minutes(90), "1 hour ago"),
+            (TimeDelta::hours(47), "47 hours ago"),
+            (TimeDelta::hours(48), "2 days ago"),
+            (TimeDelta::hours(49), "2 days ago"),
+            (TimeDelta::days(30), "30 days ago"),
+        ];
+
+        for (delta, expected) in cases {
+            assert_eq!(expected, humanize_delta(delta), "{delta}");
+        }
+    }
+}
@@ -1,42 +1,301 @@
-use chrono::{DateTime, TimeDelta, Utc};
+use std::{cmp::Ordering, fmt::Display};
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::Error;
 use crate::Result;
+use crate::{DurationStyle, RoundingMode, format_duration, round_duration};
 
 /// Notes represent a chronological journal
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Ordered by time first, then by description.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct TimeBoxNote {
     pub time: DateTime<Utc>,
     pub description: String,
+    /// Notes of stores from before there were kinds are regular.
+    #[serde(default)]
+    pub kind: NoteKind,
+}
+
+/// Who wrote a note.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteKind {
+    /// Written by the user.
+    #[default]
+    Regular,
+    /// Added by the tracker itself, e.g. to mark when a time box was resumed. Never the title.
+    System,
+}
+
+impl NoteKind {
+    pub fn is_regular(&self) -> bool {
+        *self == NoteKind::Regular
+    }
+}
+
+impl TimeBoxNote {
+    /// Hashtags inside the description, lowercase and without the leading `#`, each once.
+    /// A tag starts with `#` at the beginning of a word and consists of letters, digits, `_` and `-`.
+    /// Purely numeric tags like `#42` are issue references and no tags.
+    pub fn tags(&self) -> Vec<String> {
+        let is_tag_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+
+        let mut tags: Vec<String> = Vec::new();
+        for word in self
+            .description
+            .split(|c: char| !is_tag_char(c) && c != '#')
+        {
+            let Some(rest) = word.strip_prefix('#') else {
+                continue;
+            };
+            let tag: String = rest.chars().take_while(|c| is_tag_char(*c)).collect();
+            let tag = tag.trim_end_matches('-').to_lowercase();
+
+            if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()) && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        tags
+    }
+}
+
+/// `2024-06-12 09:00 — Fix login bug`, in UTC.
+impl Display for TimeBoxNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} — {}",
+            self.time.format("%Y-%m-%d %H:%M"),
+            self.description
+        )
+    }
+}
+
+/// Interval that does not count towards the duration of its time box, e.g. a lunch break.
+/// `stop` is `None` while the pause lasts.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Pause {
+    pub start: DateTime<Utc>,
+    pub stop: Option<DateTime<Utc>>,
 }
 
 /// Main Entity for keeping track of time.
 /// A time box by definition is a linear list of notes (`TimeBoxNote`)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TimeBox {
     pub notes: Vec<TimeBoxNote>,
+    /// When the time box was last exported, e.g. for invoicing. `None` if it never was.
+    #[serde(default)]
+    pub exported_at: Option<DateTime<Utc>>,
+    /// Only the last pause may still be open, see `TimeBox::paused_since`.
+    #[serde(default)]
+    pub pauses: Vec<Pause>,
+    /// How long the time box was expected to take, see `estimate_deviation`. Serialized as seconds.
+    #[serde(default, with = "estimate_seconds")]
+    pub estimate: Option<TimeDelta>,
+    /// External reference the time box belongs to, e.g. a ticket id like `PROJ-123` or a URL.
+    /// Trimmed and never empty, see `TimeTrackingStore::set_reference`.
+    #[serde(default, rename = "ref")]
+    pub reference: Option<String>,
+}
+
+pub(crate) mod estimate_seconds {
+    use chrono::TimeDelta;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        estimate: &Option<TimeDelta>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match estimate {
+            Some(estimate) => serializer.serialize_some(&estimate.num_seconds()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<TimeDelta>, D::Error> {
+        Ok(Option::<i64>::deserialize(deserializer)?.map(TimeDelta::seconds))
+    }
+}
+
+pub(crate) mod duration_seconds {
+    use chrono::TimeDelta;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        duration: &TimeDelta,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(duration.num_seconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TimeDelta, D::Error> {
+        Ok(TimeDelta::seconds(i64::deserialize(deserializer)?))
+    }
+}
+
+/// Total ordering so listings and exports are deterministic even for equal start times:
+/// start time, then stop time, then description of the first note, then all remaining notes,
+/// then the export time, pauses, estimate and reference.
+impl Ord for TimeBox {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.ordering_key()
+            .cmp(&other.ordering_key())
+            .then_with(|| self.notes.cmp(&other.notes))
+            .then_with(|| self.exported_at.cmp(&other.exported_at))
+            .then_with(|| self.pauses.cmp(&other.pauses))
+            .then_with(|| self.estimate.cmp(&other.estimate))
+            .then_with(|| self.reference.cmp(&other.reference))
+    }
+}
+
+impl PartialOrd for TimeBox {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl TimeBox {
-    pub fn time_start(&self) -> Result<DateTime<Utc>> {
-        match self.notes.first() {
-            Some(n) => Ok(n.time),
-            None => Err(Error::TimeBoxIsMissingNote { index: 0 }),
+    fn ordering_key(&self) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>, Option<&str>) {
+        (
+            self.notes.first().map(|n| n.time),
+            self.notes.last().map(|n| n.time),
+            self.notes.first().map(|n| n.description.as_str()),
+        )
+    }
+
+    /// First line of the first regular note, i.e. what the time box is about.
+    /// Falls back to the first note if there are only system notes.
+    /// Everything showing what a time box is about uses this, so they agree.
+    pub fn title(&self) -> &str {
+        self.notes
+            .iter()
+            .find(|n| n.kind.is_regular())
+            .or(self.notes.first())
+            .and_then(|n| n.description.lines().next())
+            .unwrap_or_default()
+    }
+
+    /// The `title` shortened to at most `max_len` columns, ending with `…` if it had to be shortened.
+    /// Counts the width on screen, so wide characters take two columns and none gets cut in half.
+    pub fn description_summary(&self, max_len: usize) -> String {
+        let title = self.title();
+        if title.width() <= max_len {
+            return title.to_owned();
+        }
+
+        // Leave room for the ellipsis
+        let mut width = 1;
+        let mut summary: String = title
+            .chars()
+            .take_while(|c| {
+                width += c.width().unwrap_or_default();
+                width <= max_len
+            })
+            .collect();
+        if max_len > 0 {
+            summary.push('…');
         }
+
+        summary
     }
 
-    pub fn time_stop(&self) -> Result<DateTime<Utc>> {
-        match self.notes.last() {
-            Some(n) => Ok(n.time),
-            None => Err(Error::TimeBoxIsMissingNote {
-                index: 0.max(self.notes.len()),
-            }),
+    /// Tags of all notes in order of their first appearance, see `TimeBoxNote::tags`.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.notes.iter().flat_map(TimeBoxNote::tags) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        tags
+    }
+
+    /// Ticket ids like `PROJ-123` inside the notes in order of their first appearance, each once.
+    /// A ticket id is a word of an uppercase letter followed by uppercase letters or digits, a `-` and digits.
+    pub fn ticket_ids(&self) -> Vec<String> {
+        let is_ticket_id = |word: &str| {
+            let Some((project, number)) = word.split_once('-') else {
+                return false;
+            };
+            project.starts_with(|c: char| c.is_ascii_uppercase())
+                && project
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+                && !number.is_empty()
+                && number.chars().all(|c| c.is_ascii_digit())
+        };
+
+        let mut ids: Vec<String> = Vec::new();
+        for note in &self.notes {
+            for word in note
+                .description
+                .split(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+            {
+                if is_ticket_id(word) && !ids.iter().any(|id| id == word) {
+                    ids.push(word.to_string());
+                }
+            }
         }
+
+        ids
+    }
+
+    /// Time of the first note, fails with `Error::EmptyTimeBox` without notes.
+    pub fn time_start(&self) -> Result<DateTime<Utc>> {
+        self.notes
+            .first()
+            .map(|n| n.time)
+            .ok_or(Error::EmptyTimeBox)
+    }
+
+    /// Time of the last note, fails with `Error::EmptyTimeBox` without notes.
+    pub fn time_stop(&self) -> Result<DateTime<Utc>> {
+        self.notes.last().map(|n| n.time).ok_or(Error::EmptyTimeBox)
+    }
+
+    /// Start of the open pause if the time box is paused right now.
+    pub fn paused_since(&self) -> Option<DateTime<Utc>> {
+        self.pauses
+            .last()
+            .filter(|p| p.stop.is_none())
+            .map(|p| p.start)
     }
 
+    /// Time spent in pauses up to `until`, an open pause lasts until `until`.
+    pub fn timedelta_paused(&self, until: DateTime<Utc>) -> TimeDelta {
+        self.pauses
+            .iter()
+            .map(|p| {
+                let stop = p.stop.unwrap_or(until).min(until);
+                stop.signed_duration_since(p.start).max(TimeDelta::zero())
+            })
+            .sum()
+    }
+
+    /// Time between the first and the last note, excluding pauses.
+    /// Negative for inconsistent data, totals use `clamped_duration` instead.
     pub fn timedelta_total(&self) -> Result<TimeDelta> {
-        Ok(self.time_stop()?.signed_duration_since(self.time_start()?))
+        let stop = self.time_stop()?;
+        Ok(stop.signed_duration_since(self.time_start()?) - self.timedelta_paused(stop))
+    }
+
+    /// `timedelta_total` rounded to a multiple of `granularity`, e.g. for invoices.
+    pub fn duration_rounded(
+        &self,
+        granularity: TimeDelta,
+        mode: RoundingMode,
+    ) -> Result<TimeDelta> {
+        round_duration(self.timedelta_total()?, granularity, mode)
     }
 
     pub fn duration_in_minutes(&self) -> Result<f64> {
@@ -47,8 +306,29 @@ impl TimeBox {
         Ok(self.timedelta_total()?.num_seconds() as f64 / 60.0 / 60.0)
     }
 
+    /// Pairs each note with the time until the next note.
+    /// The last note gets the time until now if the time box is `active`, otherwise zero.
+    pub fn notes_with_durations(
+        &self,
+        active: bool,
+    ) -> impl Iterator<Item = (&TimeBoxNote, TimeDelta)> {
+        let now = Utc::now();
+
+        self.notes.iter().enumerate().map(move |(i, note)| {
+            let delta = match (self.notes.get(i + 1), active) {
+                (Some(next), _) => next.time.signed_duration_since(note.time),
+                (None, true) => now.signed_duration_since(note.time),
+                (None, false) => TimeDelta::zero(),
+            };
+
+            (note, delta)
+        })
+    }
+
+    /// Time since the first note, excluding pauses including the open one.
     pub fn timedelta_active(&self) -> Result<TimeDelta> {
-        Ok(Utc::now().signed_duration_since(self.time_start()?))
+        let now = Utc::now();
+        Ok(now.signed_duration_since(self.time_start()?) - self.timedelta_paused(now))
     }
 
     pub fn duration_active_in_minutes(&self) -> Result<f64> {
@@ -58,4 +338,288 @@ impl TimeBox {
     pub fn duration_active_in_hours(&self) -> Result<f64> {
         Ok(self.timedelta_active()?.num_seconds() as f64 / 60.0 / 60.0)
     }
+
+    /// One line for humans, with times in `tz`:
+    /// `Fix login bug (1.42h, 3 notes, 2024-06-12 09:00–10:25)`.
+    /// The stop shows its date only if it differs from the one of the start.
+    pub fn summary_line<Tz: TimeZone>(&self, tz: &Tz) -> String
+    where
+        Tz::Offset: Display,
+    {
+        let (Ok(start), Ok(stop), Ok(duration)) =
+            (self.time_start(), self.time_stop(), self.timedelta_total())
+        else {
+            return "Time box without notes".to_string();
+        };
+        let (start, stop) = (start.with_timezone(tz), stop.with_timezone(tz));
+        let stop_format = if start.date_naive() == stop.date_naive() {
+            "%H:%M"
+        } else {
+            "%Y-%m-%d %H:%M"
+        };
+        let notes = match self.notes.len() {
+            1 => "1 note".to_string(),
+            n => format!("{n} notes"),
+        };
+
+        format!(
+            "{} ({}, {notes}, {}–{})",
+            self.title(),
+            format_duration(duration, DurationStyle::DecimalHours),
+            start.format("%Y-%m-%d %H:%M"),
+            stop.format(stop_format)
+        )
+    }
+}
+
+/// See [`TimeBox::summary_line`], in UTC.
+impl Display for TimeBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.summary_line(&Utc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{note, time, time_box_of};
+
+    #[test]
+    fn note_durations_sum_to_the_total() -> Result<()> {
+        let start: DateTime<Utc> = "2024-06-03T08:00:00Z".parse().unwrap();
+        let tb = time_box_of(vec![
+            note(start, "begin"),
+            note(start + TimeDelta::minutes(20), "middle"),
+            note(start + TimeDelta::minutes(95), "end"),
+        ]);
+
+        let deltas: Vec<TimeDelta> = tb.notes_with_durations(false).map(|(_, d)| d).collect();
+        assert_eq!(
+            vec![
+                TimeDelta::minutes(20),
+                TimeDelta::minutes(75),
+                TimeDelta::zero()
+            ],
+            deltas
+        );
+        assert_eq!(tb.timedelta_total()?, deltas.into_iter().sum());
+
+        Ok(())
+    }
+
+    #[test]
+    fn last_note_of_active_time_box_lasts_until_now() {
+        let tb = time_box_of(vec![note(Utc::now() - TimeDelta::minutes(30), "begin")]);
+
+        let (_, delta) = tb.notes_with_durations(true).last().unwrap();
+        assert!(delta >= TimeDelta::minutes(30) && delta < TimeDelta::minutes(31));
+    }
+
+    #[test]
+    fn pauses_do_not_count_towards_durations() -> Result<()> {
+        let start = Utc::now() - TimeDelta::hours(4);
+        let mut tb = TimeBox {
+            pauses: vec![Pause {
+                start: start + TimeDelta::hours(1),
+                stop: Some(start + TimeDelta::minutes(90)),
+            }],
+            ..time_box_of(vec![
+                note(start, "begin"),
+                note(start + TimeDelta::hours(3), "end"),
+            ])
+        };
+
+        assert_eq!(None, tb.paused_since());
+        assert_eq!(TimeDelta::minutes(150), tb.timedelta_total()?);
+        assert_eq!(2.5, tb.duration_in_hours()?);
+
+        // The open pause lasts until now, but only counts until the last note for the total
+        let paused_at = start + TimeDelta::minutes(150);
+        tb.pauses.push(Pause {
+            start: paused_at,
+            stop: None,
+        });
+
+        assert_eq!(Some(paused_at), tb.paused_since());
+        assert_eq!(TimeDelta::minutes(120), tb.timedelta_total()?);
+        let active = tb.timedelta_active()?;
+        assert!(active >= TimeDelta::minutes(120) && active < TimeDelta::minutes(121));
+
+        Ok(())
+    }
+
+    #[test]
+    fn title_skips_leading_system_notes() -> Result<()> {
+        let start: DateTime<Utc> = "2024-06-03T08:00:00Z".parse().unwrap();
+        let system = |time, description| TimeBoxNote {
+            kind: NoteKind::System,
+            ..note(time, description)
+        };
+        let mut tb = time_box_of(vec![
+            system(start, "resumed after 15m"),
+            note(start + TimeDelta::minutes(5), "Fix login\nwith details"),
+            system(start + TimeDelta::minutes(20), "idle for 10m"),
+        ]);
+        assert_eq!("Fix login", tb.title());
+
+        tb.notes.remove(1);
+        assert_eq!("resumed after 15m", tb.title());
+
+        let json = serde_json::to_string(&tb.notes).map_err(crate::Error::Serialization)?;
+        assert!(json.contains(r#""kind":"system""#));
+
+        // Stores from before there were kinds only contain regular notes
+        let parsed: TimeBoxNote =
+            serde_json::from_str(r#"{"time":"2024-06-03T08:00:00Z","description":"a"}"#)
+                .map_err(crate::Error::Deserialization)?;
+        assert_eq!(NoteKind::Regular, parsed.kind);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tags_get_extracted_from_descriptions() {
+        let time = Utc::now();
+        let tags = |description: &str| note(time, description).tags();
+
+        assert!(tags("No tags, not even C# or fix #42").is_empty());
+        assert_eq!(vec!["client-a", "bug"], tags("#Client-A: fix login (#bug)"));
+        assert_eq!(vec!["review", "ops_2"], tags("#review, #ops_2. #REVIEW!"));
+        assert_eq!(vec!["später"], tags("Ticket ##x #später-"));
+        // Tags only start at the beginning of a word
+        assert_eq!(vec!["a"], tags("#a#b foo#bar"));
+
+        let tb = time_box_of(vec![
+            note(time, "#ClientA standup"),
+            note(time, "#meeting with #clienta"),
+        ]);
+        assert_eq!(vec!["clienta", "meeting"], tb.tags());
+    }
+
+    #[test]
+    fn estimate_is_serialized_as_seconds() -> Result<()> {
+        let start: DateTime<Utc> = "2024-06-03T08:00:00Z".parse().unwrap();
+        let mut tb = TimeBox {
+            estimate: Some(TimeDelta::minutes(90)),
+            ..time_box_of(vec![note(start, "begin")])
+        };
+
+        let json = serde_json::to_value(&tb).map_err(Error::Serialization)?;
+        assert_eq!(5400, json["estimate"]);
+        assert_eq!(tb, serde_json::from_value(json)?);
+
+        // Stores from before estimates existed
+        tb.estimate = None;
+        let json = r#"{"notes":[{"time":"2024-06-03T08:00:00Z","description":"begin"}]}"#;
+        assert_eq!(tb, serde_json::from_str(json)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reference_is_serialized_as_ref_and_tickets_are_found_in_notes() -> Result<()> {
+        let start: DateTime<Utc> = "2024-06-03T08:00:00Z".parse().unwrap();
+        let mut tb = TimeBox {
+            reference: Some("PROJ-123".to_string()),
+            ..time_box_of(vec![
+                note(start, "PROJ-123: fix login, see (PROJ-123)"),
+                note(start, "also AB2-7 but not proj-1, X-, -12 or A1-B2"),
+            ])
+        };
+
+        let json = serde_json::to_value(&tb).map_err(Error::Serialization)?;
+        assert_eq!("PROJ-123", json["ref"]);
+        assert_eq!(tb, serde_json::from_value(json)?);
+
+        assert_eq!(vec!["PROJ-123", "AB2-7"], tb.ticket_ids());
+        tb.notes.truncate(1);
+        assert_eq!(vec!["PROJ-123"], tb.ticket_ids());
+
+        Ok(())
+    }
+
+    fn summarized() -> TimeBox {
+        time_box_of(vec![
+            note(
+                time("2024-06-12T09:00:00Z"),
+                "Fix login bug\nSession expired too early",
+            ),
+            note(time("2024-06-12T09:40:00Z"), "Found it"),
+            note(time("2024-06-12T10:25:00Z"), "Done"),
+        ])
+    }
+
+    #[test]
+    fn empty_time_box_has_neither_start_nor_stop() {
+        let tb = time_box_of(Vec::new());
+
+        assert!(matches!(tb.time_start(), Err(Error::EmptyTimeBox)));
+        assert!(matches!(tb.time_stop(), Err(Error::EmptyTimeBox)));
+        assert!(matches!(tb.timedelta_total(), Err(Error::EmptyTimeBox)));
+    }
+
+    #[test]
+    fn display_note_in_utc() {
+        let note = note(time("2024-06-12T09:00:00Z"), "Fix login bug");
+        assert_eq!("2024-06-12 09:00 — Fix login bug", note.to_string());
+    }
+
+    #[test]
+    fn display_time_box_as_summary_line_in_utc() {
+        let mut tb = summarized();
+        assert_eq!(
+            "Fix login bug (1.42h, 3 notes, 2024-06-12 09:00–10:25)",
+            tb.to_string()
+        );
+
+        tb.notes.truncate(1);
+        assert_eq!(
+            "Fix login bug (0.00h, 1 note, 2024-06-12 09:00–09:00)",
+            tb.to_string()
+        );
+
+        tb.notes.clear();
+        assert_eq!("Time box without notes", tb.to_string());
+    }
+
+    #[test]
+    fn summary_line_in_other_time_zone_shows_stop_date_when_it_differs() {
+        let tb = summarized();
+        let tokyo = chrono::FixedOffset::east_opt(9 * 3600).unwrap();
+        let honolulu = chrono::FixedOffset::west_opt(10 * 3600).unwrap();
+
+        assert_eq!(
+            "Fix login bug (1.42h, 3 notes, 2024-06-12 18:00–19:25)",
+            tb.summary_line(&tokyo)
+        );
+        assert_eq!(
+            "Fix login bug (1.42h, 3 notes, 2024-06-11 23:00–2024-06-12 00:25)",
+            tb.summary_line(&honolulu)
+        );
+    }
+
+    #[test]
+    fn description_summary_shortens_the_first_line_by_width() {
+        let tb = summarized();
+        assert_eq!("Fix login bug", tb.description_summary(13));
+        assert_eq!("Fix login bug", tb.description_summary(50));
+        assert_eq!("Fix login…", tb.description_summary(10));
+        assert_eq!("…", tb.description_summary(1));
+        assert_eq!("", tb.description_summary(0));
+
+        // Wide characters take two columns and never get cut in half
+        let wide = TimeBox {
+            notes: vec![note(time("2024-06-12T09:00:00Z"), "会議の準備\nAgenda")],
+            ..summarized()
+        };
+        assert_eq!("会議の準備", wide.description_summary(10));
+        assert_eq!("会議…", wide.description_summary(6));
+        assert_eq!("会議…", wide.description_summary(5));
+
+        let empty = TimeBox {
+            notes: Vec::new(),
+            ..summarized()
+        };
+        assert_eq!("", empty.description_summary(10));
+    }
 }
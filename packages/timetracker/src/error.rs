@@ -1,22 +1,101 @@
-use crate::TimeBoxNote;
+use std::path::PathBuf;
 
+use chrono::{DateTime, Utc};
+
+use crate::{TimeBoxNote, ValidationIssue};
+
+/// New variants are no breaking change, so matching needs a wildcard.
+/// For stable identifiers, e.g. to show in a UI, see `Error::code`.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     Serialization(serde_json::Error),
     Deserialization(serde_json::Error),
     Io(std::io::Error),
+    TomlSerialization(toml::ser::Error),
+    TomlDeserialization(toml::de::Error),
+    #[cfg(feature = "cache")]
+    Cache(postcard::Error),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
 
     ActiveTimeBoxIsMissingNote,
+    /// The finished time box at `index` of the store has no notes, see validation.
     TimeBoxIsMissingNote {
         index: usize,
     },
+    /// A time box without notes has no start or stop, e.g. one that was not validated.
+    EmptyTimeBox,
     /// Means the time of note at `[index]` comes before `[index - 1]`.
     /// Notes should always be linearly sorted, since they are a chronological journal.
     TimeBoxNoteIsNotLinearlySorted(TimeBoxNote),
+    /// Descriptions contain content that is not allowed, see `MAX_DESCRIPTION_LEN` for example.
+    InvalidContent(Vec<ValidationIssue>),
+    /// Description has `len` characters, more than the allowed `max`.
+    DescriptionTooLong {
+        len: usize,
+        max: usize,
+    },
 
     ActiveTimeBoxExistsAlready,
     NoActiveTimeBox,
+    ActiveTimeBoxIsPausedAlready,
+    ActiveTimeBoxIsNotPaused,
     NoTimeBox,
+    /// There is no finished time box at `index`, there are only `len` many.
+    TimeBoxIndexOutOfBounds {
+        index: usize,
+        len: usize,
+    },
+
+    /// Durations can only be rounded to positive multiples.
+    InvalidRoundingGranularity(chrono::TimeDelta),
+
+    /// The regular expression of a `SearchPattern` does not compile.
+    InvalidSearchPattern(regex::Error),
+
+    /// The change would leave a time box without notes, every time box needs at least one.
+    CannotDeleteLastNote,
+
+    /// The active time box has no note at `index`, there are only `len` many.
+    NoteIndexOutOfBounds {
+        index: usize,
+        len: usize,
+    },
+
+    /// A time box starting at `start` would overlap a finished one stopping at `stop`.
+    OverlapsFinishedTimeBox {
+        start: DateTime<Utc>,
+        stop: DateTime<Utc>,
+    },
+
+    /// Line `line` of an event log could not be parsed or replayed.
+    InvalidEvent {
+        line: usize,
+        error: Box<Error>,
+    },
+    /// Line `line` of a JSON Lines store is no time box.
+    InvalidLine {
+        line: usize,
+        error: Box<Error>,
+    },
+
+    /// The store of the single binary version has a `version` that cannot be migrated.
+    UnknownLegacyVersion(String),
+    /// A task of the single binary version starts or stops at another time than its notes,
+    /// `index` of the finished task or `None` for the pending one.
+    LegacyTaskTimesDisagree {
+        index: Option<usize>,
+    },
+
+    /// Another process holds the lock of the store at `path` and did not release it in time.
+    StorageLocked {
+        path: PathBuf,
+    },
+    /// The change touches time boxes starting before `before`, see `StoreSettings::locked_before`.
+    TimeBoxLocked {
+        before: DateTime<Utc>,
+    },
 }
 
 #[derive(Debug)]
@@ -26,12 +105,386 @@ pub enum StoreValidationError {
     FinishedTaskIsUnsorted { index: usize },
 }
 
+impl Error {
+    /// The message followed by the ones of its sources, for showing the whole chain on one line.
+    /// `anyhow` shows the sources on its own, e.g. via `{:#}`.
+    pub fn message_with_sources(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            message.push_str(&format!(": {error}"));
+            source = error.source();
+        }
+
+        message
+    }
+
+    /// Stable snake case identifier of the variant, unlike the message it never changes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Serialization(_) => "serialization",
+            Error::Deserialization(_) => "deserialization",
+            Error::Io(_) => "io",
+            Error::TomlSerialization(_) => "toml_serialization",
+            Error::TomlDeserialization(_) => "toml_deserialization",
+            #[cfg(feature = "cache")]
+            Error::Cache(_) => "cache",
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(_) => "sqlite",
+
+            Error::ActiveTimeBoxIsMissingNote => "active_time_box_missing_note",
+            Error::TimeBoxIsMissingNote { .. } => "time_box_missing_note",
+            Error::EmptyTimeBox => "empty_time_box",
+            Error::TimeBoxNoteIsNotLinearlySorted(_) => "notes_not_sorted",
+            Error::InvalidContent(_) => "invalid_content",
+            Error::DescriptionTooLong { .. } => "description_too_long",
+
+            Error::ActiveTimeBoxExistsAlready => "active_time_box_exists",
+            Error::NoActiveTimeBox => "no_active_time_box",
+            Error::ActiveTimeBoxIsPausedAlready => "paused_already",
+            Error::ActiveTimeBoxIsNotPaused => "not_paused",
+            Error::NoTimeBox => "no_time_box",
+            Error::TimeBoxIndexOutOfBounds { .. } => "time_box_index_out_of_bounds",
+
+            Error::InvalidRoundingGranularity(_) => "invalid_rounding_granularity",
+            Error::InvalidSearchPattern(_) => "invalid_search_pattern",
+            Error::CannotDeleteLastNote => "cannot_delete_last_note",
+            Error::NoteIndexOutOfBounds { .. } => "note_index_out_of_bounds",
+            Error::OverlapsFinishedTimeBox { .. } => "overlaps_finished_time_box",
+
+            Error::InvalidEvent { .. } => "invalid_event",
+            Error::InvalidLine { .. } => "invalid_line",
+
+            Error::UnknownLegacyVersion(_) => "unknown_legacy_version",
+            Error::LegacyTaskTimesDisagree { .. } => "legacy_task_times_disagree",
+
+            Error::StorageLocked { .. } => "store_locked",
+            Error::TimeBoxLocked { .. } => "time_box_locked",
+        }
+    }
+}
+
 // // // Error Boilerplate // // //
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{self:?}")
+        match self {
+            Error::Serialization(_) => write!(f, "could not write JSON"),
+            Error::Deserialization(_) => write!(f, "could not parse JSON"),
+            Error::Io(_) => write!(f, "could not access a file"),
+            Error::TomlSerialization(_) => write!(f, "could not write TOML"),
+            Error::TomlDeserialization(_) => write!(f, "could not parse TOML"),
+            #[cfg(feature = "cache")]
+            Error::Cache(_) => write!(f, "could not read or write the cache"),
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(_) => write!(f, "could not access the SQLite database"),
+
+            Error::ActiveTimeBoxIsMissingNote => write!(f, "the active time box has no notes"),
+            Error::TimeBoxIsMissingNote { index } => {
+                write!(f, "the finished time box {index} has no notes")
+            }
+            Error::EmptyTimeBox => write!(f, "the time box has no notes"),
+            Error::TimeBoxNoteIsNotLinearlySorted(note) => write!(
+                f,
+                "the note \"{}\" at {} is earlier than the note before it",
+                note.description, note.time
+            ),
+            Error::InvalidContent(issues) => {
+                write!(f, "the store contains invalid content: ")?;
+                for (i, issue) in issues.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "; " };
+                    write!(f, "{separator}{issue}")?;
+                }
+                Ok(())
+            }
+            Error::DescriptionTooLong { len, max } => write!(
+                f,
+                "the description has {len} characters, more than the allowed {max}"
+            ),
+
+            Error::ActiveTimeBoxExistsAlready => write!(f, "there is an active time box already"),
+            Error::NoActiveTimeBox => write!(f, "there is no active time box"),
+            Error::ActiveTimeBoxIsPausedAlready => {
+                write!(f, "the active time box is paused already")
+            }
+            Error::ActiveTimeBoxIsNotPaused => write!(f, "the active time box is not paused"),
+            Error::NoTimeBox => write!(f, "there is no time box"),
+            Error::TimeBoxIndexOutOfBounds { index, len } => write!(
+                f,
+                "there is no finished time box at index {index}, there are only {len}"
+            ),
+
+            Error::InvalidRoundingGranularity(granularity) => write!(
+                f,
+                "durations can only be rounded to positive multiples, not to {} minutes",
+                granularity.num_minutes()
+            ),
+
+            Error::InvalidSearchPattern(e) => write!(f, "invalid regular expression: {e}"),
+
+            Error::CannotDeleteLastNote => write!(
+                f,
+                "the time box would be left without notes, every time box needs at least one"
+            ),
+
+            Error::NoteIndexOutOfBounds { index, len } => write!(
+                f,
+                "the active time box has no note at index {index}, there are only {len}"
+            ),
+
+            Error::OverlapsFinishedTimeBox { start, stop } => write!(
+                f,
+                "a time box starting at {start} would overlap the finished one stopping at {stop}"
+            ),
+
+            Error::InvalidEvent { line, .. } => {
+                write!(f, "line {line} of the event log is invalid")
+            }
+            Error::InvalidLine { line, .. } => {
+                write!(f, "line {line} of the store is invalid")
+            }
+
+            Error::UnknownLegacyVersion(version) => write!(
+                f,
+                "the legacy store has the unknown version \"{version}\", only \"V1\" can be migrated"
+            ),
+            Error::LegacyTaskTimesDisagree { index } => {
+                match index {
+                    Some(index) => write!(f, "the finished legacy task {index}")?,
+                    None => write!(f, "the pending legacy task")?,
+                };
+                write!(f, " starts or stops at another time than its notes")
+            }
+
+            Error::StorageLocked { path } => write!(
+                f,
+                "the store is locked by another process, see \"{}\"",
+                path.display()
+            ),
+            Error::TimeBoxLocked { before } => write!(
+                f,
+                "time boxes starting before {before} are locked against changes"
+            ),
+        }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Serialization(e) | Error::Deserialization(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::TomlSerialization(e) => Some(e),
+            Error::TomlDeserialization(e) => Some(e),
+            #[cfg(feature = "cache")]
+            Error::Cache(e) => Some(e),
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(e) => Some(e),
+            Error::InvalidSearchPattern(e) => Some(e),
+            Error::InvalidEvent { error, .. } | Error::InvalidLine { error, .. } => {
+                Some(error.as_ref())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Reading is where JSON usually fails, so `?` assumes parsing.
+/// Writing keeps mapping explicitly via `map_err(Error::Serialization)`.
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Deserialization(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::*;
+
+    #[test]
+    fn messages_are_human_readable() {
+        assert_eq!(
+            "there is no active time box",
+            Error::NoActiveTimeBox.to_string()
+        );
+        assert_eq!(
+            "there is no finished time box at index 3, there are only 2",
+            Error::TimeBoxIndexOutOfBounds { index: 3, len: 2 }.to_string()
+        );
+
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "storage.json is missing");
+        let error = Error::from(missing);
+        assert_eq!("could not access a file", error.to_string());
+        assert_eq!(
+            "could not access a file: storage.json is missing",
+            error.message_with_sources()
+        );
+        assert_eq!(
+            "storage.json is missing",
+            error.source().unwrap().to_string()
+        );
+
+        let parse = serde_json::from_str::<u32>("{").unwrap_err();
+        let error = Error::from(parse);
+        assert!(matches!(error, Error::Deserialization(_)));
+        assert_eq!("could not parse JSON", error.to_string());
+        assert!(error.source().is_some());
+
+        let nested = Error::InvalidEvent {
+            line: 4,
+            error: Box::new(Error::NoActiveTimeBox),
+        };
+        assert_eq!("line 4 of the event log is invalid", nested.to_string());
+        assert_eq!(
+            "line 4 of the event log is invalid: there is no active time box",
+            nested.message_with_sources()
+        );
+        assert!(Error::NoActiveTimeBox.source().is_none());
+    }
+
+    #[test]
+    fn every_variant_has_a_unique_snake_case_code() {
+        let json = || serde_json::from_str::<u32>("x").unwrap_err();
+        let time = DateTime::<Utc>::default();
+        let errors = vec![
+            Error::Serialization(json()),
+            Error::Deserialization(json()),
+            Error::Io(std::io::Error::other("io")),
+            Error::TomlSerialization(toml::to_string(&1).unwrap_err()),
+            Error::TomlDeserialization(toml::from_str::<toml::Table>("=").unwrap_err()),
+            #[cfg(feature = "cache")]
+            Error::Cache(postcard::Error::DeserializeUnexpectedEnd),
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(rusqlite::Error::QueryReturnedNoRows),
+            Error::ActiveTimeBoxIsMissingNote,
+            Error::TimeBoxIsMissingNote { index: 0 },
+            Error::EmptyTimeBox,
+            Error::TimeBoxNoteIsNotLinearlySorted(TimeBoxNote {
+                time,
+                description: String::new(),
+                kind: Default::default(),
+            }),
+            Error::InvalidContent(Vec::new()),
+            Error::DescriptionTooLong { len: 2, max: 1 },
+            Error::ActiveTimeBoxExistsAlready,
+            Error::NoActiveTimeBox,
+            Error::ActiveTimeBoxIsPausedAlready,
+            Error::ActiveTimeBoxIsNotPaused,
+            Error::NoTimeBox,
+            Error::TimeBoxIndexOutOfBounds { index: 1, len: 0 },
+            Error::InvalidRoundingGranularity(chrono::TimeDelta::zero()),
+            Error::InvalidSearchPattern(regex::Error::CompiledTooBig(0)),
+            Error::CannotDeleteLastNote,
+            Error::NoteIndexOutOfBounds { index: 1, len: 0 },
+            Error::OverlapsFinishedTimeBox {
+                start: time,
+                stop: time,
+            },
+            Error::InvalidEvent {
+                line: 1,
+                error: Box::new(Error::NoActiveTimeBox),
+            },
+            Error::InvalidLine {
+                line: 1,
+                error: Box::new(Error::NoActiveTimeBox),
+            },
+            Error::UnknownLegacyVersion("V0".to_string()),
+            Error::LegacyTaskTimesDisagree { index: None },
+            Error::StorageLocked {
+                path: PathBuf::from("storage.lock"),
+            },
+            Error::TimeBoxLocked { before: time },
+        ];
+
+        // Fails to compile for new variants, add them to `errors` above as well
+        for error in &errors {
+            match error {
+                Error::Serialization(_)
+                | Error::Deserialization(_)
+                | Error::Io(_)
+                | Error::TomlSerialization(_)
+                | Error::TomlDeserialization(_)
+                | Error::ActiveTimeBoxIsMissingNote
+                | Error::TimeBoxIsMissingNote { .. }
+                | Error::EmptyTimeBox
+                | Error::TimeBoxNoteIsNotLinearlySorted(_)
+                | Error::InvalidContent(_)
+                | Error::DescriptionTooLong { .. }
+                | Error::ActiveTimeBoxExistsAlready
+                | Error::NoActiveTimeBox
+                | Error::ActiveTimeBoxIsPausedAlready
+                | Error::ActiveTimeBoxIsNotPaused
+                | Error::NoTimeBox
+                | Error::TimeBoxIndexOutOfBounds { .. }
+                | Error::InvalidRoundingGranularity(_)
+                | Error::InvalidSearchPattern(_)
+                | Error::CannotDeleteLastNote
+                | Error::NoteIndexOutOfBounds { .. }
+                | Error::OverlapsFinishedTimeBox { .. }
+                | Error::InvalidEvent { .. }
+                | Error::InvalidLine { .. }
+                | Error::UnknownLegacyVersion(_)
+                | Error::LegacyTaskTimesDisagree { .. }
+                | Error::StorageLocked { .. }
+                | Error::TimeBoxLocked { .. } => (),
+                #[cfg(feature = "cache")]
+                Error::Cache(_) => (),
+                #[cfg(feature = "sqlite")]
+                Error::Sqlite(_) => (),
+            }
+        }
+
+        let codes: std::collections::HashSet<_> = errors.iter().map(Error::code).collect();
+        assert_eq!(errors.len(), codes.len(), "{codes:?}");
+        for code in codes {
+            assert!(
+                !code.starts_with('_')
+                    && !code.ends_with('_')
+                    && !code.contains("__")
+                    && code.chars().all(|c| c.is_ascii_lowercase() || c == '_'),
+                "{code}"
+            );
+        }
+        assert_eq!("no_active_time_box", Error::NoActiveTimeBox.code());
+    }
+
+    #[test]
+    fn wrapped_errors_show_up_in_the_chain() {
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "storage.json is missing");
+        let error = anyhow::Error::new(Error::Io(missing)).context("Failed to load tracked time");
+        let chain: Vec<String> = error.chain().map(|e| e.to_string()).collect();
+        assert_eq!(
+            vec![
+                "Failed to load tracked time",
+                "could not access a file",
+                "storage.json is missing",
+            ],
+            chain
+        );
+        assert_eq!(
+            "Failed to load tracked time: could not access a file: storage.json is missing",
+            format!("{error:#}")
+        );
+
+        let serialization = Error::Serialization(serde_json::from_str::<u32>("x").unwrap_err());
+        assert!(serialization.source().is_some());
+
+        // Nested errors lead down to the innermost one
+        let line = Error::InvalidLine {
+            line: 2,
+            error: Box::new(Error::Deserialization(
+                serde_json::from_str::<u32>("{").unwrap_err(),
+            )),
+        };
+        let innermost = line.source().and_then(|e| e.source());
+        assert!(innermost.is_some_and(|e| e.is::<serde_json::Error>()));
+    }
+}
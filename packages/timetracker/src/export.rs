@@ -0,0 +1,118 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, TimeBox, TimeBoxNote};
+
+/// Time box as exported for other tools, with the values they would otherwise compute themselves.
+/// Pauses are not part of it, so importing it again loses them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedTimeBox {
+    pub time_start: DateTime<Utc>,
+    /// `None` while the time box is active.
+    pub time_stop: Option<DateTime<Utc>>,
+    pub hours: f64,
+    pub notes: Vec<TimeBoxNote>,
+    /// See `TimeBox::reference`, missing in exports from before there were references.
+    #[serde(default, rename = "ref")]
+    pub reference: Option<String>,
+}
+
+impl ExportedTimeBox {
+    /// Takes the `duration` instead of computing it, so exports can clamp or round it like everywhere else.
+    pub fn finished(time_box: &TimeBox, duration: TimeDelta) -> Result<Self> {
+        Ok(Self {
+            time_start: time_box.time_start()?,
+            time_stop: Some(time_box.time_stop()?),
+            hours: hours(duration),
+            notes: time_box.notes.clone(),
+            reference: time_box.reference.clone(),
+        })
+    }
+
+    /// The time box it was exported from, without what the export leaves out, i.e. pauses, the estimate
+    /// and when it was exported.
+    pub fn to_time_box(&self) -> TimeBox {
+        TimeBox {
+            notes: self.notes.clone(),
+            exported_at: None,
+            pauses: Vec::new(),
+            estimate: None,
+            reference: self.reference.clone(),
+        }
+    }
+
+    /// Counts the time until now, see `TimeBox::timedelta_active`.
+    pub fn active(time_box: &TimeBox) -> Result<Self> {
+        Ok(Self {
+            time_start: time_box.time_start()?,
+            time_stop: None,
+            hours: hours(time_box.timedelta_active()?),
+            notes: time_box.notes.clone(),
+            reference: time_box.reference.clone(),
+        })
+    }
+}
+
+/// Everything an export contains, serialized as one object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeTrackerExport {
+    pub active: Option<ExportedTimeBox>,
+    pub finished: Vec<ExportedTimeBox>,
+    /// Sum of the hours of `finished`, the active time box is still running.
+    pub total_hours: f64,
+}
+
+impl TimeTrackerExport {
+    pub fn new(active: Option<ExportedTimeBox>, finished: Vec<ExportedTimeBox>) -> Self {
+        Self {
+            active,
+            total_hours: finished.iter().map(|tb| tb.hours).sum(),
+            finished,
+        }
+    }
+}
+
+fn hours(duration: TimeDelta) -> f64 {
+    duration.num_seconds() as f64 / 60.0 / 60.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NoteKind, test_fixtures::time_box_of};
+
+    #[test]
+    fn exported_hours_match_the_time_box() -> Result<()> {
+        let start: DateTime<Utc> = "2024-06-03T08:00:00Z".parse().unwrap();
+        let note = |minutes: i64, description: &str| TimeBoxNote {
+            time: start + TimeDelta::minutes(minutes),
+            description: description.to_string(),
+            kind: NoteKind::Regular,
+        };
+        let finished = [
+            time_box_of(vec![note(0, "begin"), note(85, "end")]),
+            time_box_of(vec![note(120, "begin"), note(150, "end")]),
+        ];
+
+        let exported = finished
+            .iter()
+            .map(|tb| ExportedTimeBox::finished(tb, tb.timedelta_total()?))
+            .collect::<Result<Vec<_>>>()?;
+        for (tb, exported) in finished.iter().zip(exported.iter()) {
+            assert_eq!(tb.duration_in_hours()?, exported.hours);
+            assert_eq!(Some(tb.time_stop()?), exported.time_stop);
+        }
+
+        let mut active = finished[0].clone();
+        active.notes[0].time = Utc::now() - TimeDelta::minutes(90);
+        active.notes.truncate(1);
+        let active = ExportedTimeBox::active(&active)?;
+        assert_eq!(None, active.time_stop);
+        assert!((1.5..1.6).contains(&active.hours));
+
+        let export = TimeTrackerExport::new(Some(active), exported);
+        assert_eq!(115.0 / 60.0, export.total_hours);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Utc, Weekday};
+
+use crate::TimeBox;
+
+/// How to reach a weekly goal of tracked time within the remaining work days of the ISO week.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeeklyGoalPlan {
+    /// Time left to track, zero once the goal is met
+    pub remaining: TimeDelta,
+    /// Work days from today until the end of the week, including today since there may be time left
+    pub days: Vec<NaiveDate>,
+    /// `remaining` split evenly across `days`, rounded up to whole minutes.
+    /// `None` if the goal is met or there are no work days left.
+    pub per_day: Option<TimeDelta>,
+    /// Whether the goal is met or `per_day` fits into the work hours of a day
+    pub reachable: bool,
+}
+
+impl WeeklyGoalPlan {
+    pub fn is_met(&self) -> bool {
+        self.remaining <= TimeDelta::zero()
+    }
+}
+
+/// Splits what is left of `goal` after `tracked` across the `work_days` left in the week of `today`.
+/// Takes `today` instead of looking at the clock, so any day of the week can be planned for.
+pub fn plan_weekly_goal(
+    goal: TimeDelta,
+    tracked: TimeDelta,
+    today: NaiveDate,
+    work_days: &[Weekday],
+    hours_per_day: TimeDelta,
+) -> WeeklyGoalPlan {
+    let remaining = (goal - tracked).max(TimeDelta::zero());
+
+    let days: Vec<NaiveDate> = today
+        .iter_days()
+        .take(7 - today.weekday().num_days_from_monday() as usize)
+        .filter(|date| work_days.contains(&date.weekday()))
+        .collect();
+
+    let per_day = match (remaining > TimeDelta::zero(), days.len() as i64) {
+        (false, _) | (_, 0) => None,
+        (true, count) => {
+            let minutes = (remaining.num_seconds() + 59) / 60;
+            Some(TimeDelta::minutes((minutes + count - 1) / count))
+        }
+    };
+
+    let reachable = match per_day {
+        Some(per_day) => per_day <= hours_per_day,
+        None => remaining <= TimeDelta::zero(),
+    };
+
+    WeeklyGoalPlan {
+        remaining,
+        days,
+        per_day,
+        reachable,
+    }
+}
+
+/// Time of `time_box` between `from` and `to`, excluding pauses. Time boxes crossing `from` or `to` count partially.
+/// `stop` ends the time box, i.e. its last note once finished or now while it is active.
+/// Negative durations count as zero, like `clamped_duration`.
+pub fn timedelta_in_range(
+    time_box: &TimeBox,
+    stop: DateTime<Utc>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> TimeDelta {
+    let overlap = |start: DateTime<Utc>, stop: DateTime<Utc>| {
+        stop.min(to)
+            .signed_duration_since(start.max(from))
+            .max(TimeDelta::zero())
+    };
+
+    let Ok(start) = time_box.time_start() else {
+        return TimeDelta::zero();
+    };
+    let paused: TimeDelta = time_box
+        .pauses
+        .iter()
+        .map(|p| overlap(p.start, p.stop.unwrap_or(stop).min(stop)))
+        .sum();
+
+    (overlap(start, stop) - paused).max(TimeDelta::zero())
+}
+
+/// Hours of the finished `time_boxes` between `from` and `to`, see `timedelta_in_range`.
+pub fn hours_in_range(time_boxes: &[TimeBox], from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    let total: TimeDelta = time_boxes
+        .iter()
+        .filter_map(|tb| Some(timedelta_in_range(tb, tb.time_stop().ok()?, from, to)))
+        .sum();
+
+    total.num_seconds() as f64 / 60.0 / 60.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Pause,
+        test_fixtures::{time, time_box},
+    };
+
+    const WORK_WEEK: [Weekday; 5] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+    ];
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn plan(tracked_minutes: i64, today: &str) -> WeeklyGoalPlan {
+        plan_weekly_goal(
+            TimeDelta::hours(40),
+            TimeDelta::minutes(tracked_minutes),
+            date(today),
+            &WORK_WEEK,
+            TimeDelta::hours(8),
+        )
+    }
+
+    #[test]
+    fn remaining_hours_get_split_across_the_remaining_work_days() {
+        // Wednesday, 2024-06-12
+        let plan = plan(30 * 60 + 30, "2024-06-12");
+
+        assert_eq!(TimeDelta::minutes(9 * 60 + 30), plan.remaining);
+        assert_eq!(
+            vec![date("2024-06-12"), date("2024-06-13"), date("2024-06-14")],
+            plan.days
+        );
+        // 190 minutes each, rounded up so the goal is met
+        assert_eq!(Some(TimeDelta::minutes(190)), plan.per_day);
+        assert!(plan.reachable);
+        assert!(!plan.is_met());
+    }
+
+    #[test]
+    fn friday_evening_may_be_too_late() {
+        let friday = "2024-06-14";
+
+        let almost = plan(34 * 60, friday);
+        assert_eq!(vec![date(friday)], almost.days);
+        assert_eq!(Some(TimeDelta::hours(6)), almost.per_day);
+        assert!(almost.reachable);
+
+        let behind = plan(30 * 60 + 30, friday);
+        assert_eq!(Some(TimeDelta::minutes(9 * 60 + 30)), behind.per_day);
+        assert!(!behind.reachable);
+
+        // Nothing left to work on during the weekend
+        let saturday = plan(39 * 60, "2024-06-15");
+        assert!(saturday.days.is_empty());
+        assert_eq!(None, saturday.per_day);
+        assert!(!saturday.reachable);
+    }
+
+    #[test]
+    fn met_goals_need_no_more_work() {
+        for tracked in [40 * 60, 41 * 60] {
+            let plan = plan(tracked, "2024-06-12");
+
+            assert!(plan.is_met());
+            assert_eq!(TimeDelta::zero(), plan.remaining);
+            assert_eq!(None, plan.per_day);
+            assert!(plan.reachable);
+        }
+
+        let sunday = plan(40 * 60, "2024-06-16");
+        assert!(sunday.is_met() && sunday.reachable);
+    }
+
+    #[test]
+    fn hours_in_range_count_time_boxes_crossing_the_week_partially() {
+        // ISO week 24 of 2024 runs from Monday 2024-06-10 until Sunday 2024-06-16
+        let from = time("2024-06-10T00:00:00Z");
+        let to = time("2024-06-17T00:00:00Z");
+        let mut within = time_box("2024-06-12T09:00:00Z", TimeDelta::hours(4), "work");
+        within.pauses.push(Pause {
+            start: time("2024-06-12T11:00:00Z"),
+            stop: Some(time("2024-06-12T11:30:00Z")),
+        });
+        let boxes = [
+            time_box("2024-06-09T22:00:00Z", TimeDelta::hours(3), "work"),
+            within,
+            time_box("2024-06-16T23:00:00Z", TimeDelta::hours(3), "work"),
+            time_box("2024-06-18T09:00:00Z", TimeDelta::hours(1), "work"),
+        ];
+
+        assert_eq!(1.0 + 3.5 + 1.0, hours_in_range(&boxes, from, to));
+        assert_eq!(
+            2.0 + 1.0,
+            hours_in_range(&boxes, to, time("2024-06-24T00:00:00Z"))
+        );
+        assert_eq!(0.0, hours_in_range(&[], from, to));
+
+        // Inverted time boxes do not subtract from the week
+        let inverted = time_box("2024-06-12T10:00:00Z", TimeDelta::hours(-1), "work");
+        assert_eq!(0.0, hours_in_range(&[inverted], from, to));
+    }
+
+    #[test]
+    fn active_time_boxes_last_until_the_given_stop() {
+        let mut active = time_box("2024-06-16T22:00:00Z", TimeDelta::zero(), "work");
+        active.notes.truncate(1);
+        active.pauses.push(Pause {
+            start: time("2024-06-16T23:00:00Z"),
+            stop: None,
+        });
+
+        let delta = timedelta_in_range(
+            &active,
+            time("2024-06-17T01:00:00Z"),
+            time("2024-06-10T00:00:00Z"),
+            time("2024-06-17T00:00:00Z"),
+        );
+        assert_eq!(TimeDelta::hours(1), delta);
+    }
+}
@@ -0,0 +1,753 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Diagnostic, Error, ListOptions, ListResult, MAX_DESCRIPTION_LEN, Pause, Query, Repair, Result,
+    SortOrder, StoreSettings, TimeBox, TimeTrackerInitStrategy, TimeTrackerStorageStrategy,
+    TimeTrackingStore, in_memory_tracker::InMemoryTimeTracker,
+};
+
+/// One line of the event log, mirrors the mutating methods of `TimeTrackingStore`.
+/// Events carry the times the original call produced, so replaying them yields the exact same store.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum Event {
+    Begin {
+        time: DateTime<Utc>,
+        description: String,
+    },
+    Note {
+        time: DateTime<Utc>,
+        description: String,
+    },
+    /// A note added at `time` rather than now, see `push_note_at`.
+    NoteAt {
+        time: DateTime<Utc>,
+        description: String,
+    },
+    Pause {
+        time: DateTime<Utc>,
+    },
+    Unpause {
+        time: DateTime<Utc>,
+    },
+    End {},
+    Cancel {},
+    Clear {},
+    ClearFiltered {
+        query: Query,
+    },
+    Amend {
+        description: String,
+    },
+    EditNote {
+        index: usize,
+        description: String,
+    },
+    SetEstimate {
+        #[serde(default, with = "crate::entities::estimate_seconds")]
+        estimate: Option<TimeDelta>,
+    },
+    SetReference {
+        reference: Option<String>,
+    },
+    MergeLastFinished {},
+    SplitActive {
+        index: usize,
+    },
+    Resume {},
+    ResumeAt {
+        index: usize,
+    },
+    SetExportedAt {
+        index: usize,
+        exported_at: Option<DateTime<Utc>>,
+    },
+    SetSettings {
+        settings: StoreSettings,
+    },
+    /// Replaces the whole store, written by compaction.
+    Snapshot {
+        active: Option<TimeBox>,
+        finished: Vec<TimeBox>,
+        /// Snapshots from before there were settings have none.
+        #[serde(default)]
+        settings: StoreSettings,
+    },
+}
+
+impl Event {
+    /// Folds the event into `tracker`, fails just like the original call would have.
+    pub fn apply(&self, tracker: &mut InMemoryTimeTracker) -> Result<()> {
+        match self {
+            Event::Begin { time, description } => {
+                tracker.begin(description)?;
+                set_time_of_last_note(tracker, *time);
+            }
+            Event::Note { time, description } => {
+                tracker.push_note(description)?;
+                set_time_of_last_note(tracker, *time);
+            }
+            Event::NoteAt { time, description } => {
+                tracker.push_note_at(description, *time)?;
+            }
+            Event::Pause { time } => {
+                tracker.pause()?;
+                set_last_pause(tracker, |p| p.start = *time);
+            }
+            Event::Unpause { time } => {
+                tracker.unpause()?;
+                set_last_pause(tracker, |p| p.stop = Some(*time));
+            }
+            Event::End {} => {
+                tracker.end()?;
+            }
+            Event::Cancel {} => {
+                tracker.cancel()?;
+            }
+            Event::Clear {} => {
+                tracker.clear()?;
+            }
+            Event::ClearFiltered { query } => {
+                tracker.clear_filtered(query)?;
+            }
+            Event::Amend { description } => {
+                tracker.amend(description)?;
+            }
+            Event::EditNote { index, description } => {
+                tracker.edit_note(*index, description)?;
+            }
+            Event::SetEstimate { estimate } => {
+                tracker.set_estimate(*estimate)?;
+            }
+            Event::SetReference { reference } => {
+                tracker.set_reference(reference.as_deref())?;
+            }
+            Event::MergeLastFinished {} => {
+                tracker.merge_last_finished()?;
+            }
+            Event::SplitActive { index } => {
+                tracker.split_active(*index)?;
+            }
+            Event::Resume {} => {
+                tracker.resume()?;
+            }
+            Event::ResumeAt { index } => {
+                tracker.resume_at(*index)?;
+            }
+            Event::SetExportedAt { index, exported_at } => {
+                tracker.set_exported_at(*index, *exported_at)?;
+            }
+            Event::SetSettings { settings } => {
+                tracker.set_settings(settings.clone())?;
+            }
+            Event::Snapshot {
+                active,
+                finished,
+                settings,
+            } => {
+                tracker.active = active.clone();
+                tracker.finished = finished.clone().into();
+                tracker.settings = settings.clone();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn set_last_pause(tracker: &mut InMemoryTimeTracker, change: impl FnOnce(&mut Pause)) {
+    if let Some(pause) = tracker.active.as_mut().and_then(|tb| tb.pauses.last_mut()) {
+        change(pause);
+    }
+}
+
+fn set_time_of_last_note(tracker: &mut InMemoryTimeTracker, time: DateTime<Utc>) {
+    if let Some(note) = tracker.active.as_mut().and_then(|tb| tb.notes.last_mut()) {
+        note.time = time;
+    }
+}
+
+/// Where `EventLogTimeTracker::append_rotating` moves a log that grew too large, e.g. `storage.log.1`.
+pub fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+/// Keeps track of the changes since loading, so saving only appends them instead of rewriting the whole store.
+#[derive(Debug, Default)]
+pub struct EventLogTimeTracker {
+    pub tracker: InMemoryTimeTracker,
+    pending: Vec<Event>,
+}
+
+impl EventLogTimeTracker {
+    /// Events that were not appended to the log yet.
+    pub fn pending(&self) -> &[Event] {
+        &self.pending
+    }
+
+    /// Appends the pending events to the log at `path`.
+    /// A missing log gets started with a snapshot instead, see `compact`, so it is complete
+    /// even if the store was loaded from the rotated log after an interrupted rotation.
+    pub fn append(&mut self, path: &Path) -> Result<()> {
+        if !path.exists() {
+            return self.compact(path);
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        let mut writer = BufWriter::new(file);
+        EventLogStorageStrategy {}.append(&mut writer, &self.pending)?;
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+
+        self.pending.clear();
+
+        Ok(())
+    }
+
+    /// Rewrites the log at `path` as a single snapshot of the current store, dropping the history.
+    /// Replaces the file via rename, so a crash leaves either the old or the new log behind.
+    pub fn compact(&mut self, path: &Path) -> Result<()> {
+        let path_swap = path.with_extension(format!("log.{}", std::process::id()));
+
+        let mut writer = BufWriter::new(File::create(&path_swap)?);
+        self.tracker
+            .to_writer(&EventLogStorageStrategy {}, &mut writer)?;
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        drop(writer);
+
+        std::fs::rename(&path_swap, path)?;
+
+        self.pending.clear();
+
+        Ok(())
+    }
+
+    /// Appends like `append`, then rotates the log if it got larger than `max_bytes`:
+    /// the log moves to `rotated_path`, replacing the previously rotated one, and a new log starts with a snapshot.
+    /// `max_bytes` should leave room for the snapshot, otherwise every append rotates.
+    /// Returns whether the log got rotated.
+    ///
+    /// The pending events get appended before rotating, so the rotated log alone replays to the current store
+    /// and no event newer than the new snapshot is ever dropped. A crash after moving the log but before
+    /// writing the snapshot leaves no log at `path`. `EventLogLoadingStrategy` loads the rotated log then,
+    /// and the next `append` writes the snapshot.
+    pub fn append_rotating(&mut self, path: &Path, max_bytes: u64) -> Result<bool> {
+        self.append(path)?;
+
+        if std::fs::metadata(path)?.len() <= max_bytes {
+            return Ok(false);
+        }
+
+        std::fs::rename(path, rotated_path(path))?;
+        self.compact(path)?;
+
+        Ok(true)
+    }
+
+    fn record<T>(&mut self, result: Result<T>, event: impl FnOnce(&T) -> Event) -> Result<T> {
+        if let Ok(value) = result.as_ref() {
+            self.pending.push(event(value));
+        }
+
+        result
+    }
+}
+
+impl TimeTrackingStore for EventLogTimeTracker {
+    fn active(&self) -> Result<Option<TimeBox>> {
+        self.tracker.active()
+    }
+
+    fn finished(&self, options: &ListOptions) -> Result<ListResult> {
+        self.tracker.finished(options)
+    }
+
+    fn begin(&mut self, description: &str) -> Result<TimeBox> {
+        let result = self.tracker.begin(description);
+        self.record(result, |tb| Event::Begin {
+            time: tb.notes.last().map(|n| n.time).unwrap_or_default(),
+            description: description.to_owned(),
+        })
+    }
+
+    fn begin_at(&mut self, description: &str, time: DateTime<Utc>) -> Result<TimeBox> {
+        let result = self.tracker.begin_at(description, time);
+        self.record(result, |_| Event::Begin {
+            time,
+            description: description.to_owned(),
+        })
+    }
+
+    fn push_note(&mut self, description: &str) -> Result<TimeBox> {
+        let result = self.tracker.push_note(description);
+        self.record(result, |tb| Event::Note {
+            time: tb.notes.last().map(|n| n.time).unwrap_or_default(),
+            description: description.to_owned(),
+        })
+    }
+
+    fn push_note_at(&mut self, description: &str, time: DateTime<Utc>) -> Result<TimeBox> {
+        let result = self.tracker.push_note_at(description, time);
+        self.record(result, |_| Event::NoteAt {
+            time,
+            description: description.to_owned(),
+        })
+    }
+
+    fn pause(&mut self) -> Result<TimeBox> {
+        let result = self.tracker.pause();
+        self.record(result, |tb| Event::Pause {
+            time: tb.pauses.last().map(|p| p.start).unwrap_or_default(),
+        })
+    }
+
+    fn unpause(&mut self) -> Result<TimeBox> {
+        let result = self.tracker.unpause();
+        self.record(result, |tb| Event::Unpause {
+            time: tb.pauses.last().and_then(|p| p.stop).unwrap_or_default(),
+        })
+    }
+
+    fn end(&mut self) -> Result<TimeBox> {
+        let was_paused = self
+            .tracker
+            .active
+            .as_ref()
+            .and_then(|tb| tb.paused_since());
+        let result = self.tracker.end();
+
+        // Ending closes the pause at the current time, record it so replaying does not use the time of the replay
+        if was_paused.is_some()
+            && let Ok(tb) = result.as_ref()
+        {
+            let time = tb.pauses.last().and_then(|p| p.stop).unwrap_or_default();
+            self.pending.push(Event::Unpause { time });
+        }
+
+        self.record(result, |_| Event::End {})
+    }
+
+    fn amend(&mut self, description: &str) -> Result<TimeBox> {
+        let result = self.tracker.amend(description);
+        self.record(result, |_| Event::Amend {
+            description: description.to_owned(),
+        })
+    }
+
+    fn edit_note(&mut self, note_index: usize, description: &str) -> Result<TimeBox> {
+        let result = self.tracker.edit_note(note_index, description);
+        self.record(result, |_| Event::EditNote {
+            index: note_index,
+            description: description.to_owned(),
+        })
+    }
+
+    fn set_estimate(&mut self, estimate: Option<TimeDelta>) -> Result<TimeBox> {
+        let result = self.tracker.set_estimate(estimate);
+        self.record(result, |_| Event::SetEstimate { estimate })
+    }
+
+    fn set_reference(&mut self, reference: Option<&str>) -> Result<TimeBox> {
+        let result = self.tracker.set_reference(reference);
+        self.record(result, |tb| Event::SetReference {
+            reference: tb.reference.clone(),
+        })
+    }
+
+    fn split_active(&mut self, note_index: usize) -> Result<(TimeBox, TimeBox)> {
+        let result = self.tracker.split_active(note_index);
+        self.record(result, |_| Event::SplitActive { index: note_index })
+    }
+
+    fn merge_last_finished(&mut self) -> Result<TimeBox> {
+        let result = self.tracker.merge_last_finished();
+        self.record(result, |_| Event::MergeLastFinished {})
+    }
+
+    fn resume(&mut self) -> Result<TimeBox> {
+        let result = self.tracker.resume();
+        self.record(result, |_| Event::Resume {})
+    }
+
+    fn resume_at(&mut self, index: usize) -> Result<TimeBox> {
+        let result = self.tracker.resume_at(index);
+        self.record(result, |_| Event::ResumeAt { index })
+    }
+
+    fn set_exported_at(
+        &mut self,
+        index: usize,
+        exported_at: Option<DateTime<Utc>>,
+    ) -> Result<TimeBox> {
+        let result = self.tracker.set_exported_at(index, exported_at);
+        self.record(result, |_| Event::SetExportedAt { index, exported_at })
+    }
+
+    fn cancel(&mut self) -> Result<TimeBox> {
+        let result = self.tracker.cancel();
+        self.record(result, |_| Event::Cancel {})
+    }
+
+    fn clear(&mut self) -> Result<usize> {
+        let result = self.tracker.clear();
+        self.record(result, |_| Event::Clear {})
+    }
+
+    fn clear_filtered(&mut self, query: &Query) -> Result<usize> {
+        let result = self.tracker.clear_filtered(query);
+        self.record(result, |_| Event::ClearFiltered {
+            query: query.clone(),
+        })
+    }
+
+    fn settings(&self) -> Result<StoreSettings> {
+        self.tracker.settings()
+    }
+
+    fn set_settings(&mut self, settings: StoreSettings) -> Result<()> {
+        let result = self.tracker.set_settings(settings.clone());
+        self.record(result, |_| Event::SetSettings { settings })
+    }
+
+    fn repairs(&self) -> Result<Vec<Repair>> {
+        self.tracker.repairs()
+    }
+
+    fn diagnostics(&self) -> Result<Vec<Diagnostic>> {
+        self.tracker.diagnostics()
+    }
+
+    fn init(strategy: &impl TimeTrackerInitStrategy) -> Result<Self> {
+        Ok(EventLogTimeTracker {
+            tracker: InMemoryTimeTracker::init(strategy)?,
+            pending: Vec::new(),
+        })
+    }
+}
+
+/// Loads an event log by replaying it from the start.
+///
+/// An unparsable last line without line break is the remainder of an interrupted append,
+/// it gets dropped with a warning. Everything else that does not replay fails the load.
+/// Without a log at `path` the rotated log gets loaded with a warning, see `EventLogTimeTracker::append_rotating`.
+#[derive(Debug)]
+pub struct EventLogLoadingStrategy<'a> {
+    pub path: &'a Path,
+    pub strict: bool,
+    pub max_description_len: usize,
+}
+
+impl<'a> EventLogLoadingStrategy<'a> {
+    pub fn new(path: &'a Path) -> Self {
+        Self {
+            path,
+            strict: false,
+            max_description_len: MAX_DESCRIPTION_LEN,
+        }
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn max_description_len(mut self, max_description_len: usize) -> Self {
+        self.max_description_len = max_description_len;
+        self
+    }
+}
+
+impl TimeTrackerInitStrategy for EventLogLoadingStrategy<'_> {
+    fn init(&self) -> Result<impl TimeTrackingStore> {
+        let mut tracker = InMemoryTimeTracker::default();
+
+        let rotated = rotated_path(self.path);
+        let path = match self.path.exists() || !rotated.exists() {
+            true => self.path,
+            false => {
+                tracker.diagnostics.push(
+                    Diagnostic::warning(
+                        "Loading the rotated log because rotating was interrupted, the next append starts the log anew",
+                    )
+                    .context(rotated.display().to_string()),
+                );
+                &rotated
+            }
+        };
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut line = String::new();
+        let mut number = 0;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            number += 1;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let event: Event = match serde_json::from_str(&line) {
+                Ok(event) => event,
+                Err(e) if !line.ends_with('\n') => {
+                    tracker.diagnostics.push(
+                        Diagnostic::warning(format!(
+                            "Dropping the incomplete last line {number} of the event log: {e}"
+                        ))
+                        .context(path.display().to_string()),
+                    );
+                    break;
+                }
+                Err(e) => {
+                    return Err(Error::InvalidEvent {
+                        line: number,
+                        error: Box::new(Error::Deserialization(e)),
+                    });
+                }
+            };
+
+            event.apply(&mut tracker).map_err(|e| Error::InvalidEvent {
+                line: number,
+                error: Box::new(e),
+            })?;
+        }
+
+        tracker.repair(self.strict, self.max_description_len)
+    }
+}
+
+/// Newline delimited JSON events, see `Event`.
+///
+/// `write` produces a compacted log, i.e. a single snapshot of the store.
+/// Subsequent changes get appended via `append`, see `EventLogTimeTracker`.
+#[derive(Debug)]
+pub struct EventLogStorageStrategy {}
+
+impl EventLogStorageStrategy {
+    pub fn append(&self, writer: &mut impl std::io::Write, events: &[Event]) -> Result<()> {
+        for event in events {
+            serde_json::to_writer(&mut *writer, event).map_err(Error::Serialization)?;
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TimeTrackerStorageStrategy for EventLogStorageStrategy {
+    fn write(
+        &self,
+        writer: &mut impl std::io::Write,
+        store: &impl TimeTrackingStore,
+    ) -> Result<()> {
+        let snapshot = Event::Snapshot {
+            active: store.active()?,
+            finished: store
+                .finished(
+                    &ListOptions::new()
+                        .take(usize::MAX)
+                        .order(SortOrder::Ascending),
+                )?
+                .items,
+            settings: store.settings()?,
+        };
+
+        self.append(writer, &[snapshot])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(path: &Path) -> Result<InMemoryTimeTracker> {
+        InMemoryTimeTracker::init(&EventLogLoadingStrategy::new(path))
+    }
+
+    #[test]
+    fn replaying_a_synthetic_stream_matches_the_calls() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.log");
+
+        std::fs::write(
+            &path,
+            r#"{"event":"Begin","time":"2024-06-03T08:00:00Z","description":"first"}
+{"event":"Note","time":"2024-06-03T09:00:00Z","description":"typo"}
+{"event":"Amend","description":"note"}
+{"event":"End"}
+{"event":"Begin","time":"2024-06-03T10:00:00Z","description":"cancelled"}
+{"event":"Cancel"}
+{"event":"Begin","time":"2024-06-03T11:00:00Z","description":"second"}
+{"event":"End"}
+{"event":"Clear"}
+{"event":"Begin","time":"2024-06-04T08:00:00Z","description":"third"}
+{"event":"End"}
+{"event":"Begin","time":"2024-06-04T09:00:00Z","description":"active"}
+{"event":"Note","time":"2024-06-04T09:30:00Z","description":"still going"}
+"#,
+        )?;
+
+        let mut expected = InMemoryTimeTracker::default();
+        expected.begin("first")?;
+        expected.push_note("typo")?;
+        expected.amend("note")?;
+        expected.end()?;
+        expected.begin("cancelled")?;
+        expected.cancel()?;
+        expected.begin("second")?;
+        expected.end()?;
+        expected.clear()?;
+        expected.begin("third")?;
+        expected.end()?;
+        expected.begin("active")?;
+        expected.push_note("still going")?;
+
+        let replayed = load(&path)?;
+
+        let descriptions = |tb: &TimeBox| -> Vec<String> {
+            tb.notes.iter().map(|n| n.description.clone()).collect()
+        };
+        assert_eq!(
+            expected
+                .finished
+                .iter()
+                .map(descriptions)
+                .collect::<Vec<_>>(),
+            replayed
+                .finished
+                .iter()
+                .map(descriptions)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            expected.active.as_ref().map(descriptions),
+            replayed.active.as_ref().map(descriptions)
+        );
+        assert_eq!(
+            "2024-06-04T09:30:00Z".parse::<DateTime<Utc>>().unwrap(),
+            replayed.active.unwrap().notes[1].time
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn appended_events_replay_to_the_same_store() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.log");
+
+        let mut tracker = EventLogTimeTracker::default();
+        tracker.begin("first")?;
+        tracker.push_note("second note")?;
+        tracker.end()?;
+        tracker.set_exported_at(0, Some(Utc::now()))?;
+        tracker.append(&path)?;
+        assert!(tracker.pending().is_empty());
+
+        let other = tracker.begin("other")?;
+        tracker.set_estimate(Some(TimeDelta::minutes(90)))?;
+        tracker.push_note_at("backdated", other.time_start()?)?;
+        tracker.pause()?;
+        tracker.end()?;
+        tracker.resume_at(0)?;
+        tracker.edit_note(0, "edited")?;
+        tracker.split_active(1)?;
+        tracker.end()?;
+        tracker.merge_last_finished()?;
+        tracker.resume()?;
+        tracker.clear_filtered(&Query::new().search("edited"))?;
+        tracker.set_settings(StoreSettings {
+            weekly_goal: Some(TimeDelta::hours(40)),
+            ..Default::default()
+        })?;
+        assert!(tracker.begin("fails").is_err());
+        tracker.append(&path)?;
+
+        assert_eq!(tracker.tracker, load(&path)?);
+
+        tracker.compact(&path)?;
+        let log = std::fs::read_to_string(&path)?;
+        assert_eq!(1, log.lines().count());
+        assert_eq!(tracker.tracker, load(&path)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn incomplete_last_line_gets_dropped() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.log");
+
+        let complete = r#"{"event":"Begin","time":"2024-06-03T08:00:00Z","description":"first"}
+"#;
+        std::fs::write(&path, format!("{complete}{{\"event\":\"En"))?;
+        assert!(load(&path)?.active.is_some());
+
+        std::fs::write(&path, format!("{complete}{{\"event\":\"En\n"))?;
+        assert!(matches!(
+            load(&path),
+            Err(Error::InvalidEvent { line: 2, .. })
+        ));
+
+        std::fs::write(&path, format!("{complete}{{\"event\":\"Resume\"}}\n"))?;
+        assert!(matches!(
+            load(&path),
+            Err(Error::InvalidEvent { line: 2, .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rotating_keeps_every_event_even_if_interrupted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.log");
+        let rotated = rotated_path(&path);
+        assert_eq!(dir.path().join("storage.log.1"), rotated);
+
+        let mut tracker = EventLogTimeTracker::default();
+        tracker.begin("first")?;
+        assert!(!tracker.append_rotating(&path, 1024)?);
+        tracker.push_note("a note long enough to push the log over its limit")?;
+        tracker.end()?;
+        assert!(tracker.append_rotating(&path, 256)?);
+
+        // The rotated log holds the whole history, the new one only the snapshot
+        assert_eq!(tracker.tracker, load(&rotated)?);
+        assert_eq!(1, std::fs::read_to_string(&path)?.lines().count());
+        assert_eq!(tracker.tracker, load(&path)?);
+
+        // Crash after rotating but before the snapshot got written
+        tracker.begin("second")?;
+        tracker.append(&path)?;
+        std::fs::rename(&path, &rotated)?;
+
+        let mut recovered = EventLogTimeTracker::init(&EventLogLoadingStrategy::new(&path))?;
+        let diagnostics = recovered.diagnostics()?;
+        assert_eq!(Some(rotated.display().to_string()), diagnostics[0].context);
+        assert_eq!(tracker.tracker.active, recovered.tracker.active);
+        assert_eq!(tracker.tracker.finished, recovered.tracker.finished);
+
+        recovered.end()?;
+        recovered.append_rotating(&path, 1024)?;
+        let reloaded = load(&path)?;
+        assert!(reloaded.diagnostics.is_empty());
+        assert_eq!(recovered.tracker.finished, reloaded.finished);
+        assert_eq!(2, reloaded.finished.len());
+
+        Ok(())
+    }
+}
@@ -1,19 +1,44 @@
-use std::{fs::File, io::BufReader, path::Path};
+use std::{collections::HashSet, fs::File, io::BufReader, path::Path};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use log::warn;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    Error, ListFilter, ListOptions, ListResult, Result, SortOrder, TimeBox, TimeBoxNote,
-    TimeTrackerInitStrategy, TimeTrackerStorageStrategy, TimeTrackingStore,
+    Diagnostic, Error, ListOptions, ListResult, MAX_DESCRIPTION_LEN, NoteKind, NoteLocation, Pause,
+    Query, Repair, Result, SortOrder, SortedTimeBoxes, StoreSettings, TimeBox, TimeBoxNote,
+    TimeTrackerInitStrategy, TimeTrackerStorageStrategy, TimeTrackingStore, ValidationIssue,
+    assert_description_len, check_description, sanitize_description,
 };
 
+/// Description of the system note that ends the first part of `split_active`.
+const SPLIT_NOTE: &str = "(split)";
+
+fn is_split_note(note: &TimeBoxNote) -> bool {
+    note.kind == NoteKind::System && note.description == SPLIT_NOTE
+}
+
+/// Finished time boxes `InMemoryTimeTracker::import` added, and the ones it skipped since they were there already.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
 /// Example Time Tracker intended for single-user local time tracking.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InMemoryTimeTracker {
     pub active: Option<TimeBox>,
-    pub finished: Vec<TimeBox>,
+    pub finished: SortedTimeBoxes,
+    /// Stores from before there were settings have none.
+    #[serde(default)]
+    pub settings: StoreSettings,
+    /// What loading had to repair, never stored. Destructive operations should be careful with repaired stores.
+    #[serde(skip)]
+    pub repairs: Vec<Repair>,
+    /// What loading noticed, including every repair, for the application to show. Never stored.
+    #[serde(skip)]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl InMemoryTimeTracker {
@@ -23,7 +48,7 @@ impl InMemoryTimeTracker {
     /// 2. Active time box notes are sorted in ascending order
     /// 3. Each finished time box has at minimum one note
     /// 4. Finished time boxes are sorted in ascending order
-    fn assert_valid(&self) -> Result<()> {
+    pub fn assert_valid(&self) -> Result<()> {
         if let Some(tb) = self.active.as_ref() {
             if tb.notes.is_empty() {
                 return Err(Error::ActiveTimeBoxIsMissingNote);
@@ -61,6 +86,228 @@ impl InMemoryTimeTracker {
         Ok(())
     }
 
+    /// Collects every note whose description contains control characters or is longer than `max_description_len`.
+    pub fn validate_content(&self, max_description_len: usize) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        for (location, note) in self.notes_with_location() {
+            for kind in check_description(&note.description, max_description_len) {
+                issues.push(ValidationIssue { location, kind });
+            }
+        }
+
+        issues
+    }
+
+    /// Strips control characters and truncates overlong descriptions, returns what was fixed.
+    pub fn sanitize_content(&mut self, max_description_len: usize) -> Vec<ValidationIssue> {
+        let issues = self.validate_content(max_description_len);
+
+        let sanitize = |tb: &mut TimeBox| {
+            for note in tb.notes.iter_mut() {
+                note.description = sanitize_description(&note.description, max_description_len);
+            }
+        };
+
+        self.active.iter_mut().for_each(sanitize);
+        self.finished.update_all(sanitize);
+
+        issues
+    }
+
+    fn notes_with_location(&self) -> impl Iterator<Item = (NoteLocation, &TimeBoxNote)> {
+        let active = self.active.iter().flat_map(|tb| {
+            tb.notes
+                .iter()
+                .enumerate()
+                .map(|(note, n)| (NoteLocation::Active { note }, n))
+        });
+
+        let finished = self.finished.iter().enumerate().flat_map(|(time_box, tb)| {
+            tb.notes
+                .iter()
+                .enumerate()
+                .map(move |(note, n)| (NoteLocation::Finished { time_box, note }, n))
+        });
+
+        active.chain(finished)
+    }
+
+    /// Shared by all loaders of files that may have been edited manually.
+    /// Sorts unsorted notes and time boxes, then checks descriptions, see `JsonFileLoadingStrategy`.
+    pub(crate) fn repair(mut self, strict: bool, max_description_len: usize) -> Result<Self> {
+        match self.assert_valid() {
+            Ok(_) => (),
+            Err(Error::TimeBoxNoteIsNotLinearlySorted(note)) => {
+                self.diagnostics.push(Diagnostic::warning(format!(
+                    "Found finished time box that is unsorted! The time of the following note: {note:?} is earlier than the previous note -- Sorting in memory now.",
+                )));
+                self.repairs.push(Repair::SortedNotes(note));
+                self.sort_notes();
+            }
+            Err(e) => return Err(e),
+        };
+
+        if strict {
+            let issues = self.validate_content(max_description_len);
+            if !issues.is_empty() {
+                return Err(Error::InvalidContent(issues));
+            }
+        } else {
+            for issue in self.sanitize_content(max_description_len) {
+                self.diagnostics.push(Diagnostic::warning(format!(
+                    "Found invalid content: {issue} -- Sanitizing in memory now."
+                )));
+                self.repairs.push(Repair::Sanitized(issue));
+            }
+        }
+
+        Ok(self)
+    }
+
+    fn sort_notes(&mut self) {
+        if let Some(active) = self.active.as_mut() {
+            active.notes.sort_by_key(|n| n.time);
+        }
+
+        self.finished
+            .update_all(|tb| tb.notes.sort_by_key(|n| n.time));
+    }
+
+    /// Copy of `store` for the storage strategies to write, validated like loading does, see `checked_for_writing`.
+    pub(crate) fn snapshot(store: &impl TimeTrackingStore, repair: bool) -> Result<Self> {
+        InMemoryTimeTracker {
+            active: store.active()?,
+            finished: store
+                .finished(
+                    &ListOptions::new()
+                        .take(usize::MAX)
+                        .order(SortOrder::Ascending),
+                )?
+                .items
+                .into(),
+            settings: store.settings()?,
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+        .checked_for_writing(repair)
+    }
+
+    /// Keeps invalid stores from being written, so they never have to be repaired when loading.
+    /// Unsorted notes get sorted if `repair` is set, everything else that is invalid fails.
+    /// Writing has no diagnostics to return, so sorting gets logged as warning instead.
+    pub(crate) fn checked_for_writing(mut self, repair: bool) -> Result<Self> {
+        match self.assert_valid() {
+            Err(Error::TimeBoxNoteIsNotLinearlySorted(note)) if repair => {
+                warn!(
+                    "Found unsorted notes while writing! The time of the following note: {note:?} is earlier than the previous note -- Sorting before writing."
+                );
+                self.sort_notes();
+                Ok(self)
+            }
+            result => result.map(|_| self),
+        }
+    }
+
+    /// Whether loading had to change the store to make it usable, see `repairs`.
+    pub fn was_repaired(&self) -> bool {
+        !self.repairs.is_empty()
+    }
+
+    /// Whether both hold the same stored data, ignoring what loading noticed, i.e. `repairs` and `diagnostics`.
+    /// Unlike `==`, a store equals its cache even if loading it needed repairs.
+    pub fn same_data(&self, other: &InMemoryTimeTracker) -> bool {
+        self.active == other.active
+            && self.finished == other.finished
+            && self.settings == other.settings
+    }
+
+    /// Titles of the `n` most recently started finished time boxes, most recent first.
+    /// Titles differing only in case count as one, the most recent spelling wins.
+    pub fn recent_titles(&self, n: usize) -> Vec<String> {
+        let mut seen = HashSet::new();
+
+        self.finished
+            .iter()
+            .rev()
+            .map(|tb| tb.title())
+            .filter(|title| !title.is_empty() && seen.insert(title.to_lowercase()))
+            .take(n)
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Latest stop of the finished time boxes, `None` if there are none.
+    pub fn last_stop(&self) -> Result<Option<DateTime<Utc>>> {
+        self.finished
+            .iter()
+            .map(|tb| tb.time_stop())
+            .try_fold(None, |latest, stop| Ok(latest.max(Some(stop?))))
+    }
+
+    /// Fails for times before the lock, see `StoreSettings::locked_before`.
+    /// Finished time boxes starting before it cannot change, and no time box or note can be added before it.
+    fn assert_unlocked(&self, time: DateTime<Utc>) -> Result<()> {
+        match self.settings.locked_before {
+            Some(before) if time < before => Err(Error::TimeBoxLocked { before }),
+            _ => Ok(()),
+        }
+    }
+
+    fn assert_all_unlocked<'a>(
+        &self,
+        mut time_boxes: impl Iterator<Item = &'a TimeBox>,
+    ) -> Result<()> {
+        time_boxes.try_for_each(|tb| self.assert_unlocked(tb.time_start()?))
+    }
+
+    /// Whether a time box starting at `start` would overlap a finished one, see `begin_at`.
+    /// Starting right when the last one stopped does not overlap.
+    pub fn would_overlap(&self, start: DateTime<Utc>) -> Result<bool> {
+        Ok(self.last_stop()?.is_some_and(|stop| start < stop))
+    }
+
+    /// Adds the time boxes of `other`, e.g. a converted legacy store, keeping the finished time boxes sorted.
+    /// Finished time boxes with the same start, stop and first note as one here are skipped,
+    /// so importing the same export twice, e.g. to merge the stores of two machines, adds nothing.
+    /// Its active time box becomes active here, so both having one fails with `Error::ActiveTimeBoxExistsAlready`.
+    /// Validates `other` first and changes nothing if that fails, or if it would add time boxes before the lock.
+    pub fn import(&mut self, other: InMemoryTimeTracker) -> Result<ImportSummary> {
+        other.assert_valid()?;
+        if self.active.is_some() && other.active.is_some() {
+            return Err(Error::ActiveTimeBoxExistsAlready);
+        }
+
+        let identity = |tb: &TimeBox| {
+            (
+                tb.time_start().ok(),
+                tb.time_stop().ok(),
+                tb.notes.first().map(|note| note.description.clone()),
+            )
+        };
+        let mut known: HashSet<_> = self.finished.iter().map(identity).collect();
+
+        let count = other.finished.len();
+        let new: Vec<TimeBox> = Vec::from(other.finished)
+            .into_iter()
+            .filter(|tb| known.insert(identity(tb)))
+            .collect();
+        self.assert_all_unlocked(new.iter().chain(&other.active))?;
+
+        let summary = ImportSummary {
+            imported: new.len(),
+            skipped: count - new.len(),
+        };
+        for tb in new {
+            self.finished.insert_sorted(tb);
+        }
+        if other.active.is_some() {
+            self.active = other.active;
+        }
+
+        Ok(summary)
+    }
+
     pub fn to_writer(
         &self,
         strategy: &impl TimeTrackerStorageStrategy,
@@ -81,7 +328,10 @@ impl TimeTrackingStore for InMemoryTimeTracker {
 
         Ok(InMemoryTimeTracker {
             active: store.active()?,
-            finished: list.items,
+            finished: list.items.into(),
+            settings: store.settings()?,
+            repairs: store.repairs()?,
+            diagnostics: store.diagnostics()?,
         })
     }
 
@@ -90,57 +340,61 @@ impl TimeTrackingStore for InMemoryTimeTracker {
     }
 
     fn finished(&self, options: &ListOptions) -> Result<ListResult> {
-        let mut items: Vec<TimeBox> = match options.filter.as_ref() {
-            Some(filter) => self
-                .finished
-                .iter()
-                .filter(|&tb| {
-                    let start = tb.time_start().unwrap_or_default().date_naive();
-                    match filter {
-                        ListFilter::Date(date) => start == *date,
-                        ListFilter::Range { from, to } => start >= *from && start <= *to,
-                    }
-                })
-                .cloned()
-                .collect(),
-            None => self
-                .finished
-                .iter()
-                .skip(options.skip)
-                .take(options.take)
-                .cloned()
-                .collect(),
+        let range = options.query.range(&self.finished);
+        let matching = range
+            .clone()
+            .zip(&self.finished[range])
+            .filter(|(_, tb)| options.query.matches(tb));
+
+        let items: Vec<(usize, &TimeBox)> = match options.order {
+            SortOrder::Ascending => matching.collect(),
+            SortOrder::Descending => matching.rev().collect(),
         };
 
-        match options.order {
-            SortOrder::Ascending => items.sort_by(|a, b| {
-                let time_a = a.time_start().unwrap_or_default();
-                let time_b = b.time_start().unwrap_or_default();
-                time_a.cmp(&time_b)
-            }),
-            SortOrder::Descending => items.sort_by(|a, b| {
-                let time_a = a.time_start().unwrap_or_default();
-                let time_b = b.time_start().unwrap_or_default();
-                time_b.cmp(&time_a)
-            }),
-        }
+        // Paginate last, so pages are pages of the filtered time boxes in the requested order
+        let items = items
+            .into_iter()
+            .skip(options.skip)
+            .take(options.take)
+            .map(|(index, tb)| (index, tb.clone()));
+
+        let (indices, items) = items.unzip();
 
         Ok(ListResult {
             total: self.finished.len(),
             items,
+            indices,
         })
     }
 
     fn begin(&mut self, description: &str) -> Result<TimeBox> {
+        self.begin_at(description, Utc::now())
+    }
+
+    fn begin_at(&mut self, description: &str, time: DateTime<Utc>) -> Result<TimeBox> {
+        assert_description_len(description)?;
+
         match self.active {
             Some(_) => Err(Error::ActiveTimeBoxExistsAlready),
             None => {
+                if let Some(stop) = self.last_stop()?.filter(|stop| time < *stop) {
+                    return Err(Error::OverlapsFinishedTimeBox { start: time, stop });
+                }
+                self.assert_unlocked(time)?;
+
                 let note = TimeBoxNote {
                     description: description.to_owned(),
-                    time: Utc::now(),
+                    time,
+                    kind: NoteKind::Regular,
                 };
 
-                let task = TimeBox { notes: vec![note] };
+                let task = TimeBox {
+                    notes: vec![note],
+                    exported_at: None,
+                    pauses: Vec::new(),
+                    estimate: None,
+                    reference: None,
+                };
                 self.active = Some(task.clone());
 
                 Ok(task)
@@ -149,12 +403,15 @@ impl TimeTrackingStore for InMemoryTimeTracker {
     }
 
     fn push_note(&mut self, description: &str) -> Result<TimeBox> {
+        assert_description_len(description)?;
+
         match self.active.as_mut() {
             None => Err(Error::NoActiveTimeBox),
             Some(t) => {
                 t.notes.push(TimeBoxNote {
                     description: description.to_owned(),
                     time: Utc::now(),
+                    kind: NoteKind::Regular,
                 });
 
                 Ok(t.clone())
@@ -162,18 +419,83 @@ impl TimeTrackingStore for InMemoryTimeTracker {
         }
     }
 
+    fn push_note_at(&mut self, description: &str, time: DateTime<Utc>) -> Result<TimeBox> {
+        assert_description_len(description)?;
+        self.assert_unlocked(time)?;
+
+        let last_stop = self.last_stop()?;
+        let tb = self.active.as_mut().ok_or(Error::NoActiveTimeBox)?;
+        // Notes at the same time keep the order they were added in
+        let index = tb.notes.partition_point(|n| n.time <= time);
+        // A new first note moves the start, which must not overlap a finished time box either
+        if let Some(stop) = last_stop.filter(|stop| index == 0 && time < *stop) {
+            return Err(Error::OverlapsFinishedTimeBox { start: time, stop });
+        }
+        tb.notes.insert(
+            index,
+            TimeBoxNote {
+                description: description.to_owned(),
+                time,
+                kind: NoteKind::Regular,
+            },
+        );
+
+        Ok(tb.clone())
+    }
+
+    fn pause(&mut self) -> Result<TimeBox> {
+        let tb = match self.active.as_mut() {
+            Some(tb) => tb,
+            None => return Err(Error::NoActiveTimeBox),
+        };
+
+        if tb.paused_since().is_some() {
+            return Err(Error::ActiveTimeBoxIsPausedAlready);
+        }
+
+        tb.pauses.push(Pause {
+            start: Utc::now(),
+            stop: None,
+        });
+
+        Ok(tb.clone())
+    }
+
+    fn unpause(&mut self) -> Result<TimeBox> {
+        let tb = match self.active.as_mut() {
+            Some(tb) => tb,
+            None => return Err(Error::NoActiveTimeBox),
+        };
+
+        match tb.pauses.last_mut() {
+            Some(pause) if pause.stop.is_none() => pause.stop = Some(Utc::now()),
+            _ => return Err(Error::ActiveTimeBoxIsNotPaused),
+        }
+
+        Ok(tb.clone())
+    }
+
     fn end(&mut self) -> Result<TimeBox> {
-        let tb = match self.active.take() {
+        let mut tb = match self.active.take() {
             Some(t) => t,
             None => return Err(Error::NoActiveTimeBox),
         };
 
-        self.finished.push(tb.clone());
+        if let Some(pause) = tb.pauses.last_mut()
+            && pause.stop.is_none()
+        {
+            pause.stop = Some(Utc::now());
+        }
+
+        // Resumed time boxes may have started before others
+        self.finished.insert_sorted(tb.clone());
 
         Ok(tb)
     }
 
     fn amend(&mut self, description: &str) -> Result<TimeBox> {
+        assert_description_len(description)?;
+
         let tb = match self.active.as_mut() {
             Some(tb) => tb,
             None => return Err(Error::NoActiveTimeBox),
@@ -189,21 +511,209 @@ impl TimeTrackingStore for InMemoryTimeTracker {
         Ok(tb.clone())
     }
 
+    fn edit_note(&mut self, note_index: usize, description: &str) -> Result<TimeBox> {
+        assert_description_len(description)?;
+
+        let tb = match self.active.as_mut() {
+            Some(tb) => tb,
+            None => return Err(Error::NoActiveTimeBox),
+        };
+
+        let len = tb.notes.len();
+        let note = match tb.notes.get_mut(note_index) {
+            Some(note) => note,
+            None => {
+                return Err(Error::NoteIndexOutOfBounds {
+                    index: note_index,
+                    len,
+                });
+            }
+        };
+
+        note.description = description.trim().to_string();
+
+        Ok(tb.clone())
+    }
+
+    fn set_estimate(&mut self, estimate: Option<TimeDelta>) -> Result<TimeBox> {
+        let tb = match self.active.as_mut() {
+            Some(tb) => tb,
+            None => return Err(Error::NoActiveTimeBox),
+        };
+
+        tb.estimate = estimate;
+
+        Ok(tb.clone())
+    }
+
+    fn set_reference(&mut self, reference: Option<&str>) -> Result<TimeBox> {
+        let tb = match self.active.as_mut() {
+            Some(tb) => tb,
+            None => return Err(Error::NoActiveTimeBox),
+        };
+
+        tb.reference = reference
+            .map(str::trim)
+            .filter(|reference| !reference.is_empty())
+            .map(str::to_string);
+
+        Ok(tb.clone())
+    }
+
+    fn split_active(&mut self, note_index: usize) -> Result<(TimeBox, TimeBox)> {
+        let tb = match self.active.as_ref() {
+            Some(tb) => tb,
+            None => return Err(Error::NoActiveTimeBox),
+        };
+
+        if note_index > tb.notes.len() {
+            return Err(Error::NoteIndexOutOfBounds {
+                index: note_index,
+                len: tb.notes.len(),
+            });
+        }
+        if note_index == 0 || note_index == tb.notes.len() {
+            return Err(Error::CannotDeleteLastNote);
+        }
+
+        let mut first = tb.clone();
+        let mut second = TimeBox {
+            notes: first.notes.split_off(note_index),
+            exported_at: None,
+            pauses: Vec::new(),
+            estimate: None,
+            reference: first.reference.clone(),
+        };
+
+        // The stop of a time box is its last note, so the first part ends with a marker at the time of the split
+        let split = TimeBoxNote {
+            description: SPLIT_NOTE.to_owned(),
+            time: second.notes[0].time,
+            kind: NoteKind::System,
+        };
+        first.notes.push(split.clone());
+
+        // Pauses spanning the split point count towards both parts
+        let pauses = std::mem::take(&mut first.pauses);
+        for pause in pauses {
+            if pause.start < split.time {
+                first.pauses.push(Pause {
+                    start: pause.start,
+                    stop: Some(pause.stop.map_or(split.time, |stop| stop.min(split.time))),
+                });
+            }
+            if pause.stop.is_none_or(|stop| stop > split.time) {
+                second.pauses.push(Pause {
+                    start: pause.start.max(split.time),
+                    stop: pause.stop,
+                });
+            }
+        }
+
+        self.finished.insert_sorted(first.clone());
+        self.active = Some(second.clone());
+
+        Ok((first, second))
+    }
+
+    fn merge_last_finished(&mut self) -> Result<TimeBox> {
+        if self.finished.len() < 2 {
+            return Err(Error::NoTimeBox);
+        }
+        // The later one cannot be locked without the earlier one
+        self.assert_all_unlocked(self.finished.get(self.finished.len() - 2).into_iter())?;
+
+        let last: Vec<TimeBox> = self
+            .finished
+            .pop()
+            .into_iter()
+            .chain(self.finished.pop())
+            .collect();
+
+        let mut merged = TimeBox {
+            notes: last.iter().flat_map(|tb| tb.notes.clone()).collect(),
+            exported_at: None,
+            pauses: last.iter().flat_map(|tb| tb.pauses.clone()).collect(),
+            estimate: last
+                .iter()
+                .filter_map(|tb| tb.estimate)
+                .reduce(|a, b| a + b),
+            // The earlier time box wins, `last` starts with the latest one
+            reference: last.iter().rev().find_map(|tb| tb.reference.clone()),
+        };
+        // Merging what got split undoes the split
+        merged.notes.retain(|note| !is_split_note(note));
+        merged.notes.sort_by_key(|note| note.time);
+        merged.pauses.sort();
+
+        let position = self.finished.insert_sorted(merged.clone());
+
+        if let Err(e) = self.assert_valid() {
+            self.finished.remove(position);
+            for tb in last {
+                self.finished.insert_sorted(tb);
+            }
+            return Err(e);
+        }
+
+        Ok(merged)
+    }
+
     fn resume(&mut self) -> Result<TimeBox> {
         if self.active.is_some() {
             return Err(Error::ActiveTimeBoxExistsAlready);
         }
 
-        let tb = match self.finished.pop() {
+        self.assert_all_unlocked(self.finished.last().into_iter())?;
+        let mut tb = match self.finished.pop() {
             Some(tb) => tb,
             None => return Err(Error::NoTimeBox),
         };
 
+        // Continuing changes the time box, so it has to be exported again
+        tb.exported_at = None;
+        self.active = Some(tb.clone());
+
+        Ok(tb)
+    }
+
+    fn resume_at(&mut self, index: usize) -> Result<TimeBox> {
+        if self.active.is_some() {
+            return Err(Error::ActiveTimeBoxExistsAlready);
+        }
+
+        self.assert_all_unlocked(self.finished.get(index).into_iter())?;
+        let mut tb = match self.finished.remove(index) {
+            Some(tb) => tb,
+            None => {
+                return Err(Error::TimeBoxIndexOutOfBounds {
+                    index,
+                    len: self.finished.len(),
+                });
+            }
+        };
+        tb.exported_at = None;
         self.active = Some(tb.clone());
 
         Ok(tb)
     }
 
+    fn set_exported_at(
+        &mut self,
+        index: usize,
+        exported_at: Option<DateTime<Utc>>,
+    ) -> Result<TimeBox> {
+        let len = self.finished.len();
+        // The export time only decides the order of otherwise equal time boxes
+        match self.finished.update(index, |tb| {
+            tb.exported_at = exported_at;
+            tb.clone()
+        }) {
+            Some((_, tb)) => Ok(tb),
+            None => Err(Error::TimeBoxIndexOutOfBounds { index, len }),
+        }
+    }
+
     fn cancel(&mut self) -> Result<TimeBox> {
         match self.active.take() {
             Some(tb) => Ok(tb),
@@ -212,59 +722,82 @@ impl TimeTrackingStore for InMemoryTimeTracker {
     }
 
     fn clear(&mut self) -> Result<usize> {
+        self.assert_all_unlocked(self.finished.iter())?;
         let count = self.finished.len();
         self.finished.clear();
         Ok(count)
     }
+
+    fn clear_filtered(&mut self, query: &Query) -> Result<usize> {
+        self.assert_all_unlocked(self.finished.iter().filter(|tb| query.matches(tb)))?;
+        let count = self.finished.len();
+        self.finished.retain(|tb| !query.matches(tb));
+        Ok(count - self.finished.len())
+    }
+
+    fn settings(&self) -> Result<StoreSettings> {
+        Ok(self.settings.clone())
+    }
+
+    fn set_settings(&mut self, settings: StoreSettings) -> Result<()> {
+        self.settings = settings;
+        Ok(())
+    }
+
+    fn repairs(&self) -> Result<Vec<Repair>> {
+        Ok(self.repairs.clone())
+    }
+
+    fn diagnostics(&self) -> Result<Vec<Diagnostic>> {
+        Ok(self.diagnostics.clone())
+    }
 }
 
+/// Loads a JSON store that may have been edited manually.
+///
+/// Descriptions with control characters or more than `max_description_len` characters
+/// get sanitized and reported as warnings, in `strict` mode they fail the load instead.
 #[derive(Debug)]
 pub struct JsonFileLoadingStrategy<'a> {
     pub path: &'a Path,
+    pub strict: bool,
+    pub max_description_len: usize,
 }
 
-impl TimeTrackerInitStrategy for JsonFileLoadingStrategy<'_> {
-    fn init(&self) -> Result<impl TimeTrackingStore> {
-        let reader = match File::open(self.path) {
-            Ok(file) => BufReader::new(file),
-            Err(e) => return Err(Error::Io(e)),
-        };
-
-        let mut tracker: InMemoryTimeTracker = match serde_json::from_reader(reader) {
-            Ok(store_kind) => store_kind,
-            Err(e) => return Err(Error::Deserialization(e)),
-        };
+impl<'a> JsonFileLoadingStrategy<'a> {
+    pub fn new(path: &'a Path) -> Self {
+        Self {
+            path,
+            strict: false,
+            max_description_len: MAX_DESCRIPTION_LEN,
+        }
+    }
 
-        match tracker.assert_valid() {
-            Ok(_) => (),
-            Err(Error::TimeBoxNoteIsNotLinearlySorted(note)) => {
-                warn!(
-                    "Found finished time box that is unsorted! The time of the following note: {note:?} is earlier than the previous note -- Sorting in memory now.",
-                );
-                if let Some(active) = tracker.active.as_mut() {
-                    active.notes.sort_by(|a, b| a.time.cmp(&b.time));
-                }
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
 
-                for tb in tracker.finished.iter_mut() {
-                    tb.notes.sort_by(|a, b| a.time.cmp(&b.time));
-                }
+    pub fn max_description_len(mut self, max_description_len: usize) -> Self {
+        self.max_description_len = max_description_len;
+        self
+    }
+}
 
-                tracker.finished.sort_by(|a, b| {
-                    let a_time = a.time_start().unwrap_or_default();
-                    let b_time = b.time_start().unwrap_or_default();
-                    a_time.cmp(&b_time)
-                });
-            }
-            Err(e) => return Err(e),
-        };
+impl TimeTrackerInitStrategy for JsonFileLoadingStrategy<'_> {
+    fn init(&self) -> Result<impl TimeTrackingStore> {
+        let reader = BufReader::new(File::open(self.path)?);
+        let tracker: InMemoryTimeTracker = serde_json::from_reader(reader)?;
 
-        Ok(tracker)
+        tracker.repair(self.strict, self.max_description_len)
     }
 }
 
 #[derive(Debug)]
 pub struct JsonStorageStrategy {
     pub pretty: bool,
+    /// Sort unsorted notes before writing instead of failing with `Error::TimeBoxNoteIsNotLinearlySorted`.
+    pub repair: bool,
 }
 
 impl TimeTrackerStorageStrategy for JsonStorageStrategy {
@@ -273,16 +806,7 @@ impl TimeTrackerStorageStrategy for JsonStorageStrategy {
         writer: &mut impl std::io::Write,
         store: &impl TimeTrackingStore,
     ) -> Result<()> {
-        let tracker = InMemoryTimeTracker {
-            active: store.active()?,
-            finished: store
-                .finished(
-                    &ListOptions::new()
-                        .take(usize::MAX)
-                        .order(SortOrder::Ascending),
-                )?
-                .items,
-        };
+        let tracker = InMemoryTimeTracker::snapshot(store, self.repair)?;
 
         if self.pretty {
             serde_json::to_writer_pretty(writer, &tracker).map_err(Error::Serialization)
@@ -291,3 +815,1104 @@ impl TimeTrackerStorageStrategy for JsonStorageStrategy {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        ListFilter, SearchPattern, Severity, ValidationIssueKind, assert_description_len,
+        test_fixtures::{note, time, time_box, time_box_of},
+    };
+
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    fn load(strategy: &JsonFileLoadingStrategy) -> Result<InMemoryTimeTracker> {
+        InMemoryTimeTracker::init(strategy)
+    }
+
+    struct FailingWriter;
+
+    impl std::io::Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn io_and_json_errors_keep_their_kind() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.json");
+
+        match load(&JsonFileLoadingStrategy::new(&path)) {
+            Err(Error::Io(e)) => assert_eq!(std::io::ErrorKind::NotFound, e.kind()),
+            other => panic!("expected an IO error, got {other:?}"),
+        }
+
+        std::fs::write(&path, "{")?;
+        assert!(matches!(
+            load(&JsonFileLoadingStrategy::new(&path)),
+            Err(Error::Deserialization(_))
+        ));
+
+        // Failing to write is no parsing error even though serde_json reports it
+        let result = InMemoryTimeTracker::default().to_writer(
+            &JsonStorageStrategy {
+                pretty: false,
+                repair: true,
+            },
+            &mut FailingWriter,
+        );
+        assert!(matches!(result, Err(Error::Serialization(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn valid_content_loads_unchanged_in_strict_mode() -> Result<()> {
+        let path = fixture("content_valid.json");
+        let tracker = load(&JsonFileLoadingStrategy::new(&path).strict(true))?;
+
+        assert_eq!(
+            "Planning\n\t- Größe prüfen",
+            tracker.finished[0].notes[0].description
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_mode_reports_the_location_of_each_issue() {
+        let path = fixture("content_nul_byte.json");
+        let err = load(&JsonFileLoadingStrategy::new(&path).strict(true)).unwrap_err();
+        let Error::InvalidContent(issues) = err else {
+            panic!("expected InvalidContent, got {err:?}");
+        };
+        assert_eq!(
+            vec![ValidationIssue {
+                location: NoteLocation::Finished {
+                    time_box: 0,
+                    note: 1
+                },
+                kind: ValidationIssueKind::ControlCharacter('\0'),
+            }],
+            issues
+        );
+
+        let path = fixture("content_escape_sequence.json");
+        let err = load(&JsonFileLoadingStrategy::new(&path).strict(true)).unwrap_err();
+        let Error::InvalidContent(issues) = err else {
+            panic!("expected InvalidContent, got {err:?}");
+        };
+        assert_eq!(NoteLocation::Active { note: 0 }, issues[0].location);
+        assert_eq!(
+            ValidationIssueKind::ControlCharacter('\u{1b}'),
+            issues[0].kind
+        );
+
+        let path = fixture("content_too_long.json");
+        let err = load(
+            &JsonFileLoadingStrategy::new(&path)
+                .strict(true)
+                .max_description_len(11),
+        )
+        .unwrap_err();
+        let Error::InvalidContent(issues) = err else {
+            panic!("expected InvalidContent, got {err:?}");
+        };
+        assert_eq!(
+            ValidationIssueKind::TooLong { len: 29, max: 11 },
+            issues[0].kind
+        );
+    }
+
+    #[test]
+    fn lenient_mode_sanitizes() -> Result<()> {
+        let path = fixture("content_nul_byte.json");
+        let tracker = load(&JsonFileLoadingStrategy::new(&path))?;
+        assert_eq!("Review", tracker.finished[0].notes[1].description);
+
+        let path = fixture("content_escape_sequence.json");
+        let tracker = load(&JsonFileLoadingStrategy::new(&path))?;
+        assert_eq!(
+            "[31mDeploy[0m",
+            tracker.active.unwrap().notes[0].description
+        );
+
+        let path = fixture("content_too_long.json");
+        let tracker = load(&JsonFileLoadingStrategy::new(&path).max_description_len(11))?;
+        assert_eq!("Refactoring", tracker.finished[0].notes[0].description);
+        assert!(matches!(tracker.repairs[..], [Repair::Sanitized(_)]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn loading_remembers_repairs() -> Result<()> {
+        let tracker = load(&JsonFileLoadingStrategy::new(&fixture(
+            "content_valid.json",
+        )))?;
+        assert!(!tracker.was_repaired());
+
+        let tracker = load(&JsonFileLoadingStrategy::new(&fixture(
+            "notes_unsorted.json",
+        )))?;
+        let descriptions: Vec<&str> = tracker.finished[0]
+            .notes
+            .iter()
+            .map(|n| n.description.as_str())
+            .collect();
+        assert_eq!(
+            vec!["Planning", "Review", "Synced from laptop"],
+            descriptions
+        );
+
+        let [Repair::SortedNotes(note)] = &tracker.repairs[..] else {
+            panic!("Expected sorting, got {:?}", tracker.repairs);
+        };
+        assert_eq!("Review", note.description);
+
+        // Repairs are never stored
+        let json = serde_json::to_string(&tracker).map_err(Error::Serialization)?;
+        assert!(!json.contains("repairs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn loading_collects_diagnostics_instead_of_logging() -> Result<()> {
+        let tracker = load(&JsonFileLoadingStrategy::new(&fixture(
+            "content_valid.json",
+        )))?;
+        assert!(tracker.diagnostics.is_empty());
+
+        let tracker = load(&JsonFileLoadingStrategy::new(&fixture(
+            "content_nul_byte.json",
+        )))?;
+        assert_eq!(
+            vec![Diagnostic::warning(
+                "Found invalid content: note 1 of the finished time box 0 contains the control character U+0000 -- Sanitizing in memory now."
+            )],
+            tracker.diagnostics
+        );
+
+        // Every repair gets its own diagnostic
+        let tracker = load(&JsonFileLoadingStrategy::new(&fixture(
+            "notes_unsorted.json",
+        )))?;
+        assert_eq!(tracker.repairs.len(), tracker.diagnostics.len());
+        assert!(tracker.diagnostics[0].message.contains("unsorted"));
+        assert_eq!(Severity::Warning, tracker.diagnostics[0].severity);
+
+        let json = serde_json::to_string(&tracker).map_err(Error::Serialization)?;
+        assert!(!json.contains("diagnostics"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn lone_surrogates_fail_in_both_modes() {
+        let path = fixture("content_lone_surrogate.json");
+
+        for strict in [false, true] {
+            let err = load(&JsonFileLoadingStrategy::new(&path).strict(strict)).unwrap_err();
+            assert!(matches!(err, Error::Deserialization(_)), "{err:?}");
+        }
+    }
+
+    #[test]
+    fn equal_start_times_are_ordered_totally() -> Result<()> {
+        let by_stop = time_box("2024-06-03T08:00:00Z", TimeDelta::minutes(30), "b");
+        let by_description_a = time_box_of(vec![
+            note(time("2024-06-03T08:00:00Z"), "a"),
+            note(time("2024-06-03T09:00:00Z"), "z"),
+        ]);
+        let by_description_b = time_box_of(vec![
+            note(time("2024-06-03T08:00:00Z"), "b"),
+            note(time("2024-06-03T09:00:00Z"), "a"),
+        ]);
+        let by_notes = time_box_of(vec![
+            note(time("2024-06-03T08:00:00Z"), "b"),
+            note(time("2024-06-03T08:40:00Z"), "middle"),
+            note(time("2024-06-03T09:00:00Z"), "a"),
+        ]);
+        let expected = vec![
+            by_stop.clone(),
+            by_description_a.clone(),
+            // Equal up to here, the earlier second note decides
+            by_notes.clone(),
+            by_description_b.clone(),
+        ];
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.json");
+        let shuffled = InMemoryTimeTracker {
+            active: None,
+            finished: vec![by_notes, by_description_b, by_stop, by_description_a].into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        shuffled.to_writer(
+            &JsonStorageStrategy {
+                pretty: false,
+                repair: true,
+            },
+            &mut File::create(&path)?,
+        )?;
+
+        let all = |order| ListOptions::new().take(usize::MAX).order(order);
+        for _ in 0..3 {
+            let loaded = load(&JsonFileLoadingStrategy::new(&path))?;
+            let ascending = loaded.finished(&all(SortOrder::Ascending))?;
+            let mut descending = loaded.finished(&all(SortOrder::Descending))?;
+
+            assert_eq!(expected, ascending.items);
+            assert_eq!(vec![0, 1, 2, 3], ascending.indices);
+
+            descending.items.reverse();
+            descending.indices.reverse();
+            assert_eq!(ascending.items, descending.items);
+            assert_eq!(ascending.indices, descending.indices);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn resume_at_takes_the_time_box_at_its_list_index() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        for title in ["first", "second", "third"] {
+            tracker.begin(title)?;
+            tracker.end()?;
+        }
+        let total = |tracker: &InMemoryTimeTracker| -> Result<usize> {
+            Ok(tracker.finished(&ListOptions::new())?.total)
+        };
+
+        assert_eq!("first", tracker.resume_at(0)?.title());
+        assert_eq!(2, total(&tracker)?);
+        assert!(matches!(
+            tracker.resume_at(0),
+            Err(Error::ActiveTimeBoxExistsAlready)
+        ));
+        tracker.cancel()?;
+
+        assert_eq!("third", tracker.resume_at(1)?.title());
+        assert_eq!(1, total(&tracker)?);
+        tracker.cancel()?;
+
+        assert!(matches!(
+            tracker.resume_at(1),
+            Err(Error::TimeBoxIndexOutOfBounds { index: 1, len: 1 })
+        ));
+        assert_eq!(None, tracker.active);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ending_keeps_finished_in_order() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        tracker.begin("first")?;
+        tracker.end()?;
+        tracker.begin("second")?;
+        tracker.end()?;
+
+        tracker.resume_at(0)?;
+        tracker.end()?;
+
+        assert_eq!("first", tracker.finished[0].title());
+        assert_eq!("second", tracker.finished[1].title());
+
+        Ok(())
+    }
+
+    #[test]
+    fn locked_time_boxes_cannot_change() -> Result<()> {
+        let finished = |start: &str| time_box(start, TimeDelta::hours(1), "task");
+        let mut tracker = InMemoryTimeTracker::default();
+        tracker
+            .finished
+            .insert_sorted(finished("2024-06-03T08:00:00Z"));
+        tracker
+            .finished
+            .insert_sorted(finished("2024-06-03T11:00:00Z"));
+        let before = time("2024-06-03T10:00:00Z");
+        tracker.settings.locked_before = Some(before);
+        let unchanged = tracker.clone();
+
+        let locked = |error: Option<Error>| matches!(error, Some(Error::TimeBoxLocked { before: b }) if b == before);
+        assert!(locked(tracker.merge_last_finished().err()));
+        assert!(locked(tracker.resume_at(0).err()));
+        assert!(locked(tracker.clear().err()));
+        assert!(locked(tracker.clear_filtered(&Query::new()).err()));
+        let mut earlier = InMemoryTimeTracker::default();
+        earlier
+            .finished
+            .insert_sorted(finished("2024-06-03T06:00:00Z"));
+        assert!(locked(tracker.import(earlier).err()));
+        // Known time boxes get skipped, so importing them again is fine
+        assert_eq!(2, tracker.import(unchanged.clone())?.skipped);
+        assert_eq!(unchanged, tracker);
+
+        // Time boxes and notes after the lock are fine
+        tracker.resume()?;
+        let too_early = before - TimeDelta::minutes(1);
+        assert!(locked(tracker.push_note_at("too early", too_early).err()));
+        tracker.push_note_at("later", before + TimeDelta::hours(3))?;
+        tracker.end()?;
+        assert_eq!(1, tracker.clear_filtered(&Query::new().search("later"))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn begin_at_never_overlaps_finished_time_boxes() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        let start = Utc::now() - TimeDelta::hours(20);
+        assert!(!tracker.would_overlap(start)?);
+        assert_eq!(None, tracker.last_stop()?);
+
+        tracker.begin_at("yesterday", start)?;
+        tracker.push_note_at("done", start + TimeDelta::hours(2))?;
+        let stop = tracker.end()?.time_stop()?;
+        assert_eq!(Some(stop), tracker.last_stop()?);
+
+        assert!(tracker.would_overlap(start)?);
+        assert!(tracker.would_overlap(stop - TimeDelta::seconds(1))?);
+        assert!(!tracker.would_overlap(stop)?);
+
+        let earlier = stop - TimeDelta::minutes(5);
+        assert!(matches!(
+            tracker.begin_at("overlapping", earlier),
+            Err(Error::OverlapsFinishedTimeBox { start, stop: s }) if start == earlier && s == stop
+        ));
+        assert!(tracker.active.is_none());
+
+        let tb = tracker.begin_at("backfilled", stop)?;
+        assert_eq!(stop, tb.time_start()?);
+        assert_eq!("backfilled", tb.title());
+
+        Ok(())
+    }
+
+    #[test]
+    fn same_data_ignores_what_loading_noticed() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        tracker.begin("task")?;
+        let mut loaded = tracker.clone();
+        loaded.repairs.push(Repair::DroppedIncompleteLine(3));
+        loaded.diagnostics.push(Diagnostic::warning("noticed"));
+
+        assert_ne!(tracker, loaded);
+        assert!(tracker.same_data(&loaded));
+
+        loaded.push_note("changed")?;
+        assert!(!tracker.same_data(&loaded));
+
+        Ok(())
+    }
+
+    #[test]
+    fn edit_note_keeps_the_time() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        assert!(matches!(
+            tracker.edit_note(0, "x"),
+            Err(Error::NoActiveTimeBox)
+        ));
+
+        tracker.begin("first")?;
+        tracker.push_note("secnod")?;
+        tracker.push_note("third")?;
+        let before = tracker.active()?.unwrap();
+
+        let edited = tracker.edit_note(1, " second ")?;
+        assert_eq!("second", edited.notes[1].description);
+        assert_eq!(before.notes[1].time, edited.notes[1].time);
+        assert_eq!(before.notes[2], edited.notes[2]);
+
+        assert!(matches!(
+            tracker.edit_note(3, "x"),
+            Err(Error::NoteIndexOutOfBounds { index: 3, len: 3 })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn push_note_at_inserts_chronologically() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        let now = Utc::now();
+        assert!(matches!(
+            tracker.push_note_at("x", now),
+            Err(Error::NoActiveTimeBox)
+        ));
+
+        let start = tracker.begin("first")?.time_start()?;
+        tracker.push_note_at("last", start + TimeDelta::minutes(30))?;
+        let descriptions = |tb: &TimeBox| -> Vec<String> {
+            tb.notes.iter().map(|n| n.description.clone()).collect()
+        };
+
+        // In the middle
+        let tb = tracker.push_note_at("middle", start + TimeDelta::minutes(10))?;
+        assert_eq!(vec!["first", "middle", "last"], descriptions(&tb));
+
+        // At the end, after notes of the same time
+        let tb = tracker.push_note_at("end", start + TimeDelta::minutes(30))?;
+        assert_eq!(vec!["first", "middle", "last", "end"], descriptions(&tb));
+
+        // At the start, which moves the start and the title
+        let earlier = start - TimeDelta::minutes(20);
+        let tb = tracker.push_note_at("backdated", earlier)?;
+        assert_eq!(
+            vec!["backdated", "first", "middle", "last", "end"],
+            descriptions(&tb)
+        );
+        assert_eq!(earlier, tb.time_start()?);
+        assert_eq!("backdated", tb.title());
+        assert!(tb.notes.is_sorted_by_key(|n| n.time));
+
+        Ok(())
+    }
+
+    #[test]
+    fn push_note_at_cannot_move_the_start_before_the_last_stop() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        let start = Utc::now() - TimeDelta::hours(3);
+        tracker.begin_at("finished", start)?;
+        tracker.push_note_at("done", start + TimeDelta::hours(1))?;
+        let stop = tracker.end()?.time_stop()?;
+
+        tracker.begin_at("active", stop + TimeDelta::minutes(30))?;
+        let overlapping = stop - TimeDelta::minutes(5);
+        assert!(matches!(
+            tracker.push_note_at("backdated", overlapping),
+            Err(Error::OverlapsFinishedTimeBox { start, stop: s }) if start == overlapping && s == stop
+        ));
+        assert_eq!(1, tracker.active()?.unwrap().notes.len());
+
+        // Right at the last stop does not overlap
+        let tb = tracker.push_note_at("backdated", stop)?;
+        assert_eq!(stop, tb.time_start()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn writes_reject_descriptions_over_the_shared_limit() {
+        let too_long = "x".repeat(MAX_DESCRIPTION_LEN + 1);
+        assert!(assert_description_len(&"x".repeat(MAX_DESCRIPTION_LEN)).is_ok());
+
+        let mut tracker = InMemoryTimeTracker::default();
+        assert!(matches!(
+            tracker.begin(&too_long),
+            Err(Error::DescriptionTooLong { .. })
+        ));
+
+        tracker.begin("ok").unwrap();
+        assert!(matches!(
+            tracker.push_note(&too_long),
+            Err(Error::DescriptionTooLong { .. })
+        ));
+        assert!(matches!(
+            tracker.push_note_at(&too_long, Utc::now()),
+            Err(Error::DescriptionTooLong { .. })
+        ));
+        assert!(matches!(
+            tracker.amend(&too_long),
+            Err(Error::DescriptionTooLong { .. })
+        ));
+    }
+
+    #[test]
+    fn unexported_only_skips_exported_time_boxes() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        for description in ["first", "second", "third"] {
+            tracker.begin(description)?;
+            tracker.end()?;
+        }
+        tracker.set_exported_at(1, Some(Utc::now()))?;
+
+        let result = tracker.finished(&ListOptions::new().unexported_only(true))?;
+        assert_eq!(vec![0, 2], result.indices);
+        assert_eq!(3, tracker.finished(&ListOptions::new())?.items.len());
+
+        // Resuming changes the time box, so it counts as unexported again
+        tracker.resume_at(1)?;
+        tracker.end()?;
+        let result = tracker.finished(&ListOptions::new().unexported_only(true))?;
+        assert_eq!(3, result.items.len());
+
+        assert!(matches!(
+            tracker.set_exported_at(3, None),
+            Err(Error::TimeBoxIndexOutOfBounds { index: 3, len: 3 })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pausing_twice_or_unpausing_fails() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        assert!(matches!(tracker.pause(), Err(Error::NoActiveTimeBox)));
+
+        tracker.begin("first")?;
+        assert!(matches!(
+            tracker.unpause(),
+            Err(Error::ActiveTimeBoxIsNotPaused)
+        ));
+
+        tracker.pause()?;
+        assert!(matches!(
+            tracker.pause(),
+            Err(Error::ActiveTimeBoxIsPausedAlready)
+        ));
+
+        tracker.unpause()?;
+        assert!(matches!(
+            tracker.unpause(),
+            Err(Error::ActiveTimeBoxIsNotPaused)
+        ));
+
+        // Ending closes the open pause
+        tracker.pause()?;
+        let ended = tracker.end()?;
+        assert_eq!(2, ended.pauses.len());
+        assert!(ended.pauses.iter().all(|p| p.stop.is_some()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn recent_titles_are_distinct_and_most_recent_first() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        for description in [
+            "Review",
+            "standup\nwith notes",
+            "Deploy",
+            "review",
+            "Standup",
+        ] {
+            tracker.begin(description)?;
+            tracker.end()?;
+        }
+
+        assert_eq!(
+            vec!["Standup", "review", "Deploy"],
+            tracker.recent_titles(10)
+        );
+        assert_eq!(vec!["Standup", "review"], tracker.recent_titles(2));
+        assert!(InMemoryTimeTracker::default().recent_titles(10).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tag_filter_matches_any_note_case_insensitively() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        tracker.begin("Standup #meeting")?;
+        tracker.end()?;
+        tracker.begin("Fix login bug")?;
+        tracker.push_note("Pairing with #ClientA")?;
+        tracker.end()?;
+        tracker.begin("#clienta planning #Meeting")?;
+        tracker.end()?;
+
+        let titles = |tag: &str| -> Result<Vec<String>> {
+            let options = ListOptions::new().filter(ListFilter::Tag(tag.to_string()));
+            Ok(tracker
+                .finished(&options)?
+                .items
+                .iter()
+                .map(|tb| tb.title().to_string())
+                .collect())
+        };
+
+        assert_eq!(
+            vec!["Fix login bug", "#clienta planning #Meeting"],
+            titles("CLIENTA")?
+        );
+        assert_eq!(
+            vec!["Standup #meeting", "#clienta planning #Meeting"],
+            titles("#meeting")?
+        );
+        assert!(titles("client")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn references_get_trimmed_and_filter_case_insensitively() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        assert!(matches!(
+            tracker.set_reference(Some("PROJ-1")),
+            Err(Error::NoActiveTimeBox)
+        ));
+
+        tracker.begin("Login")?;
+        assert_eq!(
+            Some("PROJ-1"),
+            tracker
+                .set_reference(Some("  PROJ-1\n"))?
+                .reference
+                .as_deref()
+        );
+        tracker.end()?;
+        tracker.begin("Unrelated")?;
+        tracker.set_reference(Some("PROJ-2"))?;
+        assert_eq!(None, tracker.set_reference(Some(" "))?.reference);
+        tracker.end()?;
+        tracker.begin("Login again")?;
+        tracker.set_reference(Some("PROJ-1"))?;
+        tracker.end()?;
+
+        let titles = |reference: &str| -> Result<Vec<String>> {
+            let options = ListOptions::new().filter(ListFilter::Reference(reference.to_string()));
+            Ok(tracker
+                .finished(&options)?
+                .items
+                .iter()
+                .map(|tb| tb.title().to_string())
+                .collect())
+        };
+
+        assert_eq!(vec!["Login", "Login again"], titles(" proj-1 ")?);
+        assert!(titles("PROJ-2")?.is_empty());
+        assert!(titles("PROJ")?.is_empty());
+
+        // Splitting keeps the reference on both parts
+        tracker.resume()?;
+        tracker.push_note("second part")?;
+        let (first, second) = tracker.split_active(1)?;
+        assert_eq!(first.reference, second.reference);
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_filter_combines_with_date_range() -> Result<()> {
+        // Notes half an hour apart
+        let time_box = |start: &str, descriptions: &[&str]| {
+            let start = time(start);
+            time_box_of(
+                descriptions
+                    .iter()
+                    .zip(0..)
+                    .map(|(description, i)| note(start + TimeDelta::minutes(30 * i), description))
+                    .collect(),
+            )
+        };
+        let tracker = InMemoryTimeTracker {
+            active: None,
+            finished: vec![
+                time_box("2024-06-03T09:00:00Z", &["Fix Login bug", "done"]),
+                time_box(
+                    "2024-06-04T09:00:00Z",
+                    &["Standup", "Talked about\nthe LOGIN\tbug"],
+                ),
+                time_box("2024-06-05T09:00:00Z", &["Review", "blogging"]),
+                time_box("2024-06-06T09:00:00Z", &["Login page", "done"]),
+            ]
+            .into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let titles = |filters: Vec<ListFilter>| -> Result<Vec<String>> {
+            let options = filters
+                .into_iter()
+                .fold(ListOptions::new(), |options, f| options.filter(f));
+            Ok(tracker
+                .finished(&options)?
+                .items
+                .iter()
+                .map(|tb| tb.title().to_string())
+                .collect())
+        };
+        let search = |term: &str| ListFilter::Search(term.to_string());
+
+        assert_eq!(
+            vec!["Fix Login bug", "Standup", "Login page"],
+            titles(vec![search("login")])?
+        );
+        // Substrings match inside words and whitespace matches across lines
+        assert_eq!(vec!["Review"], titles(vec![search("LOGG")])?);
+        assert_eq!(
+            vec!["Fix Login bug", "Standup"],
+            titles(vec![search("login  bug")])?
+        );
+        assert!(titles(vec![search("logout")])?.is_empty());
+
+        // Regular expressions ignore case as well, but match descriptions as written
+        let pattern = |p: &str| SearchPattern::new(p).map(ListFilter::Pattern);
+        assert_eq!(
+            vec!["Fix Login bug", "Standup", "Login page"],
+            titles(vec![pattern(r"\blogin\b")?])?
+        );
+        assert_eq!(vec!["Login page"], titles(vec![pattern("^login p")?])?);
+        assert!(titles(vec![pattern("(?-i)^login")?])?.is_empty());
+        assert_eq!(vec!["Standup"], titles(vec![pattern(r"about\s+the")?])?);
+        assert_eq!(
+            vec!["Fix Login bug", "Login page"],
+            titles(vec![search("login"), pattern("^(done|fix)")?])?
+        );
+        assert!(matches!(
+            SearchPattern::new("login("),
+            Err(Error::InvalidSearchPattern(_))
+        ));
+        assert_eq!(
+            SearchPattern::new("a+")?,
+            serde_json::from_str(&serde_json::to_string(&SearchPattern::new("a+")?)?)?
+        );
+
+        let range = ListFilter::Range {
+            from: "2024-06-04".parse().unwrap(),
+            to: "2024-06-06".parse().unwrap(),
+        };
+        assert_eq!(
+            vec!["Standup", "Login page"],
+            titles(vec![range.clone(), search("Login")])?
+        );
+        assert!(
+            titles(vec![
+                range,
+                ListFilter::Date("2024-06-03".parse().unwrap()),
+                search("login")
+            ])?
+            .is_empty()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn clear_filtered_only_removes_matching_time_boxes() -> Result<()> {
+        let time_box = |start: &str, title: &str| time_box_of(vec![note(time(start), title)]);
+        let mut tracker = InMemoryTimeTracker {
+            active: Some(time_box("2024-06-10T09:00:00Z", "Active")),
+            finished: vec![
+                time_box("2024-05-30T09:00:00Z", "May"),
+                time_box("2024-06-01T09:00:00Z", "June 1st"),
+                time_box("2024-06-03T09:00:00Z", "June 3rd"),
+                time_box("2024-07-01T09:00:00Z", "July"),
+            ]
+            .into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let june =
+            Query::new().date_range("2024-06-01".parse().unwrap(), "2024-06-30".parse().unwrap());
+        assert_eq!(2, tracker.clear_filtered(&june)?);
+        assert_eq!(0, tracker.clear_filtered(&june)?);
+
+        let titles: Vec<&str> = tracker.finished.iter().map(|tb| tb.title()).collect();
+        assert_eq!(vec!["May", "July"], titles);
+        assert!(tracker.active.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn split_active_at_note_boundary() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        assert!(matches!(
+            tracker.split_active(1),
+            Err(Error::NoActiveTimeBox)
+        ));
+
+        let start = time("2024-06-12T09:00:00Z");
+        let minutes = |minutes: i64| start + chrono::TimeDelta::minutes(minutes);
+        tracker.active = Some(TimeBox {
+            pauses: vec![Pause {
+                start: minutes(80),
+                stop: Some(minutes(100)),
+            }],
+            ..time_box_of(vec![
+                note(minutes(0), "Fix login bug"),
+                note(minutes(30), "found it"),
+                note(minutes(90), "Review PR"),
+                note(minutes(150), "approved"),
+            ])
+        });
+
+        assert!(matches!(
+            tracker.split_active(0),
+            Err(Error::CannotDeleteLastNote)
+        ));
+        assert!(matches!(
+            tracker.split_active(4),
+            Err(Error::CannotDeleteLastNote)
+        ));
+        assert!(matches!(
+            tracker.split_active(5),
+            Err(Error::NoteIndexOutOfBounds { index: 5, len: 4 })
+        ));
+
+        let original = tracker.active.clone().unwrap();
+        let (first, second) = tracker.split_active(2)?;
+
+        // Only the second part has the note at the split point, the first ends with a marker at its time
+        let descriptions = |tb: &TimeBox| -> Vec<String> {
+            tb.notes.iter().map(|n| n.description.clone()).collect()
+        };
+        assert_eq!(
+            vec!["Fix login bug", "found it", SPLIT_NOTE],
+            descriptions(&first)
+        );
+        assert_eq!(NoteKind::System, first.notes[2].kind);
+        assert_eq!(vec!["Review PR", "approved"], descriptions(&second));
+        assert_eq!("Fix login bug", first.title());
+        assert_eq!(first.time_stop()?, second.time_start()?);
+        // 90 minutes minus the first 10 minutes of the pause
+        assert_eq!(80, first.timedelta_total()?.num_minutes());
+
+        assert_eq!(2, second.notes.len());
+        assert_eq!("Review PR", second.title());
+        // 60 minutes minus the last 10 minutes of the pause
+        assert_eq!(50, second.timedelta_total()?.num_minutes());
+
+        assert_eq!(Some(second), tracker.active);
+        assert_eq!(vec![first], *tracker.finished);
+
+        // Merging the parts again gives back the notes without duplicates or the marker
+        tracker.end()?;
+        let merged = tracker.merge_last_finished()?;
+        assert_eq!(original.notes, merged.notes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn split_active_keeps_note_times_across_strict_round_trip() -> Result<()> {
+        let start: DateTime<Utc> = "2024-06-12T09:00:00Z".parse().unwrap();
+        let times: Vec<_> = [0, 25, 70, 95]
+            .into_iter()
+            .map(|minutes| start + chrono::TimeDelta::minutes(minutes))
+            .collect();
+        let mut tracker = InMemoryTimeTracker {
+            active: Some(time_box_of(
+                times
+                    .iter()
+                    .zip(["Fix login bug", "found it", "Review PR", "approved"])
+                    .map(|(time, description)| note(*time, description))
+                    .collect(),
+            )),
+            finished: SortedTimeBoxes::new(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let (first, second) = tracker.split_active(2)?;
+        let note_times = |tb: &TimeBox| tb.notes.iter().map(|n| n.time).collect::<Vec<_>>();
+        // The split note closes the first part with its own timestamp
+        assert_eq!(vec![times[0], times[1], times[2]], note_times(&first));
+        assert_eq!(times[2..].to_vec(), note_times(&second));
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.json");
+        tracker.to_writer(
+            &JsonStorageStrategy {
+                pretty: true,
+                repair: true,
+            },
+            &mut File::create(&path)?,
+        )?;
+        let loaded = load(&JsonFileLoadingStrategy::new(&path).strict(true))?;
+
+        assert_eq!(tracker, loaded);
+        assert_eq!(Some(second), loaded.active);
+        assert_eq!(vec![first], *loaded.finished);
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_last_two_finished_time_boxes() -> Result<()> {
+        let mut tracker = InMemoryTimeTracker::default();
+        tracker.begin("only one")?;
+        tracker.end()?;
+        assert!(matches!(
+            tracker.merge_last_finished(),
+            Err(Error::NoTimeBox)
+        ));
+
+        tracker.finished = vec![
+            time_box("2024-06-12T07:00:00Z", TimeDelta::hours(1), "unrelated"),
+            time_box(
+                "2024-06-12T09:00:00Z",
+                TimeDelta::minutes(45),
+                "Fix login bug",
+            ),
+            time_box(
+                "2024-06-12T09:50:00Z",
+                TimeDelta::minutes(40),
+                "Fix login bug again",
+            ),
+        ]
+        .into();
+
+        let merged = tracker.merge_last_finished()?;
+
+        assert_eq!(4, merged.notes.len());
+        assert_eq!("Fix login bug", merged.title());
+        assert_eq!(90, merged.timedelta_total()?.num_minutes());
+        assert_eq!(2, tracker.finished.len());
+        assert_eq!(merged, tracker.finished[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn importing_skips_time_boxes_that_are_there_already() -> Result<()> {
+        let mut laptop = InMemoryTimeTracker::default();
+        laptop.begin("laptop")?;
+        laptop.end()?;
+        let mut desktop = InMemoryTimeTracker::default();
+        desktop.begin("desktop")?;
+        desktop.end()?;
+
+        let mut merged = laptop.clone();
+        assert_eq!(
+            ImportSummary {
+                imported: 1,
+                skipped: 0
+            },
+            merged.import(desktop.clone())?
+        );
+
+        // Either store again adds nothing, only a different first note makes it another time box
+        let mut both = desktop.clone();
+        both.import(laptop.clone())?;
+        assert_eq!(
+            ImportSummary {
+                imported: 0,
+                skipped: 2
+            },
+            merged.import(both)?
+        );
+
+        let mut renamed = desktop.clone();
+        renamed.finished = desktop
+            .finished
+            .iter()
+            .map(|tb| {
+                let mut tb = tb.clone();
+                tb.notes[0].description = "renamed".to_string();
+                tb
+            })
+            .collect::<Vec<_>>()
+            .into();
+        assert_eq!(1, merged.import(renamed)?.imported);
+        assert_eq!(3, merged.finished.len());
+        assert!(
+            merged
+                .finished
+                .is_sorted_by_key(|tb| tb.time_start().unwrap())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resume_takes_the_latest_start_regardless_of_insertion_order() -> Result<()> {
+        let time_box = |start: &str, title: &str| time_box(start, TimeDelta::hours(1), title);
+        let mut tracker = InMemoryTimeTracker {
+            active: None,
+            finished: vec![
+                time_box("2024-06-05T09:00:00Z", "Latest"),
+                time_box("2024-06-03T09:00:00Z", "Earliest"),
+            ]
+            .into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        // Imports come last in their source, but not here
+        let mut other = InMemoryTimeTracker::default();
+        other
+            .finished
+            .insert_sorted(time_box("2024-06-04T09:00:00Z", "Imported"));
+        tracker.import(other)?;
+
+        assert_eq!("Latest", tracker.resume()?.title());
+        tracker.end()?;
+
+        // The latest start wins, not the time box ended most recently
+        tracker.resume_at(0)?;
+        tracker.end()?;
+        assert_eq!("Latest", tracker.resume()?.title());
+
+        Ok(())
+    }
+
+    #[test]
+    fn writing_sorts_unsorted_notes_or_fails() -> Result<()> {
+        let tracker = InMemoryTimeTracker {
+            active: None,
+            finished: vec![time_box_of(vec![
+                note(time("2024-06-03T08:00:00Z"), "Planning"),
+                note(time("2024-06-03T10:00:00Z"), "Synced from laptop"),
+                note(time("2024-06-03T09:00:00Z"), "Review"),
+            ])]
+            .into(),
+            settings: StoreSettings::default(),
+            repairs: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let mut json = Vec::new();
+        let strict = JsonStorageStrategy {
+            pretty: false,
+            repair: false,
+        };
+        let err = tracker.to_writer(&strict, &mut json).unwrap_err();
+        assert!(
+            matches!(&err, Error::TimeBoxNoteIsNotLinearlySorted(n) if n.description == "Review"),
+            "{err:?}"
+        );
+        assert!(json.is_empty());
+
+        let repairing = JsonStorageStrategy {
+            pretty: false,
+            repair: true,
+        };
+        tracker.to_writer(&repairing, &mut json)?;
+        let written: InMemoryTimeTracker =
+            serde_json::from_slice(&json).map_err(Error::Deserialization)?;
+        let descriptions: Vec<&str> = written.finished[0]
+            .notes
+            .iter()
+            .map(|n| n.description.as_str())
+            .collect();
+        assert_eq!(
+            vec!["Planning", "Review", "Synced from laptop"],
+            descriptions
+        );
+
+        // Loading what got written has nothing left to repair
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.json");
+        std::fs::write(&path, &json)?;
+        let loaded = load(&JsonFileLoadingStrategy::new(&path).strict(true))?;
+        assert!(!loaded.was_repaired());
+        assert_eq!(written, loaded);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Diagnostic, Error, ListOptions, Result, SortOrder, TimeTrackerInitStrategy, TimeTrackingStore,
+    in_memory_tracker::{InMemoryTimeTracker, JsonFileLoadingStrategy},
+};
+
+/// Bump whenever the layout of the cache changes, older caches then simply count as stale.
+const CACHE_VERSION: u32 = 9;
+
+/// Identifies the exact JSON file a cache was generated from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    len: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> Result<Fingerprint> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok(Fingerprint {
+            len: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheState {
+    /// There is no cache file
+    Missing,
+    /// The cache was generated from a different version of the JSON file
+    Stale,
+    /// The cache belongs to the current JSON file
+    Valid(Box<InMemoryTimeTracker>),
+}
+
+/// Reads the cache and checks whether it belongs to the current content of the JSON file.
+/// Returns an error if the cache exists but cannot be parsed.
+pub fn inspect_cache(cache_path: &Path, json_path: &Path) -> Result<CacheState> {
+    let bytes = match std::fs::read(cache_path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(CacheState::Missing),
+        Err(e) => return Err(Error::Io(e)),
+    };
+
+    let (version, fingerprint, tracker): (u32, Fingerprint, InMemoryTimeTracker) =
+        match postcard::from_bytes(&bytes) {
+            Ok(content) => content,
+            // Layouts of older versions may not even parse
+            Err(_) if !bytes.starts_with(&[CACHE_VERSION as u8]) => {
+                return Ok(CacheState::Stale);
+            }
+            Err(e) => return Err(Error::Cache(e)),
+        };
+
+    if version != CACHE_VERSION || fingerprint != Fingerprint::of(json_path)? {
+        return Ok(CacheState::Stale);
+    }
+
+    Ok(CacheState::Valid(Box::new(tracker)))
+}
+
+/// Writes the cache for the current content of the JSON file at `json_path`.
+/// Call this after the JSON file was written, otherwise the cache is stale immediately.
+///
+/// Stores with repairs or diagnostics never get cached, the cache would hide them from later loads,
+/// e.g. from the guard against clearing repaired stores. Removes the existing cache instead.
+pub fn write_cache(
+    cache_path: &Path,
+    json_path: &Path,
+    store: &impl TimeTrackingStore,
+) -> Result<()> {
+    if !store.repairs()?.is_empty() || !store.diagnostics()?.is_empty() {
+        debug!("Not caching a store with repairs or diagnostics");
+        return match std::fs::remove_file(cache_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(Error::Io(e)),
+            _ => Ok(()),
+        };
+    }
+
+    // Same shape as `JsonStorageStrategy` writes, so the cache equals what loading the JSON file yields
+    let tracker = InMemoryTimeTracker {
+        active: store.active()?,
+        finished: store
+            .finished(
+                &ListOptions::new()
+                    .take(usize::MAX)
+                    .order(SortOrder::Ascending),
+            )?
+            .items
+            .into(),
+        settings: store.settings()?,
+        repairs: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    let fingerprint = Fingerprint::of(json_path)?;
+    let bytes =
+        postcard::to_stdvec(&(CACHE_VERSION, &fingerprint, &tracker)).map_err(Error::Cache)?;
+
+    // Write to a unique file first so concurrent readers never see a half written cache
+    let path_swap = cache_path.with_extension(format!("cache.{}", std::process::id()));
+    let mut writer = BufWriter::new(File::create(&path_swap)?);
+    writer.write_all(&bytes)?;
+    writer.flush()?;
+    drop(writer);
+
+    std::fs::rename(&path_swap, cache_path).map_err(Error::Io)
+}
+
+/// Loads the JSON file at `path` via the binary cache at `cache_path` if it is up to date.
+/// Otherwise falls back to parsing the JSON file and refreshes the cache.
+///
+/// The cache is always regenerable, so any problem with it only results in a warning, see `InMemoryTimeTracker::diagnostics`.
+#[derive(Debug)]
+pub struct CachedJsonFileLoadingStrategy<'a> {
+    pub path: &'a Path,
+    pub cache_path: &'a Path,
+}
+
+impl TimeTrackerInitStrategy for CachedJsonFileLoadingStrategy<'_> {
+    fn init(&self) -> Result<impl TimeTrackingStore> {
+        let mut diagnostics = Vec::new();
+        let context = self.cache_path.display().to_string();
+
+        match inspect_cache(self.cache_path, self.path) {
+            Ok(CacheState::Valid(tracker)) => {
+                debug!("Loaded store from cache: {}", self.cache_path.display());
+                return Ok(*tracker);
+            }
+            Ok(CacheState::Missing) | Ok(CacheState::Stale) => {
+                debug!("Cache is missing or stale: {}", self.cache_path.display())
+            }
+            Err(e) => diagnostics.push(
+                Diagnostic::warning(format!(
+                    "Ignoring unreadable cache, it gets regenerated: {}",
+                    e.message_with_sources()
+                ))
+                .context(context.clone()),
+            ),
+        }
+
+        let mut tracker = InMemoryTimeTracker::init(&JsonFileLoadingStrategy::new(self.path))?;
+
+        // Before adding the diagnostics about the cache itself, only those of the JSON file prevent caching
+        let refreshed = write_cache(self.cache_path, self.path, &tracker);
+        tracker.diagnostics.splice(0..0, diagnostics);
+
+        if let Err(e) = refreshed {
+            tracker.diagnostics.push(
+                Diagnostic::warning(format!(
+                    "Failed refreshing cache: {}",
+                    e.message_with_sources()
+                ))
+                .context(context),
+            );
+        }
+
+        Ok(tracker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Severity, in_memory_tracker::JsonStorageStrategy};
+
+    use super::*;
+
+    fn setup() -> Result<(tempfile::TempDir, InMemoryTimeTracker)> {
+        let dir = tempfile::tempdir()?;
+        let mut tracker = InMemoryTimeTracker::default();
+        tracker.begin("#1")?;
+        tracker.end()?;
+        tracker.begin("#2")?;
+
+        let mut file = File::create(dir.path().join("storage.json"))?;
+        tracker.to_writer(
+            &JsonStorageStrategy {
+                pretty: true,
+                repair: true,
+            },
+            &mut file,
+        )?;
+
+        Ok((dir, tracker))
+    }
+
+    #[test]
+    fn cache_round_trip() -> Result<()> {
+        let (dir, tracker) = setup()?;
+        let json_path = dir.path().join("storage.json");
+        let cache_path = dir.path().join("storage.cache");
+
+        assert!(matches!(
+            inspect_cache(&cache_path, &json_path)?,
+            CacheState::Missing
+        ));
+
+        write_cache(&cache_path, &json_path, &tracker)?;
+
+        match inspect_cache(&cache_path, &json_path)? {
+            CacheState::Valid(cached) => assert_eq!(tracker, *cached),
+            state => panic!("Expected valid cache, got {state:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn cache_is_stale_after_json_changed() -> Result<()> {
+        let (dir, mut tracker) = setup()?;
+        let json_path = dir.path().join("storage.json");
+        let cache_path = dir.path().join("storage.cache");
+
+        write_cache(&cache_path, &json_path, &tracker)?;
+
+        tracker.end()?;
+        let mut file = File::create(&json_path)?;
+        tracker.to_writer(
+            &JsonStorageStrategy {
+                pretty: false,
+                repair: true,
+            },
+            &mut file,
+        )?;
+
+        assert!(matches!(
+            inspect_cache(&cache_path, &json_path)?,
+            CacheState::Stale
+        ));
+
+        let loaded = InMemoryTimeTracker::init(&CachedJsonFileLoadingStrategy {
+            path: &json_path,
+            cache_path: &cache_path,
+        })?;
+        assert_eq!(tracker, loaded);
+
+        // Loading refreshed the cache
+        assert!(matches!(
+            inspect_cache(&cache_path, &json_path)?,
+            CacheState::Valid(_)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn cache_matches_json_of_unsorted_tracker() -> Result<()> {
+        let (dir, mut tracker) = setup()?;
+        let json_path = dir.path().join("storage.json");
+        let cache_path = dir.path().join("storage.cache");
+
+        // Files edited manually may be unsorted, `SortedTimeBoxes` sorts them while loading
+        tracker.end()?;
+        let unsorted: Vec<&crate::TimeBox> = tracker.finished.iter().rev().collect();
+        let content = serde_json::json!({ "active": null, "finished": unsorted });
+        std::fs::write(&json_path, content.to_string())?;
+        write_cache(&cache_path, &json_path, &tracker)?;
+
+        let from_json = InMemoryTimeTracker::init(&JsonFileLoadingStrategy::new(&json_path))?;
+        match inspect_cache(&cache_path, &json_path)? {
+            CacheState::Valid(cached) => assert_eq!(from_json, *cached),
+            state => panic!("Expected valid cache, got {state:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn repaired_stores_never_get_cached() -> Result<()> {
+        let (dir, _) = setup()?;
+        let json_path = dir.path().join("storage.json");
+        let cache_path = dir.path().join("storage.cache");
+
+        let content = serde_json::json!({
+            "active": null,
+            "finished": [{ "notes": [
+                { "time": "2024-06-03T10:00:00Z", "description": "later" },
+                { "time": "2024-06-03T09:00:00Z", "description": "earlier" },
+            ]}],
+        });
+        std::fs::write(&json_path, content.to_string())?;
+        std::fs::write(&cache_path, b"left from before")?;
+
+        let loaded = InMemoryTimeTracker::init(&CachedJsonFileLoadingStrategy {
+            path: &json_path,
+            cache_path: &cache_path,
+        })?;
+        assert!(loaded.was_repaired());
+        assert!(!cache_path.exists());
+
+        // Loading again still parses the JSON file, so the repairs stay visible
+        let loaded = InMemoryTimeTracker::init(&CachedJsonFileLoadingStrategy {
+            path: &json_path,
+            cache_path: &cache_path,
+        })?;
+        assert!(loaded.was_repaired());
+        assert!(!cache_path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_cache_falls_back_to_json() -> Result<()> {
+        let (dir, tracker) = setup()?;
+        let json_path = dir.path().join("storage.json");
+        let cache_path = dir.path().join("storage.cache");
+
+        std::fs::write(&cache_path, [CACHE_VERSION as u8, 0xff, 0xff, 0xff])?;
+        assert!(inspect_cache(&cache_path, &json_path).is_err());
+
+        let mut loaded = InMemoryTimeTracker::init(&CachedJsonFileLoadingStrategy {
+            path: &json_path,
+            cache_path: &cache_path,
+        })?;
+        let diagnostic = loaded.diagnostics.remove(0);
+        assert_eq!(Severity::Warning, diagnostic.severity);
+        assert!(diagnostic.message.starts_with("Ignoring unreadable cache"));
+        assert_eq!(Some(cache_path.display().to_string()), diagnostic.context);
+        assert_eq!(tracker, loaded);
+        Ok(())
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Diagnostic, Error, MAX_DESCRIPTION_LEN, Repair, Result, StoreSettings, TimeBox,
+    TimeTrackerInitStrategy, TimeTrackerStorageStrategy, TimeTrackingStore,
+    in_memory_tracker::InMemoryTimeTracker,
+};
+
+/// What changes on almost every command, kept apart from the finished time boxes, see `state_path`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JsonlState {
+    active: Option<TimeBox>,
+    #[serde(default)]
+    settings: StoreSettings,
+}
+
+/// File next to a JSON Lines store holding its active time box and settings,
+/// e.g. `storage.state` for `storage.jsonl`.
+pub fn state_path(path: &Path) -> PathBuf {
+    path.with_extension("state")
+}
+
+/// Loads a JSON Lines store, one finished time box per line, plus the file at `state_path`.
+/// A missing state file means there is no active time box and there are no settings.
+/// The last line gets dropped with a warning if it is incomplete, e.g. because writing it was interrupted,
+/// recorded as `Repair::DroppedIncompleteLine`. Repairs and validates like `JsonFileLoadingStrategy` otherwise.
+#[derive(Debug)]
+pub struct JsonlFileLoadingStrategy<'a> {
+    pub path: &'a Path,
+    pub strict: bool,
+    pub max_description_len: usize,
+}
+
+impl<'a> JsonlFileLoadingStrategy<'a> {
+    pub fn new(path: &'a Path) -> Self {
+        Self {
+            path,
+            strict: false,
+            max_description_len: MAX_DESCRIPTION_LEN,
+        }
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn max_description_len(mut self, max_description_len: usize) -> Self {
+        self.max_description_len = max_description_len;
+        self
+    }
+}
+
+impl TimeTrackerInitStrategy for JsonlFileLoadingStrategy<'_> {
+    fn init(&self) -> Result<impl TimeTrackingStore> {
+        let mut reader = BufReader::new(File::open(self.path)?);
+        let mut finished = Vec::new();
+        let mut repairs = Vec::new();
+        let mut diagnostics = Vec::new();
+
+        let mut line = String::new();
+        let mut number = 0;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            number += 1;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str(&line) {
+                Ok(time_box) => finished.push(time_box),
+                Err(e) if !line.ends_with('\n') => {
+                    diagnostics.push(
+                        Diagnostic::warning(format!(
+                            "Dropping the incomplete last line {number}: {e}"
+                        ))
+                        .context(self.path.display().to_string()),
+                    );
+                    repairs.push(Repair::DroppedIncompleteLine(number));
+                }
+                Err(e) => {
+                    return Err(Error::InvalidLine {
+                        line: number,
+                        error: Box::new(Error::Deserialization(e)),
+                    });
+                }
+            }
+        }
+
+        let state: JsonlState = match File::open(state_path(self.path)) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => JsonlState::default(),
+            Err(e) => return Err(Error::Io(e)),
+        };
+
+        InMemoryTimeTracker {
+            active: state.active,
+            finished: finished.into(),
+            settings: state.settings,
+            repairs,
+            diagnostics,
+        }
+        .repair(self.strict, self.max_description_len)
+    }
+}
+
+/// Writes every finished time box as one line of JSON, oldest first.
+/// The active time box and the settings go into the state file via `JsonlStateStorageStrategy`,
+/// so both strategies together write the whole store.
+#[derive(Debug)]
+pub struct JsonlStorageStrategy {
+    /// See `JsonStorageStrategy::repair`.
+    pub repair: bool,
+}
+
+impl TimeTrackerStorageStrategy for JsonlStorageStrategy {
+    fn write(
+        &self,
+        writer: &mut impl std::io::Write,
+        store: &impl TimeTrackingStore,
+    ) -> Result<()> {
+        let tracker = InMemoryTimeTracker::snapshot(store, self.repair)?;
+
+        let mut writer = BufWriter::new(writer);
+        for time_box in &tracker.finished {
+            write_line(&mut writer, time_box)?;
+        }
+
+        writer.flush().map_err(Error::Io)
+    }
+}
+
+/// Writes the active time box and the settings of a JSON Lines store, see `state_path`.
+/// Only the active time box gets validated, the finished ones are left to `JsonlStorageStrategy`.
+#[derive(Debug)]
+pub struct JsonlStateStorageStrategy {
+    /// See `JsonStorageStrategy::repair`.
+    pub repair: bool,
+}
+
+impl TimeTrackerStorageStrategy for JsonlStateStorageStrategy {
+    fn write(
+        &self,
+        writer: &mut impl std::io::Write,
+        store: &impl TimeTrackingStore,
+    ) -> Result<()> {
+        let active = InMemoryTimeTracker {
+            active: store.active()?,
+            ..Default::default()
+        }
+        .checked_for_writing(self.repair)?
+        .active;
+        let state = JsonlState {
+            active,
+            settings: store.settings()?,
+        };
+
+        serde_json::to_writer_pretty(writer, &state).map_err(Error::Serialization)
+    }
+}
+
+fn write_line(writer: &mut impl Write, time_box: &TimeBox) -> Result<()> {
+    serde_json::to_writer(&mut *writer, time_box).map_err(Error::Serialization)?;
+    writer.write_all(b"\n").map_err(Error::Io)
+}
+
+/// Appends `time_boxes` to the JSON Lines store at `path` without rewriting it, e.g. after ending a time box.
+/// All lines get written at once, so an interrupted write leaves at most an incomplete last line.
+/// The state file is not touched, write it afterwards via `JsonlStateStorageStrategy`.
+pub fn append_time_boxes(path: &Path, time_boxes: &[TimeBox]) -> Result<()> {
+    let mut file = OpenOptions::new().read(true).append(true).open(path)?;
+
+    let mut lines = Vec::new();
+
+    // Manually edited files may lack the final line break
+    if file.seek(SeekFrom::End(0))? > 0 {
+        let mut last = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            lines.push(b'\n');
+        }
+    }
+
+    for time_box in time_boxes {
+        write_line(&mut lines, time_box)?;
+    }
+
+    file.write_all(&lines)?;
+    file.sync_data().map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn stores() -> Result<Vec<InMemoryTimeTracker>> {
+        let empty = InMemoryTimeTracker::default();
+
+        let mut finished_only = InMemoryTimeTracker::default();
+        finished_only.begin("first")?;
+        finished_only.push_note("with \"quotes\"\nand a line break")?;
+        finished_only.end()?;
+        finished_only.begin("second")?;
+        finished_only.end()?;
+
+        let mut with_active = finished_only.clone();
+        with_active.begin("active")?;
+        with_active.pause()?;
+        with_active.set_settings(StoreSettings {
+            weekly_goal: Some(TimeDelta::hours(40)),
+            work_days: None,
+            work_hours: None,
+            ..Default::default()
+        })?;
+
+        Ok(vec![empty, finished_only, with_active])
+    }
+
+    fn save(store: &InMemoryTimeTracker, path: &Path) -> Result<()> {
+        store.to_writer(
+            &JsonlStorageStrategy { repair: true },
+            &mut File::create(path)?,
+        )?;
+        store.to_writer(
+            &JsonlStateStorageStrategy { repair: true },
+            &mut File::create(state_path(path))?,
+        )
+    }
+
+    fn load(path: &Path) -> Result<InMemoryTimeTracker> {
+        InMemoryTimeTracker::init(&JsonlFileLoadingStrategy::new(path))
+    }
+
+    #[test]
+    fn jsonl_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.jsonl");
+
+        for store in stores()? {
+            save(&store, &path)?;
+            assert_eq!(store, load(&path)?);
+            assert_eq!(
+                store.finished.len(),
+                std::fs::read_to_string(&path)?.lines().count()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_state_file_means_nothing_is_active() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.jsonl");
+        let store = stores()?.remove(2);
+        save(&store, &path)?;
+        std::fs::remove_file(state_path(&path))?;
+
+        let loaded = load(&path)?;
+        assert_eq!(None, loaded.active);
+        assert_eq!(StoreSettings::default(), loaded.settings);
+        assert_eq!(store.finished, loaded.finished);
+
+        // Without the lines there is no store at all
+        std::fs::remove_file(&path)?;
+        assert!(matches!(load(&path), Err(Error::Io(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn appending_equals_rewriting() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.jsonl");
+        let mut store = stores()?.remove(2);
+        save(&store, &path)?;
+
+        let ended = store.end()?;
+        store.begin("next")?;
+        append_time_boxes(&path, &[ended])?;
+        store.to_writer(
+            &JsonlStateStorageStrategy { repair: true },
+            &mut File::create(state_path(&path))?,
+        )?;
+        assert_eq!(store, load(&path)?);
+
+        // Appending to a file without final line break starts a new line nonetheless
+        let content = std::fs::read_to_string(&path)?;
+        std::fs::write(&path, content.trim_end())?;
+        let ended = store.end()?;
+        append_time_boxes(&path, &[ended])?;
+        assert_eq!(store.finished, load(&path)?.finished);
+
+        Ok(())
+    }
+
+    #[test]
+    fn incomplete_last_line_gets_dropped() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.jsonl");
+        let store = stores()?.remove(1);
+        save(&store, &path)?;
+
+        let content = std::fs::read_to_string(&path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        std::fs::write(&path, format!("{}\n{}", lines[0], &lines[1][..20]))?;
+
+        let loaded = load(&path)?;
+        assert_eq!(vec![store.finished[0].clone()], loaded.finished.to_vec());
+        assert_eq!(vec![Repair::DroppedIncompleteLine(2)], loaded.repairs);
+        assert!(
+            loaded.diagnostics[0]
+                .message
+                .starts_with("Dropping the incomplete last line 2")
+        );
+        assert_eq!(
+            Some(path.display().to_string()),
+            loaded.diagnostics[0].context
+        );
+
+        // Complete last lines are fine without line break
+        std::fs::write(&path, content.trim_end())?;
+        let loaded = load(&path)?;
+        assert_eq!(store, loaded);
+        assert!(!loaded.was_repaired());
+
+        Ok(())
+    }
+
+    #[test]
+    fn state_gets_validated_before_writing() -> Result<()> {
+        let mut store = stores()?.remove(2);
+        store.push_note("later")?;
+        let active = store.active.as_mut().unwrap();
+        active.notes.swap(0, 1);
+
+        let mut state = Vec::new();
+        assert!(matches!(
+            store.to_writer(&JsonlStateStorageStrategy { repair: false }, &mut state),
+            Err(Error::TimeBoxNoteIsNotLinearlySorted(_))
+        ));
+
+        store.to_writer(&JsonlStateStorageStrategy { repair: true }, &mut state)?;
+        let written: JsonlState = serde_json::from_slice(&state)?;
+        let notes = written.active.unwrap().notes;
+        assert!(notes.is_sorted_by_key(|n| n.time));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_lines_fail_with_their_number() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.jsonl");
+        let store = stores()?.remove(1);
+        save(&store, &path)?;
+
+        let content = std::fs::read_to_string(&path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        std::fs::write(&path, format!("{}\n\n{{\n{}\n", lines[0], lines[1]))?;
+
+        assert!(matches!(
+            load(&path),
+            Err(Error::InvalidLine { line: 3, .. })
+        ));
+
+        Ok(())
+    }
+}
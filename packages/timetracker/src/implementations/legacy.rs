@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{
+    Error, MAX_DESCRIPTION_LEN, NoteKind, Result, StoreSettings, TimeBox, TimeBoxNote,
+    TimeTrackerInitStrategy, TimeTrackingStore, in_memory_tracker::InMemoryTimeTracker,
+};
+
+/// Loads the store of the single binary version before this library, usually `tasks.json`,
+/// converting its tasks into time boxes. Repairs and validates like `JsonFileLoadingStrategy`.
+#[derive(Debug)]
+pub struct LegacyFileLoadingStrategy<'a> {
+    pub path: &'a Path,
+}
+
+impl TimeTrackerInitStrategy for LegacyFileLoadingStrategy<'_> {
+    fn init(&self) -> Result<impl TimeTrackingStore> {
+        let content = std::fs::read_to_string(self.path)?;
+
+        convert_legacy(&content)?.repair(false, MAX_DESCRIPTION_LEN)
+    }
+}
+
+/// Only read first, so unknown versions fail with their own error instead of a confusing parse error.
+#[derive(Debug, Deserialize)]
+struct LegacyVersion {
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyStoreV1 {
+    pending: Option<TaskPending>,
+    finished: Vec<TaskFinished>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskPending {
+    time_start: DateTime<Utc>,
+    notes: Vec<TaskNote>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskFinished {
+    time_start: DateTime<Utc>,
+    time_stop: DateTime<Utc>,
+    notes: Vec<TaskNote>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskNote {
+    time: DateTime<Utc>,
+    description: String,
+}
+
+/// Time boxes derive their start and stop from their notes, so tasks whose times disagree with
+/// their notes get rejected instead of silently changing their duration. Nothing gets made up.
+fn convert_legacy(content: &str) -> Result<InMemoryTimeTracker> {
+    let LegacyVersion { version } = serde_json::from_str(content)?;
+    if version != "V1" {
+        return Err(Error::UnknownLegacyVersion(version));
+    }
+
+    let store: LegacyStoreV1 = serde_json::from_str(content)?;
+
+    let active = match store.pending {
+        Some(task) => {
+            let tb = time_box(task.notes);
+            if tb.time_start().ok() != Some(task.time_start) {
+                return Err(Error::LegacyTaskTimesDisagree { index: None });
+            }
+            Some(tb)
+        }
+        None => None,
+    };
+
+    let mut finished = Vec::with_capacity(store.finished.len());
+    for (index, task) in store.finished.into_iter().enumerate() {
+        let tb = time_box(task.notes);
+        if tb.time_start().ok() != Some(task.time_start)
+            || tb.time_stop().ok() != Some(task.time_stop)
+        {
+            return Err(Error::LegacyTaskTimesDisagree { index: Some(index) });
+        }
+        finished.push(tb);
+    }
+
+    Ok(InMemoryTimeTracker {
+        active,
+        finished: finished.into(),
+        settings: StoreSettings::default(),
+        repairs: Vec::new(),
+        diagnostics: Vec::new(),
+    })
+}
+
+fn time_box(notes: Vec<TaskNote>) -> TimeBox {
+    TimeBox {
+        notes: notes
+            .into_iter()
+            .map(|n| TimeBoxNote {
+                time: n.time,
+                description: n.description,
+                kind: NoteKind::Regular,
+            })
+            .collect(),
+        exported_at: None,
+        pauses: Vec::new(),
+        estimate: None,
+        reference: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_tasks_keeping_their_notes() -> Result<()> {
+        let tracker = convert_legacy(
+            r#"{
+                "version": "V1",
+                "pending": {
+                    "time_start": "2024-06-04T08:00:00Z",
+                    "notes": [{ "time": "2024-06-04T08:00:00Z", "description": "Review" }]
+                },
+                "finished": [
+                    {
+                        "time_start": "2024-06-03T09:00:00Z",
+                        "time_stop": "2024-06-03T10:30:00Z",
+                        "notes": [
+                            { "time": "2024-06-03T09:00:00Z", "description": "Fix login" },
+                            { "time": "2024-06-03T09:45:00Z", "description": "found it" },
+                            { "time": "2024-06-03T10:30:00Z", "description": "done" }
+                        ]
+                    }
+                ]
+            }"#,
+        )?;
+
+        assert_eq!("Review", tracker.active.unwrap().title());
+        assert_eq!(1, tracker.finished.len());
+        let descriptions: Vec<_> = tracker.finished[0]
+            .notes
+            .iter()
+            .map(|n| n.description.as_str())
+            .collect();
+        assert_eq!(vec!["Fix login", "found it", "done"], descriptions);
+        assert_eq!(1.5, tracker.finished[0].duration_in_hours()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_convert_faithfully() {
+        assert!(matches!(
+            convert_legacy(r#"{ "version": "V2", "pending": null, "finished": [] }"#),
+            Err(Error::UnknownLegacyVersion(v)) if v == "V2"
+        ));
+        assert!(matches!(
+            convert_legacy(r#"{ "pending": null, "finished": [] }"#),
+            Err(Error::Deserialization(_))
+        ));
+
+        let stops_later = r#"{
+            "version": "V1",
+            "pending": null,
+            "finished": [{
+                "time_start": "2024-06-03T09:00:00Z",
+                "time_stop": "2024-06-03T11:00:00Z",
+                "notes": [{ "time": "2024-06-03T09:00:00Z", "description": "Fix login" }]
+            }]
+        }"#;
+        assert!(matches!(
+            convert_legacy(stops_later),
+            Err(Error::LegacyTaskTimesDisagree { index: Some(0) })
+        ));
+    }
+}
@@ -1 +1,10 @@
+pub mod event_log;
 pub mod in_memory_tracker;
+#[cfg(feature = "cache")]
+pub mod json_cache;
+pub mod jsonl_tracker;
+pub mod legacy;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_tracker;
+pub mod storage_lock;
+pub mod toml_tracker;
@@ -0,0 +1,406 @@
+use std::path::Path;
+
+use chrono::{DateTime, TimeDelta, Utc, Weekday};
+use rusqlite::{Connection, MAIN_DB, OpenFlags, params};
+
+use crate::{
+    Error, ListOptions, NoteKind, Pause, Result, SortOrder, StoreSettings, TimeBox, TimeBoxNote,
+    TimeTrackerInitStrategy, TimeTrackerStorageStrategy, TimeTrackingStore,
+    in_memory_tracker::InMemoryTimeTracker,
+};
+
+/// Active time boxes have no `ended_at`, there is at most one.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS time_box (
+        id INTEGER PRIMARY KEY,
+        ended_at TEXT,
+        exported_at TEXT,
+        estimate_seconds INTEGER,
+        reference TEXT
+    );
+    CREATE TABLE IF NOT EXISTS time_box_note (
+        box_id INTEGER NOT NULL REFERENCES time_box(id) ON DELETE CASCADE,
+        time TEXT NOT NULL,
+        description TEXT NOT NULL,
+        kind TEXT NOT NULL DEFAULT 'regular'
+    );
+    CREATE INDEX IF NOT EXISTS time_box_note_box_id ON time_box_note(box_id);
+    CREATE TABLE IF NOT EXISTS time_box_pause (
+        box_id INTEGER NOT NULL REFERENCES time_box(id) ON DELETE CASCADE,
+        start TEXT NOT NULL,
+        stop TEXT
+    );
+    CREATE TABLE IF NOT EXISTS store_settings (
+        weekly_goal_seconds INTEGER,
+        work_days TEXT,
+        work_hours_seconds INTEGER,
+        locked_before TEXT,
+        lock_suggested_for TEXT
+    );
+";
+
+/// Loads a store from the SQLite database at `path`, see `SqliteStorageStrategy`.
+#[derive(Debug)]
+pub struct SqliteLoadingStrategy<'a> {
+    pub path: &'a Path,
+}
+
+impl TimeTrackerInitStrategy for SqliteLoadingStrategy<'_> {
+    fn init(&self) -> Result<impl TimeTrackingStore> {
+        let connection = Connection::open_with_flags(self.path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(Error::Sqlite)?;
+
+        read_tracker(&connection)
+    }
+}
+
+fn read_tracker(connection: &Connection) -> Result<InMemoryTimeTracker> {
+    let mut tracker = InMemoryTimeTracker::default();
+
+    // Databases from before there were references lack their column
+    let reference = match has_column(connection, "time_box", "reference")? {
+        true => "reference",
+        false => "NULL",
+    };
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT time_box.id, time_box.ended_at IS NULL, exported_at, estimate_seconds, {reference}, time, description, kind
+            FROM time_box LEFT JOIN time_box_note ON time_box_note.box_id = time_box.id
+            ORDER BY time_box.id, time, time_box_note.rowid",
+        ))
+        .map_err(Error::Sqlite)?;
+
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, bool>(1)?,
+                row.get::<_, Option<DateTime<Utc>>>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<DateTime<Utc>>>(5)?,
+                row.get::<_, Option<String>>(6)?,
+                row.get::<_, Option<String>>(7)?,
+            ))
+        })
+        .map_err(Error::Sqlite)?;
+
+    // Rows are ordered by box, so each box is a consecutive run of rows
+    let mut boxes: Vec<(i64, bool, TimeBox)> = Vec::new();
+    for row in rows {
+        let (id, is_active, exported_at, estimate_seconds, reference, time, description, kind) =
+            row.map_err(Error::Sqlite)?;
+
+        if boxes.last().is_none_or(|(last_id, ..)| *last_id != id) {
+            let tb = TimeBox {
+                notes: vec![],
+                exported_at,
+                pauses: Vec::new(),
+                estimate: estimate_seconds.map(TimeDelta::seconds),
+                reference,
+            };
+            boxes.push((id, is_active, tb));
+        }
+
+        // Boxes without notes still yield one row, without any note columns
+        if let (Some(time), Some(description), Some((_, _, tb))) =
+            (time, description, boxes.last_mut())
+        {
+            let kind = match kind.as_deref() {
+                Some("system") => NoteKind::System,
+                _ => NoteKind::Regular,
+            };
+            tb.notes.push(TimeBoxNote {
+                time,
+                description,
+                kind,
+            });
+        }
+    }
+
+    let mut statement = connection
+        .prepare("SELECT box_id, start, stop FROM time_box_pause ORDER BY box_id, start")
+        .map_err(Error::Sqlite)?;
+
+    let pauses = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                Pause {
+                    start: row.get(1)?,
+                    stop: row.get(2)?,
+                },
+            ))
+        })
+        .map_err(Error::Sqlite)?;
+
+    for row in pauses {
+        let (box_id, pause) = row.map_err(Error::Sqlite)?;
+        if let Some((_, _, tb)) = boxes.iter_mut().find(|(id, ..)| *id == box_id) {
+            tb.pauses.push(pause);
+        }
+    }
+
+    let mut finished = Vec::new();
+    for (_, is_active, tb) in boxes {
+        match is_active {
+            true if tracker.active.is_some() => return Err(Error::ActiveTimeBoxExistsAlready),
+            true if tb.notes.is_empty() => return Err(Error::ActiveTimeBoxIsMissingNote),
+            true => tracker.active = Some(tb),
+            false if tb.notes.is_empty() => {
+                return Err(Error::TimeBoxIsMissingNote {
+                    index: finished.len(),
+                });
+            }
+            false => finished.push(tb),
+        }
+    }
+
+    tracker.finished = finished.into();
+    tracker.settings = read_settings(connection)?;
+
+    Ok(tracker)
+}
+
+fn has_column(connection: &Connection, table: &str, column: &str) -> Result<bool> {
+    connection
+        .prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")
+        .and_then(|mut statement| statement.exists(params![table, column]))
+        .map_err(Error::Sqlite)
+}
+
+/// Databases from before there were settings have no table for them, ones from before locking lack its columns.
+/// Work days are stored like `Mon,Tue`.
+fn read_settings(connection: &Connection) -> Result<StoreSettings> {
+    let has_table = connection
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'store_settings'")
+        .and_then(|mut statement| statement.exists([]))
+        .map_err(Error::Sqlite)?;
+    if !has_table {
+        return Ok(StoreSettings::default());
+    }
+
+    let lock = match has_column(connection, "store_settings", "locked_before")? {
+        true => "locked_before, lock_suggested_for",
+        false => "NULL, NULL",
+    };
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT weekly_goal_seconds, work_days, work_hours_seconds, {lock} FROM store_settings"
+        ))
+        .map_err(Error::Sqlite)?;
+    let row = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, Option<i64>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+                row.get::<_, Option<DateTime<Utc>>>(3)?,
+                row.get::<_, Option<DateTime<Utc>>>(4)?,
+            ))
+        })
+        .map_err(Error::Sqlite)?
+        .next();
+
+    let Some(row) = row else {
+        return Ok(StoreSettings::default());
+    };
+    let (weekly_goal, work_days, work_hours, locked_before, lock_suggested_for) =
+        row.map_err(Error::Sqlite)?;
+
+    Ok(StoreSettings {
+        weekly_goal: weekly_goal.map(TimeDelta::seconds),
+        work_days: work_days.map(|days| {
+            days.split(',')
+                .filter_map(|day| day.parse::<Weekday>().ok())
+                .collect()
+        }),
+        work_hours: work_hours.map(TimeDelta::seconds),
+        locked_before,
+        lock_suggested_for,
+    })
+}
+
+/// Writes the store as SQLite database file into the writer, so it can be saved via
+/// swap file and rename just like the JSON file. Load it with `SqliteLoadingStrategy`.
+#[derive(Debug)]
+pub struct SqliteStorageStrategy {}
+
+impl TimeTrackerStorageStrategy for SqliteStorageStrategy {
+    fn write(
+        &self,
+        writer: &mut impl std::io::Write,
+        store: &impl TimeTrackingStore,
+    ) -> Result<()> {
+        let mut connection = Connection::open_in_memory().map_err(Error::Sqlite)?;
+        connection.execute_batch(SCHEMA).map_err(Error::Sqlite)?;
+
+        let finished = store
+            .finished(
+                &ListOptions::new()
+                    .take(usize::MAX)
+                    .order(SortOrder::Ascending),
+            )?
+            .items;
+
+        let transaction = connection.transaction().map_err(Error::Sqlite)?;
+        for (tb, is_active) in finished
+            .iter()
+            .map(|tb| (tb, false))
+            .chain(store.active()?.iter().map(|tb| (tb, true)))
+            .collect::<Vec<_>>()
+        {
+            let ended_at = match is_active {
+                true => None,
+                false => Some(tb.time_stop()?),
+            };
+
+            transaction
+                .execute(
+                    "INSERT INTO time_box (ended_at, exported_at, estimate_seconds, reference) VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        ended_at,
+                        tb.exported_at,
+                        tb.estimate.map(|e| e.num_seconds()),
+                        tb.reference
+                    ],
+                )
+                .map_err(Error::Sqlite)?;
+            let box_id = transaction.last_insert_rowid();
+
+            for note in tb.notes.iter() {
+                transaction
+                    .execute(
+                        "INSERT INTO time_box_note (box_id, time, description, kind) VALUES (?1, ?2, ?3, ?4)",
+                        params![
+                            box_id,
+                            note.time,
+                            note.description,
+                            match note.kind {
+                                NoteKind::Regular => "regular",
+                                NoteKind::System => "system",
+                            }
+                        ],
+                    )
+                    .map_err(Error::Sqlite)?;
+            }
+
+            for pause in tb.pauses.iter() {
+                transaction
+                    .execute(
+                        "INSERT INTO time_box_pause (box_id, start, stop) VALUES (?1, ?2, ?3)",
+                        params![box_id, pause.start, pause.stop],
+                    )
+                    .map_err(Error::Sqlite)?;
+            }
+        }
+
+        let settings = store.settings()?;
+        transaction
+            .execute(
+                "INSERT INTO store_settings (weekly_goal_seconds, work_days, work_hours_seconds, locked_before, lock_suggested_for) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    settings.weekly_goal.map(|d| d.num_seconds()),
+                    settings.work_days.map(|days| {
+                        days.iter()
+                            .map(|day| day.to_string())
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    }),
+                    settings.work_hours.map(|d| d.num_seconds()),
+                    settings.locked_before,
+                    settings.lock_suggested_for
+                ],
+            )
+            .map_err(Error::Sqlite)?;
+        transaction.commit().map_err(Error::Sqlite)?;
+
+        let data = connection.serialize(MAIN_DB).map_err(Error::Sqlite)?;
+        writer.write_all(&data).map_err(Error::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    fn save(tracker: &InMemoryTimeTracker, path: &Path) -> Result<()> {
+        let mut file = File::create(path)?;
+        tracker.to_writer(&SqliteStorageStrategy {}, &mut file)
+    }
+
+    fn load(path: &Path) -> Result<InMemoryTimeTracker> {
+        InMemoryTimeTracker::init(&SqliteLoadingStrategy { path })
+    }
+
+    #[test]
+    fn persists_across_reloads() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.sqlite");
+
+        let mut tracker = InMemoryTimeTracker::default();
+        save(&tracker, &path)?;
+        assert_eq!(tracker, load(&path)?);
+
+        tracker.begin("first")?;
+        tracker.push_note("with a note")?;
+        tracker.end()?;
+        tracker.set_exported_at(0, Some(Utc::now()))?;
+        tracker
+            .finished
+            .update(0, |tb| tb.notes[1].kind = NoteKind::System);
+        save(&tracker, &path)?;
+
+        let mut tracker = load(&path)?;
+        assert_eq!(NoteKind::System, tracker.finished[0].notes[1].kind);
+        assert!(tracker.active.is_none());
+        assert_eq!(1, tracker.finished.len());
+        assert_eq!(2, tracker.finished[0].notes.len());
+        assert!(tracker.finished[0].exported_at.is_some());
+
+        tracker.begin("second")?;
+        tracker.set_reference(Some("PROJ-123"))?;
+        tracker.pause()?;
+        tracker.settings = StoreSettings {
+            weekly_goal: Some(TimeDelta::hours(40)),
+            work_days: Some(vec![Weekday::Mon, Weekday::Thu]),
+            work_hours: None,
+            locked_before: Some(Utc::now()),
+            lock_suggested_for: None,
+        };
+        save(&tracker, &path)?;
+
+        let reloaded = load(&path)?;
+        assert_eq!(tracker, reloaded);
+        assert_eq!("second", reloaded.active.unwrap().title());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_multiple_active_time_boxes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.sqlite");
+
+        let connection = Connection::open(&path).map_err(Error::Sqlite)?;
+        connection
+            .execute_batch(&format!(
+                "{SCHEMA}
+                INSERT INTO time_box (id, ended_at) VALUES (1, NULL), (2, NULL);
+                INSERT INTO time_box_note (box_id, time, description) VALUES
+                    (1, '2024-06-03T08:00:00Z', 'a'),
+                    (2, '2024-06-03T09:00:00Z', 'b');"
+            ))
+            .map_err(Error::Sqlite)?;
+        drop(connection);
+
+        assert!(matches!(
+            load(&path),
+            Err(Error::ActiveTimeBoxExistsAlready)
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Error, Result};
+
+/// How often a blocked lock gets retried until the timeout is reached.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Many readers may hold a shared lock at the same time.
+    Shared,
+    /// Only one writer may hold an exclusive lock and no readers may hold a shared one meanwhile.
+    Exclusive,
+}
+
+/// Advisory lock on a separate lock file next to the store.
+/// Prevents concurrent processes from reading stale data and overwriting each others changes.
+///
+/// The lock gets released when this value is dropped.
+#[derive(Debug)]
+pub struct StorageLock {
+    _file: File,
+}
+
+impl StorageLock {
+    /// Tries to acquire the lock until `timeout` elapsed.
+    /// Returns `Error::StorageLocked` if another process holds the lock for longer than that.
+    pub fn acquire(path: &Path, mode: LockMode, timeout: Duration) -> Result<StorageLock> {
+        // Never truncate, the lock file is shared between processes and has no content anyways
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+
+        let started = Instant::now();
+
+        loop {
+            let attempt = match mode {
+                LockMode::Shared => file.try_lock_shared(),
+                LockMode::Exclusive => file.try_lock(),
+            };
+
+            match attempt {
+                Ok(()) => return Ok(StorageLock { _file: file }),
+                Err(TryLockError::WouldBlock) if started.elapsed() < timeout => {
+                    thread::sleep(POLL_INTERVAL)
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(Error::StorageLocked {
+                        path: path.to_path_buf(),
+                    });
+                }
+                Err(TryLockError::Error(e)) => return Err(Error::Io(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclusive_lock_blocks_other_locks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.lock");
+
+        let _lock = StorageLock::acquire(&path, LockMode::Exclusive, Duration::ZERO)?;
+
+        let err = StorageLock::acquire(&path, LockMode::Exclusive, Duration::ZERO).unwrap_err();
+        assert!(matches!(err, Error::StorageLocked { .. }));
+
+        let err = StorageLock::acquire(&path, LockMode::Shared, Duration::ZERO).unwrap_err();
+        assert!(matches!(err, Error::StorageLocked { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn shared_locks_do_not_block_each_other() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.lock");
+
+        let _first = StorageLock::acquire(&path, LockMode::Shared, Duration::ZERO)?;
+        let _second = StorageLock::acquire(&path, LockMode::Shared, Duration::ZERO)?;
+
+        let err = StorageLock::acquire(&path, LockMode::Exclusive, Duration::ZERO).unwrap_err();
+        assert!(matches!(err, Error::StorageLocked { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn lock_gets_released_on_drop() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.lock");
+
+        drop(StorageLock::acquire(
+            &path,
+            LockMode::Exclusive,
+            Duration::ZERO,
+        )?);
+        StorageLock::acquire(&path, LockMode::Exclusive, Duration::ZERO)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use crate::{
+    Error, MAX_DESCRIPTION_LEN, Result, TimeTrackerInitStrategy, TimeTrackerStorageStrategy,
+    TimeTrackingStore, in_memory_tracker::InMemoryTimeTracker,
+};
+
+/// Loads a TOML store that may have been edited manually.
+/// Repairs and validates exactly like `JsonFileLoadingStrategy`.
+#[derive(Debug)]
+pub struct TomlFileLoadingStrategy<'a> {
+    pub path: &'a Path,
+    pub strict: bool,
+    pub max_description_len: usize,
+}
+
+impl<'a> TomlFileLoadingStrategy<'a> {
+    pub fn new(path: &'a Path) -> Self {
+        Self {
+            path,
+            strict: false,
+            max_description_len: MAX_DESCRIPTION_LEN,
+        }
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn max_description_len(mut self, max_description_len: usize) -> Self {
+        self.max_description_len = max_description_len;
+        self
+    }
+}
+
+impl TimeTrackerInitStrategy for TomlFileLoadingStrategy<'_> {
+    fn init(&self) -> Result<impl TimeTrackingStore> {
+        let content = std::fs::read_to_string(self.path)?;
+
+        let tracker: InMemoryTimeTracker =
+            toml::from_str(&content).map_err(Error::TomlDeserialization)?;
+
+        tracker.repair(self.strict, self.max_description_len)
+    }
+}
+
+/// Line based and therefore produces smaller diffs than JSON, for example when the store is versioned.
+#[derive(Debug)]
+pub struct TomlStorageStrategy {
+    /// See `JsonStorageStrategy::repair`.
+    pub repair: bool,
+}
+
+impl TimeTrackerStorageStrategy for TomlStorageStrategy {
+    fn write(
+        &self,
+        writer: &mut impl std::io::Write,
+        store: &impl TimeTrackingStore,
+    ) -> Result<()> {
+        let tracker = InMemoryTimeTracker::snapshot(store, self.repair)?;
+
+        let content = toml::to_string(&tracker).map_err(Error::TomlSerialization)?;
+        writer.write_all(content.as_bytes()).map_err(Error::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::{
+        StoreSettings,
+        in_memory_tracker::{JsonFileLoadingStrategy, JsonStorageStrategy},
+    };
+
+    use super::*;
+
+    fn stores() -> Result<Vec<InMemoryTimeTracker>> {
+        let empty = InMemoryTimeTracker::default();
+
+        let mut finished_only = InMemoryTimeTracker::default();
+        finished_only.begin("first")?;
+        finished_only.push_note("with \"quotes\"\nand a line break")?;
+        finished_only.pause()?;
+        finished_only.unpause()?;
+        finished_only.end()?;
+
+        let mut with_active = InMemoryTimeTracker::default();
+        with_active.begin("finished")?;
+        with_active.end()?;
+        with_active.begin("active")?;
+        with_active.push_note("note")?;
+        with_active.pause()?;
+        with_active.set_settings(StoreSettings {
+            weekly_goal: Some(chrono::TimeDelta::hours(40)),
+            work_days: Some(vec![chrono::Weekday::Mon, chrono::Weekday::Tue]),
+            work_hours: None,
+            locked_before: Some("2024-06-03T00:00:00Z".parse().unwrap()),
+            lock_suggested_for: None,
+        })?;
+
+        Ok(vec![empty, finished_only, with_active])
+    }
+
+    #[test]
+    fn toml_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.toml");
+
+        for store in stores()? {
+            let mut file = File::create(&path)?;
+            store.to_writer(&TomlStorageStrategy { repair: true }, &mut file)?;
+
+            let loaded = InMemoryTimeTracker::init(&TomlFileLoadingStrategy::new(&path))?;
+            assert_eq!(store, loaded);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("storage.json");
+
+        for store in stores()? {
+            for pretty in [false, true] {
+                let mut file = File::create(&path)?;
+                store.to_writer(
+                    &JsonStorageStrategy {
+                        pretty,
+                        repair: true,
+                    },
+                    &mut file,
+                )?;
+
+                let loaded = InMemoryTimeTracker::init(&JsonFileLoadingStrategy::new(&path))?;
+                assert_eq!(store, loaded);
+            }
+        }
+
+        Ok(())
+    }
+}
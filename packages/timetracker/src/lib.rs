@@ -1,9 +1,33 @@
+mod aggregation;
+mod diagnostics;
+mod diff;
+mod duration;
 mod entities;
 mod error;
+mod export;
+mod goal;
 mod implementations;
+mod lock;
+mod observer;
+mod settings;
+mod sorted_time_boxes;
+#[cfg(test)]
+mod test_fixtures;
 mod tracking;
+mod validation;
 
+pub use aggregation::*;
+pub use diagnostics::*;
+pub use diff::*;
+pub use duration::*;
 pub use entities::*;
 pub use error::*;
+pub use export::*;
+pub use goal::*;
 pub use implementations::*;
+pub use lock::*;
+pub use observer::*;
+pub use settings::*;
+pub use sorted_time_boxes::*;
 pub use tracking::*;
+pub use validation::*;
@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+
+use crate::{StoreSettings, TimeBox};
+
+/// Whether to suggest locking the `finished` time boxes before `period_start`, the start of the current period,
+/// e.g. the local midnight starting the week. Takes the period instead of looking at the clock, so any period can be checked.
+///
+/// Suggests `period_start` as `StoreSettings::locked_before` once every time box before it is exported,
+/// unless there is nothing left to lock. Applications remember showing the suggestion in `StoreSettings::lock_suggested_for`,
+/// so it comes up at most once per period, even if it gets ignored.
+pub fn suggest_lock(
+    settings: &StoreSettings,
+    finished: &[TimeBox],
+    period_start: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    // Also covers clocks turned back into an earlier period
+    if settings.lock_suggested_for >= Some(period_start)
+        || settings.locked_before >= Some(period_start)
+    {
+        return None;
+    }
+
+    let mut unlocked = finished
+        .iter()
+        .filter(|tb| {
+            tb.time_start().is_ok_and(|start| {
+                start < period_start && settings.locked_before.is_none_or(|before| start >= before)
+            })
+        })
+        .peekable();
+
+    unlocked.peek()?;
+    unlocked
+        .all(|tb| tb.exported_at.is_some())
+        .then_some(period_start)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::test_fixtures::{time, time_box};
+
+    fn tracked(start: &str, exported: bool) -> TimeBox {
+        let mut tb = time_box(start, TimeDelta::hours(1), "Fix login bug");
+        tb.exported_at = exported.then(|| time(start) + TimeDelta::hours(2));
+        tb
+    }
+
+    /// Asks like the application would on its first run in the week starting at `period_start`,
+    /// remembering the suggestion if there is one.
+    fn first_run(
+        settings: &mut StoreSettings,
+        finished: &[TimeBox],
+        period_start: &str,
+    ) -> Option<DateTime<Utc>> {
+        let suggestion = suggest_lock(settings, finished, time(period_start));
+        if suggestion.is_some() {
+            settings.lock_suggested_for = suggestion;
+        }
+        suggestion
+    }
+
+    #[test]
+    fn suggests_once_per_week_after_exporting() {
+        let mut settings = StoreSettings::default();
+        let mut finished = vec![
+            tracked("2024-06-03T09:00:00Z", true),
+            tracked("2024-06-05T09:00:00Z", false),
+        ];
+
+        // Within the first week there is nothing before the period yet
+        assert_eq!(
+            None,
+            first_run(&mut settings, &finished, "2024-06-03T00:00:00Z")
+        );
+
+        // The second week starts with an unexported time box of the first one
+        assert_eq!(
+            None,
+            first_run(&mut settings, &finished, "2024-06-10T00:00:00Z")
+        );
+
+        // Exporting it later that week lets the next run suggest, but only once
+        finished[1].exported_at = Some(time("2024-06-11T17:00:00Z"));
+        assert_eq!(
+            Some(time("2024-06-10T00:00:00Z")),
+            first_run(&mut settings, &finished, "2024-06-10T00:00:00Z")
+        );
+        assert_eq!(
+            None,
+            first_run(&mut settings, &finished, "2024-06-10T00:00:00Z")
+        );
+
+        // Ignored, so the next week suggests again with its own start
+        finished.push(tracked("2024-06-12T09:00:00Z", true));
+        assert_eq!(
+            Some(time("2024-06-17T00:00:00Z")),
+            first_run(&mut settings, &finished, "2024-06-17T00:00:00Z")
+        );
+
+        // Clocks turned back into an earlier week do not suggest again
+        assert_eq!(
+            None,
+            first_run(&mut settings, &finished, "2024-06-10T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn locked_time_boxes_need_no_suggestion() {
+        let finished = vec![
+            tracked("2024-06-03T09:00:00Z", false),
+            tracked("2024-06-10T09:00:00Z", true),
+        ];
+        let mut settings = StoreSettings {
+            locked_before: Some(time("2024-06-10T00:00:00Z")),
+            ..Default::default()
+        };
+
+        // Locking followed the suggestion or came first, the unexported time box is locked already
+        assert_eq!(
+            None,
+            first_run(&mut settings, &finished, "2024-06-10T00:00:00Z")
+        );
+        assert_eq!(
+            Some(time("2024-06-17T00:00:00Z")),
+            first_run(&mut settings, &finished, "2024-06-17T00:00:00Z")
+        );
+
+        // Nothing new was tracked in the week after locking
+        settings.locked_before = Some(time("2024-06-17T00:00:00Z"));
+        assert_eq!(
+            None,
+            first_run(&mut settings, &finished, "2024-06-24T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn empty_stores_get_no_suggestion() {
+        assert_eq!(
+            None,
+            suggest_lock(&StoreSettings::default(), &[], time("2024-06-10T00:00:00Z"))
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    Diagnostic, ListOptions, ListResult, Query, Repair, Result, StoreSettings, TimeBox,
+    TimeTrackerInitStrategy, TimeTrackingStore,
+};
+
+/// Change of a store, passed to the observers of `ObservedStore` once it succeeded.
+/// Carries what the operation returned, e.g. the newly ended time box.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackingEvent {
+    Began(TimeBox),
+    /// Via `push_note` or `push_note_at`.
+    NoteAdded(TimeBox),
+    Paused(TimeBox),
+    Unpaused(TimeBox),
+    Ended(TimeBox),
+    Amended(TimeBox),
+    NoteEdited(TimeBox),
+    EstimateSet(TimeBox),
+    ReferenceSet(TimeBox),
+    Split {
+        finished: TimeBox,
+        active: TimeBox,
+    },
+    Merged(TimeBox),
+    /// Via `resume` or `resume_at`.
+    Resumed(TimeBox),
+    ExportedAtSet {
+        index: usize,
+        time_box: TimeBox,
+    },
+    Canceled(TimeBox),
+    /// Count of removed time boxes, via `clear` or `clear_filtered`.
+    Cleared(usize),
+    SettingsChanged(StoreSettings),
+}
+
+/// Callback of `ObservedStore`, shared state needs interior mutability, e.g. a `RefCell`.
+pub type Observer = Box<dyn Fn(&TrackingEvent)>;
+
+/// Wraps a store and tells observers about every change, e.g. to notify or to update a status bar.
+/// Observers get called in the order they were added and only after the change succeeded,
+/// failing operations emit nothing. Reading passes straight through.
+pub struct ObservedStore<S: TimeTrackingStore> {
+    store: S,
+    observers: Vec<Observer>,
+}
+
+impl<S: TimeTrackingStore> ObservedStore<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn observe(mut self, observer: impl Fn(&TrackingEvent) + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.store
+    }
+
+    /// Changes through it bypass the observers, e.g. for replacing the whole store.
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    /// Passes `result` through, telling the observers about it if it succeeded.
+    fn emit<T>(&self, result: Result<T>, event: impl FnOnce(&T) -> TrackingEvent) -> Result<T> {
+        if let Ok(value) = &result {
+            let event = event(value);
+            for observer in &self.observers {
+                observer(&event);
+            }
+        }
+
+        result
+    }
+}
+
+impl<S: TimeTrackingStore + std::fmt::Debug> std::fmt::Debug for ObservedStore<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObservedStore")
+            .field("store", &self.store)
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+impl<S: TimeTrackingStore> TimeTrackingStore for ObservedStore<S> {
+    fn active(&self) -> Result<Option<TimeBox>> {
+        self.store.active()
+    }
+
+    fn finished(&self, options: &ListOptions) -> Result<ListResult> {
+        self.store.finished(options)
+    }
+
+    fn begin(&mut self, description: &str) -> Result<TimeBox> {
+        let result = self.store.begin(description);
+        self.emit(result, |tb| TrackingEvent::Began(tb.clone()))
+    }
+
+    fn begin_at(&mut self, description: &str, time: DateTime<Utc>) -> Result<TimeBox> {
+        let result = self.store.begin_at(description, time);
+        self.emit(result, |tb| TrackingEvent::Began(tb.clone()))
+    }
+
+    fn push_note(&mut self, description: &str) -> Result<TimeBox> {
+        let result = self.store.push_note(description);
+        self.emit(result, |tb| TrackingEvent::NoteAdded(tb.clone()))
+    }
+
+    fn push_note_at(&mut self, description: &str, time: DateTime<Utc>) -> Result<TimeBox> {
+        let result = self.store.push_note_at(description, time);
+        self.emit(result, |tb| TrackingEvent::NoteAdded(tb.clone()))
+    }
+
+    fn pause(&mut self) -> Result<TimeBox> {
+        let result = self.store.pause();
+        self.emit(result, |tb| TrackingEvent::Paused(tb.clone()))
+    }
+
+    fn unpause(&mut self) -> Result<TimeBox> {
+        let result = self.store.unpause();
+        self.emit(result, |tb| TrackingEvent::Unpaused(tb.clone()))
+    }
+
+    fn end(&mut self) -> Result<TimeBox> {
+        let result = self.store.end();
+        self.emit(result, |tb| TrackingEvent::Ended(tb.clone()))
+    }
+
+    fn amend(&mut self, description: &str) -> Result<TimeBox> {
+        let result = self.store.amend(description);
+        self.emit(result, |tb| TrackingEvent::Amended(tb.clone()))
+    }
+
+    fn edit_note(&mut self, note_index: usize, description: &str) -> Result<TimeBox> {
+        let result = self.store.edit_note(note_index, description);
+        self.emit(result, |tb| TrackingEvent::NoteEdited(tb.clone()))
+    }
+
+    fn set_estimate(&mut self, estimate: Option<TimeDelta>) -> Result<TimeBox> {
+        let result = self.store.set_estimate(estimate);
+        self.emit(result, |tb| TrackingEvent::EstimateSet(tb.clone()))
+    }
+
+    fn set_reference(&mut self, reference: Option<&str>) -> Result<TimeBox> {
+        let result = self.store.set_reference(reference);
+        self.emit(result, |tb| TrackingEvent::ReferenceSet(tb.clone()))
+    }
+
+    fn split_active(&mut self, note_index: usize) -> Result<(TimeBox, TimeBox)> {
+        let result = self.store.split_active(note_index);
+        self.emit(result, |(finished, active)| TrackingEvent::Split {
+            finished: finished.clone(),
+            active: active.clone(),
+        })
+    }
+
+    fn merge_last_finished(&mut self) -> Result<TimeBox> {
+        let result = self.store.merge_last_finished();
+        self.emit(result, |tb| TrackingEvent::Merged(tb.clone()))
+    }
+
+    fn resume(&mut self) -> Result<TimeBox> {
+        let result = self.store.resume();
+        self.emit(result, |tb| TrackingEvent::Resumed(tb.clone()))
+    }
+
+    fn resume_at(&mut self, index: usize) -> Result<TimeBox> {
+        let result = self.store.resume_at(index);
+        self.emit(result, |tb| TrackingEvent::Resumed(tb.clone()))
+    }
+
+    fn set_exported_at(
+        &mut self,
+        index: usize,
+        exported_at: Option<DateTime<Utc>>,
+    ) -> Result<TimeBox> {
+        let result = self.store.set_exported_at(index, exported_at);
+        self.emit(result, |tb| TrackingEvent::ExportedAtSet {
+            index,
+            time_box: tb.clone(),
+        })
+    }
+
+    fn cancel(&mut self) -> Result<TimeBox> {
+        let result = self.store.cancel();
+        self.emit(result, |tb| TrackingEvent::Canceled(tb.clone()))
+    }
+
+    fn clear(&mut self) -> Result<usize> {
+        let result = self.store.clear();
+        self.emit(result, |count| TrackingEvent::Cleared(*count))
+    }
+
+    fn clear_filtered(&mut self, query: &Query) -> Result<usize> {
+        let result = self.store.clear_filtered(query);
+        self.emit(result, |count| TrackingEvent::Cleared(*count))
+    }
+
+    fn settings(&self) -> Result<StoreSettings> {
+        self.store.settings()
+    }
+
+    fn set_settings(&mut self, settings: StoreSettings) -> Result<()> {
+        let result = self.store.set_settings(settings.clone());
+        self.emit(result, |_| TrackingEvent::SettingsChanged(settings))
+    }
+
+    fn repairs(&self) -> Result<Vec<Repair>> {
+        self.store.repairs()
+    }
+
+    fn diagnostics(&self) -> Result<Vec<Diagnostic>> {
+        self.store.diagnostics()
+    }
+
+    /// Starts without observers, add them via `observe`.
+    fn init(strategy: &impl TimeTrackerInitStrategy) -> Result<Self> {
+        Ok(Self::new(S::init(strategy)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{Error, in_memory_tracker::InMemoryTimeTracker};
+
+    use super::*;
+
+    type Events = Rc<RefCell<Vec<TrackingEvent>>>;
+
+    fn observed() -> (ObservedStore<InMemoryTimeTracker>, Events) {
+        let events = Events::default();
+        let recorded = events.clone();
+        let store = ObservedStore::new(InMemoryTimeTracker::default())
+            .observe(move |event| recorded.borrow_mut().push(event.clone()));
+        (store, events)
+    }
+
+    #[test]
+    fn events_follow_the_operations_in_order() -> Result<()> {
+        let (mut store, events) = observed();
+
+        let began = store.begin("first")?;
+        let noted = store.push_note("second")?;
+        let paused = store.pause()?;
+        let unpaused = store.unpause()?;
+        let ended = store.end()?;
+        let resumed = store.resume_at(0)?;
+        let canceled = store.cancel()?;
+        let cleared = store.clear()?;
+
+        assert_eq!(
+            vec![
+                TrackingEvent::Began(began),
+                TrackingEvent::NoteAdded(noted),
+                TrackingEvent::Paused(paused),
+                TrackingEvent::Unpaused(unpaused),
+                TrackingEvent::Ended(ended),
+                TrackingEvent::Resumed(resumed),
+                TrackingEvent::Canceled(canceled),
+                TrackingEvent::Cleared(cleared),
+            ],
+            *events.borrow()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_operations_emit_nothing() -> Result<()> {
+        let (mut store, events) = observed();
+
+        assert!(matches!(store.end(), Err(Error::NoActiveTimeBox)));
+        assert!(store.push_note("nothing active").is_err());
+        assert!(store.cancel().is_err());
+        assert!(store.merge_last_finished().is_err());
+        assert!(store.resume_at(3).is_err());
+
+        store.begin("active")?;
+        assert!(matches!(
+            store.begin("again"),
+            Err(Error::ActiveTimeBoxExistsAlready)
+        ));
+        assert!(store.split_active(0).is_err());
+
+        assert!(matches!(events.borrow()[..], [TrackingEvent::Began(_)]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn observers_get_called_in_the_order_they_were_added() -> Result<()> {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let (first, second) = (calls.clone(), calls.clone());
+        let mut store = ObservedStore::new(InMemoryTimeTracker::default())
+            .observe(move |_| first.borrow_mut().push("first"))
+            .observe(move |_| second.borrow_mut().push("second"));
+
+        store.begin("task")?;
+        store.end()?;
+
+        assert_eq!(vec!["first", "second", "first", "second"], *calls.borrow());
+        assert_eq!(1, store.into_inner().finished.len());
+
+        Ok(())
+    }
+}
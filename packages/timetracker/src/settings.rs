@@ -0,0 +1,50 @@
+use chrono::{DateTime, TimeDelta, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::entities::estimate_seconds;
+
+/// Settings describing the tracked time, so they travel with the store, e.g. when versioning it with git.
+/// `None` leaves the choice to the application. How things look stays with the application, e.g. colors.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreSettings {
+    /// Time to track per ISO week. Serialized as seconds.
+    #[serde(default, with = "estimate_seconds")]
+    pub weekly_goal: Option<TimeDelta>,
+    /// Days of the work week, e.g. to split the weekly goal across.
+    #[serde(default)]
+    pub work_days: Option<Vec<Weekday>>,
+    /// Most time worked per day. Serialized as seconds.
+    #[serde(default, with = "estimate_seconds")]
+    pub work_hours: Option<TimeDelta>,
+    /// Finished time boxes starting before this are final, e.g. once billed, see `Error::TimeBoxLocked`.
+    #[serde(default)]
+    pub locked_before: Option<DateTime<Utc>>,
+    /// Start of the period locking was suggested for last, so it gets suggested once per period, see `suggest_lock`.
+    #[serde(default)]
+    pub lock_suggested_for: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_settings_are_none() {
+        let settings: StoreSettings = serde_json::from_str("{}").unwrap();
+        assert_eq!(StoreSettings::default(), settings);
+
+        let settings = StoreSettings {
+            weekly_goal: Some(TimeDelta::hours(40)),
+            work_days: Some(vec![Weekday::Mon, Weekday::Fri]),
+            work_hours: None,
+            locked_before: Some("2024-06-03T00:00:00Z".parse().unwrap()),
+            lock_suggested_for: None,
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        assert_eq!(
+            r#"{"weekly_goal":144000,"work_days":["Mon","Fri"],"work_hours":null,"locked_before":"2024-06-03T00:00:00Z","lock_suggested_for":null}"#,
+            json
+        );
+        assert_eq!(settings, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -0,0 +1,260 @@
+use std::ops::{Deref, Range};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::TimeBox;
+
+/// Time boxes in ascending order, see `Ord for TimeBox`.
+/// Only allows changes that keep the order, so indices are stable between listing and e.g. resuming.
+/// Derefs to a slice for reading.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct SortedTimeBoxes(Vec<TimeBox>);
+
+impl SortedTimeBoxes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts behind equal time boxes, returns the index of the inserted time box.
+    pub fn insert_sorted(&mut self, tb: TimeBox) -> usize {
+        let index = self.0.partition_point(|other| other <= &tb);
+        self.0.insert(index, tb);
+        index
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<TimeBox> {
+        (index < self.0.len()).then(|| self.0.remove(index))
+    }
+
+    pub fn pop(&mut self) -> Option<TimeBox> {
+        self.0.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Keeps only the time boxes for which `f` returns `true`, removing keeps the order.
+    pub fn retain(&mut self, f: impl FnMut(&TimeBox) -> bool) {
+        self.0.retain(f);
+    }
+
+    /// Changes the time box at `index` and moves it to where it belongs afterwards.
+    /// Returns its new index and the result of `f`, `None` if `index` is out of bounds.
+    pub fn update<R>(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(&mut TimeBox) -> R,
+    ) -> Option<(usize, R)> {
+        let mut tb = self.remove(index)?;
+        let result = f(&mut tb);
+        Some((self.insert_sorted(tb), result))
+    }
+
+    /// Changes every time box, then restores the order.
+    pub fn update_all(&mut self, f: impl FnMut(&mut TimeBox)) {
+        self.0.iter_mut().for_each(f);
+        self.0.sort();
+    }
+
+    /// Indices of the time boxes starting between `from` and `to` inclusive, by UTC date.
+    /// Binary searches because the start is the most significant part of the order.
+    pub fn range_by_start_date(&self, from: NaiveDate, to: NaiveDate) -> Range<usize> {
+        let start_date = |tb: &TimeBox| tb.time_start().unwrap_or_default().date_naive();
+
+        let begin = self.0.partition_point(|tb| start_date(tb) < from);
+        let end = self.0.partition_point(|tb| start_date(tb) <= to);
+
+        begin..end.max(begin)
+    }
+}
+
+impl Deref for SortedTimeBoxes {
+    type Target = [TimeBox];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<TimeBox>> for SortedTimeBoxes {
+    fn from(mut time_boxes: Vec<TimeBox>) -> Self {
+        time_boxes.sort();
+        Self(time_boxes)
+    }
+}
+
+impl FromIterator<TimeBox> for SortedTimeBoxes {
+    fn from_iter<I: IntoIterator<Item = TimeBox>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl From<SortedTimeBoxes> for Vec<TimeBox> {
+    fn from(time_boxes: SortedTimeBoxes) -> Self {
+        time_boxes.0
+    }
+}
+
+impl<'a> IntoIterator for &'a SortedTimeBoxes {
+    type Item = &'a TimeBox;
+    type IntoIter = std::slice::Iter<'a, TimeBox>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// Files may have been edited manually, so the time boxes get sorted while loading.
+impl<'de> Deserialize<'de> for SortedTimeBoxes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<TimeBox>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta, Utc};
+
+    use super::*;
+    use crate::{
+        ListFilter, ListOptions, SortOrder, TimeTrackingStore,
+        in_memory_tracker::InMemoryTimeTracker, test_fixtures::time_box,
+    };
+
+    fn assert_sorted(time_boxes: &SortedTimeBoxes) {
+        assert!(time_boxes.is_sorted(), "Not sorted: {time_boxes:#?}");
+    }
+
+    #[test]
+    fn range_by_start_date_is_inclusive() {
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        let at = |d: u32, h: u32| day(d).and_hms_opt(h, 0, 0).unwrap().and_utc();
+        let time_boxes: SortedTimeBoxes = [(3, 9), (4, 23), (4, 8), (6, 0), (7, 12)]
+            .into_iter()
+            .map(|(d, h)| time_box(&at(d, h).to_rfc3339(), TimeDelta::minutes(30), "work"))
+            .collect();
+
+        assert_eq!(1..3, time_boxes.range_by_start_date(day(4), day(4)));
+        assert_eq!(1..4, time_boxes.range_by_start_date(day(4), day(6)));
+        assert_eq!(0..5, time_boxes.range_by_start_date(day(1), day(30)));
+        assert!(time_boxes.range_by_start_date(day(5), day(5)).is_empty());
+        assert!(time_boxes.range_by_start_date(day(7), day(3)).is_empty());
+    }
+
+    /// Pseudo random but reproducible, there is no need for a dependency
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % bound as u64) as usize
+        }
+    }
+
+    #[test]
+    fn order_holds_for_arbitrary_interleavings() -> crate::Result<()> {
+        let start: DateTime<Utc> = "2024-06-03T08:00:00Z".parse().unwrap();
+
+        for seed in 0..50 {
+            let mut rng = Lcg(seed);
+            let mut tracker = InMemoryTimeTracker::default();
+
+            for step in 0..200 {
+                let at = start + TimeDelta::minutes(rng.next(10_000) as i64);
+                let len = tracker.finished.len();
+
+                match rng.next(7) {
+                    0 | 1 => {
+                        let minutes = rng.next(120) as i64;
+                        tracker.finished.insert_sorted(time_box(
+                            &at.to_rfc3339(),
+                            TimeDelta::minutes(minutes),
+                            &format!("#{step}"),
+                        ));
+                    }
+                    2 if len > 0 => {
+                        tracker.finished.remove(rng.next(len));
+                    }
+                    3 if len > 0 && tracker.active.is_none() => {
+                        tracker.resume_at(rng.next(len))?;
+                    }
+                    4 if tracker.active.is_none() => {
+                        tracker.resume().ok();
+                    }
+                    5 if tracker.active.is_some() => {
+                        tracker.end()?;
+                    }
+                    6 if len > 0 => {
+                        let exported_at = (rng.next(2) == 0).then_some(at);
+                        tracker.set_exported_at(rng.next(len), exported_at)?;
+                    }
+                    _ => (),
+                }
+
+                assert_sorted(&tracker.finished);
+            }
+
+            let all = ListOptions::new().take(usize::MAX);
+            let ascending = tracker.finished(&all.order(SortOrder::Ascending))?;
+            assert_eq!(tracker.finished.to_vec(), ascending.items);
+
+            let from = start.date_naive() + TimeDelta::days(2);
+            let to = from + TimeDelta::days(1);
+            let filtered = tracker.finished(
+                &ListOptions::new()
+                    .take(usize::MAX)
+                    .filter(ListFilter::Range { from, to }),
+            )?;
+            let expected: Vec<_> = tracker
+                .finished
+                .iter()
+                .filter(|tb| (from..=to).contains(&tb.time_start().unwrap().date_naive()))
+                .cloned()
+                .collect();
+            assert_eq!(expected, filtered.items);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn loading_sorts_and_updates_keep_the_order() -> crate::Result<()> {
+        let start: DateTime<Utc> = "2024-06-03T08:00:00Z".parse().unwrap();
+        let unsorted = vec![
+            time_box("2024-06-03T10:00:00Z", TimeDelta::minutes(30), "third"),
+            time_box("2024-06-03T08:00:00Z", TimeDelta::minutes(30), "first"),
+            time_box("2024-06-03T09:00:00Z", TimeDelta::minutes(30), "second"),
+        ];
+
+        let json = serde_json::to_string(&unsorted).map_err(crate::Error::Serialization)?;
+        let mut time_boxes: SortedTimeBoxes =
+            serde_json::from_str(&json).map_err(crate::Error::Deserialization)?;
+        assert_sorted(&time_boxes);
+        assert_eq!("first", time_boxes[0].title());
+
+        let moved = time_boxes.update(0, |tb| {
+            for note in tb.notes.iter_mut() {
+                note.time += TimeDelta::hours(3);
+            }
+        });
+        assert_eq!(Some((2, ())), moved);
+        assert_eq!("first", time_boxes[2].title());
+        assert_eq!(None, time_boxes.update(3, |_| ()));
+
+        time_boxes.update_all(|tb| {
+            if tb.title() == "first" {
+                tb.notes[0].time = start - TimeDelta::hours(1);
+            }
+        });
+        assert_sorted(&time_boxes);
+        assert_eq!("first", time_boxes[0].title());
+
+        Ok(())
+    }
+}
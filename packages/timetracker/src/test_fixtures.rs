@@ -0,0 +1,32 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{NoteKind, TimeBox, TimeBoxNote};
+
+pub fn time(s: &str) -> DateTime<Utc> {
+    s.parse().unwrap()
+}
+
+pub fn note(time: DateTime<Utc>, description: &str) -> TimeBoxNote {
+    TimeBoxNote {
+        time,
+        description: description.to_string(),
+        kind: NoteKind::Regular,
+    }
+}
+
+/// Time box of `notes` that was never paused, estimated, referenced or exported.
+pub fn time_box_of(notes: Vec<TimeBoxNote>) -> TimeBox {
+    TimeBox {
+        notes,
+        exported_at: None,
+        pauses: Vec::new(),
+        estimate: None,
+        reference: None,
+    }
+}
+
+/// Finished time box titled `title` that ends with the note `done` after `duration`.
+pub fn time_box(start: &str, duration: TimeDelta, title: &str) -> TimeBox {
+    let start = time(start);
+    time_box_of(vec![note(start, title), note(start + duration, "done")])
+}
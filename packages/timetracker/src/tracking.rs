@@ -1,6 +1,11 @@
-use chrono::NaiveDate;
+use std::ops::Range;
 
-use crate::{TimeBox, error::Error};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Diagnostic, Repair, SortedTimeBoxes, StoreSettings, TimeBox, clamped_duration, error::Error,
+};
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
@@ -9,17 +14,37 @@ pub trait TimeTrackingStore {
     fn active(&self) -> Result<Option<TimeBox>>;
 
     /// Returns a paginated list of time boxes.
+    /// Filters apply first, then the order, then the pagination.
     fn finished(&self, options: &ListOptions) -> Result<ListResult>;
 
     /// Begin working on something. Creates a new active time box if there is none.
     /// Returns the newly created time box.
     fn begin(&mut self, description: &str) -> Result<TimeBox>;
 
+    /// Like `begin` but starting at `time`, e.g. to backfill a forgotten start.
+    /// Fails with `Error::OverlapsFinishedTimeBox` if a finished time box stops after `time`.
+    /// Returns the newly created time box.
+    fn begin_at(&mut self, description: &str, time: DateTime<Utc>) -> Result<TimeBox>;
+
     /// Adds a new note to the active time box.
     /// Returns the newly annotated time box.
     fn push_note(&mut self, description: &str) -> Result<TimeBox>;
 
-    /// Ends the active time box.
+    /// Adds a new note at `time` to the active time box, in front of the first note that is later,
+    /// so the notes stay sorted. A `time` before the first note makes it the new start and title,
+    /// failing with `Error::OverlapsFinishedTimeBox` if a finished time box stops after `time`.
+    /// Returns the newly annotated time box.
+    fn push_note_at(&mut self, description: &str, time: DateTime<Utc>) -> Result<TimeBox>;
+
+    /// Pauses the active time box, the pause does not count towards its duration.
+    /// Returns the paused time box.
+    fn pause(&mut self) -> Result<TimeBox>;
+
+    /// Ends the pause of the active time box.
+    /// Returns the unpaused time box.
+    fn unpause(&mut self) -> Result<TimeBox>;
+
+    /// Ends the active time box, ending its pause first if it is paused.
     /// Returns the newly ended time box.
     fn end(&mut self) -> Result<TimeBox>;
 
@@ -27,10 +52,51 @@ pub trait TimeTrackingStore {
     /// Returns the amended time box.
     fn amend(&mut self, description: &str) -> Result<TimeBox>;
 
-    /// Makes the last finished time box active again.
+    /// Changes the description of the active time boxes note at `note_index`, 0 being the first note.
+    /// Keeps the time of the note so the notes stay sorted.
+    /// Returns the edited time box.
+    fn edit_note(&mut self, note_index: usize, description: &str) -> Result<TimeBox>;
+
+    /// Sets or with `None` clears the estimate of the active time box.
+    /// Returns the changed time box.
+    fn set_estimate(&mut self, estimate: Option<TimeDelta>) -> Result<TimeBox>;
+
+    /// Sets or with `None` clears the reference of the active time box, e.g. a ticket id.
+    /// Gets trimmed, an empty reference clears it.
+    /// Returns the changed time box.
+    fn set_reference(&mut self, reference: Option<&str>) -> Result<TimeBox>;
+
+    /// Splits the active time box in front of the note at `note_index`.
+    /// The notes before it end as a finished time box, the remaining notes stay active.
+    /// The time of the note at `note_index` is the stop of the first and the start of the second part,
+    /// the first part ends with a system note at that time. Merging both parts again drops it.
+    /// Fails with `Error::CannotDeleteLastNote` if either part would end up without notes.
+    /// Returns the finished and the active part.
+    fn split_active(&mut self, note_index: usize) -> Result<(TimeBox, TimeBox)>;
+
+    /// Combines the last two finished time boxes into one, with their notes and pauses in chronological order.
+    /// Fails with `Error::NoTimeBox` if there are fewer than two.
+    /// Returns the merged time box.
+    fn merge_last_finished(&mut self) -> Result<TimeBox>;
+
+    /// Makes the finished time box with the latest start active again, regardless of when it was added or ended.
     /// Returns the newly active time box.
     fn resume(&mut self) -> Result<TimeBox>;
 
+    /// Makes the finished time box at `index` active again.
+    /// The index refers to the ascending order of all finished time boxes, see `ListResult::indices`.
+    /// Returns the newly active time box.
+    fn resume_at(&mut self, index: usize) -> Result<TimeBox>;
+
+    /// Sets or with `None` clears when the finished time box at `index` was exported.
+    /// The index refers to the ascending order of all finished time boxes, see `ListResult::indices`.
+    /// Returns the changed time box.
+    fn set_exported_at(
+        &mut self,
+        index: usize,
+        exported_at: Option<DateTime<Utc>>,
+    ) -> Result<TimeBox>;
+
     /// Cancels i.e. deletes the currently active time box.
     /// Returns the removed time box.
     fn cancel(&mut self) -> Result<TimeBox>;
@@ -39,6 +105,22 @@ pub trait TimeTrackingStore {
     /// Returns count of how many time boxes got removed.
     fn clear(&mut self) -> Result<usize>;
 
+    /// Deletes only the ended time boxes matching `query`, e.g. to prune old ones.
+    /// Returns count of how many time boxes got removed.
+    fn clear_filtered(&mut self, query: &Query) -> Result<usize>;
+
+    /// Returns the settings stored along with the time boxes.
+    fn settings(&self) -> Result<StoreSettings>;
+
+    /// Replaces the settings stored along with the time boxes.
+    fn set_settings(&mut self, settings: StoreSettings) -> Result<()>;
+
+    /// What loading had to change to make the store usable, e.g. because it was edited manually.
+    fn repairs(&self) -> Result<Vec<Repair>>;
+
+    /// What loading noticed and the application should show, e.g. warnings about repairs.
+    fn diagnostics(&self) -> Result<Vec<Diagnostic>>;
+
     /// Constructs the time tracker
     fn init(strategy: &impl TimeTrackerInitStrategy) -> Result<Self>
     where
@@ -60,18 +142,215 @@ pub enum SortOrder {
     Descending,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ListFilter {
     Date(NaiveDate),
-    Range { from: NaiveDate, to: NaiveDate },
+    Range {
+        from: NaiveDate,
+        to: NaiveDate,
+    },
+    /// Time boxes with the tag in any note, see `TimeBox::tags`. Case-insensitive, a leading `#` is optional.
+    Tag(String),
+    /// Time boxes with the term in any note description. Case-insensitive,
+    /// line breaks and other whitespace count as a single space so terms match across lines.
+    Search(String),
+    /// Time boxes with a match of the regular expression in any note description, see `SearchPattern`.
+    Pattern(SearchPattern),
+    /// Time boxes with the term in their title, see `TimeBox::title`. Matches like `Search`.
+    Title(String),
+    /// Time boxes with this reference, see `TimeBox::reference`. Case-insensitive, surrounding whitespace is ignored.
+    Reference(String),
+    /// Time boxes lasting at least this long, see `clamped_duration`. Serialized as seconds.
+    MinDuration(#[serde(with = "crate::entities::duration_seconds")] TimeDelta),
+    /// Time boxes lasting at most this long, see `clamped_duration`. Serialized as seconds.
+    MaxDuration(#[serde(with = "crate::entities::duration_seconds")] TimeDelta),
+}
+
+impl ListFilter {
+    /// Dates are UTC dates of the start of the time box.
+    pub fn matches(&self, time_box: &TimeBox) -> bool {
+        let start = || time_box.time_start().unwrap_or_default().date_naive();
+        let normalize = |s: &str| {
+            s.split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase()
+        };
+
+        match self {
+            ListFilter::Date(date) => start() == *date,
+            ListFilter::Range { from, to } => (*from..=*to).contains(&start()),
+            ListFilter::Tag(tag) => time_box
+                .tags()
+                .contains(&tag.trim_start_matches('#').to_lowercase()),
+            ListFilter::Search(term) => {
+                let term = normalize(term);
+                time_box
+                    .notes
+                    .iter()
+                    .any(|note| normalize(&note.description).contains(&term))
+            }
+            ListFilter::Pattern(pattern) => time_box
+                .notes
+                .iter()
+                .any(|note| pattern.0.is_match(&note.description)),
+            ListFilter::Title(term) => normalize(time_box.title()).contains(&normalize(term)),
+            ListFilter::Reference(reference) => time_box
+                .reference
+                .as_deref()
+                .is_some_and(|r| r.eq_ignore_ascii_case(reference.trim())),
+            ListFilter::MinDuration(min) => clamped_duration(time_box) >= *min,
+            ListFilter::MaxDuration(max) => clamped_duration(time_box) <= *max,
+        }
+    }
+}
+
+/// Regular expression for `ListFilter::Pattern`, compiled once. Case-insensitive like `ListFilter::Search`,
+/// `(?-i)` turns that off. Descriptions get matched as written, so `.` does not match line breaks.
+/// Compared and serialized as the pattern it was created from.
+#[derive(Debug, Clone)]
+pub struct SearchPattern(regex::Regex);
+
+impl SearchPattern {
+    pub fn new(pattern: &str) -> Result<Self> {
+        regex::RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map(Self)
+            .map_err(Error::InvalidSearchPattern)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl PartialEq for SearchPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SearchPattern {}
+
+impl Serialize for SearchPattern {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SearchPattern {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        SearchPattern::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Selects finished time boxes matching all of its clauses, for example
+/// `Query::new().date_range(from, to).tag("acme").min_duration(TimeDelta::minutes(15)).title_contains("review")`.
+/// Everything selecting finished time boxes takes a query, so listing, reports, exports and clearing agree.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Query {
+    /// Time boxes have to match all of them.
+    pub filters: Vec<ListFilter>,
+    /// Skips time boxes that were exported already, see `TimeBox::exported_at`.
+    #[serde(default)]
+    pub unexported_only: bool,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a clause, time boxes have to match all of them.
+    pub fn filter(mut self, filter: ListFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn date(self, date: NaiveDate) -> Self {
+        self.filter(ListFilter::Date(date))
+    }
+
+    /// Inclusive on both ends.
+    pub fn date_range(self, from: NaiveDate, to: NaiveDate) -> Self {
+        self.filter(ListFilter::Range { from, to })
+    }
+
+    pub fn tag(self, tag: &str) -> Self {
+        self.filter(ListFilter::Tag(tag.to_string()))
+    }
+
+    pub fn search(self, term: &str) -> Self {
+        self.filter(ListFilter::Search(term.to_string()))
+    }
+
+    pub fn pattern(self, pattern: SearchPattern) -> Self {
+        self.filter(ListFilter::Pattern(pattern))
+    }
+
+    pub fn title_contains(self, term: &str) -> Self {
+        self.filter(ListFilter::Title(term.to_string()))
+    }
+
+    pub fn reference(self, reference: &str) -> Self {
+        self.filter(ListFilter::Reference(reference.to_string()))
+    }
+
+    pub fn min_duration(self, min: TimeDelta) -> Self {
+        self.filter(ListFilter::MinDuration(min))
+    }
+
+    pub fn max_duration(self, max: TimeDelta) -> Self {
+        self.filter(ListFilter::MaxDuration(max))
+    }
+
+    pub fn unexported_only(mut self, unexported_only: bool) -> Self {
+        self.unexported_only = unexported_only;
+        self
+    }
+
+    pub fn matches(&self, time_box: &TimeBox) -> bool {
+        (!self.unexported_only || time_box.exported_at.is_none())
+            && self.filters.iter().all(|f| f.matches(time_box))
+    }
+
+    /// Indices of the `time_boxes` that may match, narrowed down by binary searching the date clauses
+    /// so not every time box has to be checked. The others still need to be checked via `matches`.
+    pub fn range(&self, time_boxes: &SortedTimeBoxes) -> Range<usize> {
+        self.filters
+            .iter()
+            .fold(0..time_boxes.len(), |range, filter| {
+                let narrowed = match filter {
+                    ListFilter::Date(date) => time_boxes.range_by_start_date(*date, *date),
+                    ListFilter::Range { from, to } => time_boxes.range_by_start_date(*from, *to),
+                    ListFilter::Tag(_)
+                    | ListFilter::Search(_)
+                    | ListFilter::Pattern(_)
+                    | ListFilter::Title(_)
+                    | ListFilter::Reference(_)
+                    | ListFilter::MinDuration(_)
+                    | ListFilter::MaxDuration(_) => return range,
+                };
+                let start = range.start.max(narrowed.start);
+                start..range.end.min(narrowed.end).max(start)
+            })
+    }
 }
 
+/// Pagination and order around a `Query`.
 #[derive(Debug)]
 pub struct ListOptions {
     pub skip: usize,
     pub take: usize,
     pub order: SortOrder,
-    pub filter: Option<ListFilter>,
+    pub query: Query,
 }
 
 impl ListOptions {
@@ -80,7 +359,7 @@ impl ListOptions {
             skip: 0,
             take: 25,
             order: SortOrder::Ascending,
-            filter: None,
+            query: Query::new(),
         }
     }
 
@@ -105,8 +384,19 @@ impl ListOptions {
         self
     }
 
+    pub fn query(mut self, query: Query) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Adds a clause to the query, see `Query::filter`.
     pub fn filter(mut self, filter: ListFilter) -> Self {
-        self.filter = Some(filter);
+        self.query = self.query.filter(filter);
+        self
+    }
+
+    pub fn unexported_only(mut self, unexported_only: bool) -> Self {
+        self.query = self.query.unexported_only(unexported_only);
         self
     }
 }
@@ -121,4 +411,235 @@ impl Default for ListOptions {
 pub struct ListResult {
     pub total: usize,
     pub items: Vec<TimeBox>,
+    /// Position of each item among all finished time boxes in ascending order.
+    /// Stays the same regardless of filtering or ordering so it can be used to address items.
+    pub indices: Vec<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::time_box;
+
+    fn time_boxes() -> SortedTimeBoxes {
+        let with_note = |mut tb: TimeBox, description: &str| {
+            tb.notes[1].description = description.to_string();
+            tb
+        };
+        let mut exported = time_box(
+            "2024-06-04T09:00:00Z",
+            TimeDelta::minutes(10),
+            "Standup #acme",
+        );
+        exported.exported_at = Some("2024-06-30T18:00:00Z".parse().unwrap());
+
+        vec![
+            time_box(
+                "2024-05-31T09:00:00Z",
+                TimeDelta::minutes(60),
+                "Code review #acme",
+            ),
+            with_note(
+                time_box(
+                    "2024-06-03T09:00:00Z",
+                    TimeDelta::minutes(90),
+                    "Code Review #acme",
+                ),
+                "left a review",
+            ),
+            exported,
+            time_box(
+                "2024-06-05T09:00:00Z",
+                TimeDelta::minutes(15),
+                "Review budget #other",
+            ),
+            with_note(
+                time_box("2024-06-06T09:00:00Z", TimeDelta::minutes(120), "Planning"),
+                "review the roadmap",
+            ),
+            time_box(
+                "2024-07-01T09:00:00Z",
+                TimeDelta::minutes(30),
+                "Review #acme",
+            ),
+        ]
+        .into()
+    }
+
+    /// Indices of the matching time boxes, also checks that narrowing never excludes matches.
+    fn matching(query: &Query) -> Vec<usize> {
+        let time_boxes = time_boxes();
+        let matches: Vec<usize> = (0..time_boxes.len())
+            .filter(|&i| query.matches(&time_boxes[i]))
+            .collect();
+
+        let range = query.range(&time_boxes);
+        assert!(
+            matches.iter().all(|i| range.contains(i)),
+            "{range:?} excludes some of {matches:?}"
+        );
+
+        matches
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(vec![0, 1, 2, 3, 4, 5], matching(&Query::new()));
+        assert_eq!(0..6, Query::new().range(&time_boxes()));
+    }
+
+    #[test]
+    fn date_clauses_narrow_down_the_range() {
+        let query = Query::new().date(date("2024-06-04"));
+        assert_eq!(vec![2], matching(&query));
+        assert_eq!(2..3, query.range(&time_boxes()));
+
+        let june = Query::new().date_range(date("2024-06-01"), date("2024-06-30"));
+        assert_eq!(vec![1, 2, 3, 4], matching(&june));
+        assert_eq!(1..5, june.range(&time_boxes()));
+
+        // Ranges intersect, disjoint ones match nothing
+        let query = june
+            .clone()
+            .date_range(date("2024-06-05"), date("2024-07-31"));
+        assert_eq!(vec![3, 4], matching(&query));
+        assert_eq!(3..5, query.range(&time_boxes()));
+
+        let query = june.date(date("2024-07-01"));
+        assert!(matching(&query).is_empty());
+        assert!(query.range(&time_boxes()).is_empty());
+    }
+
+    #[test]
+    fn tag_clause_ignores_case_and_leading_hash() {
+        assert_eq!(vec![0, 1, 2, 5], matching(&Query::new().tag("acme")));
+        assert_eq!(vec![0, 1, 2, 5], matching(&Query::new().tag("#ACME")));
+        assert!(matching(&Query::new().tag("acm")).is_empty());
+    }
+
+    #[test]
+    fn search_clause_looks_at_every_note_but_title_only_at_the_title() {
+        assert_eq!(
+            vec![0, 1, 3, 4, 5],
+            matching(&Query::new().search("review"))
+        );
+        assert_eq!(
+            vec![0, 1, 3, 5],
+            matching(&Query::new().title_contains("REVIEW"))
+        );
+        assert_eq!(
+            vec![0, 1],
+            matching(&Query::new().title_contains("code  review"))
+        );
+    }
+
+    #[test]
+    fn duration_clauses_are_inclusive() {
+        assert_eq!(
+            vec![0, 1, 4, 5],
+            matching(&Query::new().min_duration(TimeDelta::minutes(30)))
+        );
+        assert_eq!(
+            vec![2, 3],
+            matching(&Query::new().max_duration(TimeDelta::minutes(15)))
+        );
+        assert_eq!(
+            vec![0, 5],
+            matching(
+                &Query::new()
+                    .min_duration(TimeDelta::minutes(30))
+                    .max_duration(TimeDelta::minutes(60))
+            )
+        );
+    }
+
+    #[test]
+    fn durations_exclude_pauses_and_count_inverted_time_boxes_as_zero() {
+        let mut paused = time_box("2024-06-03T09:00:00Z", TimeDelta::minutes(60), "Paused");
+        paused.pauses.push(crate::Pause {
+            start: "2024-06-03T09:10:00Z".parse().unwrap(),
+            stop: Some("2024-06-03T09:40:00Z".parse().unwrap()),
+        });
+        let mut inverted = time_box("2024-06-03T09:00:00Z", TimeDelta::minutes(60), "Inverted");
+        inverted.notes.reverse();
+
+        let at_least_half_an_hour = Query::new().min_duration(TimeDelta::minutes(30));
+        assert!(at_least_half_an_hour.matches(&paused));
+        assert!(!at_least_half_an_hour.matches(&inverted));
+        assert!(
+            Query::new()
+                .max_duration(TimeDelta::zero())
+                .matches(&inverted)
+        );
+    }
+
+    #[test]
+    fn unexported_only_skips_exported_time_boxes() {
+        assert_eq!(
+            vec![0, 1, 2, 5],
+            matching(&Query::new().tag("acme").unexported_only(false))
+        );
+        assert_eq!(
+            vec![0, 1, 5],
+            matching(&Query::new().tag("acme").unexported_only(true))
+        );
+        assert_eq!(
+            vec![0, 1, 3, 4, 5],
+            matching(&Query::new().unexported_only(true))
+        );
+    }
+
+    #[test]
+    fn clauses_combine_as_conjunction() {
+        let query = Query::new()
+            .date_range(date("2024-06-01"), date("2024-06-30"))
+            .tag("acme")
+            .min_duration(TimeDelta::minutes(15))
+            .title_contains("review");
+        assert_eq!(vec![1], matching(&query));
+
+        // Every clause alone matches more, so each one of them contributes
+        for filter in &query.filters {
+            assert!(matching(&Query::new().filter(filter.clone())).len() > 1);
+        }
+
+        assert!(matching(&query.clone().unexported_only(true)).contains(&1));
+        assert!(matching(&query.search("roadmap")).is_empty());
+    }
+
+    #[test]
+    fn list_options_wrap_the_query() {
+        let options = ListOptions::new()
+            .filter(ListFilter::Tag("acme".to_string()))
+            .unexported_only(true);
+        assert_eq!(
+            Query::new().tag("acme").unexported_only(true),
+            options.query
+        );
+
+        let query = Query::new().search("review");
+        assert_eq!(query, ListOptions::new().query(query.clone()).query);
+    }
+
+    #[test]
+    fn queries_serialize_durations_as_seconds() {
+        let query = Query::new()
+            .date(date("2024-06-04"))
+            .min_duration(TimeDelta::minutes(15))
+            .unexported_only(true);
+        let json = serde_json::to_string(&query).unwrap();
+        assert_eq!(
+            r#"{"filters":[{"Date":"2024-06-04"},{"MinDuration":900}],"unexported_only":true}"#,
+            json
+        );
+        assert_eq!(query, serde_json::from_str(&json).unwrap());
+        assert_eq!(
+            Query::new(),
+            serde_json::from_str(r#"{"filters":[]}"#).unwrap()
+        );
+    }
 }
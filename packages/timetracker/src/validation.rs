@@ -0,0 +1,153 @@
+use std::fmt::Display;
+
+use crate::{Error, Result, TimeBoxNote};
+
+/// Descriptions with more characters than this get rejected when writing and flagged when loading.
+pub const MAX_DESCRIPTION_LEN: usize = 10_000;
+
+/// Where a note is located inside a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteLocation {
+    Active { note: usize },
+    Finished { time_box: usize, note: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssueKind {
+    /// Control characters besides line breaks and tabs, for example NUL bytes or terminal escape sequences.
+    ControlCharacter(char),
+    /// Description has `len` characters, more than the allowed `max`.
+    TooLong { len: usize, max: usize },
+}
+
+/// Problem with the content of a note, usually caused by editing the store manually.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub location: NoteLocation,
+    pub kind: ValidationIssueKind,
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            NoteLocation::Active { note } => write!(f, "note {note} of the active time box ")?,
+            NoteLocation::Finished { time_box, note } => {
+                write!(f, "note {note} of the finished time box {time_box} ")?
+            }
+        };
+
+        match self.kind {
+            ValidationIssueKind::ControlCharacter(c) => {
+                write!(f, "contains the control character U+{:04X}", c as u32)
+            }
+            ValidationIssueKind::TooLong { len, max } => {
+                write!(f, "has {len} characters, more than the allowed {max}")
+            }
+        }
+    }
+}
+
+/// Change made while loading a store that may have been edited manually, see `InMemoryTimeTracker::repairs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// Notes were out of order, for example after a sync conflict, so the notes of every time box got sorted.
+    /// Contains the first note found earlier than the one before it.
+    SortedNotes(TimeBoxNote),
+    /// Description got sanitized.
+    Sanitized(ValidationIssue),
+    /// The incomplete last line of a JSON Lines store got dropped, e.g. after an interrupted write.
+    DroppedIncompleteLine(usize),
+}
+
+impl Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Repair::SortedNotes(note) => write!(
+                f,
+                "sorted the notes because \"{}\" at {} is earlier than the note before it",
+                note.description.lines().next().unwrap_or_default(),
+                note.time
+            ),
+            Repair::Sanitized(issue) => write!(f, "sanitized {issue}"),
+            Repair::DroppedIncompleteLine(line) => {
+                write!(f, "dropped the incomplete last line {line}")
+            }
+        }
+    }
+}
+
+fn is_forbidden_control(c: char) -> bool {
+    c.is_control() && c != '\n' && c != '\t'
+}
+
+/// Returns every kind of problem the description has.
+pub fn check_description(description: &str, max_len: usize) -> Vec<ValidationIssueKind> {
+    let mut issues = Vec::new();
+
+    if let Some(c) = description.chars().find(|&c| is_forbidden_control(c)) {
+        issues.push(ValidationIssueKind::ControlCharacter(c));
+    }
+
+    let len = description.chars().count();
+    if len > max_len {
+        issues.push(ValidationIssueKind::TooLong { len, max: max_len });
+    }
+
+    issues
+}
+
+/// Removes forbidden control characters and truncates to `max_len` characters.
+pub fn sanitize_description(description: &str, max_len: usize) -> String {
+    description
+        .chars()
+        .filter(|&c| !is_forbidden_control(c))
+        .take(max_len)
+        .collect()
+}
+
+/// Guards writes so that stores never contain descriptions they would flag when loading.
+pub(crate) fn assert_description_len(description: &str) -> Result<()> {
+    let len = description.chars().count();
+
+    match len > MAX_DESCRIPTION_LEN {
+        true => Err(Error::DescriptionTooLong {
+            len,
+            max: MAX_DESCRIPTION_LEN,
+        }),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_finds_control_characters_but_allows_line_breaks_and_tabs() {
+        assert!(check_description("line\n\tindented", 100).is_empty());
+        assert_eq!(
+            vec![ValidationIssueKind::ControlCharacter('\0')],
+            check_description("nul\0byte", 100)
+        );
+        assert_eq!(
+            vec![ValidationIssueKind::ControlCharacter('\u{1b}')],
+            check_description("\u{1b}[31mred", 100)
+        );
+    }
+
+    #[test]
+    fn check_counts_characters_not_bytes() {
+        assert!(check_description("äöü", 3).is_empty());
+        assert_eq!(
+            vec![ValidationIssueKind::TooLong { len: 4, max: 3 }],
+            check_description("äöüß", 3)
+        );
+    }
+
+    #[test]
+    fn sanitize_strips_and_truncates() {
+        assert_eq!("nulbyte", sanitize_description("nul\0byte", 100));
+        assert_eq!("äö", sanitize_description("\0äöü", 2));
+        assert_eq!("a\nb", sanitize_description("a\nb", 100));
+    }
+}